@@ -0,0 +1,178 @@
+//! Compares both formats on the same synthetic traces, see [ottel_spaniel::load].
+//!
+//! `ingest` measures spans written per second through `start_writer` and prints the size of
+//! written files per span, `query` measures a full scan and a filtered query over written files.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use tokio::runtime::Runtime;
+
+use ottel_spaniel::load::{Generator, Options, ingest};
+use ottel_spaniel::misc::load_existing_files;
+use ottel_spaniel::write::Options as WriterOptions;
+use ottel_spaniel::{Format, SpanData, query};
+
+const TRACES: usize = 2_000;
+
+/// Intrinsics only, Vortex files keep no attributes.
+const QUERY: &str = "{ duration > 2ms && kind = server }";
+
+const WRITER: WriterOptions = WriterOptions {
+    flush_interval_millis: 10,
+    suspend_interval_millis: 1000,
+    suspend_after: u64::MAX,
+    sink_channel_size: 16,
+    request_waitlist_size: 16,
+    spans_per_file: 1 << 20,
+    builder_flush_threshold: 8192,
+    builder_capacity: 8192,
+};
+
+fn formats() -> [(&'static str, Format); 2] {
+    [("arrow", Format::Arrow), ("vortex", Format::vortex())]
+}
+
+fn spans() -> Vec<SpanData> {
+    let mut generator = Generator::new(Options::default());
+
+    (0..TRACES)
+        .flat_map(|idx| generator.trace(idx as u64 * 1_000_000))
+        .collect()
+}
+
+/// Directory removed once the benchmark is done with it.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        let idx = NEXT.fetch_add(1, Ordering::Relaxed);
+        let dir =
+            std::env::temp_dir().join(format!("spaniel-bench-{}-{name}-{idx}", std::process::id()));
+
+        Self(dir)
+    }
+
+    fn path(&self) -> Box<Path> {
+        self.0.clone().into_boxed_path()
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("runtime.ok")
+}
+
+fn bench_ingest(c: &mut Criterion) {
+    let rt = runtime();
+    let len = spans().len();
+
+    let mut group = c.benchmark_group("ingest");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(len as u64));
+
+    for (name, format) in formats() {
+        let scratch = Scratch::new(name);
+        let report = rt.block_on(ingest(&format, scratch.path(), spans(), WRITER));
+        eprintln!(
+            "ingest/{name}: {len} spans, {:.1} bytes/span",
+            report.bytes_per_span()
+        );
+
+        let format = &format;
+        group.bench_function(name, |b| {
+            b.to_async(&rt).iter_batched(
+                || (spans(), Scratch::new(name)),
+                |(spans, scratch)| async move {
+                    ingest(format, scratch.path(), spans, WRITER).await;
+                    scratch
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+/// Number of spans read from `files`, optionally filtered by [QUERY].
+async fn scan(format: &Format, files: Vec<Box<Path>>, filtered: bool) -> usize {
+    let parsed = query::parse(if filtered { QUERY } else { "{}" }).expect("query.ok");
+    let mut count = 0;
+
+    match format {
+        Format::Arrow => {
+            use ottel_spaniel::arrow::Read;
+
+            let mut read = Read::try_new(
+                None::<Vec<&str>>,
+                |schema| query::to_arrow(&parsed, schema),
+                files,
+            )
+            .expect("query.arrow");
+
+            while let Some(batch) = read.next_batch().await {
+                count += batch.num_rows();
+            }
+        }
+        f @ Format::Vortex { .. } => {
+            use ottel_spaniel::vortex::read::Read;
+
+            let mut read = Read::new(f, files);
+
+            if query::to_vortex(&parsed, &|_| true)
+                .expect("query.vortex")
+                .is_some()
+            {
+                read = read.with_file_filter(move |has| {
+                    query::to_vortex(&parsed, has)
+                        .expect("query.vortex")
+                        .expect("query.filter")
+                });
+            }
+
+            while let Some(arr) = read.next_batch().await {
+                count += arr.len();
+            }
+        }
+    }
+
+    count
+}
+
+fn bench_query(c: &mut Criterion) {
+    let rt = runtime();
+
+    let mut group = c.benchmark_group("query");
+    group.sample_size(20);
+
+    for (name, format) in formats() {
+        let scratch = Scratch::new(name);
+        rt.block_on(ingest(&format, scratch.path(), spans(), WRITER));
+
+        let files = || load_existing_files(scratch.path(), &[format.file_prefix()]);
+
+        group.bench_function(format!("{name}/scan"), |b| {
+            b.to_async(&rt).iter(|| scan(&format, files(), false))
+        });
+        group.bench_function(format!("{name}/filter"), |b| {
+            b.to_async(&rt).iter(|| scan(&format, files(), true))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_ingest, bench_query);
+criterion_main!(benches);
@@ -0,0 +1,861 @@
+//! Alerting rules evaluated against stored spans.
+//!
+//! Rules are read from a JSON file, every rule computes a value over the spans matching its query
+//! within a trailing window and compares it with a threshold:
+//!
+//! ```json
+//! [
+//!   {
+//!     "name": "pokemon-errors",
+//!     "query": "{ name = \"GET /pokedex/<lang>/pokemon/<id>\" }",
+//!     "metric": "errorRate",
+//!     "op": ">",
+//!     "threshold": 0.05,
+//!     "windowSecs": 300
+//!   },
+//!   {
+//!     "name": "pokemon-latency",
+//!     "query": "{ name = \"GET /pokedex/<lang>/pokemon/<id>\" }",
+//!     "metric": { "quantile": 0.99 },
+//!     "op": ">",
+//!     "threshold": 1000,
+//!     "windowSecs": 300,
+//!     "forSecs": 60
+//!   }
+//! ]
+//! ```
+//!
+//! A breached rule is pending until it has been breached for `forSecs`, then firing until the
+//! condition clears. State is kept in memory only, every change is sent to the [Notifier] and
+//! retried on the next evaluation until it is delivered.
+//!
+//! Rules keep aggregates of their spans in a [Window] fed by ingestion, so that evaluations read
+//! no files. Spans stored before evaluation started are read once, see [Evaluator::evaluate].
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::{Request, StatusCode, Uri, header};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+
+use crate::query::{self, Query, Source};
+use crate::{Format, SpanData, Stats};
+
+/// Span status code of errors, `STATUS_CODE_ERROR`.
+const STATUS_ERROR: i32 = 2;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    pub name: String,
+    /// TraceQL-like query selecting the spans, see [crate::query].
+    pub query: String,
+    pub metric: Metric,
+    pub op: Op,
+    pub threshold: f64,
+    /// Spans ending within this many seconds before an evaluation are considered.
+    pub window_secs: u64,
+    /// Seconds a rule stays pending before firing, fires at once when 0.
+    #[serde(default)]
+    pub for_secs: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Metric {
+    /// Fraction of spans with an error status, between 0 and 1.
+    ErrorRate,
+    /// Duration quantile in milliseconds, e.g. `0.99` for p99.
+    Quantile(f64),
+    Count,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Op {
+    #[serde(rename = ">")]
+    Gt,
+    #[serde(rename = "<")]
+    Lt,
+}
+
+impl Rule {
+    fn breached(&self, value: f64) -> bool {
+        match self.op {
+            Op::Gt => value > self.threshold,
+            Op::Lt => value < self.threshold,
+        }
+    }
+}
+
+/// Reads a JSON array of rules, checking their queries.
+pub fn read_rules(path: impl AsRef<Path>) -> Result<Vec<Rule>, String> {
+    let path = path.as_ref();
+    let data = std::fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let rules: Vec<Rule> =
+        serde_json::from_slice(&data).map_err(|e| format!("{}: {e}", path.display()))?;
+
+    for rule in rules.iter() {
+        query::parse(&rule.query).map_err(|e| format!("{}: {e}", rule.name))?;
+    }
+
+    Ok(rules)
+}
+
+/// Aggregates of the spans of a rule ending within a slice of its window.
+#[derive(Debug, Default)]
+struct Slice {
+    count: u64,
+    errors: u64,
+    /// Number of spans by [Series::bin] of their duration.
+    durations: BTreeMap<u32, u64>,
+}
+
+/// Spans matching a rule, aggregated in [Series::SLICES] slices of its window. Memory doesn't
+/// grow with the number of spans.
+#[derive(Debug)]
+struct Series {
+    window_ms: u64,
+    slice_ms: u64,
+    /// By end time of the spans divided by `slice_ms`.
+    slices: BTreeMap<u64, Slice>,
+}
+
+impl Series {
+    const SLICES: u64 = 60;
+    /// Durations are rounded down by at most 1/2^SUB_BITS, about 3%.
+    const SUB_BITS: u32 = 5;
+
+    fn new(window_secs: u64) -> Self {
+        let window_ms = window_secs * 1000;
+
+        Self {
+            window_ms,
+            slice_ms: (window_ms / Self::SLICES).max(1000),
+            slices: BTreeMap::new(),
+        }
+    }
+
+    /// Bin of a duration, exact below 2^SUB_BITS and keeping SUB_BITS bits after the highest one
+    /// above.
+    fn bin(duration: u64) -> u32 {
+        if duration < 1 << Self::SUB_BITS {
+            return duration as u32;
+        }
+
+        let shift = 63 - duration.leading_zeros() - Self::SUB_BITS;
+        let mantissa = (duration >> shift) as u32 & ((1 << Self::SUB_BITS) - 1);
+        ((shift + 1) << Self::SUB_BITS) | mantissa
+    }
+
+    /// Smallest duration of a bin.
+    fn duration(bin: u32) -> u64 {
+        if bin < 1 << Self::SUB_BITS {
+            return bin as u64;
+        }
+
+        let shift = (bin >> Self::SUB_BITS) - 1;
+        let mantissa = (bin & ((1 << Self::SUB_BITS) - 1)) as u64;
+        ((1 << Self::SUB_BITS) | mantissa) << shift
+    }
+
+    fn add(&mut self, span: &SpanData) {
+        let slice = self
+            .slices
+            .entry(span.time_end / 1_000_000 / self.slice_ms)
+            .or_default();
+
+        slice.count += 1;
+        if span.status_code == Some(STATUS_ERROR) {
+            slice.errors += 1;
+        }
+        *slice
+            .durations
+            .entry(Self::bin(span.time_duration))
+            .or_default() += 1;
+    }
+
+    /// Value of `metric` over the spans ending within the window before `now_ms`, `None` without
+    /// any such span. Older slices are dropped.
+    fn value(&mut self, metric: Metric, now_ms: u64) -> Option<f64> {
+        let first = now_ms.saturating_sub(self.window_ms) / self.slice_ms;
+        let last = now_ms / self.slice_ms;
+
+        self.slices = self.slices.split_off(&first);
+
+        let mut count = 0;
+        let mut errors = 0;
+        let mut durations: BTreeMap<u32, u64> = BTreeMap::new();
+
+        for (_, slice) in self.slices.range(..=last) {
+            count += slice.count;
+            errors += slice.errors;
+            for (bin, n) in slice.durations.iter() {
+                *durations.entry(*bin).or_default() += n;
+            }
+        }
+
+        if count == 0 {
+            return None;
+        }
+
+        let value = match metric {
+            Metric::ErrorRate => errors as f64 / count as f64,
+            Metric::Count => count as f64,
+            Metric::Quantile(q) => {
+                let rank = ((count as f64 * q.clamp(0.0, 1.0)).ceil() as u64).clamp(1, count);
+                let mut seen = 0;
+                let (bin, _) = durations
+                    .into_iter()
+                    .find(|(_, n)| {
+                        seen += n;
+                        seen >= rank
+                    })
+                    .expect("quantile.rank");
+
+                Self::duration(bin) as f64 / 1_000_000.0
+            }
+        };
+
+        Some(value)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum State {
+    Pending,
+    Firing,
+    Resolved,
+}
+
+/// In-memory state of a single rule.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Alert {
+    #[default]
+    Inactive,
+    Pending {
+        since_ms: u64,
+    },
+    Firing {
+        since_ms: u64,
+    },
+}
+
+impl Alert {
+    /// Moves to the next state given the current `value`, returning the state to notify about
+    /// along with the time it started.
+    ///
+    /// No value, e.g. when no span matched, counts as not breached. A pending alert which clears
+    /// before firing goes back to inactive without a notification.
+    pub fn step(&mut self, rule: &Rule, value: Option<f64>, now_ms: u64) -> Option<(State, u64)> {
+        let breached = value.is_some_and(|value| rule.breached(value));
+        let for_ms = rule.for_secs * 1000;
+
+        match (*self, breached) {
+            (Alert::Inactive, true) if for_ms == 0 => {
+                *self = Alert::Firing { since_ms: now_ms };
+                Some((State::Firing, now_ms))
+            }
+            (Alert::Inactive, true) => {
+                *self = Alert::Pending { since_ms: now_ms };
+                Some((State::Pending, now_ms))
+            }
+            (Alert::Pending { since_ms }, true) if now_ms.saturating_sub(since_ms) >= for_ms => {
+                *self = Alert::Firing { since_ms: now_ms };
+                Some((State::Firing, now_ms))
+            }
+            (Alert::Pending { .. }, false) => {
+                *self = Alert::Inactive;
+                None
+            }
+            (Alert::Firing { since_ms }, false) => {
+                *self = Alert::Inactive;
+                Some((State::Resolved, since_ms))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Body of a webhook request.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    pub rule: String,
+    pub tenant: String,
+    pub state: State,
+    /// Value of the last evaluation, absent when no span matched.
+    pub value: Option<f64>,
+    pub threshold: f64,
+    /// Start of the pending or firing state, in milliseconds since the epoch.
+    pub since_ms: u64,
+    pub at_ms: u64,
+}
+
+#[derive(Debug)]
+pub enum NotifyError {
+    Request(String),
+    Status(StatusCode),
+}
+
+impl std::fmt::Display for NotifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Request(e) => write!(f, "{e}"),
+            Self::Status(status) => write!(f, "webhook responded with {status}"),
+        }
+    }
+}
+
+/// Posts notifications as JSON to a webhook URL.
+#[derive(Clone, Debug)]
+pub struct Notifier {
+    url: Uri,
+    client: Client<HttpConnector, Full<Bytes>>,
+    /// Limit of a single request including the response, a stuck webhook delays every rule.
+    timeout: Duration,
+}
+
+impl Notifier {
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    pub fn new(url: Uri) -> Self {
+        Self {
+            url,
+            client: Client::builder(TokioExecutor::new()).build_http(),
+            timeout: Self::TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub async fn notify(&self, notification: &Notification) -> Result<(), NotifyError> {
+        let body = serde_json::to_vec(notification).expect("notification.json");
+        let req = Request::post(self.url.clone())
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(body)))
+            .map_err(|e| NotifyError::Request(e.to_string()))?;
+
+        let res = tokio::time::timeout(self.timeout, self.client.request(req))
+            .await
+            .map_err(|_| NotifyError::Request(format!("timed out after {:?}", self.timeout)))?
+            .map_err(|e| NotifyError::Request(e.to_string()))?;
+
+        if !res.status().is_success() {
+            return Err(NotifyError::Status(res.status()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Aggregates of the accepted spans of every rule, see [Series].
+///
+/// Nothing is kept until an [Evaluator] watches the window.
+#[derive(Clone, Debug, Default)]
+pub struct Window {
+    recent: Arc<Mutex<Recent>>,
+}
+
+#[derive(Debug, Default)]
+struct Recent {
+    /// Query and aggregates of every watched rule, in the order of the rules.
+    rules: Vec<(Query, Series)>,
+    /// Spans accepted before stored spans were read, which may be stored already.
+    accepted: Option<HashSet<([u8; 16], [u8; 8])>>,
+}
+
+impl Window {
+    pub fn push(&self, spans: &[SpanData]) {
+        let mut recent = self.recent.lock().expect("window.lock");
+        let Recent { rules, accepted } = &mut *recent;
+
+        for span in spans {
+            let mut matched = false;
+
+            for (parsed, series) in rules.iter_mut() {
+                if query::matches_span(parsed, span).unwrap_or(false) {
+                    series.add(span);
+                    matched = true;
+                }
+            }
+
+            if let Some(accepted) = accepted.as_mut().filter(|_| matched) {
+                accepted.insert((span.trace_id, span.span_id));
+            }
+        }
+    }
+
+    fn watch(&self, rules: &[(Rule, Query, Alert)]) {
+        let mut recent = self.recent.lock().expect("window.lock");
+
+        for (rule, parsed, _) in rules {
+            recent
+                .rules
+                .push((parsed.clone(), Series::new(rule.window_secs)));
+        }
+        recent.accepted = Some(HashSet::new());
+    }
+
+    /// Adds stored spans of the rule `idx` unless they were accepted by the window too.
+    fn add_stored(&self, idx: usize, spans: &[SpanData]) {
+        let mut recent = self.recent.lock().expect("window.lock");
+        let Recent { rules, accepted } = &mut *recent;
+
+        for span in spans {
+            let seen = accepted
+                .as_ref()
+                .is_some_and(|accepted| accepted.contains(&(span.trace_id, span.span_id)));
+
+            if !seen {
+                rules[idx].1.add(span);
+            }
+        }
+    }
+
+    /// Stops keeping accepted spans once stored spans were read.
+    fn stored_read(&self) {
+        self.recent.lock().expect("window.lock").accepted = None;
+    }
+
+    fn value(&self, idx: usize, metric: Metric, now_ms: u64) -> Option<f64> {
+        let mut recent = self.recent.lock().expect("window.lock");
+        recent.rules[idx].1.value(metric, now_ms)
+    }
+}
+
+/// Rules of a single tenant with their state.
+pub struct Evaluator {
+    tenant: String,
+    rules: Vec<(Rule, Query, Alert)>,
+    notifier: Option<Notifier>,
+    window: Window,
+    /// Whether spans stored before the window was watched were read.
+    stored_read: bool,
+    /// Notifications not delivered yet, oldest first.
+    undelivered: VecDeque<Notification>,
+}
+
+impl Evaluator {
+    /// Notifications kept for a failing webhook, older ones are dropped.
+    const MAX_UNDELIVERED: usize = 1024;
+
+    /// Rules have to be checked by [read_rules] first. Spans pushed to `window` from now on are
+    /// evaluated, as well as those already stored.
+    pub fn new(
+        tenant: impl Into<String>,
+        rules: Vec<Rule>,
+        notifier: Option<Notifier>,
+        window: Window,
+    ) -> Self {
+        let rules: Vec<_> = rules
+            .into_iter()
+            .map(|rule| {
+                let parsed = query::parse(&rule.query).expect("rule.query.valid");
+                (rule, parsed, Alert::default())
+            })
+            .collect();
+
+        window.watch(&rules);
+
+        Self {
+            tenant: tenant.into(),
+            rules,
+            notifier,
+            window,
+            stored_read: false,
+            undelivered: VecDeque::new(),
+        }
+    }
+
+    /// Adds spans stored within the window of every rule before `now_ms` to the window.
+    async fn read_stored(&mut self, format: &Format, stats: &Stats, now_ms: u64) {
+        let files: Vec<Box<Path>> = { stats.files.read().await.iter().cloned().collect() };
+
+        for (idx, (rule, parsed, _)) in self.rules.iter().enumerate() {
+            let window = (now_ms.saturating_sub(rule.window_secs * 1000), now_ms);
+
+            for (format, files) in format.split(files.clone()) {
+                let mut source = match Source::open(format, files, parsed, window, None) {
+                    Ok(source) => source,
+                    Err(e) => {
+                        tracing::warn!(rule = rule.name, error = %e, "alert.evaluate.error");
+                        break;
+                    }
+                };
+
+                while let Some(spans) = source.next_batch().await {
+                    self.window.add_stored(idx, &spans);
+                }
+            }
+        }
+
+        self.window.stored_read();
+        self.stored_read = true;
+    }
+
+    /// Evaluates every rule at `now_ms` and returns the notifications of changed rules.
+    ///
+    /// Stored spans are read by the first evaluation only, later ones use the window alone.
+    pub async fn evaluate(
+        &mut self,
+        format: &Format,
+        stats: &Stats,
+        now_ms: u64,
+    ) -> Vec<Notification> {
+        if !self.stored_read {
+            self.read_stored(format, stats, now_ms).await;
+        }
+
+        let mut notifications = Vec::new();
+
+        for (idx, (rule, _, alert)) in self.rules.iter_mut().enumerate() {
+            let value = self.window.value(idx, rule.metric, now_ms);
+
+            if let Some((state, since_ms)) = alert.step(rule, value, now_ms) {
+                tracing::info!(
+                    rule = rule.name,
+                    tenant = self.tenant,
+                    ?state,
+                    ?value,
+                    "alert.state"
+                );
+
+                notifications.push(Notification {
+                    rule: rule.name.clone(),
+                    tenant: self.tenant.clone(),
+                    state,
+                    value,
+                    threshold: rule.threshold,
+                    since_ms,
+                    at_ms: now_ms,
+                });
+            }
+        }
+
+        notifications
+    }
+
+    /// Sends queued notifications in order, stopping at the first failure to retry it later.
+    async fn deliver(&mut self, notifications: Vec<Notification>) {
+        let Some(notifier) = self.notifier.as_ref() else {
+            return;
+        };
+
+        self.undelivered.extend(notifications);
+
+        while let Some(notification) = self.undelivered.front() {
+            if let Err(e) = notifier.notify(notification).await {
+                tracing::warn!(
+                    rule = notification.rule,
+                    undelivered = self.undelivered.len(),
+                    error = %e,
+                    "alert.notify.error"
+                );
+                break;
+            }
+
+            self.undelivered.pop_front();
+        }
+
+        let dropped = self.undelivered.len().saturating_sub(Self::MAX_UNDELIVERED);
+        if dropped > 0 {
+            tracing::warn!(dropped, "alert.notify.dropped");
+            self.undelivered.drain(..dropped);
+        }
+    }
+
+    pub async fn run(mut self, format: Format, stats: Stats, interval: Duration) {
+        loop {
+            let now_ms = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("time.after_epoch")
+                .as_millis() as u64;
+
+            // Stored spans are read at once, spans accepted meanwhile are only kept until then.
+            let notifications = self.evaluate(&format, &stats, now_ms).await;
+            self.deliver(notifications).await;
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::*;
+
+    fn error_rate(for_secs: u64) -> Rule {
+        Rule {
+            name: "errors".into(),
+            query: "{}".into(),
+            metric: Metric::ErrorRate,
+            op: Op::Gt,
+            threshold: 0.05,
+            window_secs: 300,
+            for_secs,
+        }
+    }
+
+    #[test]
+    fn alert_moves_through_states() {
+        let rule = error_rate(60);
+        let mut alert = Alert::default();
+
+        assert_eq!(alert.step(&rule, Some(0.01), 0), None);
+        assert_eq!(
+            alert.step(&rule, Some(0.1), 1_000),
+            Some((State::Pending, 1_000))
+        );
+        assert_eq!(alert.step(&rule, Some(0.1), 30_000), None);
+        assert_eq!(
+            alert.step(&rule, Some(0.1), 61_000),
+            Some((State::Firing, 61_000))
+        );
+        assert_eq!(alert.step(&rule, Some(0.2), 90_000), None);
+        assert_eq!(
+            alert.step(&rule, None, 120_000),
+            Some((State::Resolved, 61_000))
+        );
+        assert_eq!(alert, Alert::Inactive);
+
+        // Cleared before firing.
+        assert_eq!(
+            alert.step(&rule, Some(0.1), 130_000),
+            Some((State::Pending, 130_000))
+        );
+        assert_eq!(alert.step(&rule, Some(0.0), 140_000), None);
+        assert_eq!(alert, Alert::Inactive);
+
+        let rule = Rule {
+            metric: Metric::Quantile(0.99),
+            threshold: 1000.0,
+            ..error_rate(0)
+        };
+        assert_eq!(alert.step(&rule, Some(1500.0), 0), Some((State::Firing, 0)));
+    }
+
+    /// Webhook stub answering a request per connection with each status line of `responses`,
+    /// returns the URL and the bodies received.
+    fn webhook(responses: Vec<&'static str>) -> (Uri, std::thread::JoinHandle<Vec<Vec<u8>>>) {
+        let stub = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url: Uri = format!("http://{}/hook", stub.local_addr().unwrap())
+            .parse()
+            .unwrap();
+
+        let received = std::thread::spawn(move || {
+            let mut bodies = Vec::new();
+
+            for status in responses {
+                let (mut conn, _) = stub.accept().unwrap();
+                let mut buf = Vec::new();
+                let mut chunk = [0; 4096];
+
+                loop {
+                    let len = conn.read(&mut chunk).unwrap();
+                    buf.extend_from_slice(&chunk[..len]);
+
+                    let text = String::from_utf8_lossy(&buf);
+                    let Some(end) = text.find("\r\n\r\n") else {
+                        continue;
+                    };
+                    let length: usize = text
+                        .lines()
+                        .find_map(|line| {
+                            line.to_lowercase()
+                                .strip_prefix("content-length: ")
+                                .map(|v| v.parse().unwrap())
+                        })
+                        .unwrap();
+
+                    if buf.len() >= end + 4 + length {
+                        let response = format!(
+                            "HTTP/1.1 {status}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                        );
+                        conn.write_all(response.as_bytes()).unwrap();
+                        bodies.push(buf[end + 4..end + 4 + length].to_vec());
+                        break;
+                    }
+                }
+            }
+
+            bodies
+        });
+
+        (url, received)
+    }
+
+    fn notification(rule: &str, state: State) -> Notification {
+        Notification {
+            rule: rule.into(),
+            tenant: "default".into(),
+            state,
+            value: Some(0.1),
+            threshold: 0.05,
+            since_ms: 1_000,
+            at_ms: 61_000,
+        }
+    }
+
+    #[test]
+    fn notifications_reach_webhook() {
+        let (url, received) = webhook(vec!["204 No Content"]);
+        let rt = crate::testing::runtime();
+
+        rt.block_on(Notifier::new(url).notify(&notification("errors", State::Firing)))
+            .expect("webhook.notify");
+
+        let bodies = received.join().unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bodies[0]).unwrap();
+        assert_eq!(body["rule"], "errors");
+        assert_eq!(body["state"], "firing");
+        assert_eq!(body["sinceMs"], 1_000);
+    }
+
+    #[test]
+    fn failed_notifications_are_retried_in_order() {
+        let (url, received) = webhook(vec!["500 Internal Server Error", "204 OK", "204 OK"]);
+        let rt = crate::testing::runtime();
+        let mut evaluator = Evaluator::new(
+            "default",
+            vec![],
+            Some(Notifier::new(url)),
+            Window::default(),
+        );
+
+        rt.block_on(evaluator.deliver(vec![notification("errors", State::Pending)]));
+        assert_eq!(evaluator.undelivered.len(), 1);
+
+        // Delivered before the newer one on the next evaluation.
+        rt.block_on(evaluator.deliver(vec![notification("errors", State::Firing)]));
+        assert!(evaluator.undelivered.is_empty());
+
+        let states: Vec<_> = received
+            .join()
+            .unwrap()
+            .iter()
+            .map(|body| serde_json::from_slice::<Notification>(body).unwrap().state)
+            .collect();
+        assert_eq!(states, [State::Pending, State::Pending, State::Firing]);
+    }
+
+    #[test]
+    fn notifications_time_out() {
+        // Accepts the connection but never answers.
+        let stub = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url: Uri = format!("http://{}/hook", stub.local_addr().unwrap())
+            .parse()
+            .unwrap();
+
+        let rt = crate::testing::runtime();
+        let notifier = Notifier::new(url).with_timeout(Duration::from_millis(100));
+
+        assert!(matches!(
+            rt.block_on(notifier.notify(&notification("errors", State::Pending))),
+            Err(NotifyError::Request(_))
+        ));
+        drop(stub);
+    }
+
+    #[test]
+    fn quantiles_are_kept_within_bins() {
+        use crate::load::{Generator, Options as LoadOptions};
+
+        let span = Generator::new(LoadOptions::default()).trace(0).remove(0);
+        let mut series = Series::new(300);
+        let now_ms = 1_000_000;
+
+        for ms in 1..=1000 {
+            series.add(&SpanData {
+                time_end: now_ms * 1_000_000,
+                time_duration: ms * 1_000_000,
+                ..span.clone()
+            });
+        }
+
+        let p99 = series.value(Metric::Quantile(0.99), now_ms).unwrap();
+        assert!((990.0 * (1.0 - 1.0 / 32.0)..=990.0).contains(&p99), "{p99}");
+        assert_eq!(series.value(Metric::Count, now_ms), Some(1000.0));
+
+        // Slices leave the window once it starts after them, slices are 5s for 5m.
+        assert_eq!(series.value(Metric::Count, now_ms + 304_000), Some(1000.0));
+        assert_eq!(series.value(Metric::Count, now_ms + 305_000), None);
+        assert!(series.slices.is_empty());
+    }
+
+    #[test]
+    fn recent_spans_are_counted_once() {
+        use crate::load::{Generator, Options as LoadOptions, ingest};
+        use crate::testing;
+        use crate::write::Options as WriterOptions;
+
+        let mut generator = Generator::new(LoadOptions::default());
+        let mut traces = |from: u64| -> Vec<SpanData> {
+            (from..from + 10)
+                .flat_map(|idx| generator.trace(idx * 10_000_000))
+                .collect()
+        };
+        let (stored, accepted) = (traces(0), traces(10));
+        let now_ms = accepted.iter().map(|span| span.time_end).max().unwrap() / 1_000_000 + 1;
+        let rule = Rule {
+            metric: Metric::Count,
+            threshold: 0.0,
+            ..error_rate(0)
+        };
+
+        let dir = testing::temp_dir("alert");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let rt = testing::runtime();
+        let options = WriterOptions {
+            spans_per_file: 64,
+            builder_flush_threshold: 32,
+            builder_capacity: 32,
+            ..testing::writer_options()
+        };
+        let store = |spans: Vec<SpanData>| {
+            rt.block_on(ingest(
+                &Format::Arrow,
+                dir.clone().into_boxed_path(),
+                spans,
+                options,
+            ))
+        };
+
+        // Spans stored before the window is watched are read from files.
+        store(stored.clone());
+
+        let window = Window::default();
+        window.push(&accepted);
+        let mut evaluator = Evaluator::new("default", vec![rule], None, window.clone());
+        window.push(&accepted);
+
+        // Accepted spans already stored are not counted twice.
+        store(accepted[..accepted.len() / 2].to_vec());
+
+        let stats = Stats::new(&dir);
+        let notifications = rt.block_on(evaluator.evaluate(&Format::Arrow, &stats, now_ms));
+        let len = (stored.len() + accepted.len()) as f64;
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].value, Some(len));
+
+        // Later evaluations don't read files again, new spans are pushed to the window.
+        store(traces(20));
+        let notifications = rt.block_on(evaluator.evaluate(&Format::Arrow, &stats, now_ms));
+        assert!(notifications.is_empty());
+        assert_eq!(window.value(0, Metric::Count, now_ms), Some(len));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
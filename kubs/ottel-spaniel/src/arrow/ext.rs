@@ -1,12 +1,13 @@
 use arrow::array::*;
 use arrow::datatypes::*;
 
-use super::{Attribute, columns};
+use super::{Attribute, Resources, columns};
 
 pub trait AsSpanData {
     fn get_names(&self) -> impl Iterator<Item = &str>;
-    fn get_svc_names(&self) -> impl Iterator<Item = String>;
-    fn get_spans(&self) -> impl Iterator<Item = Span>;
+    fn get_svc_names(&self, resources: &Resources) -> impl Iterator<Item = String>;
+    fn get_spans(&self, resources: &Resources) -> impl Iterator<Item = Span>;
+    fn get_span_data(&self, resources: &Resources) -> impl Iterator<Item = crate::SpanData>;
 }
 
 impl AsSpanData for RecordBatch {
@@ -42,78 +43,28 @@ impl AsSpanData for RecordBatch {
         }
     }
 
-    fn get_svc_names(&self) -> impl Iterator<Item = String> {
-        use arrow::array::{Array, AsArray, ListArray};
-        use arrow::datatypes::Int8Type;
+    fn get_svc_names(&self, resources: &Resources) -> impl Iterator<Item = String> {
+        use std::collections::BTreeSet;
 
-        struct Iter<'a> {
-            names: &'a ListArray,
-            types: &'a ListArray,
-            values: &'a ListArray,
-
-            index: usize,
-        }
-        impl<'a> Iter<'a> {
-            fn new(names: &'a ListArray, types: &'a ListArray, values: &'a ListArray) -> Self {
-                assert_eq!(names.len(), types.len());
-                assert_eq!(types.len(), values.len());
-                Self {
-                    names,
-                    types,
-                    values,
-                    index: 0,
-                }
-            }
-        }
-
-        impl<'a> Iterator for Iter<'a> {
-            type Item = String;
-
-            fn next(&mut self) -> Option<Self::Item> {
-                while self.index < self.names.len() {
-                    let names = self.names.value(self.index);
-                    let names = names.as_string_view();
-
-                    let types = self.types.value(self.index);
-                    let types = types.as_primitive::<Int8Type>();
-
-                    let values = self.values.value(self.index);
-                    let values = values.as_binary_view();
-
-                    for i in 0..names.len() {
-                        if names.value(i) != "service.name" {
-                            continue;
-                        }
-
-                        if types.value(i) != Attribute::FIELD_STR {
-                            continue;
-                        }
-
-                        self.index += 1;
-                        return Some(str::from_utf8(values.value(i)).unwrap().to_owned());
-                    }
-
-                    self.index += 1;
-                }
+        let ids: BTreeSet<u32> = self
+            .column_by_name(columns::RESOURCE_ID.name())
+            .unwrap()
+            .as_primitive::<UInt32Type>()
+            .values()
+            .iter()
+            .copied()
+            .collect();
 
-                None
-            }
-        }
+        let names: Vec<String> = ids
+            .into_iter()
+            .filter_map(|id| resources.service_name(id))
+            .map(str::to_owned)
+            .collect();
 
-        Iter::new(
-            self.column_by_name(columns::RES_ATTR_NAME.name())
-                .unwrap()
-                .as_list(),
-            self.column_by_name(columns::RES_ATTR_TYPE.name())
-                .unwrap()
-                .as_list(),
-            self.column_by_name(columns::RES_ATTR_VALUE.name())
-                .unwrap()
-                .as_list(),
-        )
+        names.into_iter()
     }
 
-    fn get_spans(&self) -> impl Iterator<Item = Span> {
+    fn get_spans(&self, resources: &Resources) -> impl Iterator<Item = Span> {
         use super::columns::*;
 
         let trace_id = self
@@ -158,14 +109,10 @@ impl AsSpanData for RecordBatch {
             .unwrap()
             .as_primitive::<UInt64Type>();
 
-        let res_attr_name: &GenericListArray<i32> =
-            self.column_by_name(RES_ATTR_NAME.name()).unwrap().as_list();
-        let res_attr_type: &GenericListArray<i32> =
-            self.column_by_name(RES_ATTR_TYPE.name()).unwrap().as_list();
-        let res_attr_values: &GenericListArray<i32> = self
-            .column_by_name(RES_ATTR_VALUE.name())
+        let resource_id = self
+            .column_by_name(RESOURCE_ID.name())
             .unwrap()
-            .as_list();
+            .as_primitive::<UInt32Type>();
 
         let span_attr_name: &GenericListArray<i32> = self
             .column_by_name(SPAN_ATTR_NAME.name())
@@ -180,6 +127,15 @@ impl AsSpanData for RecordBatch {
             .unwrap()
             .as_list();
 
+        let scope_name = self
+            .column_by_name(SCOPE_NAME.name())
+            .unwrap()
+            .as_string_view();
+        let scope_version = self
+            .column_by_name(SCOPE_VERSION.name())
+            .unwrap()
+            .as_string_view();
+
         trace_id
             .iter()
             .enumerate()
@@ -216,22 +172,228 @@ impl AsSpanData for RecordBatch {
                     end_ms: time_end.value(idx) / 1_000_000,
                     duration_ms: time_duration.value(idx) / 1_000_000,
                 },
-                resource_attributes: if res_attr_name.is_null(idx) {
-                    None
-                } else {
-                    Some(Attributes::new(
-                        res_attr_name.value(idx).as_string_view(),
-                        res_attr_type.value(idx).as_primitive::<Int8Type>(),
-                        res_attr_values.value(idx).as_binary_view(),
-                    ))
+                resource_attributes: {
+                    let attrs = resources.get(resource_id.value(idx));
+
+                    if attrs.is_empty() {
+                        None
+                    } else {
+                        Some(Attributes::from_key_values(attrs))
+                    }
                 },
                 attributes: Attributes::new(
                     span_attr_name.value(idx).as_string_view(),
                     span_attr_type.value(idx).as_primitive::<Int8Type>(),
                     span_attr_values.value(idx).as_binary_view(),
                 ),
+                scope: if scope_name.is_null(idx) {
+                    None
+                } else {
+                    Some(Scope {
+                        name: scope_name.value(idx).to_owned(),
+                        version: if scope_version.is_null(idx) {
+                            None
+                        } else {
+                            Some(scope_version.value(idx).to_owned())
+                        },
+                    })
+                },
             })
     }
+
+    fn get_span_data(&self, resources: &Resources) -> impl Iterator<Item = crate::SpanData> {
+        use super::columns::*;
+        use std::sync::Arc;
+
+        let trace_id = self
+            .column_by_name(TRACE_ID.name())
+            .unwrap()
+            .as_fixed_size_binary();
+        let span_id = self
+            .column_by_name(SPAN_ID.name())
+            .unwrap()
+            .as_primitive::<Int64Type>();
+        let span_name = self
+            .column_by_name(SPAN_NAME.name())
+            .unwrap()
+            .as_string_view();
+        let span_kind = self
+            .column_by_name(SPAN_KIND.name())
+            .unwrap()
+            .as_primitive::<Int32Type>();
+        let parent_span_id = self
+            .column_by_name(PARENT_SPAN_ID.name())
+            .unwrap()
+            .as_primitive::<Int64Type>();
+        let status_code = self
+            .column_by_name(STATUS_CODE.name())
+            .unwrap()
+            .as_primitive::<Int32Type>();
+        let status_message = self
+            .column_by_name(STATUS_MESSAGE.name())
+            .unwrap()
+            .as_string_view();
+
+        let time_start = self
+            .column_by_name(TIME_START.name())
+            .unwrap()
+            .as_primitive::<UInt64Type>();
+        let time_end = self
+            .column_by_name(TIME_END.name())
+            .unwrap()
+            .as_primitive::<UInt64Type>();
+        let time_duration = self
+            .column_by_name(TIME_DURATION.name())
+            .unwrap()
+            .as_primitive::<UInt64Type>();
+
+        let resource_id = self
+            .column_by_name(RESOURCE_ID.name())
+            .unwrap()
+            .as_primitive::<UInt32Type>();
+
+        let span_attr_name: &GenericListArray<i32> = self
+            .column_by_name(SPAN_ATTR_NAME.name())
+            .unwrap()
+            .as_list();
+        let span_attr_type: &GenericListArray<i32> = self
+            .column_by_name(SPAN_ATTR_TYPE.name())
+            .unwrap()
+            .as_list();
+        let span_attr_values: &GenericListArray<i32> = self
+            .column_by_name(SPAN_ATTR_VALUE.name())
+            .unwrap()
+            .as_list();
+
+        let trace_state = self
+            .column_by_name(TRACE_STATE.name())
+            .unwrap()
+            .as_string_view();
+        let flags = self
+            .column_by_name(FLAGS.name())
+            .unwrap()
+            .as_primitive::<UInt32Type>();
+        let dropped_attributes = self
+            .column_by_name(DROPPED_ATTRIBUTES.name())
+            .unwrap()
+            .as_primitive::<UInt32Type>();
+        let dropped_events = self
+            .column_by_name(DROPPED_EVENTS.name())
+            .unwrap()
+            .as_primitive::<UInt32Type>();
+        let dropped_links = self
+            .column_by_name(DROPPED_LINKS.name())
+            .unwrap()
+            .as_primitive::<UInt32Type>();
+        let res_schema_url = self
+            .column_by_name(RES_SCHEMA_URL.name())
+            .unwrap()
+            .as_string_view();
+
+        let scope_name = self
+            .column_by_name(SCOPE_NAME.name())
+            .unwrap()
+            .as_string_view();
+        let scope_version = self
+            .column_by_name(SCOPE_VERSION.name())
+            .unwrap()
+            .as_string_view();
+        let scope_schema_url = self
+            .column_by_name(SCOPE_SCHEMA_URL.name())
+            .unwrap()
+            .as_string_view();
+        let scope_attr_name: &GenericListArray<i32> = self
+            .column_by_name(SCOPE_ATTR_NAME.name())
+            .unwrap()
+            .as_list();
+        let scope_attr_type: &GenericListArray<i32> = self
+            .column_by_name(SCOPE_ATTR_TYPE.name())
+            .unwrap()
+            .as_list();
+        let scope_attr_values: &GenericListArray<i32> = self
+            .column_by_name(SCOPE_ATTR_VALUE.name())
+            .unwrap()
+            .as_list();
+
+        let string = |arr: &StringViewArray, idx: usize| {
+            if arr.is_null(idx) {
+                None
+            } else {
+                Some(arr.value(idx).to_owned())
+            }
+        };
+
+        (0..self.num_rows()).map(move |idx| crate::SpanData {
+            trace_id: trace_id.value(idx).try_into().unwrap(),
+            span_id: span_id.value(idx).to_be_bytes(),
+            parent_span_id: if parent_span_id.is_null(idx) {
+                None
+            } else {
+                Some(parent_span_id.value(idx).to_be_bytes())
+            },
+            name: span_name.value(idx).to_owned(),
+            kind: span_kind.value(idx),
+            status_code: if status_code.is_null(idx) {
+                None
+            } else {
+                Some(status_code.value(idx))
+            },
+            status_message: if status_message.is_null(idx) {
+                None
+            } else {
+                Some(status_message.value(idx).to_owned())
+            },
+            time_start: time_start.value(idx),
+            time_end: time_end.value(idx),
+            time_duration: time_duration.value(idx),
+            span_attributes: key_values(
+                span_attr_name.value(idx).as_string_view(),
+                span_attr_type.value(idx).as_primitive::<Int8Type>(),
+                span_attr_values.value(idx).as_binary_view(),
+            ),
+            resource_attributes: resources.get(resource_id.value(idx)).clone(),
+            trace_state: string(trace_state, idx),
+            flags: flags.value(idx),
+            dropped_attributes_count: dropped_attributes.value(idx),
+            dropped_events_count: dropped_events.value(idx),
+            dropped_links_count: dropped_links.value(idx),
+            resource_schema_url: string(res_schema_url, idx),
+            scope: Arc::new(crate::ScopeData {
+                name: string(scope_name, idx),
+                version: string(scope_version, idx),
+                attributes: if scope_attr_name.is_null(idx) {
+                    Vec::new()
+                } else {
+                    key_values(
+                        scope_attr_name.value(idx).as_string_view(),
+                        scope_attr_type.value(idx).as_primitive::<Int8Type>(),
+                        scope_attr_values.value(idx).as_binary_view(),
+                    )
+                },
+                schema_url: string(scope_schema_url, idx),
+            }),
+        })
+    }
+}
+
+pub(crate) fn key_values(
+    names: &StringViewArray,
+    types: &PrimitiveArray<Int8Type>,
+    values: &BinaryViewArray,
+) -> Vec<opentelemetry_proto::tonic::common::v1::KeyValue> {
+    use opentelemetry_proto::tonic::common::v1::{AnyValue, KeyValue};
+
+    // Corrupt values are skipped rather than failing the whole read.
+    (0..names.len())
+        .filter_map(|idx| {
+            Some(KeyValue {
+                key: names.value(idx).to_owned(),
+                value: Some(AnyValue {
+                    value: Some(Attribute::decode(types.value(idx), values.value(idx))?),
+                }),
+            })
+        })
+        .collect()
 }
 
 fn read_hex<const SIZE: usize>(value: &[u8]) -> String {
@@ -254,6 +416,38 @@ pub struct Span {
     time: Time,
     attributes: Attributes,
     resource_attributes: Option<Attributes>,
+    scope: Option<Scope>,
+}
+
+impl From<&crate::SpanData> for Span {
+    fn from(data: &crate::SpanData) -> Self {
+        Self {
+            trace_id: read_hex::<{ 16 * 2 }>(&data.trace_id),
+            span_id: read_hex::<{ 8 * 2 }>(&data.span_id),
+            parent_span_id: data.parent_span_id.map(|id| read_hex::<{ 8 * 2 }>(&id)),
+            name: data.name.clone(),
+            kind: Some(data.kind),
+            status: data.status_code.map(|code| Status {
+                code,
+                message: data.status_message.clone(),
+            }),
+            time: Time {
+                start_ms: data.time_start / 1_000_000,
+                end_ms: data.time_end / 1_000_000,
+                duration_ms: data.time_duration / 1_000_000,
+            },
+            attributes: Attributes::from_key_values(&data.span_attributes),
+            resource_attributes: if data.resource_attributes.is_empty() {
+                None
+            } else {
+                Some(Attributes::from_key_values(&data.resource_attributes))
+            },
+            scope: data.scope.name.as_ref().map(|name| Scope {
+                name: name.clone(),
+                version: data.scope.version.clone(),
+            }),
+        }
+    }
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -263,6 +457,13 @@ pub struct Status {
     message: Option<String>,
 }
 
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Scope {
+    name: String,
+    version: Option<String>,
+}
+
 #[derive(Debug, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Time {
@@ -284,28 +485,281 @@ impl Attributes {
         types: &PrimitiveArray<Int8Type>,
         values: &BinaryViewArray,
     ) -> Self {
-        use opentelemetry_proto::tonic::common::v1::any_value::Value;
+        let (keys, values) = (0..names.len())
+            .filter_map(|idx| {
+                let value = Attribute::decode(types.value(idx), values.value(idx))?;
+                Some((names.value(idx).to_owned(), value))
+            })
+            .unzip();
+
+        Self { keys, values }
+    }
 
-        let keys = names.iter().map(|v| String::from(v.unwrap())).collect();
-        let values = types
+    fn from_key_values(attrs: &[opentelemetry_proto::tonic::common::v1::KeyValue]) -> Self {
+        let attrs = attrs
             .iter()
-            .enumerate()
-            .map(|(idx, t)| match t.unwrap() {
-                Attribute::FIELD_BOOL_T => Value::BoolValue(true),
-                Attribute::FIELD_BOOL_F => Value::BoolValue(false),
-                Attribute::FIELD_STR => {
-                    Value::StringValue(String::from_utf8(values.value(idx).to_owned()).unwrap())
-                }
-                Attribute::FIELD_NUM_I => Value::IntValue(i64::from_be_bytes(unsafe {
-                    *(values.value(idx) as *const _ as *const [u8; 8])
-                })),
-                Attribute::FIELD_NUM_F => Value::DoubleValue(f64::from_be_bytes(unsafe {
-                    *(values.value(idx) as *const _ as *const [u8; 8])
-                })),
-                _ => unreachable!(),
-            })
-            .collect();
+            .filter_map(|kv| Some((kv.key.clone(), kv.value.clone()?.value?)));
+        let (keys, values) = attrs.unzip();
 
         Self { keys, values }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use opentelemetry_proto::tonic::common::v1::any_value::Value;
+    use opentelemetry_proto::tonic::common::v1::{AnyValue, ArrayValue, KeyValue, KeyValueList};
+    use parquet::arrow::ArrowWriter;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use super::*;
+    use crate::SpanBuilder;
+    use crate::arrow::resource::Dictionary;
+    use crate::arrow::{Builder, SCHEMA};
+
+    fn kv(key: &str, value: Value) -> KeyValue {
+        KeyValue {
+            key: key.to_owned(),
+            value: Some(AnyValue { value: Some(value) }),
+        }
+    }
+
+    fn every_variant() -> Vec<KeyValue> {
+        let nested = ArrayValue {
+            values: vec![
+                AnyValue {
+                    value: Some(Value::StringValue("a".to_owned())),
+                },
+                AnyValue {
+                    value: Some(Value::IntValue(-1)),
+                },
+                AnyValue {
+                    value: Some(Value::ArrayValue(ArrayValue {
+                        values: vec![AnyValue {
+                            value: Some(Value::BoolValue(true)),
+                        }],
+                    })),
+                },
+            ],
+        };
+
+        vec![
+            kv("string", Value::StringValue("value".to_owned())),
+            kv("bool.true", Value::BoolValue(true)),
+            kv("bool.false", Value::BoolValue(false)),
+            kv("int", Value::IntValue(i64::MIN)),
+            kv("double", Value::DoubleValue(-0.5)),
+            kv("bytes", Value::BytesValue(vec![0, 1, 255])),
+            kv("bytes.empty", Value::BytesValue(vec![])),
+            kv("array", Value::ArrayValue(nested.clone())),
+            kv("array.empty", Value::ArrayValue(ArrayValue::default())),
+            kv(
+                "kvlist",
+                Value::KvlistValue(KeyValueList {
+                    values: vec![
+                        kv("inner.double", Value::DoubleValue(1.25)),
+                        kv("inner.array", Value::ArrayValue(nested)),
+                        kv(
+                            "inner.kvlist",
+                            Value::KvlistValue(KeyValueList {
+                                values: vec![kv("deep", Value::BytesValue(vec![7]))],
+                            }),
+                        ),
+                    ],
+                }),
+            ),
+        ]
+    }
+
+    fn span(span_attributes: Vec<KeyValue>, resource_attributes: Vec<KeyValue>) -> crate::SpanData {
+        crate::SpanData {
+            trace_id: [1; 16],
+            span_id: [2; 8],
+            parent_span_id: Some([3; 8]),
+            name: "span".to_owned(),
+            kind: 1,
+            status_code: None,
+            status_message: None,
+            time_start: 1_000,
+            time_end: 3_000,
+            time_duration: 2_000,
+            trace_state: None,
+            flags: 0,
+            dropped_attributes_count: 0,
+            dropped_events_count: 0,
+            dropped_links_count: 0,
+            span_attributes,
+            resource_attributes: Arc::new(resource_attributes),
+            resource_schema_url: None,
+            scope: Arc::default(),
+        }
+    }
+
+    fn store(batches: Vec<Vec<crate::SpanData>>) -> Vec<u8> {
+        let mut resources = Dictionary::default();
+        let mut buf = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut buf, SCHEMA.clone(), None).unwrap();
+
+        for data in batches {
+            let mut builder = Builder::new(data.len(), data.len());
+            builder.append(data);
+            let batch = builder.build();
+
+            writer
+                .write(&resources.remap(batch.data, &batch.resources))
+                .unwrap();
+        }
+
+        writer.append_key_value_metadata(parquet::file::metadata::KeyValue::new(
+            Resources::METADATA_KEY.to_owned(),
+            resources.take().encode(),
+        ));
+        writer.close().unwrap();
+
+        buf
+    }
+
+    fn load(buf: Vec<u8>) -> (Resources, Vec<crate::SpanData>) {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(buf)).unwrap();
+        let resources =
+            Resources::from_metadata(builder.metadata().file_metadata().key_value_metadata())
+                .unwrap();
+
+        let data = builder
+            .build()
+            .unwrap()
+            .flat_map(|batch| batch.unwrap().get_span_data(&resources).collect::<Vec<_>>())
+            .collect();
+
+        (resources, data)
+    }
+
+    fn store_and_load(data: Vec<crate::SpanData>) -> Vec<crate::SpanData> {
+        load(store(vec![data])).1
+    }
+
+    #[test]
+    fn resources_are_stored_once_per_file() {
+        let service = |name: &str| vec![kv("service.name", Value::StringValue(name.to_owned()))];
+
+        let first = Arc::new(service("first"));
+        let mut a = span(vec![], vec![]);
+        a.resource_attributes = first.clone();
+        let mut b = span(vec![], vec![]);
+        b.resource_attributes = first;
+        // Same content, different allocation.
+        let c = span(vec![], service("first"));
+        let d = span(vec![], service("second"));
+        let e = span(vec![], service("first"));
+
+        let (resources, loaded) = load(store(vec![vec![a, b, c, d], vec![e]]));
+
+        assert_eq!(resources.len(), 2);
+        assert_eq!(resources.service_name(0), Some("first"));
+        assert_eq!(resources.service_name(1), Some("second"));
+
+        let names: Vec<_> = loaded
+            .iter()
+            .map(|span| span.resource_attributes.as_ref().clone())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                service("first"),
+                service("first"),
+                service("first"),
+                service("second"),
+                service("first"),
+            ]
+        );
+        assert!(Arc::ptr_eq(
+            &loaded[0].resource_attributes,
+            &loaded[4].resource_attributes
+        ));
+    }
+
+    #[test]
+    fn every_any_value_survives_store_and_load() {
+        let loaded = store_and_load(vec![span(every_variant(), every_variant())]);
+
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].span_attributes, every_variant());
+        assert_eq!(*loaded[0].resource_attributes, every_variant());
+    }
+
+    #[test]
+    fn attributes_stay_aligned_with_their_span() {
+        let first = vec![kv("array", Value::ArrayValue(ArrayValue::default()))];
+        let second = vec![
+            kv("bytes", Value::BytesValue(vec![1])),
+            kv("string", Value::StringValue("b".to_owned())),
+        ];
+
+        let loaded = store_and_load(vec![
+            span(first.clone(), vec![]),
+            span(second.clone(), first.clone()),
+        ]);
+
+        assert_eq!(loaded[0].span_attributes, first);
+        assert!(loaded[0].resource_attributes.is_empty());
+        assert_eq!(loaded[1].span_attributes, second);
+        assert_eq!(*loaded[1].resource_attributes, first);
+    }
+
+    #[test]
+    fn scope_and_span_metadata_survive_store_and_load() {
+        let scope = crate::ScopeData {
+            name: Some("tracing-opentelemetry".to_owned()),
+            version: Some("0.31.0".to_owned()),
+            attributes: every_variant(),
+            schema_url: Some("https://opentelemetry.io/schemas/1.26.0".to_owned()),
+        };
+
+        let mut data = span(vec![], vec![]);
+        data.trace_state = Some("vendor=value".to_owned());
+        data.flags = 0x301;
+        data.dropped_attributes_count = 1;
+        data.dropped_events_count = 2;
+        data.dropped_links_count = 3;
+        data.resource_schema_url = Some("https://opentelemetry.io/schemas/1.24.0".to_owned());
+        data.scope = Arc::new(scope.clone());
+
+        let loaded = store_and_load(vec![data, span(vec![], vec![])]);
+
+        assert_eq!(loaded[0].trace_state.as_deref(), Some("vendor=value"));
+        assert_eq!(loaded[0].flags, 0x301);
+        assert_eq!(loaded[0].dropped_attributes_count, 1);
+        assert_eq!(loaded[0].dropped_events_count, 2);
+        assert_eq!(loaded[0].dropped_links_count, 3);
+        assert_eq!(
+            loaded[0].resource_schema_url.as_deref(),
+            Some("https://opentelemetry.io/schemas/1.24.0")
+        );
+        assert_eq!(*loaded[0].scope, scope);
+        assert_eq!(*loaded[1].scope, crate::ScopeData::default());
+        assert_eq!(loaded[1].trace_state, None);
+    }
+
+    #[test]
+    fn corrupt_values_are_skipped() {
+        assert_eq!(Attribute::decode(Attribute::FIELD_NUM_I, &[1, 2, 3]), None);
+        assert_eq!(Attribute::decode(Attribute::FIELD_STR, &[0xff, 0xfe]), None);
+        assert_eq!(Attribute::decode(Attribute::FIELD_ARRAY, &[0xff]), None);
+        assert_eq!(Attribute::decode(42, &[]), None);
+        assert_eq!(
+            Attribute::decode(Attribute::FIELD_NUM_I, &7i64.to_be_bytes()),
+            Some(Value::IntValue(7))
+        );
+    }
+
+    #[test]
+    fn span_json_contains_nested_values() {
+        let data = span(every_variant(), vec![]);
+        let json = serde_json::to_value(Span::from(&data)).unwrap();
+        let values = json["attributes"]["values"].as_array().unwrap();
+
+        assert_eq!(values.len(), every_variant().len());
+    }
+}
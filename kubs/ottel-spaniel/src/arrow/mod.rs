@@ -1,12 +1,18 @@
 mod build;
 pub mod ext;
 mod read;
+mod resource;
 mod schema;
+pub mod stream;
 mod write;
 
-pub(crate) use build::Builder;
+pub(crate) use build::{Batch, Builder};
 pub(crate) use write::Writer;
 
 pub use ext::AsSpanData;
-pub use read::{Boolean, CustomFilter, Filter, Null, Read};
+pub use read::{
+    AttrFilter, Boolean, CustomFilter, FileSchema, Filter, Null, Read, file_summary,
+    load_resource_table, load_resources,
+};
+pub use resource::Resources;
 pub use schema::{Attribute, SCHEMA, columns};
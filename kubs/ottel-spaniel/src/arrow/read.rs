@@ -1,20 +1,94 @@
+use std::collections::HashMap;
+use std::ops::Range;
 use std::path::Path;
 use std::sync::Arc;
 
-use arrow::array::{Array, BooleanArray, Datum, RecordBatch};
+use arrow::array::{Array, ArrayRef, BooleanArray, Datum, RecordBatch, UInt32Array};
+use arrow::datatypes::{Field, SchemaRef};
 use arrow::error::ArrowError;
+use opentelemetry_proto::tonic::common::v1::any_value::Value as AnyValue;
 use parquet::arrow::ArrowSchemaConverter;
 use parquet::arrow::ProjectionMask;
-use parquet::arrow::arrow_reader::{ArrowPredicate, RowFilter};
+use parquet::arrow::arrow_reader::{ArrowPredicate, RowFilter, RowSelection};
 use parquet::arrow::arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder};
+use parquet::errors::ParquetError;
+use parquet::file::metadata::{ParquetMetaData, ParquetMetaDataReader};
+use parquet::file::reader::ChunkReader;
+use parquet::file::statistics::Statistics;
 use parquet::schema::types::SchemaDescriptor;
+use tokio::sync::mpsc;
 
-use super::SCHEMA;
+use super::{Attribute, Resources, SCHEMA, columns};
+use crate::scan::{self, Scan};
+use crate::tier::{RemoteFile, Tier};
 
 pub trait CustomFilter: ArrowPredicate + Sync {
     fn eval(&self, batch: &RecordBatch) -> Result<BooleanArray, ArrowError>;
 
     fn cloned(&self) -> Box<dyn CustomFilter>;
+
+    /// Prepares the filter for the columns and the resource table of a file.
+    fn bind(&self, file: &FileSchema) -> Box<dyn CustomFilter>;
+}
+
+/// Layout of a stored file, files written by older versions lack columns added since.
+pub struct FileSchema<'a> {
+    schema: &'a SchemaDescriptor,
+    resources: &'a Resources,
+}
+
+impl FileSchema<'_> {
+    pub fn has(&self, column: &str) -> bool {
+        self.schema
+            .root_schema()
+            .get_fields()
+            .iter()
+            .any(|field| field.name() == column)
+    }
+
+    /// Whether the file was written before resources were stored once per file, see
+    /// [Resources::from_legacy].
+    pub fn is_legacy(&self) -> bool {
+        !self.has(columns::RESOURCE_ID.name()) && self.has(columns::RES_ATTR_NAME.name())
+    }
+
+    /// Projection of the columns of `names` the file has.
+    pub fn mask(&self, names: &[&str]) -> ProjectionMask {
+        let present: Vec<&str> = names
+            .iter()
+            .copied()
+            .filter(|column| self.has(column))
+            .collect();
+
+        // Batches without columns would lose their row count.
+        if present.is_empty() {
+            return ProjectionMask::columns(self.schema, [columns::TIME_START.name()]);
+        }
+
+        ProjectionMask::columns(self.schema, present)
+    }
+}
+
+/// Column of [SCHEMA] filled in for files written before it existed: nulls, or zeros for
+/// counters and flags.
+fn missing_column(field: &Field, len: usize) -> ArrayRef {
+    if field.is_nullable() {
+        return arrow::array::new_null_array(field.data_type(), len);
+    }
+
+    arrow::compute::cast(&UInt32Array::from(vec![0; len]), field.data_type())
+        .expect("column.default")
+}
+
+/// Column `name` of a batch, or its default when the file lacks it.
+fn column(batch: &RecordBatch, name: &str) -> ArrayRef {
+    match batch.column_by_name(name) {
+        Some(column) => column.clone(),
+        None => missing_column(
+            SCHEMA.field_with_name(name).expect("column.known"),
+            batch.num_rows(),
+        ),
+    }
 }
 
 #[derive(Clone)]
@@ -44,14 +118,19 @@ impl Null {
 
 impl CustomFilter for Null {
     fn eval(&self, batch: &RecordBatch) -> Result<BooleanArray, ArrowError> {
-        let col = batch.column_by_name(&self.col_name).expect("col.exists");
-
-        (self.function)(col)
+        (self.function)(&column(batch, &self.col_name))
     }
 
     fn cloned(&self) -> Box<dyn CustomFilter> {
         Box::new(self.clone())
     }
+
+    fn bind(&self, file: &FileSchema) -> Box<dyn CustomFilter> {
+        Box::new(Self {
+            mask: file.mask(&[&*self.col_name]),
+            ..self.clone()
+        })
+    }
 }
 
 impl ArrowPredicate for Null {
@@ -90,7 +169,17 @@ impl Boolean {
         Self {
             mask: Self::mask(filters.as_slice()),
             filters,
-            function: arrow::compute::kernels::boolean::and,
+            function: arrow::compute::kernels::boolean::and_kleene,
+        }
+    }
+
+    pub fn or(filters: Vec<Arc<dyn CustomFilter>>) -> Self {
+        assert!(!filters.is_empty());
+
+        Self {
+            mask: Self::mask(filters.as_slice()),
+            filters,
+            function: arrow::compute::kernels::boolean::or_kleene,
         }
     }
 }
@@ -110,6 +199,20 @@ impl CustomFilter for Boolean {
     fn cloned(&self) -> Box<dyn CustomFilter> {
         Box::new(self.clone())
     }
+
+    fn bind(&self, file: &FileSchema) -> Box<dyn CustomFilter> {
+        let filters: Vec<Arc<dyn CustomFilter>> = self
+            .filters
+            .iter()
+            .map(|filter| Arc::from(filter.bind(file)))
+            .collect();
+
+        Box::new(Self {
+            mask: Self::mask(&filters),
+            filters,
+            function: self.function,
+        })
+    }
 }
 
 impl ArrowPredicate for Boolean {
@@ -153,6 +256,43 @@ impl Filter {
         }
     }
 
+    pub fn new_i32(schema: &SchemaDescriptor, column: &str, value: i32) -> Self {
+        use arrow::array::Int32Array;
+
+        Self {
+            mask: ProjectionMask::columns(schema, [column]),
+            col_name: Arc::from(column),
+            value: Arc::new(Int32Array::new_scalar(value)),
+            function: arrow::compute::kernels::cmp::eq,
+        }
+    }
+
+    pub fn new_trace_id(schema: &SchemaDescriptor, column: &str, value: [u8; 16]) -> Self {
+        use arrow::array::FixedSizeBinaryArray;
+
+        Self {
+            mask: ProjectionMask::columns(schema, [column]),
+            col_name: Arc::from(column),
+            value: Arc::new(FixedSizeBinaryArray::new_scalar(value)),
+            function: arrow::compute::kernels::cmp::eq,
+        }
+    }
+
+    pub fn neq(mut self) -> Self {
+        self.function = arrow::compute::kernels::cmp::neq;
+        self
+    }
+
+    pub fn gt(mut self) -> Self {
+        self.function = arrow::compute::kernels::cmp::gt;
+        self
+    }
+
+    pub fn lt(mut self) -> Self {
+        self.function = arrow::compute::kernels::cmp::lt;
+        self
+    }
+
     pub fn gte(mut self) -> Self {
         self.function = arrow::compute::kernels::cmp::gt_eq;
         self
@@ -176,14 +316,19 @@ impl Filter {
 
 impl CustomFilter for Filter {
     fn eval(&self, batch: &RecordBatch) -> Result<BooleanArray, ArrowError> {
-        let col = batch.column_by_name(&self.col_name).expect("col.exists");
-
-        (self.function)(col, &*self.value)
+        (self.function)(&column(batch, &self.col_name), &*self.value)
     }
 
     fn cloned(&self) -> Box<dyn CustomFilter> {
         Box::new(self.clone())
     }
+
+    fn bind(&self, file: &FileSchema) -> Box<dyn CustomFilter> {
+        Box::new(Self {
+            mask: file.mask(&[&*self.col_name]),
+            ..self.clone()
+        })
+    }
 }
 
 impl ArrowPredicate for Filter {
@@ -196,39 +341,573 @@ impl ArrowPredicate for Filter {
     }
 }
 
+/// Matches rows where any attribute named `key` satisfies `predicate`.
+#[derive(Clone)]
+pub struct AttrFilter {
+    mask: ProjectionMask,
+    source: Source,
+    key: Arc<str>,
+    predicate: Arc<dyn Fn(&AnyValue) -> bool + Send + Sync>,
+}
+
+#[derive(Clone)]
+enum Source {
+    /// Name, type and value list columns.
+    List([Arc<str>; 3]),
+    /// Resource table of the file, `matches` is set by [CustomFilter::bind].
+    Resource {
+        column: Arc<str>,
+        matches: Arc<[bool]>,
+    },
+}
+
+impl AttrFilter {
+    fn list(
+        schema: &SchemaDescriptor,
+        columns: [&str; 3],
+        key: &str,
+        predicate: Arc<dyn Fn(&AnyValue) -> bool + Send + Sync>,
+    ) -> Self {
+        Self {
+            mask: ProjectionMask::columns(schema, columns),
+            source: Source::List(columns.map(Arc::from)),
+            key: Arc::from(key),
+            predicate,
+        }
+    }
+
+    pub fn span(
+        schema: &SchemaDescriptor,
+        key: &str,
+        predicate: Arc<dyn Fn(&AnyValue) -> bool + Send + Sync>,
+    ) -> Self {
+        use super::columns::{SPAN_ATTR_NAME, SPAN_ATTR_TYPE, SPAN_ATTR_VALUE};
+
+        Self::list(
+            schema,
+            [
+                SPAN_ATTR_NAME.name(),
+                SPAN_ATTR_TYPE.name(),
+                SPAN_ATTR_VALUE.name(),
+            ],
+            key,
+            predicate,
+        )
+    }
+
+    pub fn resource(
+        schema: &SchemaDescriptor,
+        key: &str,
+        predicate: Arc<dyn Fn(&AnyValue) -> bool + Send + Sync>,
+    ) -> Self {
+        use super::columns::RESOURCE_ID;
+
+        Self {
+            mask: ProjectionMask::columns(schema, [RESOURCE_ID.name()]),
+            source: Source::Resource {
+                column: Arc::from(RESOURCE_ID.name()),
+                matches: Arc::from([]),
+            },
+            key: Arc::from(key),
+            predicate,
+        }
+    }
+
+    pub fn scope(
+        schema: &SchemaDescriptor,
+        key: &str,
+        predicate: Arc<dyn Fn(&AnyValue) -> bool + Send + Sync>,
+    ) -> Self {
+        use super::columns::{SCOPE_ATTR_NAME, SCOPE_ATTR_TYPE, SCOPE_ATTR_VALUE};
+
+        Self::list(
+            schema,
+            [
+                SCOPE_ATTR_NAME.name(),
+                SCOPE_ATTR_TYPE.name(),
+                SCOPE_ATTR_VALUE.name(),
+            ],
+            key,
+            predicate,
+        )
+    }
+
+    fn eval_list(
+        &self,
+        batch: &RecordBatch,
+        [names, types, values]: &[Arc<str>; 3],
+    ) -> Result<BooleanArray, ArrowError> {
+        use arrow::array::{AsArray, BooleanBuilder};
+        use arrow::datatypes::Int8Type;
+
+        let (names, types, values) = (
+            column(batch, names),
+            column(batch, types),
+            column(batch, values),
+        );
+        let (names, types, values) = (
+            names.as_list::<i32>(),
+            types.as_list::<i32>(),
+            values.as_list::<i32>(),
+        );
+
+        let mut result = BooleanBuilder::with_capacity(batch.num_rows());
+
+        for idx in 0..batch.num_rows() {
+            if names.is_null(idx) {
+                result.append_value(false);
+                continue;
+            }
+
+            let names = names.value(idx);
+            let names = names.as_string_view();
+            let types = types.value(idx);
+            let types = types.as_primitive::<Int8Type>();
+            let values = values.value(idx);
+            let values = values.as_binary_view();
+
+            let found = (0..names.len()).any(|i| {
+                names.value(i) == &*self.key
+                    && Attribute::decode(types.value(i), values.value(i))
+                        .is_some_and(|value| (self.predicate)(&value))
+            });
+
+            result.append_value(found);
+        }
+
+        Ok(result.finish())
+    }
+}
+
+impl CustomFilter for AttrFilter {
+    fn eval(&self, batch: &RecordBatch) -> Result<BooleanArray, ArrowError> {
+        use arrow::array::AsArray;
+        use arrow::datatypes::UInt32Type;
+
+        match &self.source {
+            Source::List(columns) => self.eval_list(batch, columns),
+            Source::Resource {
+                column: name,
+                matches,
+            } => {
+                let ids = column(batch, name);
+                let ids = ids.as_primitive::<UInt32Type>();
+
+                Ok(ids
+                    .iter()
+                    .map(|id| id.map(|id| matches.get(id as usize).copied().unwrap_or(false)))
+                    .collect())
+            }
+        }
+    }
+
+    fn cloned(&self) -> Box<dyn CustomFilter> {
+        Box::new(self.clone())
+    }
+
+    fn bind(&self, file: &FileSchema) -> Box<dyn CustomFilter> {
+        use super::columns::{RES_ATTR_NAME, RES_ATTR_TYPE, RES_ATTR_VALUE};
+
+        let column = match &self.source {
+            Source::List(columns) => {
+                return Box::new(Self {
+                    mask: file.mask(&columns.each_ref().map(|column| &**column)),
+                    ..self.clone()
+                });
+            }
+            // Older files keep the attributes of the resource in every row.
+            Source::Resource { .. } if file.is_legacy() => {
+                let columns = [
+                    RES_ATTR_NAME.name(),
+                    RES_ATTR_TYPE.name(),
+                    RES_ATTR_VALUE.name(),
+                ];
+
+                return Box::new(Self {
+                    mask: file.mask(&columns),
+                    source: Source::List(columns.map(Arc::from)),
+                    ..self.clone()
+                });
+            }
+            Source::Resource { column, .. } => column,
+        };
+
+        let matches = file
+            .resources
+            .iter()
+            .map(|attrs| {
+                attrs.iter().any(|kv| {
+                    kv.key == *self.key
+                        && kv
+                            .value
+                            .as_ref()
+                            .and_then(|v| v.value.as_ref())
+                            .is_some_and(|v| (self.predicate)(v))
+                })
+            })
+            .collect();
+
+        Box::new(Self {
+            mask: file.mask(&[&**column]),
+            source: Source::Resource {
+                column: column.clone(),
+                matches,
+            },
+            ..self.clone()
+        })
+    }
+}
+
+impl ArrowPredicate for AttrFilter {
+    fn projection(&self) -> &ProjectionMask {
+        &self.mask
+    }
+
+    fn evaluate(&mut self, batch: RecordBatch) -> Result<BooleanArray, ArrowError> {
+        self.eval(&batch)
+    }
+}
+
+/// Turns batches of a file into batches of the selected [SCHEMA] columns.
+struct Evolve {
+    schema: SchemaRef,
+    /// Resource ids are assigned per batch, see [Resources::from_legacy].
+    legacy: bool,
+}
+
+impl Evolve {
+    fn new(select: Option<&[String]>, file: &FileSchema) -> Self {
+        let mut indices: Vec<usize> = match select {
+            Some(select) => select
+                .iter()
+                .filter_map(|name| SCHEMA.index_of(name).ok())
+                .collect(),
+            None => (0..SCHEMA.fields().len()).collect(),
+        };
+        indices.sort_unstable();
+        indices.dedup();
+
+        let schema = SCHEMA.project(&indices).expect("schema.project");
+        let legacy = file.is_legacy() && schema.index_of(columns::RESOURCE_ID.name()).is_ok();
+
+        Self {
+            schema: Arc::new(schema),
+            legacy,
+        }
+    }
+
+    /// Columns read from the file.
+    fn columns(&self) -> Vec<&str> {
+        use super::columns::{RES_ATTR_NAME, RES_ATTR_TYPE, RES_ATTR_VALUE, RESOURCE_ID};
+
+        let mut columns: Vec<&str> = self
+            .schema
+            .fields()
+            .iter()
+            .map(|field| field.name().as_str())
+            .collect();
+
+        if self.legacy {
+            columns.retain(|name| *name != RESOURCE_ID.name());
+            columns.extend([
+                RES_ATTR_NAME.name(),
+                RES_ATTR_TYPE.name(),
+                RES_ATTR_VALUE.name(),
+            ]);
+        }
+
+        columns
+    }
+
+    fn apply(
+        &self,
+        batch: RecordBatch,
+        resources: &Arc<Resources>,
+    ) -> Result<(RecordBatch, Arc<Resources>), ArrowError> {
+        if !self.legacy && batch.schema().fields() == self.schema.fields() {
+            return Ok((batch, resources.clone()));
+        }
+
+        let (ids, resources) = if self.legacy {
+            let (ids, resources) = Resources::from_legacy(&batch);
+            (Some(Arc::new(ids) as ArrayRef), Arc::new(resources))
+        } else {
+            (None, resources.clone())
+        };
+
+        let columns = self
+            .schema
+            .fields()
+            .iter()
+            .map(|field| {
+                if field.name() == columns::RESOURCE_ID.name()
+                    && let Some(ids) = &ids
+                {
+                    return ids.clone();
+                }
+
+                match batch.column_by_name(field.name()) {
+                    Some(column) => column.clone(),
+                    None => missing_column(field, batch.num_rows()),
+                }
+            })
+            .collect();
+
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)?;
+
+        Ok((batch, resources))
+    }
+}
+
+fn open_reader<T: ChunkReader + 'static>(
+    input: T,
+    select: Option<&[String]>,
+    filter: Vec<Box<dyn CustomFilter>>,
+    limit: Option<usize>,
+    rows: Option<Vec<Range<usize>>>,
+) -> Result<(ParquetRecordBatchReader, Arc<Resources>, Evolve), ParquetError> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(input)?;
+    let total = builder.metadata().file_metadata().num_rows() as usize;
+    let resources =
+        Resources::from_metadata(builder.metadata().file_metadata().key_value_metadata())?;
+
+    let file = FileSchema {
+        schema: builder.metadata().file_metadata().schema_descr(),
+        resources: &resources,
+    };
+    let evolve = Evolve::new(select, &file);
+    let projection = file.mask(&evolve.columns());
+
+    let filter = RowFilter::new(
+        filter
+            .iter()
+            .map(|v| v.bind(&file) as Box<dyn ArrowPredicate>)
+            .collect(),
+    );
+    let mut builder = builder.with_row_filter(filter).with_projection(projection);
+
+    if let Some(rows) = rows {
+        let selection = RowSelection::from_consecutive_ranges(rows.into_iter(), total);
+        builder = builder.with_row_selection(selection);
+    }
+
+    if let Some(limit) = limit {
+        builder = builder.with_limit(limit);
+    }
+
+    Ok((builder.build()?, Arc::new(resources), evolve))
+}
+
+/// Opens the remote copy when the file is no longer available locally.
+async fn open_remote(path: &Path) -> Result<Option<RemoteFile>, ParquetError> {
+    let Some(tier) = Tier::installed() else {
+        return Ok(None);
+    };
+
+    if path.exists() {
+        return Ok(None);
+    }
+
+    match tier.open(path).await {
+        Ok(remote) => Ok(Some(remote)),
+        Err(e) => Err(ParquetError::External(Box::new(e))),
+    }
+}
+
 async fn read_arrow_file(
     path: Box<Path>,
-    projection: Option<ProjectionMask>,
-    filter: RowFilter,
+    select: Option<Arc<[String]>>,
+    filter: Vec<Box<dyn CustomFilter>>,
     limit: Option<usize>,
-) -> ParquetRecordBatchReader {
+    rows: Option<Vec<Range<usize>>>,
+) -> Result<(ParquetRecordBatchReader, Arc<Resources>, Evolve), ParquetError> {
+    let remote = open_remote(&path).await?;
+
     tokio::task::spawn_blocking(move || {
-        tracing::info!(file = ?path, "Reading");
-        let file = std::fs::File::open(path).expect("file.open");
-        let builder = ParquetRecordBatchReaderBuilder::try_new(file).expect("builder.new");
-        let mut builder = builder.with_row_filter(filter);
+        tracing::info!(file = ?path, remote = remote.is_some(), "Reading");
 
-        if let Some(proj) = projection {
-            builder = builder.with_projection(proj);
-        }
+        let select = select.as_deref();
 
-        if let Some(limit) = limit {
-            builder = builder.with_limit(limit);
+        match remote {
+            Some(remote) => open_reader(remote, select, filter, limit, rows),
+            None => {
+                let file = std::fs::File::open(path)?;
+                open_reader(file, select, filter, limit, rows)
+            }
         }
+    })
+    .await
+    .unwrap()
+}
 
-        builder.build().expect("builder.build")
+async fn load_metadata(path: Box<Path>) -> Result<ParquetMetaData, ParquetError> {
+    let remote = open_remote(&path).await?;
+
+    tokio::task::spawn_blocking(move || match remote {
+        Some(remote) => ParquetMetaDataReader::new().parse_and_finish(&remote),
+        None => {
+            let file = std::fs::File::open(path)?;
+            ParquetMetaDataReader::new().parse_and_finish(&file)
+        }
     })
     .await
     .unwrap()
 }
 
+/// Resource table of a file, `None` for older files keeping resources in every row, see
+/// [FileSchema::is_legacy].
+pub async fn load_resource_table(path: Box<Path>) -> Result<Option<Resources>, ParquetError> {
+    let metadata = load_metadata(path).await?;
+    let file = FileSchema {
+        schema: metadata.file_metadata().schema_descr(),
+        resources: &Resources::default(),
+    };
+
+    if file.is_legacy() {
+        return Ok(None);
+    }
+
+    Resources::from_metadata(metadata.file_metadata().key_value_metadata()).map(Some)
+}
+
+/// Reads only the resource table of a file, older files keeping resources in every row are
+/// scanned.
+pub async fn load_resources(path: Box<Path>) -> Result<Resources, ParquetError> {
+    if let Some(resources) = load_resource_table(path.clone()).await? {
+        return Ok(resources);
+    }
+
+    let mut read = Read::new(Some([columns::RESOURCE_ID.name()]), |_| vec![], vec![path]);
+    let mut dictionary = super::resource::Dictionary::default();
+
+    while read.next_batch().await.is_some() {
+        for attrs in read.resources().iter() {
+            dictionary.id(attrs);
+        }
+    }
+
+    Ok(dictionary.take())
+}
+
+/// Row count and time range of a file, taken from the footer statistics.
+pub async fn file_summary(
+    path: Box<Path>,
+) -> Result<(u64, Option<u64>, Option<u64>), ParquetError> {
+    let metadata = load_metadata(path).await?;
+    let schema = metadata.file_metadata().schema_descr();
+
+    let column = |name: &str| {
+        schema
+            .columns()
+            .iter()
+            .position(|c| c.path().string() == name)
+            .ok_or_else(|| ParquetError::General(format!("column {name} is missing")))
+    };
+    let start = column(columns::TIME_START.name())?;
+    let end = column(columns::TIME_END.name())?;
+
+    let mut time_start: Option<u64> = None;
+    let mut time_end: Option<u64> = None;
+
+    for group in metadata.row_groups() {
+        // Unsigned columns are stored as INT64 and ordered as unsigned.
+        if let Some(Statistics::Int64(stats)) = group.column(start).statistics()
+            && let Some(min) = stats.min_opt()
+        {
+            let min = *min as u64;
+            time_start = Some(time_start.map_or(min, |v| v.min(min)));
+        }
+
+        if let Some(Statistics::Int64(stats)) = group.column(end).statistics()
+            && let Some(max) = stats.max_opt()
+        {
+            let max = *max as u64;
+            time_end = Some(time_end.map_or(max, |v| v.max(max)));
+        }
+    }
+
+    let rows = metadata.file_metadata().num_rows() as u64;
+
+    Ok((rows, time_start, time_end))
+}
+
+/// A file which couldn't be opened or decoded, batches read from it before were yielded.
+#[derive(Debug)]
+pub struct FileError {
+    pub path: Box<Path>,
+    pub error: ParquetError,
+}
+
+impl std::fmt::Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+impl std::error::Error for FileError {}
+
+type Decoded = Result<(RecordBatch, Arc<Resources>), FileError>;
+
+/// Decodes a file on a blocking thread and sends its batches until done, an error or `tx` is
+/// closed.
+async fn scan_arrow_file(
+    path: Box<Path>,
+    select: Option<Arc<[String]>>,
+    filter: Vec<Box<dyn CustomFilter>>,
+    limit: Option<usize>,
+    rows: Option<Vec<Range<usize>>>,
+    tx: mpsc::Sender<Decoded>,
+) {
+    let opened = read_arrow_file(path.clone(), select, filter, limit, rows).await;
+    let (mut reader, resources, evolve) = match opened {
+        Ok(opened) => opened,
+        Err(error) => {
+            tracing::error!(file = ?path, error = %error, "file.unreadable");
+            let _ = tx.send(Err(FileError { path, error })).await;
+            return;
+        }
+    };
+
+    loop {
+        // Decoding reads the file, which for remote files blocks on range requests.
+        let (returned, next) = tokio::task::spawn_blocking(move || {
+            let next = reader.next();
+            (reader, next)
+        })
+        .await
+        .unwrap();
+        reader = returned;
+
+        let Some(batch) = next else {
+            break;
+        };
+
+        let batch = match batch.and_then(|batch| evolve.apply(batch, &resources)) {
+            Ok(batch) => Ok(batch),
+            Err(error) => {
+                tracing::error!(file = ?path, error = %error, "file.unreadable");
+                let error = error.into();
+                let _ = tx.send(Err(FileError { path, error })).await;
+                break;
+            }
+        };
+
+        if tx.send(batch).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Reads stored files as batches of [SCHEMA] columns, columns a file lacks are filled in.
 pub struct Read {
-    select: Option<ProjectionMask>,
+    select: Option<Arc<[String]>>,
     filter: Vec<Box<dyn CustomFilter>>,
     files: Vec<Box<Path>>,
-    limit: Option<usize>,
-    index: usize,
-    reader: Option<ParquetRecordBatchReader>,
+    /// Rows read from a file, every row of files not listed.
+    rows: HashMap<Box<Path>, Vec<Range<usize>>>,
+    options: scan::Options,
+    scan: Option<Scan<Decoded>>,
+    resources: Arc<Resources>,
 }
 
 impl Read {
@@ -237,61 +916,381 @@ impl Read {
         make_filter: impl Fn(&SchemaDescriptor) -> Vec<Box<dyn CustomFilter>>,
         files: Vec<Box<Path>>,
     ) -> Self {
+        let Ok(read) = Self::try_new(
+            select,
+            |schema| Ok::<_, std::convert::Infallible>(make_filter(schema)),
+            files,
+        );
+
+        read
+    }
+
+    pub fn try_new<'a, E>(
+        select: Option<impl IntoIterator<Item = &'a str>>,
+        make_filter: impl Fn(&SchemaDescriptor) -> Result<Vec<Box<dyn CustomFilter>>, E>,
+        files: Vec<Box<Path>>,
+    ) -> Result<Self, E> {
+        // Filters are bound to the columns of every file before it is read.
         let schema = ArrowSchemaConverter::new().convert(&SCHEMA).unwrap();
-        let select = select.map(|s| ProjectionMask::columns(&schema, s));
-        let filter = make_filter(&schema);
+        let select = select.map(|s| s.into_iter().map(str::to_owned).collect());
+        let filter = make_filter(&schema)?;
 
-        Self {
+        Ok(Self {
             select,
             filter,
             files,
-            limit: None,
-            index: 0,
-            reader: None,
-        }
+            rows: HashMap::new(),
+            options: scan::Options::default(),
+            scan: None,
+            resources: Arc::default(),
+        })
+    }
+
+    /// Number of files decoded at once, defaults to [scan::parallelism].
+    pub fn with_parallelism(mut self, parallelism: usize) -> Self {
+        self.options.parallelism = parallelism;
+        self
+    }
+
+    /// Yields batches in the order of files instead of the order they are decoded in.
+    pub fn ordered(mut self) -> Self {
+        self.options.ordered = true;
+        self
+    }
+
+    /// Stops once `limit` rows were read.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.options.limit = Some(limit);
+        self
+    }
+
+    /// Reads only the given sorted, non-overlapping row ranges of the listed files, see
+    /// [crate::index::lookup].
+    pub fn with_rows(mut self, rows: HashMap<Box<Path>, Vec<Range<usize>>>) -> Self {
+        self.rows = rows;
+        self
     }
 }
 
 impl Read {
-    async fn init_reader(&mut self) {
-        assert!(self.reader.is_none());
-        let file = &self.files[self.index];
-
-        self.reader.replace(
-            read_arrow_file(
-                file.clone(),
-                self.select.clone(),
-                RowFilter::new(
-                    self.filter
-                        .iter()
-                        .map(|v| (*v).cloned() as Box<dyn ArrowPredicate>)
-                        .collect(),
-                ),
-                self.limit,
-            )
-            .await,
-        );
+    fn start(&mut self) -> Scan<Decoded> {
+        let select = self.select.clone();
+        let filter: Vec<_> = self.filter.iter().map(|v| v.cloned()).collect();
+        let limit = self.options.limit;
+        let rows = std::mem::take(&mut self.rows);
+
+        Scan::new(
+            std::mem::take(&mut self.files),
+            self.options,
+            |decoded| decoded.as_ref().map_or(0, |(batch, _)| batch.num_rows()),
+            move |path, tx| {
+                let filter = filter.iter().map(|v| v.cloned()).collect();
+                let rows = rows.get(&path).cloned();
+                scan_arrow_file(path, select.clone(), filter, limit, rows, tx)
+            },
+        )
     }
 
-    pub async fn next_batch(&mut self) -> Option<RecordBatch> {
-        tracing::info!(idx = self.index, len = self.files.len(), "next_batch");
+    /// Resources of the file the last batch was read from.
+    pub fn resources(&self) -> &Resources {
+        &self.resources
+    }
 
-        while self.index < self.files.len() {
-            if self.reader.is_none() {
-                self.init_reader().await;
+    /// Next batch, unreadable files are logged and skipped.
+    pub async fn next_batch(&mut self) -> Option<RecordBatch> {
+        loop {
+            if let Ok(batch) = self.try_next_batch().await? {
+                return Some(batch);
             }
+        }
+    }
 
-            let next = self.reader.as_mut().expect("reader.exists").next();
+    /// Next batch or the error of a file which couldn't be read, reading continues with the
+    /// other files.
+    pub async fn try_next_batch(&mut self) -> Option<Result<RecordBatch, FileError>> {
+        if self.scan.is_none() {
+            self.scan = Some(self.start());
+        }
 
-            if next.is_none() {
-                self.index += 1;
-                self.reader = None;
-                continue;
+        let decoded = self.scan.as_mut().expect("scan.exists").next().await?;
+
+        Some(decoded.map(|(batch, resources)| {
+            self.resources = resources;
+            batch
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use arrow::array::AsArray;
+    use arrow::datatypes::UInt32Type;
+    use parquet::arrow::ArrowWriter;
+
+    use super::*;
+    use crate::SpanBuilder;
+    use crate::arrow::{AsSpanData, Builder};
+    use crate::load::{Generator, Options};
+    use crate::testing::{runtime, temp_dir};
+
+    /// Columns added after the first release.
+    const ADDED: [&str; 12] = [
+        "trace_state",
+        "flags",
+        "dropped_attributes_count",
+        "dropped_events_count",
+        "dropped_links_count",
+        "resource_schema_url",
+        "scope_name",
+        "scope_version",
+        "scope_schema_url",
+        "scope_attribute_name",
+        "scope_attribute_type",
+        "scope_attribute_value",
+    ];
+
+    /// File of `spans` written without the columns of [ADDED], with the resource in every row
+    /// instead of a resource table when `legacy`.
+    fn old_file(name: &str, spans: Vec<crate::SpanData>, legacy: bool) -> PathBuf {
+        let mut builder = Builder::new(spans.len(), spans.len());
+        builder.append(spans);
+        let batch = builder.build();
+
+        let data = if legacy {
+            crate::arrow::stream::inline_resources(&batch.data, &batch.resources)
+        } else {
+            batch.data
+        };
+
+        let schema = data.schema();
+        let indices: Vec<usize> = (0..schema.fields().len())
+            .filter(|idx| !ADDED.contains(&schema.field(*idx).name().as_str()))
+            .collect();
+        let data = data.project(&indices).unwrap();
+
+        let path = temp_dir(name);
+        let file = std::fs::File::create(&path).unwrap();
+        let mut writer = ArrowWriter::try_new(file, data.schema(), None).unwrap();
+        writer.write(&data).unwrap();
+
+        if !legacy {
+            writer.append_key_value_metadata(parquet::file::metadata::KeyValue::new(
+                Resources::METADATA_KEY.to_owned(),
+                batch.resources.encode(),
+            ));
+        }
+
+        writer.close().unwrap();
+
+        path
+    }
+
+    async fn count(mut read: Read) -> usize {
+        let mut rows = 0;
+        while let Some(batch) = read.next_batch().await {
+            rows += batch.num_rows();
+        }
+        rows
+    }
+
+    #[test]
+    fn unreadable_files_are_skipped() {
+        let spans = Generator::new(Options::default()).trace(0);
+        let len = spans.len();
+
+        let good = old_file("read-good", spans, false);
+        let corrupt = temp_dir("read-corrupt");
+        std::fs::write(&corrupt, b"PAR1 not a parquet file").unwrap();
+        let missing = temp_dir("read-missing");
+
+        runtime().block_on(async {
+            let files = vec![
+                corrupt.clone().into_boxed_path(),
+                good.clone().into_boxed_path(),
+                missing.clone().into_boxed_path(),
+            ];
+            assert_eq!(
+                count(Read::new(None::<Vec<&str>>, |_| vec![], files.clone())).await,
+                len
+            );
+
+            // Callers which must not miss rows get the errors of the files.
+            let mut read = Read::new(None::<Vec<&str>>, |_| vec![], files).ordered();
+            let mut failed = Vec::new();
+            let mut rows = 0;
+
+            while let Some(next) = read.try_next_batch().await {
+                match next {
+                    Ok(batch) => rows += batch.num_rows(),
+                    Err(e) => failed.push(e.path),
+                }
             }
+            assert_eq!(rows, len);
+            assert_eq!(
+                failed,
+                vec![
+                    corrupt.clone().into_boxed_path(),
+                    missing.clone().into_boxed_path()
+                ]
+            );
+
+            assert!(
+                file_summary(corrupt.clone().into_boxed_path())
+                    .await
+                    .is_err()
+            );
+            assert!(
+                file_summary(missing.clone().into_boxed_path())
+                    .await
+                    .is_err()
+            );
+            assert!(
+                load_resources(corrupt.clone().into_boxed_path())
+                    .await
+                    .is_err()
+            );
+
+            let (rows, _, _) = file_summary(good.clone().into_boxed_path()).await.unwrap();
+            assert_eq!(rows, len as u64);
+        });
+
+        std::fs::remove_file(&good).unwrap();
+        std::fs::remove_file(&corrupt).unwrap();
+    }
+
+    #[test]
+    fn missing_columns_read_as_null_or_zero() {
+        use super::columns::{FLAGS, SCOPE_NAME, SPAN_NAME};
 
-            return Some(next.unwrap().expect("recordbatch.ok"));
+        let mut spans = Generator::new(Options::default()).trace(0);
+        for span in spans.iter_mut() {
+            span.flags = 1;
         }
+        let len = spans.len();
+
+        let path = old_file("read-old", spans, false);
+        let files = || vec![path.clone().into_boxed_path()];
+
+        runtime().block_on(async {
+            let mut read = Read::new(None::<Vec<&str>>, |_| vec![], files());
+            let mut spans = Vec::new();
+            while let Some(batch) = read.next_batch().await {
+                assert_eq!(batch.schema(), *SCHEMA);
+                spans.extend(batch.get_span_data(read.resources()));
+            }
+
+            assert_eq!(spans.len(), len);
+            assert!(spans.iter().all(|span| span.flags == 0));
+            assert!(spans.iter().all(|span| span.scope.name.is_none()));
+            assert!(spans.iter().all(|span| span.trace_state.is_none()));
+
+            let mut read = Read::new(Some([FLAGS.name(), SPAN_NAME.name()]), |_| vec![], files());
+            let batch = read.next_batch().await.unwrap();
+            assert_eq!(batch.num_columns(), 2);
+            let flags = batch.column_by_name(FLAGS.name()).unwrap();
+            assert!(
+                flags
+                    .as_primitive::<UInt32Type>()
+                    .iter()
+                    .all(|v| v == Some(0))
+            );
+
+            let scoped = |schema: &SchemaDescriptor| -> Vec<Box<dyn CustomFilter>> {
+                vec![Box::new(Filter::new_str(schema, SCOPE_NAME.name(), "x"))]
+            };
+            assert_eq!(
+                count(Read::new(None::<Vec<&str>>, scoped, files())).await,
+                0
+            );
+
+            let unscoped = |schema: &SchemaDescriptor| -> Vec<Box<dyn CustomFilter>> {
+                vec![Box::new(Null::is_null(schema, SCOPE_NAME.name()))]
+            };
+            let read = Read::new(Some([SPAN_NAME.name()]), unscoped, files());
+            assert_eq!(count(read).await, len);
+
+            let flagged = |schema: &SchemaDescriptor| -> Vec<Box<dyn CustomFilter>> {
+                vec![Box::new(Null::not_null(schema, FLAGS.name()))]
+            };
+            assert_eq!(
+                count(Read::new(None::<Vec<&str>>, flagged, files())).await,
+                len
+            );
+        });
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn legacy_resources_are_read_from_rows() {
+        use opentelemetry_proto::tonic::common::v1::any_value::Value;
+
+        let spans = Generator::new(Options::default()).trace(0);
+        let mut expected: Vec<_> = spans
+            .iter()
+            .map(|span| (span.span_id, span.resource_attributes.as_ref().clone()))
+            .collect();
+        expected.sort_by_key(|(span_id, _)| *span_id);
+        let service = |attrs: &[opentelemetry_proto::tonic::common::v1::KeyValue]| {
+            attrs
+                .iter()
+                .find_map(|kv| match kv.value.as_ref()?.value.as_ref()? {
+                    Value::StringValue(name) if kv.key == "service.name" => Some(name.clone()),
+                    _ => None,
+                })
+        };
+        let name = expected
+            .iter()
+            .find_map(|(_, attrs)| service(attrs))
+            .unwrap();
+        let matching = expected
+            .iter()
+            .filter(|(_, attrs)| service(attrs).as_ref() == Some(&name))
+            .count();
+
+        let path = old_file("read-legacy", spans, true);
+        let files = || vec![path.clone().into_boxed_path()];
+
+        runtime().block_on(async {
+            let mut read = Read::new(None::<Vec<&str>>, |_| vec![], files()).ordered();
+            let mut loaded = Vec::new();
+            while let Some(batch) = read.next_batch().await {
+                loaded.extend(
+                    batch
+                        .get_span_data(read.resources())
+                        .map(|span| (span.span_id, span.resource_attributes.as_ref().clone())),
+                );
+            }
+            // Rows are stored sorted by service.
+            loaded.sort_by_key(|(span_id, _)| *span_id);
+            assert_eq!(loaded, expected);
+
+            let by_service = |schema: &SchemaDescriptor| -> Vec<Box<dyn CustomFilter>> {
+                let name = name.clone();
+                let predicate = Arc::new(
+                    move |value: &AnyValue| matches!(value, AnyValue::StringValue(v) if *v == name),
+                );
+                vec![Box::new(AttrFilter::resource(
+                    schema,
+                    "service.name",
+                    predicate,
+                ))]
+            };
+            let read = Read::new(Some([columns::SPAN_NAME.name()]), by_service, files());
+            assert_eq!(count(read).await, matching);
+
+            let resources = load_resources(path.clone().into_boxed_path())
+                .await
+                .unwrap();
+            assert!(
+                resources
+                    .iter()
+                    .any(|attrs| service(attrs) == Some(name.clone()))
+            );
+        });
 
-        None
+        std::fs::remove_file(&path).unwrap();
     }
 }
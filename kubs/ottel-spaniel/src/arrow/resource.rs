@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow::array::{Array, AsArray, RecordBatch, UInt32Array};
+use arrow::datatypes::{Int8Type, UInt32Type};
+use opentelemetry_proto::tonic::common::v1::any_value::Value;
+use opentelemetry_proto::tonic::common::v1::{AnyValue, ArrayValue, KeyValue, KeyValueList};
+use parquet::errors::ParquetError;
+use prost::Message;
+
+use super::columns::{RES_ATTR_NAME, RES_ATTR_TYPE, RES_ATTR_VALUE, RESOURCE_ID};
+use super::ext::key_values;
+
+/// Resource attributes referenced by spans of a single file, indexed by [RESOURCE_ID].
+///
+/// Stored once per file in the Parquet key-value metadata under [Resources::METADATA_KEY].
+#[derive(Debug, Default, Clone)]
+pub struct Resources {
+    list: Vec<Arc<Vec<KeyValue>>>,
+}
+
+impl Resources {
+    pub const METADATA_KEY: &str = "spaniel.resources";
+
+    pub fn get(&self, id: u32) -> &Arc<Vec<KeyValue>> {
+        &self.list[id as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Vec<KeyValue>>> {
+        self.list.iter()
+    }
+
+    /// Value of the `service.name` string attribute of the resource.
+    pub fn service_name(&self, id: u32) -> Option<&str> {
+        self.get(id).iter().find_map(|kv| {
+            if kv.key != "service.name" {
+                return None;
+            }
+
+            match kv.value.as_ref()?.value.as_ref()? {
+                Value::StringValue(name) => Some(name.as_str()),
+                _ => None,
+            }
+        })
+    }
+
+    /// Hex encoded `ArrayValue` with one `KvlistValue` per resource.
+    pub fn encode(&self) -> String {
+        let array = ArrayValue {
+            values: self
+                .list
+                .iter()
+                .map(|attrs| AnyValue {
+                    value: Some(Value::KvlistValue(KeyValueList {
+                        values: attrs.as_ref().clone(),
+                    })),
+                })
+                .collect(),
+        };
+
+        const_hex::encode(array.encode_to_vec())
+    }
+
+    pub fn decode(value: &str) -> Result<Self, ParquetError> {
+        let malformed = |e: &dyn std::fmt::Display| {
+            ParquetError::General(format!("malformed {}: {e}", Self::METADATA_KEY))
+        };
+
+        let buf = const_hex::decode(value).map_err(|e| malformed(&e))?;
+        let array = ArrayValue::decode(buf.as_slice()).map_err(|e| malformed(&e))?;
+
+        let list = array
+            .values
+            .into_iter()
+            .map(|v| match v.value {
+                Some(Value::KvlistValue(kvlist)) => Ok(Arc::new(kvlist.values)),
+                _ => Err(malformed(&"resource is not a kvlist")),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { list })
+    }
+
+    /// Resource table of a file, empty for files written before resources were stored once per
+    /// file, see [Resources::from_legacy].
+    pub fn from_metadata(
+        metadata: Option<&Vec<parquet::file::metadata::KeyValue>>,
+    ) -> Result<Self, ParquetError> {
+        metadata
+            .into_iter()
+            .flatten()
+            .find(|kv| kv.key == Self::METADATA_KEY)
+            .and_then(|kv| kv.value.as_deref())
+            .map(Self::decode)
+            .unwrap_or_else(|| Ok(Self::default()))
+    }
+
+    /// Resource table of a batch of a file written before resources were stored once per file,
+    /// together with the [RESOURCE_ID] of every row. Such files keep the attributes of the
+    /// resource in the [RES_ATTR_NAME], [RES_ATTR_TYPE] and [RES_ATTR_VALUE] columns of every
+    /// row, null for root spans.
+    pub(crate) fn from_legacy(batch: &RecordBatch) -> (UInt32Array, Self) {
+        let column = |name: &str| batch.column_by_name(name).expect("col.exists").clone();
+        let (names, types, values) = (
+            column(RES_ATTR_NAME.name()),
+            column(RES_ATTR_TYPE.name()),
+            column(RES_ATTR_VALUE.name()),
+        );
+        let (names, types, values) = (
+            names.as_list::<i32>(),
+            types.as_list::<i32>(),
+            values.as_list::<i32>(),
+        );
+
+        let mut dictionary = Dictionary::default();
+        let ids = (0..batch.num_rows())
+            .map(|row| {
+                if names.is_null(row) {
+                    return dictionary.id(&Arc::default());
+                }
+
+                let (names, types, values) =
+                    (names.value(row), types.value(row), values.value(row));
+                let attrs = key_values(
+                    names.as_string_view(),
+                    types.as_primitive::<Int8Type>(),
+                    values.as_binary_view(),
+                );
+
+                dictionary.id(&Arc::new(attrs))
+            })
+            .collect();
+
+        (ids, dictionary.take())
+    }
+}
+
+/// Assigns ids to resources, deduplicating them by content.
+#[derive(Default)]
+pub(crate) struct Dictionary {
+    resources: Resources,
+    /// Spans of one request share the same `Arc`, so the pointer is checked first.
+    by_ptr: HashMap<usize, u32>,
+    by_content: HashMap<Vec<u8>, u32>,
+}
+
+impl Dictionary {
+    pub fn id(&mut self, attrs: &Arc<Vec<KeyValue>>) -> u32 {
+        // Pointers stay unique as long as `resources` holds the `Arc`.
+        let ptr = Arc::as_ptr(attrs) as usize;
+
+        if let Some(id) = self.by_ptr.get(&ptr) {
+            return *id;
+        }
+
+        let content = KeyValueList {
+            values: attrs.as_ref().clone(),
+        }
+        .encode_to_vec();
+
+        let id = *self.by_content.entry(content).or_insert_with(|| {
+            self.resources.list.push(attrs.clone());
+            (self.resources.list.len() - 1) as u32
+        });
+
+        if Arc::ptr_eq(&self.resources.list[id as usize], attrs) {
+            self.by_ptr.insert(ptr, id);
+        }
+
+        id
+    }
+
+    /// Resources collected so far.
+    pub fn resources(&self) -> &Resources {
+        &self.resources
+    }
+
+    /// Returns collected resources and starts over.
+    pub fn take(&mut self) -> Resources {
+        self.by_ptr.clear();
+        self.by_content.clear();
+
+        std::mem::take(&mut self.resources)
+    }
+
+    /// Rewrites [RESOURCE_ID] of a batch built with `resources` to ids of this dictionary.
+    pub fn remap(&mut self, batch: RecordBatch, resources: &Resources) -> RecordBatch {
+        let idx = batch.schema().index_of(RESOURCE_ID.name()).unwrap();
+        let mapping: Vec<u32> = resources.iter().map(|attrs| self.id(attrs)).collect();
+
+        let ids = batch.column(idx).as_primitive::<UInt32Type>();
+        let ids: UInt32Array = ids.unary(|id| mapping[id as usize]);
+
+        let mut columns = batch.columns().to_vec();
+        columns[idx] = Arc::new(ids);
+
+        RecordBatch::try_new(batch.schema(), columns).expect("remap.batch")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_tables_are_errors() {
+        assert!(Resources::decode("not hex").is_err());
+        assert!(Resources::decode("ff").is_err());
+
+        let not_kvlist = ArrayValue {
+            values: vec![AnyValue {
+                value: Some(Value::IntValue(1)),
+            }],
+        };
+        assert!(Resources::decode(&const_hex::encode(not_kvlist.encode_to_vec())).is_err());
+
+        let resources = Resources {
+            list: vec![Arc::new(vec![]), Arc::default()],
+        };
+        assert_eq!(Resources::decode(&resources.encode()).unwrap().len(), 2);
+    }
+}
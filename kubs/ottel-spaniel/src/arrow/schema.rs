@@ -70,20 +70,42 @@ pub mod columns {
     pub static TIME_START: Column = Column::new("time_start", DataType::UInt64, false);
     pub static TIME_END: Column = Column::new("time_end", DataType::UInt64, false);
     pub static TIME_DURATION: Column = Column::new("time_duration", DataType::UInt64, false);
+    /// Index into the per-file resource table, see [crate::arrow::Resources].
+    pub static RESOURCE_ID: Column = Column::new("resource_id", DataType::UInt32, false);
+    pub static SPAN_ATTR_NAME: Column = Column::list("attr_name", DataType::Utf8View, false, false);
+    pub static SPAN_ATTR_TYPE: Column = Column::list("attr_type", DataType::Int8, false, false);
+    pub static SPAN_ATTR_VALUE: Column =
+        Column::list("attr_value", DataType::BinaryView, false, false);
+    pub static TRACE_STATE: Column = Column::new("trace_state", DataType::Utf8View, true);
+    pub static FLAGS: Column = Column::new("flags", DataType::UInt32, false);
+    pub static DROPPED_ATTRIBUTES: Column =
+        Column::new("dropped_attributes_count", DataType::UInt32, false);
+    pub static DROPPED_EVENTS: Column =
+        Column::new("dropped_events_count", DataType::UInt32, false);
+    pub static DROPPED_LINKS: Column = Column::new("dropped_links_count", DataType::UInt32, false);
+    pub static RES_SCHEMA_URL: Column =
+        Column::new("resource_schema_url", DataType::Utf8View, true);
+    pub static SCOPE_NAME: Column = Column::new("scope_name", DataType::Utf8View, true);
+    pub static SCOPE_VERSION: Column = Column::new("scope_version", DataType::Utf8View, true);
+    pub static SCOPE_SCHEMA_URL: Column = Column::new("scope_schema_url", DataType::Utf8View, true);
+    pub static SCOPE_ATTR_NAME: Column =
+        Column::list("scope_attribute_name", DataType::Utf8View, true, false);
+    pub static SCOPE_ATTR_TYPE: Column =
+        Column::list("scope_attribute_type", DataType::Int8, true, false);
+    pub static SCOPE_ATTR_VALUE: Column =
+        Column::list("scope_attribute_value", DataType::BinaryView, true, false);
+    /// Resource attributes inlined into every row of streamed batches, see
+    /// [crate::arrow::stream::STREAM_SCHEMA].
     pub static RES_ATTR_NAME: Column =
-        Column::list("resource_attribute_name", DataType::Utf8View, true, false);
+        Column::list("resource_attribute_name", DataType::Utf8View, false, false);
     pub static RES_ATTR_TYPE: Column =
-        Column::list("resource_attribute_type", DataType::Int8, true, false);
+        Column::list("resource_attribute_type", DataType::Int8, false, false);
     pub static RES_ATTR_VALUE: Column = Column::list(
         "resource_attribute_value",
         DataType::BinaryView,
-        true,
+        false,
         false,
     );
-    pub static SPAN_ATTR_NAME: Column = Column::list("attr_name", DataType::Utf8View, false, false);
-    pub static SPAN_ATTR_TYPE: Column = Column::list("attr_type", DataType::Int8, false, false);
-    pub static SPAN_ATTR_VALUE: Column =
-        Column::list("attr_value", DataType::BinaryView, false, false);
 }
 
 pub static SCHEMA: LazyLock<Arc<Schema>> = LazyLock::new(create_schema);
@@ -102,12 +124,22 @@ fn create_schema() -> Arc<Schema> {
         TIME_START.as_field(),
         TIME_END.as_field(),
         TIME_DURATION.as_field(),
-        RES_ATTR_NAME.as_field(),
-        RES_ATTR_TYPE.as_field(),
-        RES_ATTR_VALUE.as_field(),
+        RESOURCE_ID.as_field(),
         SPAN_ATTR_NAME.as_field(),
         SPAN_ATTR_TYPE.as_field(),
         SPAN_ATTR_VALUE.as_field(),
+        TRACE_STATE.as_field(),
+        FLAGS.as_field(),
+        DROPPED_ATTRIBUTES.as_field(),
+        DROPPED_EVENTS.as_field(),
+        DROPPED_LINKS.as_field(),
+        RES_SCHEMA_URL.as_field(),
+        SCOPE_NAME.as_field(),
+        SCOPE_VERSION.as_field(),
+        SCOPE_SCHEMA_URL.as_field(),
+        SCOPE_ATTR_NAME.as_field(),
+        SCOPE_ATTR_TYPE.as_field(),
+        SCOPE_ATTR_VALUE.as_field(),
     ];
 
     Arc::new(Schema::new(cols))
@@ -119,6 +151,12 @@ impl Attribute {
     pub const FIELD_STR: i8 = 1;
     pub const FIELD_NUM_I: i8 = 2;
     pub const FIELD_NUM_F: i8 = 3;
+    /// Raw bytes of `BytesValue`.
+    pub const FIELD_BYTES: i8 = 4;
+    /// Protobuf encoded `ArrayValue`.
+    pub const FIELD_ARRAY: i8 = 5;
+    /// Protobuf encoded `KeyValueList`.
+    pub const FIELD_KVLIST: i8 = 6;
     pub const FIELD_BOOL_T: i8 = -7;
     pub const FIELD_BOOL_F: i8 = -8;
 
@@ -141,6 +179,7 @@ impl Attribute {
         attrs: &[KeyValue],
     ) {
         use opentelemetry_proto::tonic::common::v1::any_value::Value;
+        use prost::Message;
 
         for attr in attrs {
             let val = attr.value.as_ref().and_then(|v| v.value.as_ref());
@@ -155,7 +194,8 @@ impl Attribute {
                     } else {
                         Self::FIELD_BOOL_F
                     });
-                    val_builder.values().append_null();
+                    // Value elements aren't nullable, the type alone holds a bool.
+                    val_builder.values().append_value([]);
                 }
                 Value::IntValue(integer) => {
                     ty_builder.values().append_value(Self::FIELD_NUM_I);
@@ -169,10 +209,45 @@ impl Attribute {
                     ty_builder.values().append_value(Self::FIELD_STR);
                     val_builder.values().append_value(string);
                 }
-                _ => continue,
+                Value::BytesValue(bytes) => {
+                    ty_builder.values().append_value(Self::FIELD_BYTES);
+                    val_builder.values().append_value(bytes);
+                }
+                Value::ArrayValue(array) => {
+                    ty_builder.values().append_value(Self::FIELD_ARRAY);
+                    val_builder.values().append_value(array.encode_to_vec());
+                }
+                Value::KvlistValue(kvlist) => {
+                    ty_builder.values().append_value(Self::FIELD_KVLIST);
+                    val_builder.values().append_value(kvlist.encode_to_vec());
+                }
             }
 
             name_builder.values().append_value(attr.key.as_str());
         }
     }
+
+    /// Value of a stored attribute, `None` when its type is unknown or its bytes are corrupt.
+    pub fn decode(
+        ty: i8,
+        value: &[u8],
+    ) -> Option<opentelemetry_proto::tonic::common::v1::any_value::Value> {
+        use opentelemetry_proto::tonic::common::v1::any_value::Value;
+        use opentelemetry_proto::tonic::common::v1::{ArrayValue, KeyValueList};
+        use prost::Message;
+
+        let value = match ty {
+            Self::FIELD_BOOL_T => Value::BoolValue(true),
+            Self::FIELD_BOOL_F => Value::BoolValue(false),
+            Self::FIELD_STR => Value::StringValue(String::from_utf8(value.to_owned()).ok()?),
+            Self::FIELD_NUM_I => Value::IntValue(i64::from_be_bytes(value.try_into().ok()?)),
+            Self::FIELD_NUM_F => Value::DoubleValue(f64::from_be_bytes(value.try_into().ok()?)),
+            Self::FIELD_BYTES => Value::BytesValue(value.to_owned()),
+            Self::FIELD_ARRAY => Value::ArrayValue(ArrayValue::decode(value).ok()?),
+            Self::FIELD_KVLIST => Value::KvlistValue(KeyValueList::decode(value).ok()?),
+            _ => return None,
+        };
+
+        Some(value)
+    }
 }
@@ -0,0 +1,156 @@
+//! Batches sent to clients as an Arrow IPC stream.
+//!
+//! Stored batches reference resources by [columns::RESOURCE_ID] into a table of their file, which
+//! clients never see. Streamed batches carry the attributes of the resource in every row instead,
+//! see [STREAM_SCHEMA].
+
+use std::sync::{Arc, LazyLock};
+
+use arrow::array::*;
+use arrow::datatypes::{Schema, UInt32Type};
+use arrow::ipc::writer::StreamWriter;
+use bytes::Bytes;
+
+use super::{Attribute, Builder, Resources, SCHEMA, columns};
+use crate::{SpanBuilder, SpanData};
+
+pub const CONTENT_TYPE: &str = "application/vnd.apache.arrow.stream";
+
+/// [SCHEMA] with [columns::RESOURCE_ID] replaced by the attributes of the resource.
+pub static STREAM_SCHEMA: LazyLock<Arc<Schema>> = LazyLock::new(|| {
+    use columns::*;
+
+    let fields: Vec<_> = SCHEMA
+        .fields()
+        .iter()
+        .flat_map(|field| {
+            if field.name() == RESOURCE_ID.name() {
+                vec![
+                    RES_ATTR_NAME.as_field(),
+                    RES_ATTR_TYPE.as_field(),
+                    RES_ATTR_VALUE.as_field(),
+                ]
+            } else {
+                vec![field.as_ref().clone()]
+            }
+        })
+        .collect();
+
+    Arc::new(Schema::new(fields))
+});
+
+/// Turns a stored batch into a batch of [STREAM_SCHEMA], `resources` being those of its file.
+pub fn inline_resources(batch: &RecordBatch, resources: &Resources) -> RecordBatch {
+    use columns::*;
+
+    let idx = batch
+        .schema()
+        .index_of(RESOURCE_ID.name())
+        .expect("col.exists");
+    let ids = batch.column(idx).as_primitive::<UInt32Type>();
+
+    let mut names = ListBuilder::with_capacity(
+        StringViewBuilder::new().with_deduplicate_strings(),
+        ids.len(),
+    )
+    .with_field(RES_ATTR_NAME.as_list_field());
+    let mut types = ListBuilder::with_capacity(Int8Builder::new(), ids.len())
+        .with_field(RES_ATTR_TYPE.as_list_field());
+    let mut values = ListBuilder::with_capacity(BinaryViewBuilder::new(), ids.len())
+        .with_field(RES_ATTR_VALUE.as_list_field());
+
+    for id in ids.values() {
+        Attribute::append(&mut names, &mut types, &mut values, resources.get(*id));
+        names.append(true);
+        types.append(true);
+        values.append(true);
+    }
+
+    let mut cols = batch.columns().to_vec();
+    cols.splice(
+        idx..idx + 1,
+        [
+            Arc::new(names.finish()) as ArrayRef,
+            Arc::new(types.finish()),
+            Arc::new(values.finish()),
+        ],
+    );
+
+    RecordBatch::try_new(STREAM_SCHEMA.clone(), cols).expect("stream.batch")
+}
+
+/// Batch of [STREAM_SCHEMA] holding `spans`, e.g. read from Vortex files.
+pub fn from_span_data(spans: Vec<SpanData>) -> RecordBatch {
+    let mut builder = Builder::new(usize::MAX, spans.len());
+    builder.append(spans);
+
+    let batch = builder.build();
+    inline_resources(&batch.data, &batch.resources)
+}
+
+/// Encodes batches of [STREAM_SCHEMA] into consecutive chunks of a single IPC stream.
+pub struct Encoder {
+    writer: StreamWriter<Vec<u8>>,
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        let writer = StreamWriter::try_new(Vec::new(), &STREAM_SCHEMA).expect("ipc.writer");
+
+        Self { writer }
+    }
+}
+
+impl Encoder {
+    /// Encoded `batch`, the first chunk starts with the schema.
+    pub fn encode(&mut self, batch: &RecordBatch) -> Bytes {
+        self.writer.write(batch).expect("ipc.write");
+        self.take()
+    }
+
+    /// End of the stream, or the schema alone when no batch was encoded.
+    pub fn finish(mut self) -> Bytes {
+        self.writer.finish().expect("ipc.finish");
+        self.take()
+    }
+
+    fn take(&mut self) -> Bytes {
+        Bytes::from(std::mem::take(self.writer.get_mut()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::ipc::reader::StreamReader;
+
+    use super::*;
+    use crate::load::{Generator, Options};
+
+    #[test]
+    fn streamed_batches_keep_resources() {
+        let spans = Generator::new(Options::default()).trace(0);
+        let len = spans.len();
+        let service = spans[0].resource_attributes[0].clone();
+
+        let mut encoder = Encoder::default();
+        let mut buf = encoder.encode(&from_span_data(spans)).to_vec();
+        buf.extend_from_slice(&encoder.finish());
+
+        let batches: Vec<_> = StreamReader::try_new(buf.as_slice(), None)
+            .expect("ipc.reader")
+            .collect::<Result<_, _>>()
+            .expect("ipc.read");
+
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].num_rows(), len);
+        assert_eq!(batches[0].schema(), *STREAM_SCHEMA);
+
+        let names = batches[0]
+            .column_by_name(columns::RES_ATTR_NAME.name())
+            .expect("col.exists")
+            .as_list::<i32>()
+            .value(0);
+
+        assert_eq!(names.as_string_view().value(0), service.key);
+    }
+}
@@ -1,28 +1,64 @@
 use std::fs::File;
 use std::io::BufWriter;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
-use arrow::array::RecordBatch;
+use arrow::array::{AsArray, RecordBatch};
+use arrow::compute::concat_batches;
 use parquet::arrow::arrow_writer::ArrowWriter;
+use parquet::file::metadata::KeyValue;
 
-use super::SCHEMA;
-use crate::{SpanWriter, Stats};
+use super::build::sort_by_service;
+use super::resource::Dictionary;
+use super::{Batch, Builder, Read, Resources, SCHEMA, columns};
+use crate::{Format, Location, SpanBuilder, SpanWriter, Stats, index};
+
+/// Prefix of a merged file until it is complete, it isn't loaded as a data file.
+const MERGING_PREF: &str = "merging-";
+/// Prefix of the marker listing the files replaced by a merged file, see [replay_retired].
+const RETIRED_PREF: &str = "retired-";
+
+/// Files replaced by a merged file, deleted together with their marker.
+struct Retired {
+    marker: Box<Path>,
+    files: Vec<Box<Path>>,
+    since: Instant,
+}
 
 pub struct Writer {
     file_id: usize,
+    /// Directory holding the files of this writer.
+    dir: Box<Path>,
     writer: Option<ArrowWriter<BufWriter<File>>>,
+    /// Path of the current file.
+    path: Option<Box<Path>>,
     stats: Stats,
     /// Number of written spans to current file.
     writes: usize,
     /// Maximum number of Spans per file.
     threshold: usize,
+    /// Resources referenced by the current file.
+    resources: Dictionary,
+    /// Trace ids of the current file.
+    index: index::Builder,
+    /// Batches of the next row group, sorted together once it is written.
+    pending: Vec<RecordBatch>,
+    /// Files replaced by merges, readers listing them earlier may still open them for a while.
+    retired: Vec<Retired>,
 }
 
 impl Writer {
-    const DIR: &str = "data-arrow";
-    const PREF: &str = "spaniel-live-arrow-";
+    pub const PREF: &str = "spaniel-live-arrow-";
+    /// Key-value metadata naming the order of rows within every row group, `service.name` is a
+    /// resource attribute so Parquet sorting columns can't express it.
+    pub const SORTING_COLUMNS_KEY: &str = "spaniel.sorting_columns";
+    /// Rows buffered before a row group is written.
+    const ROW_GROUP_ROWS: usize = 64 * 1024;
+    /// Time given to readers of merged files before they are deleted.
+    const RETIRE_AFTER: Duration = Duration::from_secs(60);
 
     fn init_file_id(&mut self) {
-        self.file_id = crate::misc::get_next_file_id(Self::DIR, Self::PREF);
+        self.file_id = crate::misc::get_next_file_id(&self.dir, Self::PREF);
     }
 
     async fn next_file(&mut self) {
@@ -32,9 +68,20 @@ impl Writer {
     }
 
     async fn close_writer(&mut self) {
-        if let Some(writer) = self.writer.take() {
+        self.write_row_group();
+
+        if let Some(mut writer) = self.writer.take() {
+            writer.append_key_value_metadata(KeyValue::new(
+                Resources::METADATA_KEY.to_owned(),
+                self.resources.take().encode(),
+            ));
+            writer.append_key_value_metadata(sorting_columns());
             writer.close().expect("writer.close");
             tracing::info!(len = self.writes, "writer.finish");
+
+            let path = self.path.take().expect("path.exists");
+            index::write(&path, &self.index.take());
+            self.writes = 0;
             self.stats.end_dirty_file().await;
         }
     }
@@ -42,40 +89,294 @@ impl Writer {
     async fn create_new_writer(&mut self) {
         self.close_writer().await;
 
-        let file_path = format!("{}/{}{}", Self::DIR, Self::PREF, self.file_id);
+        let file_path = self.dir.join(format!("{}{}", Self::PREF, self.file_id));
 
         #[allow(clippy::borrow_interior_mutable_const)]
         let writer = ArrowWriter::try_new(crate::misc::open_file(&file_path), SCHEMA.clone(), None)
             .expect("arrow-writer.create");
 
         assert!(self.writer.replace(writer).is_none());
+        self.path = Some(file_path.clone().into_boxed_path());
 
         self.stats.set_dirty_file(&file_path).await;
     }
 
-    pub fn new(spans_per_file: usize) -> Self {
+    pub fn new(dir: Box<Path>, spans_per_file: usize) -> Self {
+        std::fs::create_dir_all(&dir).expect("writer.dir.create");
+        replay_retired(&dir);
+
         Self {
             file_id: 0,
+            stats: Stats::new(&dir),
+            dir,
             writer: None,
-            stats: Stats::new(Self::DIR, Self::PREF),
+            path: None,
             writes: 0,
             threshold: spans_per_file,
+            resources: Dictionary::default(),
+            index: index::Builder::default(),
+            pending: Vec::new(),
+            retired: Vec::new(),
         }
     }
 
-    fn write_data(&mut self, data: RecordBatch) {
-        let Some(writer) = self.writer.as_mut() else {
-            unreachable!();
+    /// Deletes merged files retired for at least `after`.
+    fn remove_retired(&mut self, after: Duration) {
+        self.retired.retain(|retired| {
+            if retired.since.elapsed() < after {
+                return true;
+            }
+
+            for file in &retired.files {
+                remove_file(file);
+            }
+            remove_file(&retired.marker);
+
+            false
+        });
+    }
+
+    /// Writes spans of `files` into a new file and removes them. Vortex files are rewritten as
+    /// Arrow, they keep no attributes so nothing is lost.
+    ///
+    /// Every file comes with its row count, nothing is removed unless all of them were read.
+    async fn merge(&mut self, files: Vec<(Box<Path>, usize)>) {
+        let (files, rows): (Vec<_>, Vec<_>) = files.into_iter().unzip();
+        let expected: usize = rows.into_iter().sum();
+
+        let (batches, mut resources) = match read_sources(files.clone(), self.threshold).await {
+            Ok(read) => read,
+            Err(e) => {
+                tracing::error!(error = %e, "writer.compact.unreadable");
+                return;
+            }
         };
 
+        // Merged files fit into a single file, so they are sorted as a whole into one row group.
+        #[allow(clippy::borrow_interior_mutable_const)]
+        let batch = sort_by_service(
+            &concat_batches(&SCHEMA, &batches).expect("merge.concat"),
+            resources.resources(),
+        );
+        let rows = batch.num_rows();
+
+        if rows != expected {
+            tracing::error!(rows, expected, "writer.compact.rows");
+            return;
+        }
+
+        self.init_file_id();
+        let name = format!("{}{}", Self::PREF, self.file_id);
+        let file_path = self.dir.join(&name);
+        let merging = self.dir.join(format!("{MERGING_PREF}{name}"));
+
+        #[allow(clippy::borrow_interior_mutable_const)]
+        let mut writer =
+            ArrowWriter::try_new(crate::misc::open_file(&merging), SCHEMA.clone(), None)
+                .expect("arrow-writer.create");
+
+        let mut index = index::Builder::default();
+        push_trace_ids(&mut index, &batch);
+        writer.write(&batch).expect("write.ok");
+
+        writer.append_key_value_metadata(KeyValue::new(
+            Resources::METADATA_KEY.to_owned(),
+            resources.take().encode(),
+        ));
+        writer.append_key_value_metadata(sorting_columns());
+        let file = writer.into_inner().expect("writer.close");
+        file.into_inner()
+            .expect("writer.flush")
+            .sync_all()
+            .expect("writer.sync");
+        index::write(&file_path, &index.take());
+
+        // The marker outlives a restart, the merged file is only listed once it exists.
+        let marker = self.dir.join(format!("{RETIRED_PREF}{name}"));
+        write_marker(&marker, &files);
+        std::fs::rename(&merging, &file_path).expect("merge.rename");
+
+        // Readers see the merged file before the old ones disappear, readers which listed the old
+        // files already get [Self::RETIRE_AFTER] to open them.
+        if !self.stats.replace_files(&files, &file_path).await {
+            // The object store keeps uploaded files as they are.
+            tracing::info!(file = ?file_path, "writer.compact.uploaded");
+            remove_file(&file_path);
+            std::fs::remove_file(&marker).expect("marker.remove");
+            return;
+        }
+
+        self.retired.push(Retired {
+            marker: marker.into_boxed_path(),
+            files: files.clone(),
+            since: Instant::now(),
+        });
+
+        tracing::info!(file = ?file_path, merged = files.len(), rows, "writer.compacted");
+    }
+
+    fn write_data(&mut self, data: RecordBatch, resources: &Resources) {
+        let data = self.resources.remap(data, resources);
+
         tracing::info!(len = data.num_rows(), writes = self.writes, "writer.save");
         self.writes += data.num_rows();
+        self.pending.push(data);
+
+        let rows: usize = self.pending.iter().map(RecordBatch::num_rows).sum();
+        if rows >= Self::ROW_GROUP_ROWS {
+            self.write_row_group();
+        }
+    }
+
+    /// Writes pending batches as a single sorted row group.
+    fn write_row_group(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        let Some(writer) = self.writer.as_mut() else {
+            unreachable!();
+        };
+
+        #[allow(clippy::borrow_interior_mutable_const)]
+        let data = concat_batches(&SCHEMA, &self.pending).expect("row_group.concat");
+        let data = sort_by_service(&data, self.resources.resources());
+        self.pending.clear();
+
         writer.write(&data).expect("write.ok");
+        writer.flush().expect("write.flush");
+        push_trace_ids(&mut self.index, &data);
+    }
+}
+
+/// Rows of every row group are sorted by `service.name`, [columns::TRACE_ID],
+/// [columns::SPAN_NAME] and [columns::TIME_START].
+fn sorting_columns() -> KeyValue {
+    let columns = [
+        "service.name",
+        columns::TRACE_ID.name(),
+        columns::SPAN_NAME.name(),
+        columns::TIME_START.name(),
+    ];
+
+    KeyValue::new(Writer::SORTING_COLUMNS_KEY.to_owned(), columns.join(","))
+}
+
+/// Batches of `files` remapped to a single resource table, any unreadable file fails the read.
+async fn read_sources(
+    files: Vec<Box<Path>>,
+    threshold: usize,
+) -> Result<(Vec<RecordBatch>, Dictionary), Box<dyn std::error::Error + Send + Sync>> {
+    let mut resources = Dictionary::default();
+    let mut batches = Vec::new();
+
+    for (format, files) in Format::Arrow.split(files) {
+        if let Format::Arrow = format {
+            let mut read = Read::new(None::<[&str; 0]>, |_| Vec::new(), files).ordered();
+
+            while let Some(batch) = read.try_next_batch().await {
+                batches.push(resources.remap(batch?, read.resources()));
+            }
+        } else {
+            use crate::vortex::read::{AsSpanData, Read};
+
+            let mut read = Read::new(format, files).ordered();
+            let mut builder = Builder::new(usize::MAX, threshold);
+
+            while let Some(arr) = read.try_next_batch().await {
+                builder.append(arr?.get_span_data().collect());
+            }
+
+            let Batch {
+                data,
+                resources: built,
+            } = builder.build();
+            batches.push(resources.remap(data, &built));
+        }
+    }
+
+    Ok((batches, resources))
+}
+
+/// Lists `files` in `marker`, one name per line, synced before it returns.
+fn write_marker(marker: &Path, files: &[Box<Path>]) {
+    use std::io::Write;
+
+    let mut file = File::create(marker).expect("marker.create");
+    for path in files {
+        let name = path.file_name().expect("file.name").to_string_lossy();
+        writeln!(file, "{name}").expect("marker.write");
+    }
+    file.sync_all().expect("marker.sync");
+}
+
+/// Deletes a data file and its index.
+fn remove_file(file: &Path) {
+    if let Err(e) = std::fs::remove_file(file)
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        tracing::warn!(file = ?file, error = %e, "file.remove");
+    }
+    index::remove(file);
+}
+
+/// Finishes merges cut short by a restart, before the files of `dir` are loaded. Files replaced
+/// by a merged file are deleted, unless one of them was uploaded meanwhile and the merged file is
+/// dropped instead. Incomplete merged files are dropped, their sources are still listed.
+fn replay_retired(dir: &Path) {
+    let remote = crate::misc::read_remote_manifest(dir);
+
+    for name in crate::misc::read_dir(dir) {
+        if let Some(merged) = name.strip_prefix(MERGING_PREF) {
+            tracing::info!(file = merged, "writer.compact.incomplete");
+            remove_file(&dir.join(&name));
+            index::remove(&dir.join(merged));
+            continue;
+        }
+
+        let Some(merged) = name.strip_prefix(RETIRED_PREF) else {
+            continue;
+        };
+        let marker = dir.join(&name);
+        let merged = dir.join(merged);
+        let content = std::fs::read_to_string(&marker).expect("marker.read");
+
+        if merged.exists() {
+            if content
+                .lines()
+                .any(|file| remote.iter().any(|name| name == file))
+            {
+                tracing::info!(file = ?merged, "writer.compact.uploaded");
+                remove_file(&merged);
+            } else {
+                for file in content.lines() {
+                    remove_file(&dir.join(file));
+                }
+            }
+        }
+
+        std::fs::remove_file(&marker).expect("marker.remove");
+    }
+}
+
+fn push_trace_ids(index: &mut index::Builder, data: &RecordBatch) {
+    let trace_ids = data
+        .column_by_name(columns::TRACE_ID.name())
+        .expect("col.exists")
+        .as_fixed_size_binary();
+
+    for trace_id in trace_ids.iter() {
+        index.push(
+            trace_id
+                .expect("trace_id.not_null")
+                .try_into()
+                .expect("trace_id.len"),
+        );
     }
 }
 
 impl SpanWriter for Writer {
-    type Input = RecordBatch;
+    type Input = Batch;
 
     fn is_dirty(&self) -> bool {
         self.writes > 0
@@ -85,7 +386,9 @@ impl SpanWriter for Writer {
         &self.stats
     }
 
-    async fn write(&mut self, data: Self::Input) {
+    async fn write(&mut self, batch: Self::Input) {
+        let Batch { data, resources } = batch;
+
         if self.writer.is_none() {
             self.init_file_id();
             self.create_new_writer().await;
@@ -95,20 +398,275 @@ impl SpanWriter for Writer {
             let diff = self.threshold - self.writes;
             let first = data.slice(0, diff);
             let second = data.slice(diff, data.num_rows() - diff);
-            self.write_data(first);
+            self.write_data(first, &resources);
             self.next_file().await;
-            self.write_data(second);
+            self.write_data(second, &resources);
             return;
         }
 
-        self.write_data(data);
+        self.write_data(data, &resources);
     }
 
     async fn suspend(&mut self) {
         self.close_writer().await;
+        self.remove_retired(Self::RETIRE_AFTER);
+    }
+
+    async fn compact(&mut self) {
+        assert!(self.writer.is_none());
+
+        let files: Vec<Box<Path>> = { self.stats.files.read().await.clone() };
+        let mut small = Vec::new();
+
+        for file in files {
+            // Uploaded files are left alone, the object store keeps them as they are.
+            if self.stats.location(&file).await != Location::Local {
+                continue;
+            }
+
+            let rows = match Format::Arrow.file_info(file.clone(), &self.stats).await {
+                Ok(info) => info.rows,
+                Err(e) => {
+                    tracing::error!(file = ?file, error = %e, "file.unreadable");
+                    continue;
+                }
+            };
+
+            if (rows as usize) < self.threshold {
+                small.push((file, rows as usize));
+            }
+        }
+
+        // Groups of files which together fit into a single file.
+        let mut groups: Vec<Vec<(Box<Path>, usize)>> = Vec::new();
+        let mut group_rows = 0;
+
+        for (file, rows) in small {
+            match groups.last_mut() {
+                Some(group) if group_rows + rows <= self.threshold => {
+                    group.push((file, rows));
+                    group_rows += rows;
+                }
+                _ => {
+                    groups.push(vec![(file, rows)]);
+                    group_rows = rows;
+                }
+            }
+        }
+
+        for group in groups.into_iter().filter(|group| group.len() > 1) {
+            self.merge(group).await;
+        }
     }
 
     async fn finish(mut self) {
         self.close_writer().await;
+        // Left over files would be read again next to the merged ones after a restart.
+        self.remove_retired(Duration::ZERO);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    use super::*;
+    use crate::load::{Generator, Options as LoadOptions, ingest};
+    use crate::testing;
+    use crate::write::Options as WriterOptions;
+
+    #[test]
+    fn flushed_batches_share_a_sorted_row_group() {
+        let mut generator = Generator::new(LoadOptions::default());
+        let spans: Vec<_> = (0..100)
+            .flat_map(|idx| generator.trace(idx * 1_000_000))
+            .collect();
+        let len = spans.len();
+
+        let dir = testing::temp_dir("row-groups");
+
+        let options = WriterOptions {
+            spans_per_file: 1 << 20,
+            builder_flush_threshold: 16,
+            builder_capacity: 16,
+            ..testing::writer_options()
+        };
+
+        let rt = testing::runtime();
+        rt.block_on(ingest(
+            &Format::Arrow,
+            dir.clone().into_boxed_path(),
+            spans,
+            options,
+        ));
+
+        let files = crate::misc::load_existing_files(&dir, &[Writer::PREF]);
+        assert_eq!(files.len(), 1);
+
+        let reader = SerializedFileReader::new(File::open(&files[0]).unwrap()).unwrap();
+        let metadata = reader.metadata().file_metadata();
+        // Every flush of the builder wrote a batch, none of them ended a row group.
+        assert_eq!(reader.num_row_groups(), 1);
+        assert_eq!(metadata.num_rows() as usize, len);
+        assert!(metadata.key_value_metadata().unwrap().iter().any(|kv| {
+            kv.key == Writer::SORTING_COLUMNS_KEY
+                && kv.value.as_deref() == Some("service.name,trace_id,span_name,time_start")
+        }));
+
+        let mut read = Read::new(None::<[&str; 0]>, |_| Vec::new(), files).ordered();
+        let mut services = Vec::new();
+
+        while let Some(batch) = rt.block_on(read.next_batch()) {
+            let ids = batch
+                .column_by_name(columns::RESOURCE_ID.name())
+                .unwrap()
+                .as_primitive::<arrow::datatypes::UInt32Type>();
+
+            services.extend(
+                ids.values()
+                    .iter()
+                    .map(|id| read.resources().service_name(*id).map(str::to_owned)),
+            );
+        }
+        assert_eq!(services.len(), len);
+        assert!(services.is_sorted());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn indexed_rows_of_a_trace_are_tight() {
+        use std::collections::{HashMap, HashSet};
+
+        let mut generator = Generator::new(LoadOptions::default());
+        let spans: Vec<_> = (0..100)
+            .flat_map(|idx| generator.trace(idx * 1_000_000))
+            .collect();
+
+        // Trace spanning the most services, its spans end up in several runs of rows.
+        let mut services: HashMap<[u8; 16], HashSet<String>> = HashMap::new();
+        for span in &spans {
+            let service = span
+                .resource_attributes
+                .iter()
+                .find(|kv| kv.key == "service.name");
+            services
+                .entry(span.trace_id)
+                .or_default()
+                .insert(format!("{service:?}"));
+        }
+        let (trace_id, _) = services
+            .iter()
+            .max_by_key(|(_, services)| services.len())
+            .unwrap();
+        assert!(services[trace_id].len() > 1);
+        let len = spans
+            .iter()
+            .filter(|span| span.trace_id == *trace_id)
+            .count();
+
+        let dir = testing::temp_dir("index-rows");
+        let rt = testing::runtime();
+        // A single batch, sorted as a whole.
+        let options = WriterOptions {
+            spans_per_file: 1 << 20,
+            builder_flush_threshold: spans.len(),
+            builder_capacity: spans.len(),
+            ..testing::writer_options()
+        };
+        rt.block_on(ingest(
+            &Format::Arrow,
+            dir.clone().into_boxed_path(),
+            spans,
+            options,
+        ));
+
+        let files = crate::misc::load_existing_files(&dir, &[Writer::PREF]);
+        let (found, rows) = index::lookup(files, &[*trace_id]);
+        assert_eq!(found.len(), 1);
+
+        // Only rows of the trace are read, a run of rows per service.
+        let ranges = &rows[&found[0]];
+        assert!(ranges.len() <= services[trace_id].len());
+        assert_eq!(
+            ranges.iter().map(ExactSizeIterator::len).sum::<usize>(),
+            len
+        );
+
+        let mut read = Read::new(None::<[&str; 0]>, |_| Vec::new(), found).with_rows(rows);
+        let mut read_rows = 0;
+
+        while let Some(batch) = rt.block_on(read.next_batch()) {
+            let trace_ids = batch
+                .column_by_name(columns::TRACE_ID.name())
+                .unwrap()
+                .as_fixed_size_binary();
+
+            assert!(trace_ids.iter().all(|id| id == Some(trace_id.as_slice())));
+            read_rows += batch.num_rows();
+        }
+        assert_eq!(read_rows, len);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Names of the files left in `dir`, sorted.
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = crate::misc::read_dir(dir).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn interrupted_merges_are_replayed() {
+        let dir = testing::temp_dir("replay");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let touch = |name: &str| std::fs::write(dir.join(name), b"").unwrap();
+        let pref = Writer::PREF;
+
+        // A merged file replacing 0 and 1, and a merge of 2 and 3 which wasn't renamed yet.
+        for idx in 0..5 {
+            touch(&format!("{pref}{idx}"));
+        }
+        std::fs::write(
+            dir.join(format!("{RETIRED_PREF}{pref}4")),
+            format!("{pref}0\n{pref}1\n"),
+        )
+        .unwrap();
+        touch(&format!("{MERGING_PREF}{pref}5"));
+        std::fs::write(
+            dir.join(format!("{RETIRED_PREF}{pref}5")),
+            format!("{pref}2\n{pref}3\n"),
+        )
+        .unwrap();
+
+        let writer = Writer::new(dir.clone().into_boxed_path(), 16);
+        assert_eq!(
+            names(&dir),
+            [2, 3, 4].map(|idx| format!("{pref}{idx}")).to_vec()
+        );
+        assert_eq!(writer.stats.files.blocking_read().len(), 3);
+
+        // Sources uploaded before the restart are kept, the merged file is dropped instead.
+        std::fs::write(
+            dir.join(format!("{RETIRED_PREF}{pref}4")),
+            format!("{pref}2\n{pref}3\n"),
+        )
+        .unwrap();
+        crate::misc::append_remote_manifest(&dir, &format!("{pref}3"));
+
+        let writer = Writer::new(dir.clone().into_boxed_path(), 16);
+        assert_eq!(
+            names(&dir),
+            vec![
+                crate::misc::REMOTE_MANIFEST.to_owned(),
+                format!("{pref}2"),
+                format!("{pref}3"),
+            ]
+        );
+        assert_eq!(writer.stats.files.blocking_read().len(), 2);
+
+        std::fs::remove_dir_all(dir).unwrap();
     }
 }
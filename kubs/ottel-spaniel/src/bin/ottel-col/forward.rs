@@ -0,0 +1,439 @@
+//! Forwards accepted export requests to upstream OTLP/gRPC collectors.
+//!
+//! Every destination has its own bounded channel and worker, handlers wait while a channel is
+//! full. Workers move every request into a bounded on-disk queue right away and deliver the queue
+//! in order, retrying with backoff, so an unreachable destination doesn't hold up ingest. Queued
+//! requests are kept across restarts.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+use opentelemetry_proto::tonic::collector::trace::v1::trace_service_client::TraceServiceClient;
+use prost::Message;
+use tokio::sync::mpsc;
+use tonic::transport::{Channel, Endpoint};
+
+pub struct Options {
+    /// OTLP/gRPC endpoints receiving a copy of every request.
+    pub endpoints: Vec<String>,
+    /// Directory holding retry queues, one subdirectory per endpoint.
+    pub queue_dir: &'static str,
+    /// Maximum number of requests kept in the retry queue of a single endpoint.
+    pub queue_capacity: usize,
+    /// Capacity of the channel between request handlers and the endpoint worker.
+    pub channel_size: usize,
+    /// Delay after the first failed delivery, doubled after every further failure up to
+    /// [MAX_BACKOFF] times the interval.
+    pub retry_interval_millis: u64,
+    /// Time a single export may take before it is treated as failed and queued.
+    pub timeout_millis: u64,
+}
+
+/// Limit of the delay between deliveries, as a multiple of [Options::retry_interval_millis].
+const MAX_BACKOFF: u32 = 16;
+
+#[derive(Clone, Debug, Default)]
+pub struct Forward {
+    senders: Arc<Vec<(String, mpsc::Sender<ExportTraceServiceRequest>)>>,
+}
+
+impl Forward {
+    /// Hands a copy of the request to every destination without waiting for delivery, but
+    /// waits while the channel of a destination is full.
+    pub async fn send(&self, request: &ExportTraceServiceRequest) {
+        for (endpoint, sender) in self.senders.iter() {
+            if sender.send(request.clone()).await.is_err() {
+                tracing::warn!(endpoint, "forward.closed");
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.senders.is_empty()
+    }
+}
+
+pub fn start(options: Options) -> (Forward, Vec<impl Future<Output = ()> + Send + 'static>) {
+    let mut senders = Vec::with_capacity(options.endpoints.len());
+    let mut workers = Vec::with_capacity(options.endpoints.len());
+
+    for endpoint in options.endpoints.iter() {
+        let (tx, rx) = mpsc::channel(options.channel_size);
+        let target = Endpoint::from_shared(endpoint.clone())
+            .expect("forward.endpoint.valid")
+            .timeout(Duration::from_millis(options.timeout_millis));
+
+        let mut dir = PathBuf::from(options.queue_dir);
+        dir.push(endpoint.replace(|c: char| !c.is_ascii_alphanumeric(), "_"));
+
+        let worker = Worker {
+            endpoint: endpoint.clone(),
+            target,
+            queue: Arc::new(Mutex::new(Queue::open(dir, options.queue_capacity))),
+            retry_interval: Duration::from_millis(options.retry_interval_millis),
+        };
+
+        senders.push((endpoint.clone(), tx));
+        workers.push(worker.run(rx));
+    }
+
+    let forward = Forward {
+        senders: Arc::new(senders),
+    };
+
+    (forward, workers)
+}
+
+struct Worker {
+    endpoint: String,
+    target: Endpoint,
+    queue: Arc<Mutex<Queue>>,
+    retry_interval: Duration,
+}
+
+enum Sent {
+    Ok,
+    Retry,
+}
+
+async fn send(
+    client: &mut TraceServiceClient<Channel>,
+    endpoint: &str,
+    request: ExportTraceServiceRequest,
+) -> Sent {
+    match client.export(request).await {
+        Ok(_) => Sent::Ok,
+        Err(status) if ottel_spaniel::export::is_retryable(&status) => {
+            tracing::warn!(endpoint, ?status, "forward.retry");
+            Sent::Retry
+        }
+        Err(status) => {
+            tracing::error!(endpoint, ?status, "forward.rejected");
+            Sent::Ok
+        }
+    }
+}
+
+impl Worker {
+    /// Runs a queue operation on the blocking pool, it reads and writes files.
+    async fn queue<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Queue) -> T + Send + 'static,
+    ) -> T {
+        let queue = self.queue.clone();
+        tokio::task::spawn_blocking(move || f(&mut queue.lock().expect("queue.lock")))
+            .await
+            .expect("queue.task")
+    }
+
+    async fn run(self, mut rx: mpsc::Receiver<ExportTraceServiceRequest>) {
+        let queued = tokio::sync::Notify::new();
+        let closing = tokio::sync::Notify::new();
+        let closed = AtomicBool::new(false);
+
+        // Drains the channel no matter how delivery goes.
+        let intake = async {
+            while let Some(request) = rx.recv().await {
+                self.queue(move |queue| queue.push(&request)).await;
+                queued.notify_one();
+            }
+
+            closed.store(true, Ordering::Release);
+            closing.notify_one();
+            queued.notify_one();
+        };
+
+        let deliver = async {
+            // The channel has to be created inside the runtime.
+            let client = &mut TraceServiceClient::new(self.target.connect_lazy());
+            let endpoint = self.endpoint.as_str();
+            let mut backoff = self.retry_interval;
+
+            loop {
+                let front = self
+                    .queue(|queue| queue.front().map(|request| (queue.head, request)))
+                    .await;
+
+                let Some((seq, request)) = front else {
+                    if closed.load(Ordering::Acquire) {
+                        return;
+                    }
+
+                    queued.notified().await;
+                    continue;
+                };
+
+                // Requests left queued on shutdown are delivered after a restart.
+                let sent = tokio::select! {
+                    sent = send(client, endpoint, request) => sent,
+                    _ = closing.notified() => return,
+                };
+
+                match sent {
+                    Sent::Ok => {
+                        self.queue(move |queue| queue.remove(seq)).await;
+                        backoff = self.retry_interval;
+                    }
+                    Sent::Retry => {
+                        tokio::select! {
+                            _ = tokio::time::sleep(backoff) => {}
+                            _ = closing.notified() => return,
+                        }
+
+                        backoff = (backoff * 2).min(self.retry_interval * MAX_BACKOFF);
+                    }
+                }
+            }
+        };
+
+        futures::future::join(intake, deliver).await;
+
+        let queued = self.queue(|queue| queue.len()).await;
+        tracing::info!(endpoint = self.endpoint, queued, "forward.finish");
+    }
+}
+
+/// Bounded FIFO of encoded requests stored as sequentially numbered files.
+struct Queue {
+    dir: PathBuf,
+    capacity: usize,
+    /// Sequence number of the oldest request.
+    head: u64,
+    /// Sequence number assigned to the next request.
+    tail: u64,
+}
+
+impl Queue {
+    fn open(dir: PathBuf, capacity: usize) -> Self {
+        std::fs::create_dir_all(&dir).expect("queue.dir.create");
+
+        let seqs: Vec<u64> = ottel_spaniel::misc::read_dir(&dir)
+            .filter_map(|name| name.parse().ok())
+            .collect();
+
+        let head = seqs.iter().copied().min().unwrap_or(0);
+        let tail = seqs.iter().copied().max().map(|v| v + 1).unwrap_or(0);
+
+        if tail > head {
+            tracing::info!(dir = ?dir, len = tail - head, "forward.queue.restored");
+        }
+
+        Self {
+            dir,
+            capacity,
+            head,
+            tail,
+        }
+    }
+
+    fn path(&self, seq: u64) -> PathBuf {
+        self.dir.join(format!("{seq:020}"))
+    }
+
+    fn len(&self) -> usize {
+        (self.tail - self.head) as usize
+    }
+
+    fn is_empty(&self) -> bool {
+        self.head == self.tail
+    }
+
+    fn push(&mut self, request: &ExportTraceServiceRequest) {
+        if self.len() >= self.capacity {
+            tracing::warn!(dir = ?self.dir, "forward.queue.full");
+            self.pop_front();
+        }
+
+        std::fs::write(self.path(self.tail), request.encode_to_vec()).expect("queue.write");
+        self.tail += 1;
+    }
+
+    fn front(&mut self) -> Option<ExportTraceServiceRequest> {
+        while !self.is_empty() {
+            let path = self.path(self.head);
+
+            match std::fs::read(&path)
+                .ok()
+                .and_then(|buf| ExportTraceServiceRequest::decode(buf.as_slice()).ok())
+            {
+                Some(request) => return Some(request),
+                None => {
+                    tracing::error!(file = ?path, "forward.queue.corrupted");
+                    self.pop_front();
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Removes request `seq` unless it was dropped from a full queue meanwhile.
+    fn remove(&mut self, seq: u64) {
+        if self.head == seq && !self.is_empty() {
+            self.pop_front();
+        }
+    }
+
+    fn pop_front(&mut self) {
+        assert!(!self.is_empty());
+
+        let _ = std::fs::remove_file(self.path(self.head));
+        self.head += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry_proto::tonic::trace::v1::ResourceSpans;
+
+    use super::*;
+    use crate::testing::{runtime, temp_dir};
+
+    /// Requests are told apart by their number of resources.
+    fn request(len: usize) -> ExportTraceServiceRequest {
+        ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans::default(); len],
+        }
+    }
+
+    #[test]
+    fn queue_is_bounded_ordered_and_restored() {
+        let dir = temp_dir("forward-queue");
+
+        let mut queue = Queue::open(dir.clone(), 2);
+        for len in 1..=3 {
+            queue.push(&request(len));
+        }
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.front(), Some(request(2)));
+
+        let mut queue = Queue::open(dir.clone(), 2);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.front(), Some(request(2)));
+
+        std::fs::write(queue.path(queue.head), b"corrupted").unwrap();
+        assert_eq!(queue.front(), Some(request(3)));
+        queue.pop_front();
+        assert!(queue.is_empty());
+        assert_eq!(queue.front(), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn send_waits_for_a_full_channel() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async {
+            let (tx, mut rx) = mpsc::channel(1);
+            let forward = Forward {
+                senders: Arc::new(vec![("test".to_owned(), tx)]),
+            };
+
+            forward.send(&request(1)).await;
+            let blocked =
+                tokio::time::timeout(Duration::from_millis(50), forward.send(&request(2))).await;
+            assert!(blocked.is_err());
+
+            assert_eq!(rx.recv().await, Some(request(1)));
+            forward.send(&request(3)).await;
+            assert_eq!(rx.recv().await, Some(request(3)));
+        });
+    }
+
+    #[test]
+    fn undelivered_requests_are_queued() {
+        let dir = temp_dir("forward-worker");
+        let endpoint = "http://127.0.0.1:1".to_owned();
+
+        let options = Options {
+            endpoints: vec![endpoint.clone()],
+            queue_dir: dir.to_string_lossy().into_owned().leak(),
+            queue_capacity: 16,
+            channel_size: 4,
+            retry_interval_millis: 10,
+            timeout_millis: 1_000,
+        };
+
+        let rt = runtime();
+
+        rt.block_on(async {
+            let (forward, workers) = start(options);
+            let workers: Vec<_> = workers.into_iter().map(tokio::spawn).collect();
+
+            forward.send(&request(1)).await;
+            forward.send(&request(2)).await;
+            // Closing the channels stops the workers.
+            drop(forward);
+
+            for worker in workers {
+                worker.await.unwrap();
+            }
+        });
+
+        let mut queue = Queue::open(
+            dir.join(endpoint.replace(|c: char| !c.is_ascii_alphanumeric(), "_")),
+            16,
+        );
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.front(), Some(request(1)));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn blackholed_endpoint_does_not_block_senders() {
+        // Accepts connections but never answers.
+        let blackhole = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", blackhole.local_addr().unwrap());
+        let dir = temp_dir("forward-blackhole");
+
+        let options = Options {
+            endpoints: vec![endpoint.clone()],
+            queue_dir: dir.to_string_lossy().into_owned().leak(),
+            queue_capacity: 16,
+            channel_size: 1,
+            retry_interval_millis: 10,
+            timeout_millis: 60_000,
+        };
+
+        let rt = runtime();
+
+        rt.block_on(async {
+            let (forward, workers) = start(options);
+            let workers: Vec<_> = workers.into_iter().map(tokio::spawn).collect();
+
+            let sent = async {
+                for len in 1..=8 {
+                    forward.send(&request(len)).await;
+                }
+            };
+            tokio::time::timeout(Duration::from_secs(5), sent)
+                .await
+                .expect("forward.send.blocked");
+            drop(forward);
+
+            for worker in workers {
+                tokio::time::timeout(Duration::from_secs(5), worker)
+                    .await
+                    .expect("forward.worker.stuck")
+                    .unwrap();
+            }
+        });
+
+        let queue = Queue::open(
+            dir.join(endpoint.replace(|c: char| !c.is_ascii_alphanumeric(), "_")),
+            16,
+        );
+        assert_eq!(queue.len(), 8);
+
+        drop(blackhole);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,13 +1,35 @@
-use ottel_spaniel::write::{Format, Options, start_writer};
+use std::path::Path;
+use std::time::Duration;
 
-mod convert;
+use ottel_spaniel::alert::{self, Evaluator, Notifier};
+use ottel_spaniel::tier::{self, Tier};
+use ottel_spaniel::write::{Format, Options};
+use ottel_spaniel::{scan, sql};
+
+mod forward;
 mod runtime;
 mod server;
+mod tenant;
+
+// Fixtures of the library tests, they name its modules through `crate::`. Not every one of them
+// is needed here.
+#[cfg(test)]
+#[path = "../../testing.rs"]
+#[allow(dead_code)]
+mod testing;
+#[cfg(test)]
+use ottel_spaniel::write;
 
 fn main() {
     init_tracing();
 
     let format = get_format();
+
+    // Files read at once by a single query, every core by default.
+    if let Some(parallelism) = get_args("--scan-parallelism=").last() {
+        scan::set_parallelism(parallelism.parse().expect("scan-parallelism.valid"));
+    }
+
     let options = Options {
         flush_interval_millis: 2_000,
         suspend_interval_millis: 180_000,
@@ -23,15 +45,109 @@ fn main() {
         host: "0.0.0.0",
         port: 44318,
         shutdown_timeout_secs: 60,
+        cors_origins: get_args("--cors-origin="),
+        tls: get_args("--tls-cert=")
+            .pop()
+            .zip(get_args("--tls-key=").pop()),
+        sql: get_sql_options(),
     };
 
-    let (sink, stats, task) = start_writer(&format, options);
+    let auth = server::Auth::new(
+        get_args("--ingest-tokens=")
+            .iter()
+            .flat_map(server::read_tokens)
+            .collect(),
+        get_args("--query-tokens=")
+            .iter()
+            .flat_map(server::read_tokens)
+            .collect(),
+        get_args("--admin-tokens=")
+            .iter()
+            .flat_map(server::read_tokens)
+            .collect(),
+    );
 
-    let server_task = server::run_server(server_options, format.clone(), stats.clone(), sink);
+    let forward_options = forward::Options {
+        endpoints: get_forward_endpoints(),
+        queue_dir: "data-forward",
+        queue_capacity: 10_000,
+        channel_size: 256,
+        retry_interval_millis: 5_000,
+        timeout_millis: 10_000,
+    };
+
+    let tenant_options = tenant::Options {
+        data_dir: get_args("--data-dir=")
+            .pop()
+            .map_or(Path::new(ottel_spaniel::write::DATA_DIR).into(), |dir| {
+                Path::new(&dir).into()
+            }),
+        tenants: get_args("--tenant="),
+        api_keys: get_args("--api-keys=")
+            .last()
+            .map(tenant::read_api_keys)
+            .unwrap_or_default(),
+        spans_per_sec: get_args("--tenant-rate=")
+            .last()
+            .map(|v| v.parse().expect("tenant-rate.valid"))
+            .unwrap_or(0),
+        burst: 10_000,
+    };
+
+    let (tenants, tasks) = tenant::start(&format, options, tenant_options);
+    let (forward, forward_tasks) = forward::start(forward_options);
+
+    let server_task = server::run_server(
+        server_options,
+        format.clone(),
+        tenants.clone(),
+        auth,
+        forward,
+    );
 
     let rt = runtime::RT::new();
     rt.run_server_future(server_task);
-    rt.run_writer_future(task);
+    for task in forward_tasks {
+        rt.run_server_future(task);
+    }
+    if let Some(url) = get_tier_url() {
+        let options = tier::Options::new(url);
+        let tier = Tier::from_options(&options).expect("tier.ok");
+
+        tier.clone().install();
+        for tenant in tenants.iter() {
+            let options = tier::Options::new(options.url.clone());
+            rt.run_server_future(tier.clone().run(tenant.stats.clone(), options));
+        }
+    }
+
+    if let Some(path) = get_args("--alert-rules=").pop() {
+        let rules = alert::read_rules(path).expect("alert-rules.valid");
+        let notifier = get_args("--alert-webhook=")
+            .pop()
+            .map(|url| Notifier::new(url.parse().expect("alert-webhook.valid")));
+        let interval = Duration::from_secs(
+            get_args("--alert-interval-secs=")
+                .last()
+                .map(|v| v.parse().expect("alert-interval-secs.valid"))
+                .unwrap_or(60),
+        );
+
+        for tenant in tenants.iter() {
+            let evaluator = Evaluator::new(
+                tenant.id.clone(),
+                rules.clone(),
+                notifier.clone(),
+                tenant.window.clone(),
+            );
+            rt.run_server_future(evaluator.run(format.clone(), tenant.stats.clone(), interval));
+        }
+    }
+
+    let tasks = tasks.into_iter().map(Box::into_pin);
+    rt.run_writer_future(Box::new(async {
+        futures::future::join_all(tasks).await;
+    }));
 }
 
 fn get_format() -> Format {
@@ -41,16 +157,40 @@ fn get_format() -> Format {
         return Format::Arrow;
     }
 
-    use vortex::VortexSessionDefault;
-    use vortex::io::runtime::BlockingRuntime;
-    use vortex::io::runtime::current::CurrentThreadRuntime;
-    use vortex::io::session::RuntimeSessionExt;
-    use vortex::session::VortexSession;
+    Format::vortex()
+}
+
+/// Upstream OTLP/gRPC collectors given as `--forward=<url>`, may be repeated.
+fn get_forward_endpoints() -> Vec<String> {
+    get_args("--forward=")
+}
+
+/// Limits of `/v0/sql` queries, `--sql-timeout-secs=` and `--sql-max-rows=`.
+fn get_sql_options() -> sql::Options {
+    let defaults = sql::Options::default();
+
+    sql::Options {
+        timeout: get_args("--sql-timeout-secs=")
+            .last()
+            .map(|v| Duration::from_secs(v.parse().expect("sql-timeout-secs.valid")))
+            .unwrap_or(defaults.timeout),
+        max_rows: get_args("--sql-max-rows=")
+            .last()
+            .map(|v| v.parse().expect("sql-max-rows.valid"))
+            .unwrap_or(defaults.max_rows),
+    }
+}
 
-    let runtime = CurrentThreadRuntime::new();
-    let session = VortexSession::default().with_handle(runtime.handle());
+/// Values of every `<prefix><value>` argument.
+fn get_args(prefix: &str) -> Vec<String> {
+    std::env::args()
+        .filter_map(|i| i.strip_prefix(prefix).map(str::to_owned))
+        .collect()
+}
 
-    Format::Vortex { runtime, session }
+/// Object store receiving closed files, given as `--tier=<url>`.
+fn get_tier_url() -> Option<String> {
+    std::env::args().find_map(|i| i.strip_prefix("--tier=").map(str::to_owned))
 }
 
 fn init_tracing() {
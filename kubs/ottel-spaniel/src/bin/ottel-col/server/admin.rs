@@ -0,0 +1,239 @@
+use poem::http::StatusCode;
+use poem::web::{Data, Json};
+
+use ottel_spaniel::{Command, Format, Location, Sink, Stats};
+
+use crate::tenant::Tenants;
+
+#[poem::handler]
+pub async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// Ready once every tenant writer is running, existing files are loaded before that.
+#[poem::handler]
+pub async fn readyz(Data(tenants): Data<&Tenants>) -> (StatusCode, &'static str) {
+    if tenants.iter().all(|tenant| tenant.stats.is_ready()) {
+        return (StatusCode::OK, "ok");
+    }
+
+    (StatusCode::SERVICE_UNAVAILABLE, "starting")
+}
+
+#[poem::handler]
+pub async fn admin_rotate(Data(sink): Data<&Sink>) -> StatusCode {
+    sink.command(Command::Rotate).await;
+    StatusCode::NO_CONTENT
+}
+
+#[poem::handler]
+pub async fn admin_flush(Data(sink): Data<&Sink>) -> StatusCode {
+    sink.command(Command::Flush).await;
+    StatusCode::NO_CONTENT
+}
+
+/// Only Arrow files are merged, Vortex writers can't compact.
+#[poem::handler]
+pub async fn admin_compact(Data(format): Data<&Format>, Data(sink): Data<&Sink>) -> StatusCode {
+    if let Format::Vortex { .. } = format {
+        return StatusCode::NOT_IMPLEMENTED;
+    }
+
+    sink.command(Command::Compact).await;
+    StatusCode::NO_CONTENT
+}
+
+#[poem::handler]
+pub async fn admin_get_files(
+    Data(format): Data<&Format>,
+    Data(stats): Data<&Stats>,
+) -> Json<response::Files> {
+    let files: Vec<Box<_>> = { stats.files.read().await.iter().cloned().collect() };
+    let mut list = Vec::with_capacity(files.len());
+
+    for file in files {
+        let location = |location| match location {
+            Location::Local => "local",
+            Location::Both => "both",
+            Location::Remote => "remote",
+        };
+
+        // Unreadable files are listed with the error instead of their summary.
+        let entry = match format.file_info(file.clone(), stats).await {
+            Ok(info) => response::File {
+                path: info.path.to_string_lossy().into_owned(),
+                location: location(info.location),
+                size_bytes: info.size,
+                rows: Some(info.rows),
+                time_start: info.time_start,
+                time_end: info.time_end,
+                error: None,
+            },
+            Err(e) => {
+                tracing::error!(file = ?file, error = %e, "file.unreadable");
+
+                response::File {
+                    path: file.to_string_lossy().into_owned(),
+                    location: location(stats.location(&file).await),
+                    size_bytes: std::fs::metadata(&file).ok().map(|meta| meta.len()),
+                    rows: None,
+                    time_start: None,
+                    time_end: None,
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+
+        list.push(entry);
+    }
+
+    Json(response::Files { files: list })
+}
+
+pub mod response {
+    #[derive(Debug, serde::Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Files {
+        pub files: Vec<File>,
+    }
+
+    #[derive(Debug, serde::Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct File {
+        pub path: String,
+        pub location: &'static str,
+        pub size_bytes: Option<u64>,
+        pub rows: Option<u64>,
+        pub time_start: Option<u64>,
+        pub time_end: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub error: Option<String>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use poem::{Endpoint, EndpointExt, Request, Route, get, post};
+
+    use ottel_spaniel::load::{Generator, Options as LoadOptions};
+    use ottel_spaniel::write::{Options as WriterOptions, start_writer};
+
+    use super::*;
+    use crate::testing;
+
+    /// Arrow writer running on a thread of its own, it stops once the sink is dropped.
+    fn writer(dir: std::path::PathBuf) -> (Sink, Stats, std::thread::JoinHandle<()>) {
+        let options = WriterOptions {
+            spans_per_file: 1024,
+            builder_flush_threshold: 1024,
+            builder_capacity: 1024,
+            ..testing::writer_options()
+        };
+        let (send, recv) = std::sync::mpsc::channel();
+
+        let thread = std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            let format = Format::Arrow;
+            let (sink, stats, task) = start_writer(&format, dir.into_boxed_path(), options);
+            send.send((sink, stats)).unwrap();
+
+            rt.block_on(Box::into_pin(task));
+        });
+
+        let (sink, stats) = recv.recv().unwrap();
+        (sink, stats, thread)
+    }
+
+    async fn post_status(ep: &impl Endpoint, path: &str) -> StatusCode {
+        let req = Request::builder()
+            .method(poem::http::Method::POST)
+            .uri(path.parse().unwrap())
+            .finish();
+
+        ep.get_response(req).await.status()
+    }
+
+    /// Rows of every listed file.
+    async fn file_rows(ep: &impl Endpoint) -> Vec<u64> {
+        let req = Request::builder()
+            .uri("/admin/files".parse().unwrap())
+            .finish();
+        let body = ep.get_response(req).await.into_body().into_string().await;
+        let files: serde_json::Value = serde_json::from_str(&body.unwrap()).unwrap();
+
+        files["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| file["rows"].as_u64().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn rotated_files_are_compacted() {
+        let dir = testing::temp_dir("admin");
+
+        let (sink, stats, thread) = writer(dir.clone());
+        let ep = Route::new()
+            .at("/admin/rotate", post(admin_rotate))
+            .at("/admin/flush", post(admin_flush))
+            .at("/admin/compact", post(admin_compact))
+            .at("/admin/files", get(admin_get_files))
+            .data(Format::Arrow)
+            .data(sink.clone())
+            .data(stats);
+
+        let rt = testing::runtime();
+        let mut generator = Generator::new(LoadOptions::default());
+
+        rt.block_on(async {
+            let mut len = 0;
+
+            for idx in 0..3 {
+                let spans = generator.trace(idx * 1_000);
+                len += spans.len() as u64;
+
+                sink.send(spans).await;
+                assert_eq!(
+                    post_status(&ep, "/admin/flush").await,
+                    StatusCode::NO_CONTENT
+                );
+                assert_eq!(
+                    post_status(&ep, "/admin/rotate").await,
+                    StatusCode::NO_CONTENT
+                );
+            }
+
+            let rows = file_rows(&ep).await;
+            assert_eq!(rows.len(), 3);
+            assert_eq!(rows.iter().sum::<u64>(), len);
+
+            assert_eq!(
+                post_status(&ep, "/admin/compact").await,
+                StatusCode::NO_CONTENT
+            );
+            assert_eq!(file_rows(&ep).await, vec![len]);
+        });
+
+        // Vortex writers can't merge files.
+        let vortex = Route::new()
+            .at("/admin/compact", post(admin_compact))
+            .data(Format::vortex())
+            .data(sink.clone());
+        let status = rt.block_on(post_status(&vortex, "/admin/compact"));
+        assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+
+        // Merged files are kept for readers until the writer stops.
+        drop((ep, vortex, sink));
+        thread.join().unwrap();
+
+        let files = ottel_spaniel::misc::load_existing_files(&dir, &[Format::Arrow.file_prefix()]);
+        assert_eq!(files.len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
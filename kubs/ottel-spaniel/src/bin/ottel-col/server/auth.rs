@@ -0,0 +1,243 @@
+//! Bearer token authentication, configured separately for ingest (`/v1/*`), admin (`/admin/*`)
+//! and query (`/v0/*`, `/api/*`, `/metrics`) routes, other paths are not served.
+//!
+//! Tenant API keys are accepted by ingest and query routes, since they already identify a known
+//! caller. Admin routes fall back to query tokens when no admin tokens are configured. Ingest and
+//! query tokens aren't tied to a tenant, with API keys configured they can't pick one, see
+//! [crate::tenant]. Admin tokens can, their requests are marked as [Operator].
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+
+use poem::http::StatusCode;
+use poem::{Endpoint, IntoResponse, Request, Response};
+
+use crate::tenant::Tenants;
+
+#[derive(Clone, Debug, Default)]
+pub struct Auth {
+    /// Tokens accepted by ingest routes, empty disables authentication of them.
+    ingest: Arc<HashSet<String>>,
+    /// Tokens accepted by query routes, empty disables authentication of them.
+    query: Arc<HashSet<String>>,
+    /// Tokens accepted by admin routes, tenant keys are not.
+    admin: Arc<HashSet<String>>,
+}
+
+impl Auth {
+    pub fn new(ingest: HashSet<String>, query: HashSet<String>, admin: HashSet<String>) -> Self {
+        Self {
+            ingest: Arc::new(ingest),
+            query: Arc::new(query),
+            admin: Arc::new(admin),
+        }
+    }
+
+    /// Accepted tokens of a route and whether tenant keys are accepted too.
+    fn tokens(&self, kind: Kind) -> (&HashSet<String>, bool) {
+        match kind {
+            Kind::Ingest => (&self.ingest, true),
+            Kind::Admin if !self.admin.is_empty() => (&self.admin, false),
+            Kind::Admin | Kind::Query => (&self.query, true),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Ingest,
+    Query,
+    Admin,
+}
+
+impl Kind {
+    fn of(path: &str) -> Option<Self> {
+        if path.starts_with("/v1/") {
+            Some(Self::Ingest)
+        } else if path.starts_with("/admin/") {
+            Some(Self::Admin)
+        } else if path.starts_with("/v0/") || path.starts_with("/api/") || path == "/metrics" {
+            Some(Self::Query)
+        } else {
+            None
+        }
+    }
+}
+
+/// Marks requests authenticated with an admin token, operators may pick any tenant.
+#[derive(Clone, Copy, Debug)]
+pub struct Operator;
+
+/// Compares tokens in time independent of where they differ, only their length leaks.
+pub fn token_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Whether `tokens` contains `token`, every token is compared.
+fn contains(tokens: &HashSet<String>, token: &str) -> bool {
+    tokens
+        .iter()
+        .fold(false, |found, accepted| found | token_eq(accepted, token))
+}
+
+fn bearer(req: &Request) -> Option<&str> {
+    req.headers()
+        .get(poem::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+/// Rejects requests without a token accepted by the requested route.
+pub async fn check<E: Endpoint>(ep: Arc<E>, mut req: Request) -> poem::Result<Response> {
+    let Some(kind) = Kind::of(req.uri().path()) else {
+        return Err(poem::Error::from_status(StatusCode::NOT_FOUND));
+    };
+
+    let auth = req.data::<Auth>().expect("auth.data");
+    let (tokens, tenant_keys) = auth.tokens(kind);
+    let mut operator = false;
+
+    if !tokens.is_empty() {
+        let tenants = req.data::<Tenants>().expect("tenants.data");
+        let token = bearer(&req)
+            .filter(|token| contains(tokens, token) || (tenant_keys && tenants.is_api_key(token)));
+
+        let Some(token) = token else {
+            tracing::warn!(path = req.uri().path(), "auth.rejected");
+            return Err(poem::Error::from_string(
+                "unauthorized",
+                StatusCode::UNAUTHORIZED,
+            ));
+        };
+
+        operator = !tenant_keys && contains(tokens, token);
+    }
+
+    if operator {
+        req.extensions_mut().insert(Operator);
+    }
+
+    Ok(ep.call(req).await?.into_response())
+}
+
+/// Reads one token per line, empty lines and lines starting with `#` are skipped.
+pub fn read_tokens(path: impl AsRef<Path>) -> HashSet<String> {
+    let content = std::fs::read_to_string(path).expect("tokens.read");
+
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use poem::web::Data;
+    use poem::{EndpointExt, Route, get};
+
+    use super::*;
+    use crate::tenant::{self, Tenant};
+
+    #[poem::handler]
+    fn tenant_id(Data(tenant): Data<&Arc<Tenant>>) -> String {
+        tenant.id.clone()
+    }
+
+    fn set(tokens: &[&str]) -> HashSet<String> {
+        tokens.iter().map(|token| token.to_string()).collect()
+    }
+
+    async fn call(
+        ep: &impl Endpoint,
+        path: &str,
+        token: Option<&str>,
+        tenant: Option<&str>,
+    ) -> (StatusCode, String) {
+        let mut req = Request::builder().uri(path.parse().unwrap());
+
+        if let Some(token) = token {
+            req = req.header(poem::http::header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        if let Some(tenant) = tenant {
+            req = req.header(tenant::HEADER, tenant);
+        }
+
+        let resp = ep.get_response(req.finish()).await;
+        let status = resp.status();
+
+        (status, resp.into_body().into_string().await.unwrap())
+    }
+
+    #[test]
+    fn token_eq_compares_whole_tokens() {
+        assert!(token_eq("secret", "secret"));
+        assert!(!token_eq("secret", "secreT"));
+        assert!(!token_eq("secret", "secret2"));
+        assert!(!token_eq("", "secret"));
+    }
+
+    #[test]
+    fn tokens_are_checked_per_route_and_tenant() {
+        let rt = crate::testing::runtime();
+
+        rt.block_on(async {
+            let auth = Auth::new(set(&["ingest"]), set(&["query"]), set(&["admin"]));
+            let tenants = tenant::tests::tenants("auth", &[("key-a", "a"), ("key-b", "b")]);
+
+            let scoped = Route::new()
+                .at("/v1/traces", get(tenant_id))
+                .at("/v0/query", get(tenant_id))
+                .at("/admin/files", get(tenant_id))
+                .around(tenant::scope);
+            let ep = Route::new()
+                .at("/other", get(tenant_id))
+                .nest("/", scoped)
+                .around(check)
+                .data(tenants)
+                .data(auth);
+
+            let ok = |id: &str| (StatusCode::OK, id.to_owned());
+
+            // Rejected: missing, unknown and tokens of another route.
+            for (path, token) in [
+                ("/v0/query", None),
+                ("/v0/query", Some("unknown")),
+                ("/v0/query", Some("ingest")),
+                ("/v1/traces", Some("query")),
+                ("/admin/files", Some("query")),
+                ("/admin/files", Some("key-a")),
+            ] {
+                let (status, _) = call(&ep, path, token, Some("a")).await;
+                assert_eq!(status, StatusCode::UNAUTHORIZED, "{path} {token:?}");
+            }
+
+            // Unclassified paths are never served.
+            let (status, _) = call(&ep, "/other", Some("admin"), None).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+
+            // Accepted: tenant keys resolve their own tenant, operators pick one.
+            assert_eq!(call(&ep, "/v0/query", Some("key-a"), None).await, ok("a"));
+            assert_eq!(
+                call(&ep, "/v1/traces", Some("key-b"), Some("b")).await,
+                ok("b")
+            );
+            assert_eq!(
+                call(&ep, "/admin/files", Some("admin"), Some("b")).await,
+                ok("b")
+            );
+
+            // Mismatched: a key can't reach another tenant, a query token can't pick one.
+            let (status, _) = call(&ep, "/v0/query", Some("key-a"), Some("b")).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            let (status, _) = call(&ep, "/v0/query", Some("query"), Some("a")).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        });
+    }
+}
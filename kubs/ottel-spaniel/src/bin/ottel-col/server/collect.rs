@@ -1,22 +1,64 @@
+use std::sync::Arc;
+
 use opentelemetry_proto::tonic::collector::trace::v1::{
     ExportTraceServiceRequest, ExportTraceServiceResponse,
 };
+
+use poem::http::StatusCode;
 use poem::web::{Data, Json};
 
 use ottel_spaniel::Sink;
 
+use super::metrics::SERVER;
+use crate::forward::Forward;
+use crate::tenant::Tenant;
+
 #[poem::handler]
 pub async fn v1_handle_export_trace_request(
     Data(sink): Data<&Sink>,
+    Data(forward): Data<&Forward>,
+    Data(tenant): Data<&Arc<Tenant>>,
     Json(body): Json<ExportTraceServiceRequest>,
-) -> Json<ExportTraceServiceResponse> {
-    let spans = crate::convert::request_to_span_data(body);
+) -> poem::Result<Json<ExportTraceServiceResponse>> {
+    let len: usize = body
+        .resource_spans
+        .iter()
+        .flat_map(|r| r.scope_spans.iter())
+        .map(|s| s.spans.len())
+        .sum();
+
+    if !tenant.admit(len) {
+        tracing::warn!(tenant = tenant.id, len, "collect.rate_limited");
+        SERVER
+            .rejected_spans
+            .get(&[("tenant", &tenant.id), ("reason", "rate_limit")])
+            .add(len as u64);
+        return Err(poem::Error::from_string(
+            "tenant ingest rate exceeded",
+            StatusCode::TOO_MANY_REQUESTS,
+        ));
+    }
+
+    SERVER
+        .ingested_spans
+        .get(&[("tenant", &tenant.id)])
+        .add(len as u64);
+
+    // Copies are forwarded only once the spans are stored locally.
+    let copy = (!forward.is_empty()).then(|| body.clone());
+    let spans = ottel_spaniel::convert::request_to_span_data(body);
 
     if !spans.is_empty() {
+        tenant.tap.publish(&spans);
+        tenant.window.push(&spans);
         sink.send(spans).await;
     }
 
-    Json(ExportTraceServiceResponse {
+    if let Some(copy) = copy {
+        forward.send(&copy).await;
+    }
+
+    Ok(Json(ExportTraceServiceResponse {
         partial_success: None,
-    })
+    }))
 }
@@ -0,0 +1,850 @@
+//! Subset of the Jaeger query API (`/api/*`) used by Grafana's Jaeger datasource.
+//!
+//! https://www.jaegertracing.io/docs/latest/apis/#http-json-internal
+//!
+//! Vortex files keep no attributes, their spans are reported as [UNKNOWN_SERVICE].
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use opentelemetry_proto::tonic::common::v1::any_value::Value;
+use poem::http::StatusCode;
+use poem::web::{Data, Json, Path, Query};
+
+use ottel_spaniel::query::Source;
+use ottel_spaniel::{FileFormat, Format, SpanData, Stats};
+
+/// Service of spans without a `service.name` resource attribute.
+const UNKNOWN_SERVICE: &str = "unknown_service";
+
+#[poem::handler]
+pub async fn api_get_services(Data(stats): Data<&Stats>) -> Json<response::Response<Vec<String>>> {
+    use ottel_spaniel::arrow::load_resources;
+
+    let files: Vec<Box<_>> = { stats.files.read().await.iter().cloned().collect() };
+    let mut names = BTreeSet::new();
+
+    if files
+        .iter()
+        .any(|file| FileFormat::of(file) == Some(FileFormat::Vortex))
+    {
+        names.insert(UNKNOWN_SERVICE.to_owned());
+    }
+
+    // Every stored resource is referenced by at least one span, so rows can be skipped.
+    for file in FileFormat::Arrow.only(files) {
+        let resources = match load_resources(file.clone()).await {
+            Ok(resources) => resources,
+            Err(e) => {
+                tracing::error!(file = ?file, error = %e, "file.unreadable");
+                continue;
+            }
+        };
+
+        names.extend(
+            (0..resources.len() as u32)
+                .filter_map(|id| resources.service_name(id))
+                .map(str::to_owned),
+        );
+    }
+
+    Json(response::Response::new(names.into_iter().collect()))
+}
+
+#[poem::handler]
+pub async fn api_get_operations(
+    Data(format): Data<&Format>,
+    Data(stats): Data<&Stats>,
+    Path(service): Path<String>,
+) -> Json<response::Response<Vec<String>>> {
+    use ottel_spaniel::arrow::{AttrFilter, CustomFilter, Read, load_resources};
+    use std::sync::Arc;
+
+    let files: Vec<Box<_>> = { stats.files.read().await.iter().cloned().collect() };
+    let mut names = BTreeSet::new();
+
+    // Arrow files without a resource of the service are skipped without reading their rows.
+    let mut arrow_files = Vec::new();
+    for file in FileFormat::Arrow.only(files.clone()) {
+        let resources = match load_resources(file.clone()).await {
+            Ok(resources) => resources,
+            Err(e) => {
+                tracing::error!(file = ?file, error = %e, "file.unreadable");
+                continue;
+            }
+        };
+
+        if (0..resources.len() as u32)
+            .any(|id| resources.service_name(id).unwrap_or(UNKNOWN_SERVICE) == service)
+        {
+            arrow_files.push(file);
+        }
+    }
+
+    let mut readers = vec![Source::Arrow(Read::new(
+        None::<Vec<&str>>,
+        |schema| {
+            // Spans without a service can't be matched by their resource attributes.
+            if service == UNKNOWN_SERVICE {
+                return vec![];
+            }
+
+            let service = service.clone();
+            let filter = AttrFilter::resource(
+                schema,
+                "service.name",
+                Arc::new(
+                    move |value| matches!(value, Value::StringValue(name) if *name == service),
+                ),
+            );
+
+            vec![Box::new(filter) as Box<dyn CustomFilter>]
+        },
+        arrow_files,
+    ))];
+
+    if service == UNKNOWN_SERVICE {
+        let vortex_files = FileFormat::Vortex.only(files);
+        readers.extend(sources(format, vortex_files, &Bounds::default()));
+    }
+
+    for mut source in readers {
+        while let Some(spans) = source.next_batch().await {
+            for span in spans {
+                if service_name(&span) == service {
+                    names.insert(span.name);
+                }
+            }
+        }
+    }
+
+    Json(response::Response::new(names.into_iter().collect()))
+}
+
+#[poem::handler]
+pub async fn api_find_traces(
+    Data(format): Data<&Format>,
+    Data(stats): Data<&Stats>,
+    Query(query): Query<request::TraceQuery>,
+) -> poem::Result<Json<response::Response<Vec<response::Trace>>>> {
+    let tags: HashMap<String, String> = match query.tags.as_deref() {
+        Some(tags) if !tags.is_empty() => serde_json::from_str(tags)
+            .map_err(|e| poem::Error::from_string(e.to_string(), StatusCode::BAD_REQUEST))?,
+        _ => HashMap::new(),
+    };
+    let bounds = Bounds {
+        start: query.start.map(|start| start * 1_000),
+        end: query.end.map(|end| end * 1_000),
+        operation: query.operation.clone(),
+        min_duration: query
+            .min_duration
+            .as_deref()
+            .map(parse_duration)
+            .transpose()?,
+        max_duration: query
+            .max_duration
+            .as_deref()
+            .map(parse_duration)
+            .transpose()?,
+    };
+    let limit = query.limit.unwrap_or(20);
+
+    let files: Vec<Box<_>> = { stats.files.read().await.iter().cloned().collect() };
+    // Ordered by first match, the set only speeds up lookups.
+    let mut trace_ids: Vec<[u8; 16]> = Vec::with_capacity(limit);
+    let mut seen: HashSet<[u8; 16]> = HashSet::with_capacity(limit);
+
+    'outer: for mut source in sources(format, files.clone(), &bounds) {
+        while let Some(spans) = source.next_batch().await {
+            for span in spans {
+                if seen.contains(&span.trace_id) {
+                    continue;
+                }
+
+                if let Some(service) = query.service.as_ref()
+                    && service_name(&span) != service.as_str()
+                {
+                    continue;
+                }
+
+                if !matches_tags(&span, &tags) {
+                    continue;
+                }
+
+                seen.insert(span.trace_id);
+                trace_ids.push(span.trace_id);
+
+                if trace_ids.len() >= limit {
+                    break 'outer;
+                }
+            }
+        }
+    }
+
+    let mut traces = read_traces(format, files, &trace_ids).await;
+    let traces = trace_ids
+        .iter()
+        .filter_map(|id| traces.remove(id))
+        .map(response::Trace::new)
+        .collect();
+
+    Ok(Json(response::Response::new(traces)))
+}
+
+#[poem::handler]
+pub async fn api_get_trace(
+    Data(format): Data<&Format>,
+    Data(stats): Data<&Stats>,
+    Path(trace_id): Path<String>,
+) -> poem::Result<Json<response::Response<Vec<response::Trace>>>> {
+    let trace_id = parse_trace_id(&trace_id)?;
+
+    let files: Vec<Box<_>> = { stats.files.read().await.iter().cloned().collect() };
+    let Some(spans) = read_traces(format, files, &[trace_id])
+        .await
+        .remove(&trace_id)
+    else {
+        return Err(poem::Error::from_status(StatusCode::NOT_FOUND));
+    };
+
+    Ok(Json(response::Response::new(vec![response::Trace::new(
+        spans,
+    )])))
+}
+
+/// Conditions of a request which both formats filter on while reading.
+#[derive(Debug, Default)]
+struct Bounds {
+    /// Unix time in nanoseconds.
+    start: Option<u64>,
+    /// Unix time in nanoseconds.
+    end: Option<u64>,
+    operation: Option<String>,
+    min_duration: Option<u64>,
+    max_duration: Option<u64>,
+}
+
+impl Bounds {
+    fn arrow(
+        &self,
+        schema: &parquet::schema::types::SchemaDescriptor,
+    ) -> Vec<Box<dyn ottel_spaniel::arrow::CustomFilter>> {
+        use ottel_spaniel::arrow::{
+            CustomFilter, Filter,
+            columns::{SPAN_NAME, TIME_DURATION, TIME_START},
+        };
+
+        let mut base: Vec<Box<dyn CustomFilter>> = Vec::new();
+
+        if let Some(start) = self.start {
+            base.push(Box::new(
+                Filter::new_u64(schema, TIME_START.name(), start).gte(),
+            ));
+        }
+
+        if let Some(end) = self.end {
+            base.push(Box::new(
+                Filter::new_u64(schema, TIME_START.name(), end).lte(),
+            ));
+        }
+
+        if let Some(operation) = self.operation.as_ref() {
+            base.push(Box::new(Filter::new_str(
+                schema,
+                SPAN_NAME.name(),
+                operation.as_str(),
+            )));
+        }
+
+        if let Some(min) = self.min_duration {
+            base.push(Box::new(
+                Filter::new_u64(schema, TIME_DURATION.name(), min).gte(),
+            ));
+        }
+
+        if let Some(max) = self.max_duration {
+            base.push(Box::new(
+                Filter::new_u64(schema, TIME_DURATION.name(), max).lte(),
+            ));
+        }
+
+        base
+    }
+
+    fn vortex(&self) -> Option<vortex::expr::Expression> {
+        use vortex::expr::*;
+
+        let filters = [
+            self.start
+                .map(|start| gt_eq(get_item("time_start", root()), lit(start))),
+            self.end
+                .map(|end| lt_eq(get_item("time_start", root()), lit(end))),
+            self.operation
+                .as_ref()
+                .map(|operation| eq(get_item("name", root()), lit(operation.clone()))),
+            self.min_duration
+                .map(|min| gt_eq(get_item("time_duration", root()), lit(min))),
+            self.max_duration
+                .map(|max| lt_eq(get_item("time_duration", root()), lit(max))),
+        ];
+
+        filters.into_iter().flatten().reduce(and)
+    }
+}
+
+/// Readers of `files` grouped by format, each only yielding spans within `bounds`.
+fn sources(format: &Format, files: Vec<Box<std::path::Path>>, bounds: &Bounds) -> Vec<Source> {
+    format
+        .split(files)
+        .into_iter()
+        .map(|(format, files)| match format {
+            Format::Arrow => {
+                use ottel_spaniel::arrow::Read;
+
+                Source::Arrow(Read::new(
+                    None::<Vec<&str>>,
+                    |schema| bounds.arrow(schema),
+                    files,
+                ))
+            }
+            f @ Format::Vortex { .. } => {
+                use ottel_spaniel::vortex::read::Read;
+
+                let mut read = Read::new(f, files);
+
+                if let Some(filter) = bounds.vortex() {
+                    read = read.with_filter(filter);
+                }
+
+                Source::Vortex(read)
+            }
+        })
+        .collect()
+}
+
+async fn read_traces(
+    format: &Format,
+    files: Vec<Box<std::path::Path>>,
+    trace_ids: &[[u8; 16]],
+) -> HashMap<[u8; 16], Vec<SpanData>> {
+    use ottel_spaniel::arrow::{Boolean, CustomFilter, Filter, Read, columns};
+    use std::sync::Arc;
+
+    let mut traces: HashMap<[u8; 16], Vec<SpanData>> = HashMap::with_capacity(trace_ids.len());
+    let wanted: HashSet<[u8; 16]> = trace_ids.iter().copied().collect();
+
+    if trace_ids.is_empty() {
+        return traces;
+    }
+
+    // Indexes narrow files and rows down, spans of other traces are still dropped below.
+    let ids = trace_ids.to_vec();
+    let (files, rows) =
+        tokio::task::spawn_blocking(move || ottel_spaniel::index::lookup(files, &ids))
+            .await
+            .unwrap();
+
+    for (format, files) in format.split(files) {
+        let mut source = match format {
+            Format::Arrow => {
+                let read = Read::new(
+                    None::<Vec<&str>>,
+                    |schema| {
+                        let filters: Vec<Arc<dyn CustomFilter>> = trace_ids
+                            .iter()
+                            .map(|id| {
+                                Arc::new(Filter::new_trace_id(
+                                    schema,
+                                    columns::TRACE_ID.name(),
+                                    *id,
+                                )) as Arc<dyn CustomFilter>
+                            })
+                            .collect();
+
+                        vec![Box::new(Boolean::or(filters))]
+                    },
+                    files,
+                )
+                .with_rows(rows.clone());
+
+                Source::Arrow(read)
+            }
+            f @ Format::Vortex { .. } => {
+                Source::Vortex(ottel_spaniel::vortex::read::Read::new(f, files))
+            }
+        };
+
+        while let Some(spans) = source.next_batch().await {
+            for span in spans {
+                if wanted.contains(&span.trace_id) {
+                    traces.entry(span.trace_id).or_default().push(span);
+                }
+            }
+        }
+    }
+
+    traces
+}
+
+fn service_name(span: &SpanData) -> &str {
+    span.resource_attributes
+        .iter()
+        .find(|kv| kv.key == "service.name")
+        .and_then(|kv| kv.value.as_ref())
+        .and_then(|v| match v.value.as_ref() {
+            Some(Value::StringValue(name)) => Some(name.as_str()),
+            _ => None,
+        })
+        .unwrap_or(UNKNOWN_SERVICE)
+}
+
+fn matches_tags(span: &SpanData, tags: &HashMap<String, String>) -> bool {
+    if tags.is_empty() {
+        return true;
+    }
+
+    let span_tags = response::KeyValue::from_span(span);
+    let process_tags = span
+        .resource_attributes
+        .iter()
+        .filter_map(response::KeyValue::new);
+
+    let all: Vec<response::KeyValue> = span_tags.into_iter().chain(process_tags).collect();
+
+    tags.iter().all(|(key, value)| {
+        all.iter()
+            .any(|tag| &tag.key == key && tag.value_as_string() == *value)
+    })
+}
+
+/// Parses Jaeger duration strings such as `150ms`, `1.5s` or `300us` into nanoseconds.
+pub(super) fn parse_duration(value: &str) -> poem::Result<u64> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let multiplier = match unit {
+        "ns" => 1.0,
+        "us" | "µs" => 1_000.0,
+        "ms" => 1_000_000.0,
+        "s" | "" => 1_000_000_000.0,
+        "m" => 60_000_000_000.0,
+        "h" => 3_600_000_000_000.0,
+        _ => {
+            return Err(poem::Error::from_string(
+                format!("invalid duration unit: {value}"),
+                StatusCode::BAD_REQUEST,
+            ));
+        }
+    };
+
+    let number: f64 = number.parse().map_err(|_| {
+        poem::Error::from_string(
+            format!("invalid duration: {value}"),
+            StatusCode::BAD_REQUEST,
+        )
+    })?;
+
+    Ok((number * multiplier) as u64)
+}
+
+fn parse_trace_id(value: &str) -> poem::Result<[u8; 16]> {
+    if value.len() > 32 {
+        return Err(poem::Error::from_string(
+            "trace id too long",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    // Jaeger drops leading zeros of the trace id.
+    let padded = format!("{value:0>32}");
+
+    const_hex::decode_to_array(padded)
+        .map_err(|e| poem::Error::from_string(e.to_string(), StatusCode::BAD_REQUEST))
+}
+
+pub mod request {
+    #[derive(Debug, serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct TraceQuery {
+        pub service: Option<String>,
+        pub operation: Option<String>,
+        /// JSON object with tag values, e.g. `{"http.status_code":"500"}`.
+        pub tags: Option<String>,
+        pub min_duration: Option<String>,
+        pub max_duration: Option<String>,
+        /// Unix time in microseconds.
+        pub start: Option<u64>,
+        /// Unix time in microseconds.
+        pub end: Option<u64>,
+        pub limit: Option<usize>,
+    }
+}
+
+pub mod response {
+    use std::collections::HashMap;
+
+    use opentelemetry_proto::tonic::common::v1::any_value::Value;
+    use ottel_spaniel::SpanData;
+
+    #[derive(Debug, serde::Serialize)]
+    pub struct Response<T> {
+        pub data: T,
+        pub total: usize,
+        pub limit: usize,
+        pub offset: usize,
+        pub errors: Option<Vec<String>>,
+    }
+
+    impl<T> Response<Vec<T>> {
+        pub fn new(data: Vec<T>) -> Self {
+            Self {
+                total: data.len(),
+                data,
+                limit: 0,
+                offset: 0,
+                errors: None,
+            }
+        }
+    }
+
+    #[derive(Debug, serde::Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Trace {
+        #[serde(rename = "traceID")]
+        pub trace_id: String,
+        pub spans: Vec<Span>,
+        pub processes: HashMap<String, Process>,
+        pub warnings: Option<Vec<String>>,
+    }
+
+    impl Trace {
+        pub fn new(spans: Vec<SpanData>) -> Self {
+            assert!(!spans.is_empty());
+
+            let trace_id = const_hex::encode(spans[0].trace_id);
+            let mut resources: Vec<&[opentelemetry_proto::tonic::common::v1::KeyValue]> =
+                Vec::new();
+            let mut processes = HashMap::new();
+            let mut result = Vec::with_capacity(spans.len());
+
+            for span in spans.iter() {
+                let resource = span.resource_attributes.as_slice();
+                let idx = match resources.iter().position(|r| *r == resource) {
+                    Some(idx) => idx,
+                    None => {
+                        resources.push(resource);
+                        processes.insert(
+                            format!("p{}", resources.len()),
+                            Process::new(super::service_name(span), resource),
+                        );
+                        resources.len() - 1
+                    }
+                };
+
+                result.push(Span::new(span, format!("p{}", idx + 1)));
+            }
+
+            Self {
+                trace_id,
+                spans: result,
+                processes,
+                warnings: None,
+            }
+        }
+    }
+
+    #[derive(Debug, serde::Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Span {
+        #[serde(rename = "traceID")]
+        pub trace_id: String,
+        #[serde(rename = "spanID")]
+        pub span_id: String,
+        pub operation_name: String,
+        pub references: Vec<Reference>,
+        /// Unix time in microseconds.
+        pub start_time: u64,
+        /// Duration in microseconds.
+        pub duration: u64,
+        pub tags: Vec<KeyValue>,
+        pub logs: Vec<()>,
+        #[serde(rename = "processID")]
+        pub process_id: String,
+        pub warnings: Option<Vec<String>>,
+    }
+
+    impl Span {
+        fn new(span: &SpanData, process_id: String) -> Self {
+            let trace_id = const_hex::encode(span.trace_id);
+
+            Self {
+                references: span
+                    .parent_span_id
+                    .map(|parent| Reference {
+                        ref_type: "CHILD_OF",
+                        trace_id: trace_id.clone(),
+                        span_id: const_hex::encode(parent),
+                    })
+                    .into_iter()
+                    .collect(),
+                trace_id,
+                span_id: const_hex::encode(span.span_id),
+                operation_name: span.name.clone(),
+                start_time: span.time_start / 1_000,
+                duration: span.time_duration / 1_000,
+                tags: KeyValue::from_span(span),
+                logs: Vec::new(),
+                process_id,
+                warnings: None,
+            }
+        }
+    }
+
+    #[derive(Debug, serde::Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Reference {
+        pub ref_type: &'static str,
+        #[serde(rename = "traceID")]
+        pub trace_id: String,
+        #[serde(rename = "spanID")]
+        pub span_id: String,
+    }
+
+    #[derive(Debug, serde::Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Process {
+        pub service_name: String,
+        pub tags: Vec<KeyValue>,
+    }
+
+    impl Process {
+        fn new(
+            service_name: &str,
+            resource: &[opentelemetry_proto::tonic::common::v1::KeyValue],
+        ) -> Self {
+            Self {
+                service_name: service_name.to_owned(),
+                tags: resource.iter().filter_map(KeyValue::new).collect(),
+            }
+        }
+    }
+
+    #[derive(Debug, serde::Serialize)]
+    pub struct KeyValue {
+        pub key: String,
+        #[serde(rename = "type")]
+        pub ty: &'static str,
+        pub value: serde_json::Value,
+    }
+
+    impl KeyValue {
+        pub fn new(kv: &opentelemetry_proto::tonic::common::v1::KeyValue) -> Option<Self> {
+            let value = kv.value.as_ref()?.value.as_ref()?;
+
+            let (ty, value) = match value {
+                Value::StringValue(s) => ("string", serde_json::Value::from(s.as_str())),
+                Value::BoolValue(b) => ("bool", serde_json::Value::from(*b)),
+                Value::IntValue(i) => ("int64", serde_json::Value::from(*i)),
+                Value::DoubleValue(d) => ("float64", serde_json::Value::from(*d)),
+                other => (
+                    "string",
+                    serde_json::Value::from(serde_json::to_string(other).ok()?),
+                ),
+            };
+
+            Some(Self {
+                key: kv.key.clone(),
+                ty,
+                value,
+            })
+        }
+
+        fn tag(key: &str, ty: &'static str, value: impl Into<serde_json::Value>) -> Self {
+            Self {
+                key: key.to_owned(),
+                ty,
+                value: value.into(),
+            }
+        }
+
+        pub fn from_span(span: &SpanData) -> Vec<Self> {
+            let mut tags: Vec<Self> = span.span_attributes.iter().filter_map(Self::new).collect();
+
+            let kind = match span.kind {
+                1 => Some("internal"),
+                2 => Some("server"),
+                3 => Some("client"),
+                4 => Some("producer"),
+                5 => Some("consumer"),
+                _ => None,
+            };
+
+            if let Some(kind) = kind {
+                tags.push(Self::tag("span.kind", "string", kind));
+            }
+
+            match span.status_code {
+                Some(1) => tags.push(Self::tag("otel.status_code", "string", "OK")),
+                Some(2) => {
+                    tags.push(Self::tag("otel.status_code", "string", "ERROR"));
+                    tags.push(Self::tag("error", "bool", true));
+                }
+                _ => {}
+            }
+
+            if let Some(message) = span.status_message.as_ref()
+                && !message.is_empty()
+            {
+                tags.push(Self::tag(
+                    "otel.status_description",
+                    "string",
+                    message.as_str(),
+                ));
+            }
+
+            if let Some(name) = span.scope.name.as_ref() {
+                tags.push(Self::tag("otel.scope.name", "string", name.as_str()));
+            }
+
+            if let Some(version) = span.scope.version.as_ref() {
+                tags.push(Self::tag("otel.scope.version", "string", version.as_str()));
+            }
+
+            tags
+        }
+
+        pub fn value_as_string(&self) -> String {
+            match &self.value {
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use opentelemetry_proto::tonic::common::v1::{AnyValue, KeyValue};
+
+    use super::*;
+
+    fn kv(key: &str, value: Value) -> KeyValue {
+        KeyValue {
+            key: key.to_owned(),
+            value: Some(AnyValue { value: Some(value) }),
+        }
+    }
+
+    fn span(span_id: u8, parent: Option<u8>, resource: &Arc<Vec<KeyValue>>) -> SpanData {
+        SpanData {
+            trace_id: [1; 16],
+            span_id: [span_id; 8],
+            parent_span_id: parent.map(|id| [id; 8]),
+            name: format!("op-{span_id}"),
+            kind: 2,
+            status_code: Some(2),
+            status_message: Some("failed".to_owned()),
+            time_start: 1_500_000,
+            time_end: 4_500_000,
+            time_duration: 3_000_000,
+            trace_state: None,
+            flags: 0,
+            dropped_attributes_count: 0,
+            dropped_events_count: 0,
+            dropped_links_count: 0,
+            span_attributes: vec![kv("http.response.status_code", Value::IntValue(500))],
+            resource_attributes: resource.clone(),
+            resource_schema_url: None,
+            scope: Arc::default(),
+        }
+    }
+
+    #[test]
+    fn trace_json_follows_jaeger_model() {
+        let api = Arc::new(vec![kv(
+            "service.name",
+            Value::StringValue("api".to_owned()),
+        )]);
+        let db = Arc::new(vec![kv(
+            "service.name",
+            Value::StringValue("db".to_owned()),
+        )]);
+
+        let trace = response::Trace::new(vec![
+            span(1, None, &Arc::default()),
+            span(2, Some(1), &api),
+            span(3, Some(2), &db),
+            span(4, Some(2), &api),
+        ]);
+        let json = serde_json::to_value(&trace).unwrap();
+
+        assert_eq!(json["traceID"], const_hex::encode([1; 16]));
+        assert_eq!(json["processes"].as_object().unwrap().len(), 3);
+        assert_eq!(json["processes"]["p1"]["serviceName"], UNKNOWN_SERVICE);
+        assert_eq!(json["processes"]["p2"]["serviceName"], "api");
+        assert_eq!(json["processes"]["p3"]["serviceName"], "db");
+
+        let spans = json["spans"].as_array().unwrap();
+        assert_eq!(spans.len(), 4);
+        assert_eq!(spans[3]["processID"], "p2");
+        assert_eq!(spans[1]["spanID"], const_hex::encode([2; 8]));
+        assert_eq!(spans[1]["operationName"], "op-2");
+        assert_eq!(spans[1]["startTime"], 1_500);
+        assert_eq!(spans[1]["duration"], 3_000);
+
+        assert!(spans[0]["references"].as_array().unwrap().is_empty());
+        assert_eq!(spans[1]["references"][0]["refType"], "CHILD_OF");
+        assert_eq!(
+            spans[1]["references"][0]["spanID"],
+            const_hex::encode([1; 8])
+        );
+
+        let tags = spans[1]["tags"].as_array().unwrap();
+        let tag = |key: &str| tags.iter().find(|tag| tag["key"] == key).cloned();
+        assert_eq!(tag("http.response.status_code").unwrap()["type"], "int64");
+        assert_eq!(tag("http.response.status_code").unwrap()["value"], 500);
+        assert_eq!(tag("span.kind").unwrap()["value"], "server");
+        assert_eq!(tag("error").unwrap()["value"], true);
+        assert_eq!(tag("otel.status_description").unwrap()["value"], "failed");
+    }
+
+    #[test]
+    fn tags_match_span_and_process_values() {
+        let api = Arc::new(vec![kv(
+            "service.name",
+            Value::StringValue("api".to_owned()),
+        )]);
+        let data = span(2, Some(1), &api);
+        let tags = |json: &str| serde_json::from_str::<HashMap<String, String>>(json).unwrap();
+
+        assert!(matches_tags(&data, &HashMap::new()));
+        assert!(matches_tags(
+            &data,
+            &tags(r#"{"http.response.status_code":"500"}"#)
+        ));
+        assert!(matches_tags(
+            &data,
+            &tags(r#"{"service.name":"api","error":"true"}"#)
+        ));
+        assert!(!matches_tags(
+            &data,
+            &tags(r#"{"http.response.status_code":"200"}"#)
+        ));
+    }
+
+    #[test]
+    fn durations_and_trace_ids_parse() {
+        assert_eq!(parse_duration("150ms").unwrap(), 150_000_000);
+        assert_eq!(parse_duration("1.5s").unwrap(), 1_500_000_000);
+        assert_eq!(parse_duration("300us").unwrap(), 300_000);
+        assert!(parse_duration("5 fortnights").is_err());
+
+        let mut id = [0; 16];
+        id[15] = 0xab;
+        assert_eq!(parse_trace_id("ab").unwrap(), id);
+        assert!(parse_trace_id(&"f".repeat(33)).is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use std::sync::{Arc, LazyLock};
+use std::time::Instant;
+
+use poem::web::Data;
+use poem::{Endpoint, IntoResponse, Request, Response};
+
+use ottel_spaniel::metrics::{Counter, Family, Histogram, LATENCY_BUCKETS, Text, WRITER};
+
+use crate::tenant::Tenants;
+
+pub struct ServerMetrics {
+    pub ingested_spans: Family<Counter>,
+    pub rejected_spans: Family<Counter>,
+    pub request_seconds: Family<Histogram>,
+    /// Spans of the live tail which didn't fit into the buffer of a subscriber.
+    pub tail_dropped_spans: Family<Counter>,
+}
+
+pub static SERVER: LazyLock<ServerMetrics> = LazyLock::new(|| ServerMetrics {
+    ingested_spans: Family::new(Counter::default),
+    rejected_spans: Family::new(Counter::default),
+    request_seconds: Family::new(|| Histogram::new(LATENCY_BUCKETS)),
+    tail_dropped_spans: Family::new(Counter::default),
+});
+
+/// Route of a request path, so that path parameters don't end up in labels.
+fn endpoint(path: &str) -> &'static str {
+    const EXACT: &[&str] = &[
+        "/v1/traces",
+        "/v0/search/span",
+        "/v0/search/span/name",
+        "/v0/search/resource/name",
+        "/v0/query",
+        "/v0/query/stream",
+        "/v0/sql",
+        "/v0/tail",
+        "/api/services",
+        "/api/traces",
+        "/admin/rotate",
+        "/admin/flush",
+        "/admin/compact",
+        "/admin/files",
+        "/metrics",
+    ];
+
+    if let Some(route) = EXACT.iter().find(|route| **route == path) {
+        return route;
+    }
+
+    if path.starts_with("/api/services/") {
+        return "/api/services/:service/operations";
+    }
+
+    if path.starts_with("/api/traces/") {
+        return "/api/traces/:trace_id";
+    }
+
+    "other"
+}
+
+/// Records latency of every request by route.
+pub async fn track<E: Endpoint>(ep: Arc<E>, req: Request) -> poem::Result<Response> {
+    let endpoint = endpoint(req.uri().path());
+    let start = Instant::now();
+
+    let result = ep.call(req).await.map(IntoResponse::into_response);
+
+    SERVER
+        .request_seconds
+        .get(&[("endpoint", endpoint)])
+        .observe(start.elapsed().as_secs_f64());
+
+    result
+}
+
+#[poem::handler]
+pub async fn get_metrics(Data(tenants): Data<&Tenants>) -> String {
+    let mut text = Text::default();
+
+    text.counter_family(
+        "spaniel_ingested_spans_total",
+        "Spans accepted by the collector.",
+        &SERVER.ingested_spans,
+    );
+    text.counter_family(
+        "spaniel_rejected_spans_total",
+        "Spans rejected by the collector.",
+        &SERVER.rejected_spans,
+    );
+    text.histogram_family(
+        "spaniel_request_seconds",
+        "Latency of handled requests.",
+        &SERVER.request_seconds,
+    );
+    text.counter_family(
+        "spaniel_tail_dropped_spans_total",
+        "Spans dropped by slow live tail subscribers.",
+        &SERVER.tail_dropped_spans,
+    );
+
+    WRITER.render(&mut text);
+
+    let mut queued = Vec::new();
+    let mut open = Vec::new();
+    let mut closed = Vec::new();
+    let mut tails = Vec::new();
+
+    for tenant in tenants.iter() {
+        let (open_files, closed_files) = tenant.stats.file_counts().await;
+
+        queued.push((tenant.id.as_str(), tenant.sink.queued() as u64));
+        open.push((tenant.id.as_str(), open_files as u64));
+        closed.push((tenant.id.as_str(), closed_files as u64));
+        tails.push((tenant.id.as_str(), tenant.tap.subscribers() as u64));
+    }
+
+    text.gauge_by(
+        "spaniel_sink_queue_depth",
+        "Messages waiting for the writer.",
+        "tenant",
+        &queued,
+    );
+    text.gauge_by(
+        "spaniel_open_files",
+        "Files being written.",
+        "tenant",
+        &open,
+    );
+    text.gauge_by(
+        "spaniel_closed_files",
+        "Files available for reading.",
+        "tenant",
+        &closed,
+    );
+    text.gauge_by(
+        "spaniel_tail_subscribers",
+        "Clients of the live tail.",
+        "tenant",
+        &tails,
+    );
+
+    text.finish()
+}
@@ -1,46 +1,124 @@
 use std::time::Duration;
 
-use poem::listener::TcpListener;
+use poem::listener::{BoxListener, Listener, RustlsCertificate, RustlsConfig, TcpListener};
 use poem::middleware::*;
-use poem::{EndpointExt, Route, Server, post};
+use poem::{EndpointExt, Route, Server, get, post};
 
-use ottel_spaniel::{Format, Sink, Stats};
+use ottel_spaniel::Format;
 
+pub use auth::{Auth, Operator, read_tokens, token_eq};
+
+use crate::forward::Forward;
+use crate::tenant::{self, Tenants};
+
+mod admin;
+mod auth;
 mod collect;
+mod jaeger;
+mod metrics;
+mod query;
 mod search;
+mod sql;
+mod tail;
 
 pub struct Options {
     pub host: &'static str,
     pub port: u16,
     pub shutdown_timeout_secs: u8,
+    /// Origins allowed to make cross-origin requests, empty rejects every cross-origin request.
+    pub cors_origins: Vec<String>,
+    /// PEM certificate chain and private key, TLS is terminated by the server when set.
+    pub tls: Option<(String, String)>,
+    /// Limits of `/v0/sql` queries.
+    pub sql: ottel_spaniel::sql::Options,
 }
 
 impl Options {
-    fn addr(&self) -> impl tokio::net::ToSocketAddrs {
+    fn addr(&self) -> impl tokio::net::ToSocketAddrs + use<> {
         (self.host, self.port)
     }
 
     fn shutdown_timeout(&self) -> Duration {
         Duration::from_secs(self.shutdown_timeout_secs.into())
     }
+
+    fn cors(&self) -> Cors {
+        let origins = self.cors_origins.clone();
+
+        // Without listed origins `Cors` would allow any, the function keeps the list exhaustive.
+        Cors::new()
+            .allow_origins_fn(move |origin| origins.iter().any(|allowed| allowed == origin))
+            .allow_headers(["authorization", "content-type", crate::tenant::HEADER])
+    }
+
+    fn listener(&self) -> BoxListener {
+        let tcp = TcpListener::bind(self.addr());
+
+        let Some((cert, key)) = self.tls.as_ref() else {
+            return tcp.boxed();
+        };
+
+        let cert = std::fs::read(cert).expect("tls.cert.read");
+        let key = std::fs::read(key).expect("tls.key.read");
+        let config = RustlsConfig::new().fallback(RustlsCertificate::new().cert(cert).key(key));
+
+        tcp.rustls(config).boxed()
+    }
 }
 
-pub async fn run_server(options: Options, format: Format, stats: Stats, sink: Sink) {
+pub async fn run_server(
+    options: Options,
+    format: Format,
+    tenants: Tenants,
+    auth: Auth,
+    forward: Forward,
+) {
+    use admin::*;
     use collect::*;
+    use jaeger::*;
+    use query::*;
     use search::*;
+    use sql::*;
+    use tail::*;
 
-    let routes = Route::new()
+    let scoped = Route::new()
         .at("/v1/traces", post(v1_handle_export_trace_request))
         .at("/v0/search/span", post(v0_search_traces))
         .at("/v0/search/span/name", post(v0_search_get_span_names))
         .at("/v0/search/resource/name", post(v0_search_get_svc_names))
-        .with(Cors::default())
-        .with(AddData::new(sink))
-        .with(AddData::new(stats))
-        .with(AddData::new(format));
+        .at("/v0/query", post(v0_query))
+        .at("/v0/query/stream", post(v0_query_stream))
+        .at("/v0/sql", post(v0_sql))
+        .at("/v0/tail", get(v0_tail))
+        .at("/api/services", get(api_get_services))
+        .at("/api/services/:service/operations", get(api_get_operations))
+        .at("/api/traces", get(api_find_traces))
+        .at("/api/traces/:trace_id", get(api_get_trace))
+        .at("/admin/rotate", post(admin_rotate))
+        .at("/admin/flush", post(admin_flush))
+        .at("/admin/compact", post(admin_compact))
+        .at("/admin/files", get(admin_get_files))
+        .around(tenant::scope);
+
+    let authenticated = Route::new()
+        .at("/metrics", get(metrics::get_metrics))
+        .nest("/", scoped)
+        .around(metrics::track)
+        .around(auth::check);
+
+    // Probes are answered without authentication.
+    let routes = Route::new()
+        .at("/healthz", get(healthz))
+        .at("/readyz", get(readyz))
+        .nest("/", authenticated)
+        .with(options.cors())
+        .with(AddData::new(tenants))
+        .with(AddData::new(auth))
+        .with(AddData::new(forward))
+        .with(AddData::new(format))
+        .with(AddData::new(options.sql));
 
-    let tcp = TcpListener::bind(options.addr());
-    let server = Server::new(tcp);
+    let server = Server::new(options.listener());
     let signal = async {
         tokio::signal::ctrl_c().await.unwrap();
     };
@@ -0,0 +1,367 @@
+use bytes::Bytes;
+use poem::http::{StatusCode, header};
+use poem::web::{Data, Json};
+use poem::{Body, Request, Response};
+use tokio::sync::mpsc;
+
+use ottel_spaniel::arrow::ext::Span;
+use ottel_spaniel::arrow::stream::{self, Encoder};
+use ottel_spaniel::query::{self, FileError, Source};
+use ottel_spaniel::{Format, Stats};
+
+use super::search::response;
+
+const NDJSON: &str = "application/x-ndjson";
+
+/// Chunks of a streamed response buffered ahead of the client.
+const STREAM_BUFFERED: usize = 4;
+
+#[poem::handler]
+pub async fn v0_query(
+    Data(format): Data<&Format>,
+    Data(stats): Data<&Stats>,
+    Json(body): Json<request::Query>,
+) -> poem::Result<Json<response::Traces>> {
+    let parsed = query::parse(&body.query).map_err(bad_request)?;
+    let files: Vec<Box<_>> = { stats.files.read().await.iter().cloned().collect() };
+    let limit = usize::from(body.limit);
+    let mut traces: Vec<Span> = Vec::with_capacity(limit);
+
+    for (format, files) in format.split(files) {
+        if traces.len() >= limit {
+            break;
+        }
+
+        let window = (body.start_time_ms, body.end_time_ms);
+        let mut source =
+            Source::open(format, files, &parsed, window, Some(limit)).map_err(bad_request)?;
+
+        'outer: while let Some(spans) = source.next_batch().await {
+            for span in spans {
+                if traces.len() >= limit {
+                    break 'outer;
+                }
+
+                traces.push(Span::from(&span));
+            }
+        }
+    }
+
+    Ok(Json(response::Traces { traces }))
+}
+
+/// Streams every span matching a query, see [stream_spans].
+#[poem::handler]
+pub async fn v0_query_stream(
+    req: &Request,
+    Data(format): Data<&Format>,
+    Data(stats): Data<&Stats>,
+    Json(body): Json<request::Stream>,
+) -> poem::Result<Response> {
+    let parsed = query::parse(&body.query).map_err(bad_request)?;
+    let files: Vec<Box<_>> = { stats.files.read().await.iter().cloned().collect() };
+    let window = (body.start_time_ms, body.end_time_ms);
+
+    // Queries are checked before the response starts, failures later only cut it short.
+    let sources = format
+        .split(files)
+        .into_iter()
+        .map(|(format, files)| Source::open(format, files, &parsed, window, body.limit))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(bad_request)?;
+
+    Ok(stream_spans(req, sources, body.limit))
+}
+
+/// Whether the client asks for a stream, and for an Arrow IPC one rather than NDJSON.
+pub(super) fn accepts_stream(req: &Request) -> Option<bool> {
+    let accept = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())?;
+
+    if accept.contains(stream::CONTENT_TYPE) {
+        Some(true)
+    } else if accept.contains(NDJSON) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Streams spans of `sources` as NDJSON, or as an Arrow IPC stream when the client accepts
+/// [stream::CONTENT_TYPE].
+///
+/// Batches are sent as soon as they are read, the scan stops once the client goes away.
+pub(super) fn stream_spans(req: &Request, sources: Vec<Source>, limit: Option<usize>) -> Response {
+    let ipc = accepts_stream(req) == Some(true);
+
+    respond(ipc, move |tx| async move {
+        if ipc {
+            send_arrow(sources, limit, &tx).await
+        } else {
+            send_ndjson(sources, limit, &tx).await
+        }
+    })
+}
+
+/// Runs `produce` and streams the chunks it sends. A failing producer, e.g. one reading an
+/// unreadable file, doesn't end the response like a complete one: NDJSON gets an error line and
+/// the body is cut off with an error.
+fn respond<F>(ipc: bool, produce: impl FnOnce(mpsc::Sender<Chunk>) -> F) -> Response
+where
+    F: Future<Output = Result<Option<usize>, FileError>> + Send + 'static,
+{
+    let (tx, mut rx) = mpsc::channel(STREAM_BUFFERED);
+    let producer = tokio::spawn(produce(tx.clone()));
+
+    tokio::spawn(async move {
+        let message = match producer.await {
+            Ok(Ok(Some(rows))) => {
+                tracing::info!(rows, ipc, "query.stream.done");
+                return;
+            }
+            Ok(Ok(None)) => {
+                tracing::info!(ipc, "query.stream.cancelled");
+                return;
+            }
+            // Read errors are passed on, panics are only logged.
+            Ok(Err(e)) => {
+                tracing::error!(error = %e, ipc, "query.stream.failed");
+                format!("query failed: {e}")
+            }
+            Err(e) => {
+                tracing::error!(error = %e, ipc, "query.stream.failed");
+                "query failed".to_owned()
+            }
+        };
+
+        if !ipc {
+            let line = serde_json::json!({ "error": message }).to_string() + "\n";
+            let _ = tx.send(Ok(line.into())).await;
+        }
+
+        let _ = tx.send(Err(std::io::Error::other(message))).await;
+    });
+
+    let chunks = futures::stream::poll_fn(move |cx| rx.poll_recv(cx));
+
+    Response::builder()
+        .content_type(if ipc { stream::CONTENT_TYPE } else { NDJSON })
+        .body(Body::from_bytes_stream(chunks))
+}
+
+type Chunk = Result<Bytes, std::io::Error>;
+
+/// Next item of `next`, `None` once the client is gone, so that a slow read doesn't outlive it.
+async fn unless_closed<T>(tx: &mpsc::Sender<Chunk>, next: impl Future<Output = T>) -> Option<T> {
+    tokio::select! {
+        biased;
+        _ = tx.closed() => None,
+        next = next => Some(next),
+    }
+}
+
+/// Sends a span per line and returns their number, `None` once the client is gone.
+async fn send_ndjson(
+    mut sources: Vec<Source>,
+    limit: Option<usize>,
+    tx: &mpsc::Sender<Chunk>,
+) -> Result<Option<usize>, FileError> {
+    let mut rows = 0;
+
+    for source in sources.iter_mut() {
+        loop {
+            let Some(next) = unless_closed(tx, source.try_next_batch()).await else {
+                return Ok(None);
+            };
+            let Some(spans) = next.transpose()? else {
+                break;
+            };
+
+            let mut buf = Vec::new();
+
+            for span in spans
+                .iter()
+                .take(limit.map_or(usize::MAX, |limit| limit - rows))
+            {
+                serde_json::to_writer(&mut buf, &Span::from(span)).expect("span.json");
+                buf.push(b'\n');
+                rows += 1;
+            }
+
+            if tx.send(Ok(buf.into())).await.is_err() {
+                return Ok(None);
+            }
+
+            if limit.is_some_and(|limit| rows >= limit) {
+                return Ok(Some(rows));
+            }
+        }
+    }
+
+    Ok(Some(rows))
+}
+
+/// Sends an Arrow IPC stream and returns the number of rows, `None` once the client is gone.
+async fn send_arrow(
+    mut sources: Vec<Source>,
+    limit: Option<usize>,
+    tx: &mpsc::Sender<Chunk>,
+) -> Result<Option<usize>, FileError> {
+    let mut encoder = Encoder::default();
+    let mut rows = 0;
+
+    'outer: for source in sources.iter_mut() {
+        loop {
+            let Some(next) = unless_closed(tx, source.try_next_stream_batch()).await else {
+                return Ok(None);
+            };
+            let Some(mut batch) = next.transpose()? else {
+                break;
+            };
+
+            if let Some(limit) = limit {
+                batch = batch.slice(0, batch.num_rows().min(limit - rows));
+            }
+
+            rows += batch.num_rows();
+            if tx.send(Ok(encoder.encode(&batch))).await.is_err() {
+                return Ok(None);
+            }
+
+            if limit.is_some_and(|limit| rows >= limit) {
+                break 'outer;
+            }
+        }
+    }
+
+    if tx.send(Ok(encoder.finish())).await.is_err() {
+        return Ok(None);
+    }
+
+    Ok(Some(rows))
+}
+
+pub(super) fn bad_request(e: query::Error) -> poem::Error {
+    poem::Error::from_string(e.to_string(), StatusCode::BAD_REQUEST)
+}
+
+pub mod request {
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Query {
+        /// TraceQL-like query, see [ottel_spaniel::query].
+        pub query: String,
+        pub start_time_ms: u64,
+        pub end_time_ms: u64,
+        pub limit: u8,
+    }
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Stream {
+        /// TraceQL-like query, see [ottel_spaniel::query].
+        pub query: String,
+        pub start_time_ms: u64,
+        pub end_time_ms: u64,
+        /// Every matching span is sent when not set.
+        pub limit: Option<usize>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+
+    enum Outcome {
+        Done,
+        Unreadable,
+        Panic,
+    }
+
+    /// Chunks streamed by a producer sending a line, `None` for an error.
+    fn run(ipc: bool, outcome: Outcome) -> Vec<Option<Bytes>> {
+        let rt = crate::testing::runtime();
+
+        rt.block_on(async {
+            let resp = respond(ipc, move |tx| async move {
+                if tx.send(Ok(Bytes::from_static(b"{}\n"))).await.is_err() {
+                    return Ok(None);
+                }
+
+                match outcome {
+                    Outcome::Done => Ok(Some(1)),
+                    Outcome::Unreadable => Err("file unreadable".into()),
+                    Outcome::Panic => panic!("producer.fails"),
+                }
+            });
+
+            resp.into_body()
+                .into_bytes_stream()
+                .map(Result::ok)
+                .collect()
+                .await
+        })
+    }
+
+    #[test]
+    fn complete_streams_end_cleanly() {
+        assert_eq!(
+            run(false, Outcome::Done),
+            vec![Some(Bytes::from_static(b"{}\n"))]
+        );
+    }
+
+    #[test]
+    fn failed_streams_end_with_an_error() {
+        let ndjson = run(false, Outcome::Panic);
+        assert_eq!(ndjson.len(), 3);
+        assert_eq!(
+            ndjson[1],
+            Some(Bytes::from("{\"error\":\"query failed\"}\n"))
+        );
+        assert_eq!(ndjson[2], None);
+
+        // Unreadable files end the stream the same way, naming the error.
+        let ndjson = run(false, Outcome::Unreadable);
+        assert_eq!(
+            ndjson,
+            vec![
+                Some(Bytes::from_static(b"{}\n")),
+                Some(Bytes::from(
+                    "{\"error\":\"query failed: file unreadable\"}\n"
+                )),
+                None
+            ]
+        );
+
+        // An error line would break an IPC stream, it is only cut off.
+        for outcome in [Outcome::Panic, Outcome::Unreadable] {
+            let ipc = run(true, outcome);
+            assert_eq!(ipc, vec![Some(Bytes::from_static(b"{}\n")), None]);
+        }
+    }
+
+    #[test]
+    fn gone_clients_stop_waiting_reads() {
+        let rt = crate::testing::runtime();
+
+        rt.block_on(async {
+            let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+
+            let resp = respond(false, move |tx| async move {
+                // A read which never completes, e.g. a file on an unresponsive object store.
+                let next = unless_closed(&tx, std::future::pending::<()>()).await;
+                let _ = done_tx.send(next.is_none());
+                Ok(None)
+            });
+            drop(resp);
+
+            let stopped = tokio::time::timeout(std::time::Duration::from_secs(5), done_rx).await;
+            assert!(stopped.unwrap().unwrap());
+        });
+    }
+}
@@ -1,7 +1,11 @@
 use poem::web::{Data, Json};
+use poem::{IntoResponse, Request, Response};
 
+use ottel_spaniel::query::{Expr, Field, Literal, Op, Query, Source};
 use ottel_spaniel::{Format, Stats};
 
+use super::query::{accepts_stream, bad_request, stream_spans};
+
 #[poem::handler]
 pub async fn v0_search_get_svc_names(
     Data(format): Data<&Format>,
@@ -11,54 +15,56 @@ pub async fn v0_search_get_svc_names(
     let files: Vec<Box<_>> = { stats.files.read().await.iter().cloned().collect() };
     let mut names: Sagarray<50, String> = Sagarray::new();
 
-    match format {
-        Format::Arrow => {
-            use ottel_spaniel::arrow::{AsSpanData, Filter, Null, Read, columns};
-            let mut read = Read::new(
-                Some([
-                    columns::RES_ATTR_NAME.name(),
-                    columns::RES_ATTR_TYPE.name(),
-                    columns::RES_ATTR_VALUE.name(),
-                ]),
-                |schema| {
-                    vec![
-                        Box::new(Null::not_null(schema, columns::PARENT_SPAN_ID.name())),
-                        Box::new(
-                            Filter::new_u64(
-                                schema,
-                                columns::TIME_START.name(),
-                                body.start_time_ms * 1_000_000,
-                            )
-                            .gte(),
-                        ),
-                        Box::new(
-                            Filter::new_u64(
-                                schema,
-                                columns::TIME_END.name(),
-                                body.end_time_ms * 1_000_000,
-                            )
-                            .lte(),
-                        ),
-                    ]
-                },
-                files,
-            );
-
-            'outter: while let Some(batch) = read.next_batch().await {
-                for svc_name in batch.get_svc_names() {
-                    if names.contains(svc_name.as_str()) {
-                        continue;
-                    }
+    for (format, files) in format.split(files) {
+        if names.len >= names.cap {
+            break;
+        }
+
+        match format {
+            Format::Arrow => {
+                use ottel_spaniel::arrow::{AsSpanData, Filter, Read, columns};
+                let mut read = Read::new(
+                    Some([columns::RESOURCE_ID.name()]),
+                    |schema| {
+                        vec![
+                            Box::new(
+                                Filter::new_u64(
+                                    schema,
+                                    columns::TIME_START.name(),
+                                    body.start_time_ms * 1_000_000,
+                                )
+                                .gte(),
+                            ),
+                            Box::new(
+                                Filter::new_u64(
+                                    schema,
+                                    columns::TIME_END.name(),
+                                    body.end_time_ms * 1_000_000,
+                                )
+                                .lte(),
+                            ),
+                        ]
+                    },
+                    files,
+                );
+
+                'outer: while let Some(batch) = read.next_batch().await {
+                    for svc_name in batch.get_svc_names(read.resources()) {
+                        if names.contains(svc_name.as_str()) {
+                            continue;
+                        }
 
-                    names.push(svc_name);
+                        names.push(svc_name);
 
-                    if names.len >= names.cap {
-                        break 'outter;
+                        if names.len >= names.cap {
+                            break 'outer;
+                        }
                     }
                 }
             }
+            // Vortex files keep no resource attributes.
+            Format::Vortex { .. } => continue,
         }
-        Format::Vortex { .. } => unimplemented!(),
     }
 
     let mut names: Vec<_> = names.into_vec();
@@ -75,94 +81,105 @@ pub async fn v0_search_get_span_names(
     let files: Vec<Box<_>> = { stats.files.read().await.iter().cloned().collect() };
     let mut names: Sagarray<50, String> = Sagarray::new();
 
-    match format {
-        Format::Arrow => {
-            use ottel_spaniel::arrow::{
-                AsSpanData, CustomFilter, Filter, Read,
-                columns::{SPAN_NAME, TIME_END, TIME_START},
-            };
-
-            let mut read = Read::new(
-                Some([SPAN_NAME.name()]),
-                |schema| {
-                    let mut base: Vec<Box<dyn CustomFilter>> = vec![
-                        Box::new(
-                            Filter::new_u64(
-                                schema,
-                                TIME_START.name(),
-                                body.start_time_ms * 1_000_000,
-                            )
-                            .gte(),
-                        ),
-                        Box::new(
-                            Filter::new_u64(schema, TIME_END.name(), body.end_time_ms * 1_000_000)
-                                .lte(),
-                        ),
-                    ];
-
-                    if let Some(contains) = body.contains.as_ref() {
-                        base.push(Box::new(
-                            Filter::new_str(schema, SPAN_NAME.name(), contains.as_str()).contains(),
-                        ));
-                    }
+    for (format, files) in format.split(files) {
+        if names.len >= names.cap {
+            break;
+        }
 
-                    base
-                },
-                files,
-            );
+        match format {
+            Format::Arrow => {
+                use ottel_spaniel::arrow::{
+                    AsSpanData, CustomFilter, Filter, Read,
+                    columns::{SPAN_NAME, TIME_END, TIME_START},
+                };
+
+                let mut read = Read::new(
+                    Some([SPAN_NAME.name()]),
+                    |schema| {
+                        let mut base: Vec<Box<dyn CustomFilter>> = vec![
+                            Box::new(
+                                Filter::new_u64(
+                                    schema,
+                                    TIME_START.name(),
+                                    body.start_time_ms * 1_000_000,
+                                )
+                                .gte(),
+                            ),
+                            Box::new(
+                                Filter::new_u64(
+                                    schema,
+                                    TIME_END.name(),
+                                    body.end_time_ms * 1_000_000,
+                                )
+                                .lte(),
+                            ),
+                        ];
+
+                        if let Some(contains) = body.contains.as_ref() {
+                            base.push(Box::new(
+                                Filter::new_str(schema, SPAN_NAME.name(), contains.as_str())
+                                    .contains(),
+                            ));
+                        }
+
+                        base
+                    },
+                    files,
+                );
 
-            'outter: while let Some(batch) = read.next_batch().await {
-                for name in batch.get_names() {
-                    if names.contains(name) {
-                        continue;
-                    }
+                'outer: while let Some(batch) = read.next_batch().await {
+                    for name in batch.get_names() {
+                        if names.contains(name) {
+                            continue;
+                        }
 
-                    names.push(name.to_owned());
+                        names.push(name.to_owned());
 
-                    if names.len >= names.cap {
-                        break 'outter;
+                        if names.len >= names.cap {
+                            break 'outer;
+                        }
                     }
                 }
             }
-        }
-        f @ Format::Vortex { .. } => {
-            use ottel_spaniel::vortex::read::*;
-            use vortex::expr::*;
-
-            let mut filter = and(
-                gt(
-                    get_item("time_start", root()),
-                    lit(body.start_time_ms * 1_000_000),
-                ),
-                lt(
-                    get_item("time_end", root()),
-                    lit(body.end_time_ms * 1_000_000),
-                ),
-            );
-
-            if let Some(c) = body.contains {
-                filter = and(
-                    filter,
-                    ilike(get_item("name", root()), lit(format!("%{c}%"))),
+            f @ Format::Vortex { .. } => {
+                use ottel_spaniel::vortex::read::*;
+                use vortex::expr::*;
+
+                let mut filter = and(
+                    gt(
+                        get_item("time_start", root()),
+                        lit(body.start_time_ms * 1_000_000),
+                    ),
+                    lt(
+                        get_item("time_end", root()),
+                        lit(body.end_time_ms * 1_000_000),
+                    ),
                 );
-            }
 
-            let mut read = Read::new(f, files)
-                .with_filter(filter)
-                .with_projection(select(["name"], root()));
+                if let Some(c) = body.contains.as_ref() {
+                    filter = and(
+                        filter,
+                        ilike(get_item("name", root()), lit(format!("%{c}%"))),
+                    );
+                }
 
-            'outter: while let Some(arr) = read.next_batch().await {
-                for name in arr.get_names() {
-                    let name = name.as_utf8().value().unwrap().as_str();
+                let mut read = Read::new(f, files)
+                    .with_filter(filter)
+                    .with_projection(select(["name"], root()));
 
-                    if names.contains(name) {
-                        continue;
-                    }
+                'outer: while let Some(arr) = read.next_batch().await {
+                    for name in arr.get_names() {
+                        let name = name.as_utf8().value().unwrap().as_str();
+
+                        if names.contains(name) {
+                            continue;
+                        }
 
-                    names.push(name.to_owned());
+                        names.push(name.to_owned());
 
-                    if names.len >= names.cap {
-                        break 'outter;
+                        if names.len >= names.cap {
+                            break 'outer;
+                        }
                     }
                 }
             }
@@ -175,56 +192,143 @@ pub async fn v0_search_get_span_names(
     Json(response::Names { names: span_names })
 }
 
+/// Up to 50 spans as JSON. Clients accepting NDJSON or an Arrow IPC stream get every matching
+/// span streamed instead, see [stream_spans].
 #[poem::handler]
 pub async fn v0_search_traces(
+    req: &Request,
     Data(format): Data<&Format>,
     Data(stats): Data<&Stats>,
     Json(body): Json<request::TraceFilter>,
-) -> Json<response::Traces> {
+) -> poem::Result<Response> {
     // TODO: Should return top level spans only.
 
     let files: Vec<Box<_>> = { stats.files.read().await.iter().cloned().collect() };
-    let mut traces: Sagarray<50, Span> = Sagarray::new();
 
-    if let Format::Vortex { .. } = format {
-        return Json(response::Traces { traces: vec![] });
+    if accepts_stream(req).is_some() {
+        let parsed = Query {
+            expr: body.scope_name.clone().map(|name| Expr::Cmp {
+                field: Field::ScopeName,
+                op: Op::Eq,
+                value: Literal::Str(name),
+            }),
+        };
+        let window = (body.start_time_ms, body.end_time_ms);
+
+        let sources = format
+            .split(files)
+            .into_iter()
+            .map(|(format, files)| Source::open(format, files, &parsed, window, None))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(bad_request)?;
+
+        return Ok(stream_spans(req, sources, None));
     }
 
-    use ottel_spaniel::arrow::{
-        AsSpanData, Filter, Read,
-        columns::{TIME_END, TIME_START},
-        ext::*,
-    };
-
-    let mut read = Read::new(
-        None::<Vec<&str>>,
-        |schema| {
-            vec![
-                Box::new(
-                    Filter::new_u64(schema, TIME_START.name(), body.start_time_ms * 1_000_000)
-                        .gte(),
-                ),
-                Box::new(
-                    Filter::new_u64(schema, TIME_END.name(), body.end_time_ms * 1_000_000).lte(),
-                ),
-            ]
-        },
-        files,
-    );
-
-    'outter: while let Some(batch) = read.next_batch().await {
-        for span in batch.get_spans() {
-            traces.push(span);
-
-            if traces.len >= traces.cap {
-                break 'outter;
+    let mut traces: Sagarray<50, ottel_spaniel::arrow::ext::Span> = Sagarray::new();
+
+    for (format, files) in format.split(files) {
+        if traces.len >= traces.cap {
+            break;
+        }
+
+        match format {
+            Format::Arrow => {
+                use ottel_spaniel::arrow::{
+                    AsSpanData, CustomFilter, Filter, Read,
+                    columns::{SCOPE_NAME, TIME_END, TIME_START},
+                };
+
+                let mut read = Read::new(
+                    None::<Vec<&str>>,
+                    |schema| {
+                        let mut base: Vec<Box<dyn CustomFilter>> = vec![
+                            Box::new(
+                                Filter::new_u64(
+                                    schema,
+                                    TIME_START.name(),
+                                    body.start_time_ms * 1_000_000,
+                                )
+                                .gte(),
+                            ),
+                            Box::new(
+                                Filter::new_u64(
+                                    schema,
+                                    TIME_END.name(),
+                                    body.end_time_ms * 1_000_000,
+                                )
+                                .lte(),
+                            ),
+                        ];
+
+                        if let Some(scope_name) = body.scope_name.as_ref() {
+                            base.push(Box::new(Filter::new_str(
+                                schema,
+                                SCOPE_NAME.name(),
+                                scope_name.as_str(),
+                            )));
+                        }
+
+                        base
+                    },
+                    files,
+                );
+
+                'outer: while let Some(batch) = read.next_batch().await {
+                    for span in batch.get_spans(read.resources()) {
+                        traces.push(span);
+
+                        if traces.len >= traces.cap {
+                            break 'outer;
+                        }
+                    }
+                }
+            }
+            f @ Format::Vortex { .. } => {
+                use ottel_spaniel::vortex::read::{AsSpanData, Read};
+                use vortex::expr::*;
+
+                let base = and(
+                    gt_eq(
+                        get_item("time_start", root()),
+                        lit(body.start_time_ms * 1_000_000),
+                    ),
+                    lt_eq(
+                        get_item("time_end", root()),
+                        lit(body.end_time_ms * 1_000_000),
+                    ),
+                );
+                let scope_name = body.scope_name.clone();
+
+                let mut read = Read::new(f, files).with_file_filter(move |has| {
+                    match scope_name.as_ref() {
+                        None => base.clone(),
+                        // Files written before scopes were stored hold no matching span.
+                        Some(_) if !has("scope_name") => lit(false),
+                        Some(name) => and(
+                            base.clone(),
+                            eq(get_item("scope_name", root()), lit(name.clone())),
+                        ),
+                    }
+                });
+
+                'outer: while let Some(arr) = read.next_batch().await {
+                    for data in arr.get_span_data() {
+                        traces.push((&data).into());
+
+                        if traces.len >= traces.cap {
+                            break 'outer;
+                        }
+                    }
+                }
             }
         }
     }
 
-    Json(response::Traces {
+    Ok(Json(response::Traces {
         traces: traces.into_vec(),
     })
+    .into_response())
 }
 
 struct Sagarray<const CAP: usize, T> {
@@ -298,6 +402,8 @@ pub mod request {
     pub struct TraceFilter {
         pub start_time_ms: u64,
         pub end_time_ms: u64,
+        /// Only spans created by this instrumentation scope, e.g. `tracing-opentelemetry`.
+        pub scope_name: Option<String>,
         pub limit: u8,
     }
 }
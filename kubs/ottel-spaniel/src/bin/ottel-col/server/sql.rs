@@ -0,0 +1,52 @@
+use poem::http::StatusCode;
+use poem::web::{Data, Json};
+
+use ottel_spaniel::{Stats, sql};
+
+/// Runs a read-only SQL statement over files of the tenant, see [ottel_spaniel::sql].
+#[poem::handler]
+pub async fn v0_sql(
+    Data(stats): Data<&Stats>,
+    Data(options): Data<&sql::Options>,
+    Json(body): Json<request::Sql>,
+) -> poem::Result<Json<response::Rows>> {
+    let files: Vec<Box<_>> = { stats.files.read().await.iter().cloned().collect() };
+
+    let batches = sql::run(files, &body.query, *options).await.map_err(|e| {
+        let status = match e {
+            sql::Error::Query(_) => StatusCode::BAD_REQUEST,
+            sql::Error::Timeout(_) => StatusCode::REQUEST_TIMEOUT,
+        };
+
+        poem::Error::from_string(e.to_string(), status)
+    })?;
+
+    let mut writer = arrow::json::ArrayWriter::new(Vec::new());
+    writer
+        .write_batches(&batches.iter().collect::<Vec<_>>())
+        .and_then(|_| writer.finish())
+        .map_err(poem::error::InternalServerError)?;
+
+    let rows =
+        serde_json::from_slice(&writer.into_inner()).map_err(poem::error::InternalServerError)?;
+
+    Ok(Json(response::Rows { rows }))
+}
+
+pub mod request {
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Sql {
+        /// Single `SELECT` over the `spans` table.
+        pub query: String,
+    }
+}
+
+pub mod response {
+    #[derive(Debug, serde::Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Rows {
+        /// A JSON object per row, NULL columns are left out.
+        pub rows: Vec<serde_json::Map<String, serde_json::Value>>,
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use poem::web::sse::{Event, SSE};
+use poem::web::{Data, Query};
+
+use ottel_spaniel::tail::{Filter, Subscription};
+
+use super::jaeger::parse_duration;
+use super::metrics::SERVER;
+use crate::tenant::Tenant;
+
+/// Ingest requests buffered for a single client before its spans are dropped.
+const BUFFERED: usize = 64;
+
+/// Streams spans of the tenant matching the filter as they are ingested, as Server-Sent Events.
+///
+/// Every `spans` event carries the number of spans dropped so far, since a slow client never
+/// holds up ingestion.
+#[poem::handler]
+pub async fn v0_tail(
+    Data(tenant): Data<&Arc<Tenant>>,
+    Query(query): Query<request::Tail>,
+) -> poem::Result<SSE> {
+    let filter = Filter {
+        service: query.service,
+        span_name: query.span_name,
+        min_duration_nanos: query
+            .min_duration
+            .as_deref()
+            .map(parse_duration)
+            .transpose()?,
+        errors_only: query.errors_only.unwrap_or(false),
+    };
+
+    let subscription = tenant.tap.subscribe(filter, BUFFERED);
+    let tenant = tenant.id.clone();
+    tracing::info!(tenant, "tail.subscribed");
+
+    // The subscription ends once the client goes away and the stream is dropped.
+    let events = futures::stream::unfold(
+        (subscription, 0),
+        move |(mut subscription, reported): (Subscription, u64)| {
+            let tenant = tenant.clone();
+
+            async move {
+                let spans = subscription.recv().await?;
+                let dropped = subscription.dropped();
+
+                if dropped > reported {
+                    SERVER
+                        .tail_dropped_spans
+                        .get(&[("tenant", &tenant)])
+                        .add(dropped - reported);
+                }
+
+                let data =
+                    serde_json::to_string(&response::Spans { spans, dropped }).expect("spans.json");
+                let event = Event::message(data).event_type("spans");
+
+                Some((event, (subscription, dropped)))
+            }
+        },
+    );
+
+    Ok(SSE::new(events).keep_alive(Duration::from_secs(15)))
+}
+
+pub mod request {
+    #[derive(Debug, serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Tail {
+        pub service: Option<String>,
+        pub span_name: Option<String>,
+        /// Jaeger duration such as `150ms`.
+        pub min_duration: Option<String>,
+        pub errors_only: Option<bool>,
+    }
+}
+
+pub mod response {
+    #[derive(Debug, serde::Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Spans {
+        pub spans: Vec<ottel_spaniel::arrow::ext::Span>,
+        /// Spans dropped since the client subscribed.
+        pub dropped: u64,
+    }
+}
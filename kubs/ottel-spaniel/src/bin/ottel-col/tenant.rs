@@ -0,0 +1,362 @@
+//! Tenant isolation: every tenant has its own writer, data directory, [Stats] and ingest limit.
+//!
+//! The tenant of a request comes from its API key (`Authorization: Bearer <key>`). Any client can
+//! set the tenant header, so it only selects the tenant when no API keys are configured or for an
+//! [Operator], otherwise it has to match the tenant of the key. Handlers keep using `Data<&Stats>` and
+//! `Data<&Sink>`, [scope] puts the ones of the calling tenant into the request.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use poem::http::{HeaderMap, StatusCode};
+use poem::{Endpoint, IntoResponse, Request, Response};
+
+use ottel_spaniel::alert::Window;
+use ottel_spaniel::tail::Tap;
+use ottel_spaniel::write::{Format, Options as WriterOptions, start_writer};
+use ottel_spaniel::{Sink, Stats};
+
+use crate::server::{Operator, token_eq};
+
+/// Header carrying the tenant id.
+pub const HEADER: &str = "x-scope-orgid";
+
+pub struct Options {
+    /// Data directory of the single tenant, tenant directories are created inside of it.
+    pub data_dir: Box<Path>,
+    /// Tenants identified by the header, only reachable by API key once keys are configured.
+    pub tenants: Vec<String>,
+    /// API keys mapped to tenant ids, requests have to carry one when any is configured.
+    pub api_keys: HashMap<String, String>,
+    /// Spans per second accepted from a single tenant, `0` disables the limit.
+    pub spans_per_sec: u64,
+    /// Spans accepted at once above the rate.
+    pub burst: u64,
+}
+
+impl Options {
+    /// Without configured tenants everything goes to the single default tenant.
+    fn is_single(&self) -> bool {
+        self.tenants.is_empty() && self.api_keys.is_empty()
+    }
+}
+
+pub struct Tenant {
+    pub id: String,
+    pub sink: Sink,
+    pub stats: Stats,
+    /// Live tail of spans accepted for this tenant.
+    pub tap: Tap,
+    /// Recent spans for alerting rules.
+    pub window: Window,
+    limiter: Option<Mutex<Bucket>>,
+}
+
+impl Tenant {
+    /// Takes `spans` from the ingest budget, returns `false` when the tenant is over its limit.
+    pub fn admit(&self, spans: usize) -> bool {
+        let Some(limiter) = self.limiter.as_ref() else {
+            return true;
+        };
+
+        limiter.lock().expect("limiter.lock").take(spans as f64)
+    }
+}
+
+/// Token bucket refilled at a fixed rate.
+struct Bucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(rate: u64, burst: u64) -> Self {
+        let capacity = (rate + burst) as f64;
+
+        Self {
+            rate: rate as f64,
+            capacity,
+            tokens: capacity,
+            updated: Instant::now(),
+        }
+    }
+
+    fn take(&mut self, amount: f64) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+
+        if self.tokens < amount {
+            return false;
+        }
+
+        self.tokens -= amount;
+        true
+    }
+}
+
+#[derive(Clone)]
+pub struct Tenants {
+    by_id: Arc<HashMap<String, Arc<Tenant>>>,
+    api_keys: Arc<HashMap<String, String>>,
+    /// Tenant of every request when no tenants are configured.
+    single: Option<Arc<Tenant>>,
+}
+
+impl Tenants {
+    /// Every tenant, for background jobs working on all of them.
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Tenant>> {
+        self.single.iter().chain(self.by_id.values())
+    }
+
+    pub fn is_api_key(&self, key: &str) -> bool {
+        self.key_tenant(key).is_some()
+    }
+
+    /// Tenant of an API key, every key is compared so the lookup doesn't leak a matching prefix.
+    fn key_tenant(&self, key: &str) -> Option<&String> {
+        self.api_keys.iter().fold(None, |found, (api_key, id)| {
+            if token_eq(api_key, key) {
+                Some(id)
+            } else {
+                found
+            }
+        })
+    }
+
+    fn resolve(&self, headers: &HeaderMap, operator: bool) -> poem::Result<Arc<Tenant>> {
+        if let Some(tenant) = self.single.as_ref() {
+            return Ok(tenant.clone());
+        }
+
+        let key = headers
+            .get(poem::http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .and_then(|key| self.key_tenant(key));
+        let header = headers.get(HEADER).and_then(|v| v.to_str().ok());
+
+        let id = match (key, header) {
+            (Some(id), Some(header)) if header != id => {
+                return Err(poem::Error::from_string(
+                    "tenant header does not match the API key",
+                    StatusCode::FORBIDDEN,
+                ));
+            }
+            (Some(id), _) => id.as_str(),
+            // With API keys configured the header alone would let anyone pick a tenant.
+            (None, Some(header)) if self.api_keys.is_empty() || operator => header,
+            (None, _) => {
+                return Err(poem::Error::from_string(
+                    "missing tenant",
+                    StatusCode::UNAUTHORIZED,
+                ));
+            }
+        };
+
+        self.by_id
+            .get(id)
+            .cloned()
+            .ok_or_else(|| poem::Error::from_string("unknown tenant", StatusCode::FORBIDDEN))
+    }
+}
+
+/// Starts a writer per tenant, the returned futures have to be run on the writer runtime.
+pub fn start(
+    format: &Format,
+    writer: WriterOptions,
+    options: Options,
+) -> (Tenants, Vec<Box<dyn Future<Output = ()> + '_>>) {
+    let mut tasks = Vec::new();
+
+    let mut create = |id: &str, dir: Box<Path>| {
+        let (sink, stats, task) = start_writer(format, dir, writer);
+        tasks.push(task);
+
+        let limiter = (options.spans_per_sec > 0)
+            .then(|| Mutex::new(Bucket::new(options.spans_per_sec, options.burst)));
+
+        Arc::new(Tenant {
+            id: id.to_owned(),
+            sink,
+            stats,
+            tap: Tap::default(),
+            window: Window::default(),
+            limiter,
+        })
+    };
+
+    if options.is_single() {
+        let single = create("default", options.data_dir.clone());
+
+        let tenants = Tenants {
+            by_id: Arc::default(),
+            api_keys: Arc::default(),
+            single: Some(single),
+        };
+
+        return (tenants, tasks);
+    }
+
+    let mut by_id = HashMap::new();
+
+    for id in options.tenants.iter().chain(options.api_keys.values()) {
+        if by_id.contains_key(id) {
+            continue;
+        }
+
+        assert!(
+            ottel_spaniel::misc::is_valid_tenant(id),
+            "invalid tenant id: {id}"
+        );
+        tracing::info!(tenant = id, "tenant.start");
+
+        by_id.insert(
+            id.clone(),
+            create(id, ottel_spaniel::misc::tenant_dir(&options.data_dir, id)),
+        );
+    }
+
+    let tenants = Tenants {
+        by_id: Arc::new(by_id),
+        api_keys: Arc::new(options.api_keys),
+        single: None,
+    };
+
+    (tenants, tasks)
+}
+
+/// Resolves the tenant of a request and makes its [Sink], [Stats] and [Tenant] available to
+/// handlers, requests without a known tenant are rejected.
+pub async fn scope<E: Endpoint>(ep: Arc<E>, mut req: Request) -> poem::Result<Response> {
+    let tenants = req.data::<Tenants>().expect("tenants.data").clone();
+    let operator = req.extensions().get::<Operator>().is_some();
+    let tenant = tenants.resolve(req.headers(), operator)?;
+
+    req.extensions_mut().insert(tenant.sink.clone());
+    req.extensions_mut().insert(tenant.stats.clone());
+    req.extensions_mut().insert(tenant);
+
+    Ok(ep.call(req).await?.into_response())
+}
+
+/// Reads `<key> <tenant>` lines, empty lines and lines starting with `#` are skipped.
+pub fn read_api_keys(path: impl AsRef<Path>) -> HashMap<String, String> {
+    let content = std::fs::read_to_string(path).expect("api-keys.read");
+
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (key, tenant) = line
+                .split_once(char::is_whitespace)
+                .expect("api-keys.line.valid");
+
+            (key.to_owned(), tenant.trim().to_owned())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use poem::http::HeaderValue;
+
+    use super::*;
+
+    pub(crate) fn tenants(name: &str, api_keys: &[(&str, &str)]) -> Tenants {
+        let data_dir = crate::testing::temp_dir(&format!("tenant-{name}"));
+
+        let options = Options {
+            data_dir: data_dir.clone().into_boxed_path(),
+            tenants: vec!["a".to_owned(), "b".to_owned()],
+            api_keys: api_keys
+                .iter()
+                .map(|(key, id)| (key.to_string(), id.to_string()))
+                .collect(),
+            spans_per_sec: 0,
+            burst: 0,
+        };
+
+        // Writers are never run, the tests only resolve tenants.
+        let (tenants, _) = start(&Format::Arrow, crate::testing::writer_options(), options);
+        std::fs::remove_dir_all(data_dir).unwrap();
+
+        tenants
+    }
+
+    fn headers(key: Option<&str>, tenant: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        if let Some(key) = key {
+            let value = HeaderValue::from_str(&format!("Bearer {key}")).unwrap();
+            headers.insert(poem::http::header::AUTHORIZATION, value);
+        }
+        if let Some(tenant) = tenant {
+            headers.insert(HEADER, HeaderValue::from_str(tenant).unwrap());
+        }
+
+        headers
+    }
+
+    fn resolve(tenants: &Tenants, headers: HeaderMap) -> Result<String, StatusCode> {
+        tenants
+            .resolve(&headers, false)
+            .map(|tenant| tenant.id.clone())
+            .map_err(|e| e.status())
+    }
+
+    #[test]
+    fn header_selects_the_tenant_without_api_keys() {
+        let tenants = tenants("header", &[]);
+
+        assert_eq!(
+            resolve(&tenants, headers(None, Some("b"))),
+            Ok("b".to_owned())
+        );
+        assert_eq!(
+            resolve(&tenants, headers(None, Some("c"))),
+            Err(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(
+            resolve(&tenants, headers(None, None)),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+    }
+
+    #[test]
+    fn api_keys_decide_the_tenant() {
+        let tenants = tenants("keys", &[("key-a", "a"), ("key-b", "b")]);
+
+        assert_eq!(
+            resolve(&tenants, headers(Some("key-a"), None)),
+            Ok("a".to_owned())
+        );
+        assert_eq!(
+            resolve(&tenants, headers(Some("key-b"), Some("b"))),
+            Ok("b".to_owned())
+        );
+
+        // A disagreeing header is rejected instead of switching tenants.
+        assert_eq!(
+            resolve(&tenants, headers(Some("key-a"), Some("b"))),
+            Err(StatusCode::FORBIDDEN)
+        );
+
+        // The header alone can't select a tenant, neither can an unknown key.
+        assert_eq!(
+            resolve(&tenants, headers(None, Some("a"))),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(
+            resolve(&tenants, headers(Some("other"), Some("a"))),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+    }
+}
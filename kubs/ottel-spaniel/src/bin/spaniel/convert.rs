@@ -0,0 +1,185 @@
+use std::path::Path;
+
+use ottel_spaniel::misc::load_existing_files;
+use ottel_spaniel::write::{DATA_DIR, Options, start_writer};
+use ottel_spaniel::{FileFormat, Format, Sink, Stats};
+
+use crate::Args;
+
+pub const USAGE: &str = "Usage: spaniel convert --to <arrow|vortex> [options]
+
+Rewrites stored files of the other format, Vortex files keep no attributes.
+
+Options:
+    --dir <path>             Directory with stored files (default: data).
+    --out <path>             Directory receiving converted files (default: --dir).
+    --spans-per-file <n>     Spans per converted file (default: 1048576).
+    --replace                Delete source files once every span is converted, only when
+                             converting to Arrow since Vortex would drop attributes.
+";
+
+pub fn run(args: Args) {
+    if args.has("--help") {
+        eprintln!("{USAGE}");
+        return;
+    }
+
+    let to = args.get("--to").map(str::parse::<FileFormat>);
+    let Some(Ok(to)) = to else {
+        if let Some(Err(e)) = to {
+            eprintln!("{e}\n");
+        }
+        eprintln!("{USAGE}");
+        std::process::exit(1);
+    };
+
+    if args.has("--replace") && to == FileFormat::Vortex {
+        eprintln!("--replace would drop the attributes of converted spans\n\n{USAGE}");
+        std::process::exit(1);
+    }
+
+    let (source, target) = match to {
+        FileFormat::Arrow => (Format::vortex(), Format::Arrow),
+        FileFormat::Vortex => (Format::Arrow, Format::vortex()),
+    };
+
+    let dir = args.get("--dir").unwrap_or(DATA_DIR);
+    let out: Box<Path> = Path::new(args.get("--out").unwrap_or(dir)).into();
+
+    // Only local files can be rewritten, uploaded ones are left to the tier.
+    let files: Vec<Box<Path>> = load_existing_files(dir, &[source.file_prefix()])
+        .into_iter()
+        .filter(|file| file.exists())
+        .collect();
+
+    let options = Options {
+        flush_interval_millis: 100,
+        suspend_interval_millis: 1000,
+        suspend_after: u64::MAX,
+        sink_channel_size: 16,
+        request_waitlist_size: 16,
+        spans_per_file: args.parse("--spans-per-file", 1024 * 1024),
+        builder_flush_threshold: 8192,
+        builder_capacity: 8192,
+    };
+
+    tracing::info!(files = files.len(), out = %out.display(), "convert.start");
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("runtime.ok");
+
+    // Files of the target format already in `out` are not part of the output.
+    let existing = load_existing_files(&out, &[target.file_prefix()]);
+
+    let converted = rt.block_on(async {
+        let (sink, stats, task) = start_writer(&target, out.clone(), options);
+
+        let (_, converted) = tokio::join!(
+            Box::into_pin(task),
+            convert_files(&source, dir, files.clone(), sink)
+        );
+
+        match converted {
+            Ok(converted) => check_output(&target, &out, &stats, &existing, converted).await,
+            Err(e) => Err(e),
+        }
+    });
+
+    let converted = match converted {
+        Ok(converted) => converted,
+        Err(e) => {
+            // Nothing is deleted, sources are kept as long as a single span may be missing.
+            tracing::error!(error = %e, "convert.failed");
+            std::process::exit(1);
+        }
+    };
+
+    tracing::info!(converted, "convert.done");
+
+    if args.has("--replace") {
+        for file in files {
+            std::fs::remove_file(&file).expect("convert.remove");
+            tracing::info!(path = %file.display(), "convert.removed");
+        }
+    }
+}
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Reads every span from `files` and passes them to the writer behind `sink`, fails once a file
+/// can't be read or yields fewer rows than its footer lists.
+///
+/// The sink is dropped once done, which lets the writer close its last file.
+async fn convert_files(
+    source: &Format,
+    dir: &str,
+    files: Vec<Box<Path>>,
+    sink: Sink,
+) -> Result<u64, Error> {
+    let stats = Stats::new(dir);
+    let mut converted = 0;
+
+    // Files are read one by one, so that the rows of every file are counted.
+    for file in files {
+        let expected = source.file_info(file.clone(), &stats).await?.rows;
+        let mut rows = 0;
+
+        match source {
+            Format::Arrow => {
+                use ottel_spaniel::arrow::{AsSpanData, Read};
+
+                let mut read = Read::new(None::<Vec<&str>>, |_| vec![], vec![file.clone()]);
+
+                while let Some(batch) = read.try_next_batch().await {
+                    let data: Vec<_> = batch?.get_span_data(read.resources()).collect();
+                    rows += data.len() as u64;
+                    sink.send(data).await;
+                }
+            }
+            f @ Format::Vortex { .. } => {
+                use ottel_spaniel::vortex::read::{AsSpanData, Read};
+
+                let mut read = Read::new(f, vec![file.clone()]);
+
+                while let Some(arr) = read.try_next_batch().await {
+                    let data: Vec<_> = arr?.get_span_data().collect();
+                    rows += data.len() as u64;
+                    sink.send(data).await;
+                }
+            }
+        }
+
+        if rows != expected {
+            return Err(format!("{}: read {rows} of {expected} rows", file.display()).into());
+        }
+
+        tracing::info!(path = %file.display(), rows, "convert.file");
+        converted += rows;
+    }
+
+    Ok(converted)
+}
+
+/// Fails unless the files written to `out`, those of `existing` aside, hold `converted` rows.
+async fn check_output(
+    target: &Format,
+    out: &Path,
+    stats: &Stats,
+    existing: &[Box<Path>],
+    converted: u64,
+) -> Result<u64, Error> {
+    let files = load_existing_files(out, &[target.file_prefix()]);
+    let mut written = 0;
+
+    for file in files.into_iter().filter(|file| !existing.contains(file)) {
+        written += target.file_info(file, stats).await?.rows;
+    }
+
+    if written != converted {
+        return Err(format!("wrote {written} of {converted} converted rows").into());
+    }
+
+    Ok(converted)
+}
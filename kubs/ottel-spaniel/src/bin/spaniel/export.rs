@@ -0,0 +1,71 @@
+use std::path::Path;
+
+use ottel_spaniel::FileFormat;
+use ottel_spaniel::export::{Options, export_files};
+use ottel_spaniel::misc::{load_existing_files, tenant_dir};
+use ottel_spaniel::tier::{self, Tier};
+use ottel_spaniel::write::DATA_DIR;
+
+use crate::Args;
+
+pub const USAGE: &str = "Usage: spaniel export [options]
+
+Options:
+    --format <arrow|vortex>    Format of stored files (default: arrow).
+    --dir <path>               Directory with stored files (default: data).
+    --tenant <id>              Export files of a tenant stored under --dir.
+    --endpoint <url>           OTLP/gRPC endpoint (default: http://localhost:4317).
+    --batch-size <n>           Spans per request (default: 512).
+    --max-retries <n>          Retries of a failed request (default: 5).
+    --tier <url>               Object store holding uploaded files, e.g. s3://bucket/prefix.
+";
+
+pub fn run(args: Args) {
+    if args.has("--help") {
+        eprintln!("{USAGE}");
+        return;
+    }
+
+    let format = args.format();
+    let root = args.get("--dir").unwrap_or(DATA_DIR);
+    let dir: Box<Path> = match args.get("--tenant") {
+        Some(tenant) => tenant_dir(root, tenant),
+        None => Path::new(root).into(),
+    };
+    let files = load_existing_files(dir, &FileFormat::prefixes());
+
+    if let Some(url) = args.get("--tier") {
+        Tier::from_options(&tier::Options::new(url))
+            .expect("tier.ok")
+            .install();
+    }
+
+    let options = Options {
+        endpoint: args
+            .get("--endpoint")
+            .unwrap_or("http://localhost:4317")
+            .to_owned(),
+        batch_size: args.parse("--batch-size", 512),
+        max_retries: args.parse("--max-retries", 5),
+        retry_backoff_millis: 500,
+    };
+
+    tracing::info!(
+        files = files.len(),
+        endpoint = %options.endpoint,
+        "export.start"
+    );
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("runtime.ok");
+
+    match rt.block_on(export_files(&format, files, options)) {
+        Ok(exported) => tracing::info!(exported, "export.done"),
+        Err(e) => {
+            tracing::error!(error = %e, "export.failed");
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::{Method, Request, StatusCode, header};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+
+use ottel_spaniel::export::span_data_to_request;
+use ottel_spaniel::load::{Generator, Options};
+
+use crate::Args;
+
+pub const USAGE: &str = "Usage: spaniel load [options]
+
+Sends synthetic traces to a running collector, then reports ingest throughput,
+file size per span and latency of every search endpoint.
+
+Options:
+    --collector <url>      Collector address (default: http://localhost:44318).
+    --token <token>        Bearer token sent with every request, needs admin access.
+    --tenant <id>          Tenant sent in the X-Scope-OrgID header.
+    --traces <n>           Traces to send (default: 10000).
+    --rate <n>             Traces per second, 0 sends as fast as possible (default: 0).
+    --batch <n>            Traces per request (default: 16).
+    --services <n>         Services of generated traces (default: 8).
+    --fanout <n>           Maximum children of a span (default: 4).
+    --depth <n>            Maximum depth of a trace (default: 4).
+    --attributes <n>       Attributes of every span (default: 8).
+    --cardinality <n>      Distinct values of an attribute (default: 100).
+    --seed <n>             Seed of generated traces.
+    --queries <n>          Requests per search endpoint (default: 20).
+";
+
+struct Collector {
+    client: Client<HttpConnector, Full<Bytes>>,
+    url: String,
+    token: Option<String>,
+    tenant: Option<String>,
+}
+
+impl Collector {
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<(StatusCode, Bytes), String> {
+        let mut req = Request::builder()
+            .method(method)
+            .uri(format!("{}{path}", self.url))
+            .header(header::CONTENT_TYPE, "application/json");
+
+        if let Some(token) = self.token.as_ref() {
+            req = req.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+
+        if let Some(tenant) = self.tenant.as_ref() {
+            req = req.header("x-scope-orgid", tenant);
+        }
+
+        let req = req
+            .body(Full::new(Bytes::from(body.unwrap_or_default())))
+            .map_err(|e| e.to_string())?;
+
+        let res = self.client.request(req).await.map_err(|e| e.to_string())?;
+        let status = res.status();
+        let body = res
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| e.to_string())?
+            .to_bytes();
+
+        Ok((status, body))
+    }
+
+    /// Sends a request which has to succeed.
+    async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<Bytes, String> {
+        let (status, body) = self.send(method, path, body).await?;
+
+        if !status.is_success() {
+            return Err(format!(
+                "{path}: {status} {}",
+                String::from_utf8_lossy(&body)
+            ));
+        }
+
+        Ok(body)
+    }
+}
+
+/// Latency percentiles of a single endpoint.
+fn report_latency(endpoint: &str, mut samples: Vec<Duration>) {
+    if samples.is_empty() {
+        return;
+    }
+
+    samples.sort();
+
+    let at = |q: usize| samples[(samples.len() * q / 100).min(samples.len() - 1)];
+
+    println!(
+        "{endpoint:<28} p50 {:>9.2?}  p99 {:>9.2?}  max {:>9.2?}",
+        at(50),
+        at(99),
+        samples[samples.len() - 1],
+    );
+}
+
+pub fn run(args: Args) {
+    if args.has("--help") {
+        eprintln!("{USAGE}");
+        return;
+    }
+
+    let defaults = Options::default();
+    let options = Options {
+        services: args.parse("--services", defaults.services),
+        fanout: args.parse("--fanout", defaults.fanout),
+        depth: args.parse("--depth", defaults.depth),
+        attributes: args.parse("--attributes", defaults.attributes),
+        cardinality: args.parse("--cardinality", defaults.cardinality),
+        span_names: defaults.span_names,
+        seed: args.parse("--seed", defaults.seed),
+    };
+
+    let collector = Collector {
+        client: Client::builder(TokioExecutor::new()).build_http(),
+        url: args
+            .get("--collector")
+            .unwrap_or("http://localhost:44318")
+            .trim_end_matches('/')
+            .to_owned(),
+        token: args.get("--token").map(str::to_owned),
+        tenant: args.get("--tenant").map(str::to_owned),
+    };
+
+    let traces: usize = args.parse("--traces", 10_000);
+    let rate: u64 = args.parse("--rate", 0);
+    let batch: usize = args.parse("--batch", 16);
+    let queries: usize = args.parse("--queries", 20);
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("runtime.ok");
+
+    let result = rt.block_on(async {
+        let mut generator = Generator::new(options);
+        let mut trace_ids = Vec::new();
+        let mut spans = 0;
+        let mut window = (u64::MAX, 0);
+
+        let start = Instant::now();
+        let mut sent = 0;
+
+        while sent < traces {
+            if rate > 0 {
+                let due = start + Duration::from_secs_f64(sent as f64 / rate as f64);
+                tokio::time::sleep_until(due.into()).await;
+            }
+
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("time.ok")
+                .as_nanos() as u64;
+
+            let mut data = Vec::new();
+
+            for _ in 0..batch.min(traces - sent) {
+                let trace = generator.trace(now);
+
+                if trace_ids.len() < queries {
+                    trace_ids.push(trace[0].trace_id);
+                }
+
+                window.0 = window.0.min(trace[0].time_start);
+                window.1 = window.1.max(trace[0].time_end);
+
+                data.extend(trace);
+                sent += 1;
+            }
+
+            spans += data.len();
+
+            let body = serde_json::to_vec(&span_data_to_request(data)).expect("request.json");
+            collector
+                .request(Method::POST, "/v1/traces", Some(body))
+                .await?;
+        }
+
+        let elapsed = start.elapsed();
+        println!(
+            "ingest: {sent} traces, {spans} spans in {elapsed:.2?}, {:.0} spans/s",
+            spans as f64 / elapsed.as_secs_f64()
+        );
+
+        // Rotating flushes buffered spans and closes the open file, only closed files are listed
+        // with their size and queried.
+        collector
+            .request(Method::POST, "/admin/rotate", None)
+            .await?;
+
+        let files = collector.request(Method::GET, "/admin/files", None).await?;
+        let files: serde_json::Value = serde_json::from_slice(&files).map_err(|e| e.to_string())?;
+        let (bytes, rows) =
+            files["files"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .fold((0, 0), |(bytes, rows), file| {
+                    (
+                        bytes + file["sizeBytes"].as_u64().unwrap_or(0),
+                        rows + file["rows"].as_u64().unwrap_or(0),
+                    )
+                });
+
+        if rows > 0 {
+            println!(
+                "storage: {bytes} bytes, {rows} spans, {:.1} bytes/span",
+                bytes as f64 / rows as f64
+            );
+        }
+
+        let filter = serde_json::json!({
+            "startTimeMs": window.0 / 1_000_000,
+            "endTimeMs": window.1 / 1_000_000 + 1,
+            "limit": 50,
+        });
+        let mut query = filter.clone();
+        // Intrinsics only, so that collectors storing Vortex files answer it too.
+        query["query"] = "{ duration > 2ms && kind = server }".into();
+
+        let searches = [
+            ("/v0/search/resource/name", &filter),
+            ("/v0/search/span/name", &filter),
+            ("/v0/search/span", &filter),
+            ("/v0/query", &query),
+        ];
+
+        for (path, body) in searches {
+            let body = serde_json::to_vec(body).expect("body.json");
+            let mut samples = Vec::with_capacity(queries);
+
+            for _ in 0..queries {
+                let start = Instant::now();
+                collector
+                    .request(Method::POST, path, Some(body.clone()))
+                    .await?;
+                samples.push(start.elapsed());
+            }
+
+            report_latency(path, samples);
+        }
+
+        let mut samples = Vec::with_capacity(queries);
+        for _ in 0..queries {
+            let start = Instant::now();
+            collector
+                .request(Method::GET, "/api/services", None)
+                .await?;
+            samples.push(start.elapsed());
+        }
+        report_latency("/api/services", samples);
+
+        // Vortex files keep no attributes, the collector answers 404 for traces stored in them.
+        let mut samples = Vec::with_capacity(trace_ids.len());
+        for trace_id in trace_ids {
+            let path = format!("/api/traces/{}", const_hex::encode(trace_id));
+            let start = Instant::now();
+            let (status, body) = collector.send(Method::GET, &path, None).await?;
+
+            if !status.is_success() && status != StatusCode::NOT_FOUND {
+                return Err(format!(
+                    "{path}: {status} {}",
+                    String::from_utf8_lossy(&body)
+                ));
+            }
+
+            samples.push(start.elapsed());
+        }
+        report_latency("/api/traces/:trace_id", samples);
+
+        Ok::<_, String>(())
+    });
+
+    if let Err(e) = result {
+        tracing::error!(error = %e, "load.failed");
+        std::process::exit(1);
+    }
+}
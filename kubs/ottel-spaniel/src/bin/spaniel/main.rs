@@ -0,0 +1,76 @@
+use ottel_spaniel::Format;
+
+mod convert;
+mod export;
+mod load;
+mod sql;
+
+const USAGE: &str = "Usage: spaniel <command> [options]
+
+Commands:
+    export    Send stored spans to an OTLP/gRPC endpoint.
+    convert   Rewrite stored files in the other format.
+    load      Send synthetic traces to a collector and measure it.
+    sql       Run a read-only SQL query over stored Arrow files.
+";
+
+fn main() {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let Some(command) = args.first() else {
+        eprintln!("{USAGE}");
+        std::process::exit(1);
+    };
+
+    let args = Args(&args[1..]);
+
+    match command.as_str() {
+        "export" => export::run(args),
+        "convert" => convert::run(args),
+        "load" => load::run(args),
+        "sql" => sql::run(args),
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Minimal `--name value` argument lookup.
+pub struct Args<'a>(&'a [String]);
+
+impl Args<'_> {
+    pub fn has(&self, name: &str) -> bool {
+        self.0.iter().any(|arg| arg == name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .position(|arg| arg == name)
+            .and_then(|idx| self.0.get(idx + 1))
+            .map(String::as_str)
+    }
+
+    pub fn parse<T: std::str::FromStr>(&self, name: &str, default: T) -> T {
+        match self.get(name) {
+            Some(value) => value
+                .parse()
+                .unwrap_or_else(|_| panic!("invalid value for {name}: {value}")),
+            None => default,
+        }
+    }
+
+    /// Format selected with `--format arrow|vortex`, defaults to Arrow.
+    pub fn format(&self) -> Format {
+        match self.get("--format") {
+            None | Some("arrow") => Format::Arrow,
+            Some("vortex") => Format::vortex(),
+            Some(other) => panic!("unknown format: {other}"),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+
+use ottel_spaniel::FileFormat;
+use ottel_spaniel::misc::{load_existing_files, tenant_dir};
+use ottel_spaniel::sql::{self, Options};
+use ottel_spaniel::write::DATA_DIR;
+
+use crate::Args;
+
+pub const USAGE: &str = "Usage: spaniel sql [options]
+
+Runs a read-only SQL query over stored Arrow files, registered as the `spans` table.
+Typed attribute values are read with attr_str, attr_int, attr_float and attr_bool:
+
+    SELECT span_name, count(*) FROM spans
+    WHERE attr_int(attr_name, attr_type, attr_value, 'http.response.status_code') >= 500
+    GROUP BY span_name
+
+Options:
+    --query <sql>          Query to run, read from stdin when not set.
+    --dir <path>           Directory with stored files (default: data).
+    --tenant <id>          Query files of a tenant stored under --dir.
+    --timeout-secs <n>     Cancels the query after this long (default: 30).
+    --max-rows <n>         Rows printed at most (default: 10000).
+";
+
+pub fn run(args: Args) {
+    if args.has("--help") {
+        eprintln!("{USAGE}");
+        return;
+    }
+
+    let query = match args.get("--query") {
+        Some(query) => query.to_owned(),
+        None => {
+            let mut query = String::new();
+            std::io::stdin()
+                .read_to_string(&mut query)
+                .expect("stdin.read");
+            query
+        }
+    };
+
+    let root = args.get("--dir").unwrap_or(DATA_DIR);
+    let dir: Box<Path> = match args.get("--tenant") {
+        Some(tenant) => tenant_dir(root, tenant),
+        None => Path::new(root).into(),
+    };
+    let files = load_existing_files(dir, &[FileFormat::Arrow.prefix()]);
+
+    let defaults = Options::default();
+    let options = Options {
+        timeout: Duration::from_secs(args.parse("--timeout-secs", defaults.timeout.as_secs())),
+        max_rows: args.parse("--max-rows", defaults.max_rows),
+    };
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("runtime.ok");
+
+    match rt.block_on(sql::run(files, &query, options)) {
+        Ok(batches) => {
+            datafusion::arrow::util::pretty::print_batches(&batches).expect("batches.print")
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "sql.failed");
+            std::process::exit(1);
+        }
+    }
+}
@@ -1,18 +1,27 @@
 use std::sync::Arc;
 
 use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
-use ottel_spaniel::SpanData;
+
+use crate::{ScopeData, SpanData};
 
 // Should report number of rejected spans.
-// Should be moved to bin/api
 pub fn request_to_span_data(request: ExportTraceServiceRequest) -> Vec<SpanData> {
     let mut result = Vec::new();
 
     for rs in request.resource_spans {
         let rs_attrs = rs.resource.map(|v| v.attributes).unwrap_or(Vec::new());
         let rs_attrs = Arc::new(rs_attrs);
+        let rs_schema_url = non_empty(rs.schema_url);
 
         for ss in rs.scope_spans {
+            let scope = ss.scope.unwrap_or_default();
+            let scope = Arc::new(ScopeData {
+                name: non_empty(scope.name),
+                version: non_empty(scope.version),
+                attributes: scope.attributes,
+                schema_url: non_empty(ss.schema_url),
+            });
+
             for span in ss.spans {
                 if span.trace_id.len() != 16 {
                     continue;
@@ -39,9 +48,16 @@ pub fn request_to_span_data(request: ExportTraceServiceRequest) -> Vec<SpanData>
                     time_start: span.start_time_unix_nano,
                     time_end: span.end_time_unix_nano,
                     time_duration: span.end_time_unix_nano - span.start_time_unix_nano,
+                    trace_state: non_empty(span.trace_state),
+                    flags: span.flags,
+                    dropped_attributes_count: span.dropped_attributes_count,
+                    dropped_events_count: span.dropped_events_count,
+                    dropped_links_count: span.dropped_links_count,
 
                     span_attributes: span.attributes,
                     resource_attributes: rs_attrs.clone(),
+                    resource_schema_url: rs_schema_url.clone(),
+                    scope: scope.clone(),
                 });
             }
         }
@@ -49,3 +65,7 @@ pub fn request_to_span_data(request: ExportTraceServiceRequest) -> Vec<SpanData>
 
     result
 }
+
+fn non_empty(value: String) -> Option<String> {
+    if value.is_empty() { None } else { Some(value) }
+}
@@ -0,0 +1,390 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+use opentelemetry_proto::tonic::collector::trace::v1::trace_service_client::TraceServiceClient;
+use opentelemetry_proto::tonic::common::v1::{InstrumentationScope, KeyValue};
+use opentelemetry_proto::tonic::resource::v1::Resource;
+use opentelemetry_proto::tonic::trace::v1::{ResourceSpans, ScopeSpans, Span, Status};
+use tonic::transport::Channel;
+
+use crate::{Format, ScopeData, SpanData};
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// OTLP/gRPC endpoint, e.g. `http://localhost:4317`.
+    pub endpoint: String,
+    /// Number of spans sent in a single request.
+    pub batch_size: usize,
+    /// Number of attempts after the first failed request.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled after every attempt.
+    pub retry_backoff_millis: u64,
+}
+
+/// Groups spans by resource and instrumentation scope into a single request.
+pub fn span_data_to_request(spans: Vec<SpanData>) -> ExportTraceServiceRequest {
+    type Scopes = Vec<(Arc<ScopeData>, Vec<Span>)>;
+    let mut groups: Vec<(Arc<Vec<KeyValue>>, Option<String>, Scopes)> = Vec::new();
+
+    for data in spans {
+        let idx = groups.iter().position(|(resource, schema_url, _)| {
+            (Arc::ptr_eq(resource, &data.resource_attributes)
+                || *resource == data.resource_attributes)
+                && *schema_url == data.resource_schema_url
+        });
+        let idx = match idx {
+            Some(idx) => idx,
+            None => {
+                groups.push((
+                    data.resource_attributes.clone(),
+                    data.resource_schema_url.clone(),
+                    Vec::new(),
+                ));
+                groups.len() - 1
+            }
+        };
+
+        let scopes = &mut groups[idx].2;
+        let idx = scopes
+            .iter()
+            .position(|(scope, _)| Arc::ptr_eq(scope, &data.scope) || *scope == data.scope);
+        let idx = match idx {
+            Some(idx) => idx,
+            None => {
+                scopes.push((data.scope.clone(), Vec::new()));
+                scopes.len() - 1
+            }
+        };
+
+        scopes[idx].1.push(Span {
+            trace_id: data.trace_id.to_vec(),
+            span_id: data.span_id.to_vec(),
+            trace_state: data.trace_state.unwrap_or_default(),
+            parent_span_id: data
+                .parent_span_id
+                .map(|id| id.to_vec())
+                .unwrap_or_default(),
+            flags: data.flags,
+            name: data.name,
+            kind: data.kind,
+            start_time_unix_nano: data.time_start,
+            end_time_unix_nano: data.time_end,
+            attributes: data.span_attributes,
+            dropped_attributes_count: data.dropped_attributes_count,
+            dropped_events_count: data.dropped_events_count,
+            dropped_links_count: data.dropped_links_count,
+            status: data.status_code.map(|code| Status {
+                code,
+                message: data.status_message.unwrap_or_default(),
+            }),
+            ..Default::default()
+        });
+    }
+
+    ExportTraceServiceRequest {
+        resource_spans: groups
+            .into_iter()
+            .map(|(resource, schema_url, scopes)| ResourceSpans {
+                resource: Some(Resource {
+                    attributes: Arc::unwrap_or_clone(resource),
+                    ..Default::default()
+                }),
+                scope_spans: scopes
+                    .into_iter()
+                    .map(|(scope, spans)| {
+                        let scope = Arc::unwrap_or_clone(scope);
+
+                        ScopeSpans {
+                            scope: Some(InstrumentationScope {
+                                name: scope.name.unwrap_or_default(),
+                                version: scope.version.unwrap_or_default(),
+                                attributes: scope.attributes,
+                                ..Default::default()
+                            }),
+                            spans,
+                            schema_url: scope.schema_url.unwrap_or_default(),
+                        }
+                    })
+                    .collect(),
+                schema_url: schema_url.unwrap_or_default(),
+            })
+            .collect(),
+    }
+}
+
+pub struct Exporter {
+    client: TraceServiceClient<Channel>,
+    options: Options,
+    buffer: Vec<SpanData>,
+    /// Number of spans accepted by the endpoint.
+    pub exported: usize,
+}
+
+impl Exporter {
+    pub async fn connect(options: Options) -> Result<Self, tonic::transport::Error> {
+        let client = TraceServiceClient::connect(options.endpoint.clone()).await?;
+
+        Ok(Self {
+            client,
+            buffer: Vec::with_capacity(options.batch_size),
+            options,
+            exported: 0,
+        })
+    }
+
+    /// Buffers spans and sends them once the batch is full.
+    pub async fn push(
+        &mut self,
+        data: impl IntoIterator<Item = SpanData>,
+    ) -> Result<(), tonic::Status> {
+        for span in data {
+            self.buffer.push(span);
+
+            if self.buffer.len() >= self.options.batch_size {
+                self.flush().await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn flush(&mut self) -> Result<(), tonic::Status> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let spans: Vec<SpanData> = self.buffer.drain(..).collect();
+        let len = spans.len();
+        let request = span_data_to_request(spans);
+
+        let mut attempt = 0;
+        let mut backoff = Duration::from_millis(self.options.retry_backoff_millis);
+
+        loop {
+            match self.client.export(request.clone()).await {
+                Ok(response) => {
+                    let rejected = response
+                        .into_inner()
+                        .partial_success
+                        .map(|p| p.rejected_spans)
+                        .unwrap_or(0);
+
+                    if rejected > 0 {
+                        tracing::warn!(rejected, "export.partial_success");
+                    }
+
+                    // Servers may count rejected spans of their own, e.g. split ones.
+                    self.exported += len.saturating_sub(rejected as usize);
+                    tracing::info!(len, exported = self.exported, "export.ok");
+
+                    return Ok(());
+                }
+                Err(status) if attempt < self.options.max_retries && is_retryable(&status) => {
+                    tracing::warn!(?status, attempt, "export.retry");
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                    backoff *= 2;
+                }
+                Err(status) => return Err(status),
+            }
+        }
+    }
+}
+
+/// Whether a failed request may succeed when sent again.
+pub fn is_retryable(status: &tonic::Status) -> bool {
+    use tonic::Code;
+
+    matches!(
+        status.code(),
+        Code::Unavailable
+            | Code::ResourceExhausted
+            | Code::DeadlineExceeded
+            | Code::Aborted
+            | Code::Cancelled
+    )
+}
+
+/// Reads every span from `files` of any format and sends them to the configured endpoint.
+///
+/// Returns the number of exported spans.
+pub async fn export_files(
+    format: &Format,
+    files: Vec<Box<Path>>,
+    options: Options,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut exporter = Exporter::connect(options).await?;
+
+    for (format, files) in format.split(files) {
+        match format {
+            Format::Arrow => {
+                use crate::arrow::{AsSpanData, Read};
+
+                let mut read = Read::new(None::<Vec<&str>>, |_| vec![], files);
+
+                while let Some(batch) = read.next_batch().await {
+                    exporter.push(batch.get_span_data(read.resources())).await?;
+                }
+            }
+            f @ Format::Vortex { .. } => {
+                use crate::vortex::read::{AsSpanData, Read};
+
+                let mut read = Read::new(f, files);
+
+                while let Some(arr) = read.next_batch().await {
+                    exporter.push(arr.get_span_data()).await?;
+                }
+            }
+        }
+    }
+
+    exporter.flush().await?;
+
+    Ok(exporter.exported)
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry_proto::tonic::common::v1::AnyValue;
+    use opentelemetry_proto::tonic::common::v1::any_value::Value;
+
+    use super::*;
+    use crate::convert::request_to_span_data;
+
+    fn kv(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.to_owned(),
+            value: Some(AnyValue {
+                value: Some(Value::StringValue(value.to_owned())),
+            }),
+        }
+    }
+
+    fn span(id: u8, parent: Option<u8>, status: Option<Status>) -> Span {
+        Span {
+            trace_id: vec![7; 16],
+            span_id: vec![id; 8],
+            trace_state: if id.is_multiple_of(2) {
+                "vendor=value".to_owned()
+            } else {
+                String::new()
+            },
+            parent_span_id: parent.map(|id| vec![id; 8]).unwrap_or_default(),
+            flags: 0x301,
+            name: format!("span-{id}"),
+            kind: 2,
+            start_time_unix_nano: 1_000 * id as u64,
+            end_time_unix_nano: 1_000 * id as u64 + 500,
+            attributes: vec![kv("attr", &id.to_string())],
+            dropped_attributes_count: 1,
+            dropped_events_count: 2,
+            dropped_links_count: 3,
+            status,
+            ..Default::default()
+        }
+    }
+
+    fn scope(name: &str, spans: Vec<Span>) -> ScopeSpans {
+        ScopeSpans {
+            scope: Some(InstrumentationScope {
+                name: name.to_owned(),
+                version: "1.0".to_owned(),
+                attributes: vec![kv("scope.attr", name)],
+                ..Default::default()
+            }),
+            spans,
+            schema_url: "https://opentelemetry.io/schemas/1.26.0".to_owned(),
+        }
+    }
+
+    #[test]
+    fn request_survives_span_data_round_trip() {
+        let error = Status {
+            code: 2,
+            message: "failed".to_owned(),
+        };
+        let request = ExportTraceServiceRequest {
+            resource_spans: vec![
+                ResourceSpans {
+                    resource: Some(Resource {
+                        attributes: vec![kv("service.name", "api")],
+                        ..Default::default()
+                    }),
+                    scope_spans: vec![
+                        scope(
+                            "http",
+                            vec![span(1, None, None), span(2, Some(1), Some(error))],
+                        ),
+                        scope("db", vec![span(3, Some(2), Some(Status::default()))]),
+                    ],
+                    schema_url: "https://opentelemetry.io/schemas/1.24.0".to_owned(),
+                },
+                ResourceSpans {
+                    resource: Some(Resource {
+                        attributes: vec![kv("service.name", "worker")],
+                        ..Default::default()
+                    }),
+                    scope_spans: vec![scope("queue", vec![span(4, Some(1), None)])],
+                    schema_url: String::new(),
+                },
+            ],
+        };
+
+        let spans = request_to_span_data(request.clone());
+        assert_eq!(spans.len(), 4);
+        assert_eq!(spans[3].time_duration, 500);
+
+        assert_eq!(span_data_to_request(spans), request);
+    }
+
+    #[test]
+    fn root_span_resource_survives_storage() {
+        use crate::arrow::{AsSpanData, Read};
+        use crate::load::ingest;
+        use crate::testing;
+
+        let resource = vec![kv("service.name", "api")];
+        let request = ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                resource: Some(Resource {
+                    attributes: resource.clone(),
+                    ..Default::default()
+                }),
+                scope_spans: vec![scope("http", vec![span(1, None, None)])],
+                schema_url: String::new(),
+            }],
+        };
+
+        let dir = testing::temp_dir("export-root");
+        let format = Format::Arrow;
+        let rt = testing::runtime();
+
+        let spans = rt.block_on(async {
+            let options = testing::writer_options();
+            let spans = request_to_span_data(request);
+            ingest(&format, dir.clone().into_boxed_path(), spans, options).await;
+
+            let files = crate::misc::load_existing_files(&dir, &[format.file_prefix()]);
+            let mut read = Read::new(None::<Vec<&str>>, |_| vec![], files);
+            let mut spans = Vec::new();
+            while let Some(batch) = read.next_batch().await {
+                spans.extend(batch.get_span_data(read.resources()));
+            }
+            spans
+        });
+
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].parent_span_id, None);
+
+        let exported = span_data_to_request(spans);
+        let attributes = exported.resource_spans[0]
+            .resource
+            .as_ref()
+            .map(|resource| resource.attributes.clone());
+        assert_eq!(attributes, Some(resource));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
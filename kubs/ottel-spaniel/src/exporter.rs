@@ -0,0 +1,241 @@
+//! [SpanExporter] writing spans of the current process into local files, without a collector.
+//!
+//! ```ignore
+//! let exporter = Exporter::new(Format::Arrow, Path::new("data-traces"), Exporter::OPTIONS);
+//! let provider = SdkTracerProvider::builder()
+//!     .with_batch_exporter(exporter)
+//!     .build();
+//! ```
+//!
+//! Written files are read with the same readers as files of `ottel-col`, see [Exporter::stats].
+
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+use opentelemetry_proto::transform::common::tonic::ResourceAttributesWithSchema;
+use opentelemetry_proto::transform::trace::tonic::group_spans_by_resource_and_scope;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::error::{OTelSdkError, OTelSdkResult};
+use opentelemetry_sdk::trace::{SpanData as SdkSpanData, SpanExporter};
+
+use crate::write::{Command, Options, start_writer};
+use crate::{Format, Sink, SpanData, Stats};
+
+#[derive(Debug)]
+pub struct Exporter {
+    /// Gone once shut down, which lets the writer close its file.
+    sink: Option<Sink>,
+    stats: Stats,
+    /// Thread running the writer.
+    writer: Option<JoinHandle<()>>,
+    /// Disconnected once the writer thread exits, in a mutex since exporters are shared.
+    finished: Mutex<Receiver<()>>,
+    resource: ResourceAttributesWithSchema,
+}
+
+impl Exporter {
+    /// Options for a process exporting its own spans, exports wait at most the flush interval.
+    pub const OPTIONS: Options = Options {
+        flush_interval_millis: 500,
+        suspend_interval_millis: 10_000,
+        suspend_after: 10,
+        sink_channel_size: 64,
+        request_waitlist_size: 64,
+        spans_per_file: 1024 * 10,
+        builder_flush_threshold: 1024,
+        builder_capacity: 2048,
+    };
+
+    /// Starts a writer storing files in `dir` on its own thread.
+    pub fn new(format: Format, dir: impl Into<Box<Path>>, options: Options) -> Self {
+        let dir = dir.into();
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let (finished_tx, finished) = std::sync::mpsc::channel::<()>();
+
+        let writer = std::thread::Builder::new()
+            .name("spaniel-exporter".into())
+            .spawn(move || {
+                // Dropped when the thread exits, also when it panics.
+                let _finished = finished_tx;
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("runtime.ok");
+                let _guard = rt.enter();
+
+                let (sink, stats, task) = start_writer(&format, dir, options);
+                started_tx.send((sink, stats)).expect("exporter.started");
+
+                rt.block_on(Box::into_pin(task));
+            })
+            .expect("exporter.spawn");
+
+        let (sink, stats) = started_rx.recv().expect("exporter.started");
+
+        Self {
+            sink: Some(sink),
+            stats,
+            writer: Some(writer),
+            finished: Mutex::new(finished),
+            resource: ResourceAttributesWithSchema::default(),
+        }
+    }
+
+    /// Files written so far, e.g. for [crate::arrow::Read] or [crate::vortex::read::Read].
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+}
+
+/// Groups spans the same way an OTLP exporter does and converts the request.
+fn to_span_data(batch: Vec<SdkSpanData>, resource: &ResourceAttributesWithSchema) -> Vec<SpanData> {
+    let request = ExportTraceServiceRequest {
+        resource_spans: group_spans_by_resource_and_scope(batch, resource),
+    };
+
+    crate::convert::request_to_span_data(request)
+}
+
+impl SpanExporter for Exporter {
+    fn export(&self, batch: Vec<SdkSpanData>) -> impl Future<Output = OTelSdkResult> + Send {
+        let sink = self.sink.clone();
+        let spans = to_span_data(batch, &self.resource);
+
+        async move {
+            let Some(sink) = sink else {
+                return Err(OTelSdkError::AlreadyShutdown);
+            };
+
+            sink.send(spans).await;
+            Ok(())
+        }
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+        if self.sink.take().is_none() {
+            return Err(OTelSdkError::AlreadyShutdown);
+        }
+
+        let Some(writer) = self.writer.take() else {
+            return Ok(());
+        };
+
+        // Writer finishes once every sink is dropped, including clones held by exports.
+        let finished = self.finished.get_mut().expect("finished.lock");
+        if let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(timeout) {
+            return Err(OTelSdkError::Timeout(timeout));
+        }
+
+        writer
+            .join()
+            .map_err(|_| OTelSdkError::InternalFailure("writer panicked".into()))
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        let Some(sink) = self.sink.as_ref() else {
+            return Err(OTelSdkError::AlreadyShutdown);
+        };
+
+        // Only closed files are listed, so the current one is closed too.
+        futures::executor::block_on(sink.command(Command::Rotate));
+        Ok(())
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = resource.into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::trace::{Tracer, TracerProvider};
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+
+    use super::*;
+    use crate::arrow::{AsSpanData, Read};
+    use crate::load::{Generator, Options as LoadOptions};
+    use crate::testing;
+
+    #[test]
+    fn exported_spans_are_readable() {
+        let dir = testing::temp_dir("exporter");
+
+        let options = Options {
+            flush_interval_millis: 10,
+            ..Exporter::OPTIONS
+        };
+        let exporter = Exporter::new(Format::Arrow, dir.as_path(), options);
+        let stats = exporter.stats().clone();
+
+        let provider = SdkTracerProvider::builder()
+            .with_resource(
+                Resource::builder()
+                    .with_service_name("exporter-test")
+                    .build(),
+            )
+            .with_simple_exporter(exporter)
+            .build();
+
+        let tracer = provider.tracer("spaniel");
+        tracer.in_span("parent", |_| {
+            tracer.in_span("child", |_| {});
+        });
+
+        provider.shutdown().expect("provider.shutdown");
+
+        let rt = testing::runtime();
+
+        let mut names: Vec<_> = rt.block_on(async {
+            let files = stats.files.read().await.iter().cloned().collect();
+            let mut read = Read::new(None::<Vec<&str>>, |_| vec![], files);
+            let mut names = Vec::new();
+
+            while let Some(batch) = read.next_batch().await {
+                for span in batch.get_span_data(read.resources()) {
+                    assert_eq!(span.scope.name.as_deref(), Some("spaniel"));
+                    names.push(span.name);
+                }
+            }
+
+            names
+        });
+        names.sort();
+
+        assert_eq!(names, ["child", "parent"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn force_flush_lists_exported_spans() {
+        let dir = testing::temp_dir("exporter-flush");
+
+        let mut exporter = Exporter::new(Format::Arrow, dir.as_path(), Exporter::OPTIONS);
+        let stats = exporter.stats().clone();
+
+        let spans = Generator::new(LoadOptions::default()).trace(0);
+        let len = spans.len() as u64;
+        let sink = exporter.sink.clone().unwrap();
+        futures::executor::block_on(sink.send(spans));
+
+        // Spans are listed without waiting for the flush interval or a shutdown.
+        exporter.force_flush().expect("exporter.flush");
+        let files = stats.files.blocking_read().clone();
+        assert_eq!(files.len(), 1);
+
+        let (rows, _, _) = testing::runtime()
+            .block_on(crate::arrow::file_summary(files[0].clone()))
+            .unwrap();
+        assert_eq!(rows, len);
+
+        drop(sink);
+        exporter
+            .shutdown_with_timeout(Duration::from_secs(5))
+            .expect("exporter.shutdown");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,311 @@
+//! Per-file trace_id index, written next to an Arrow file once it is closed.
+//!
+//! An index lists every trace of its file sorted by trace_id, together with the runs of rows
+//! holding its spans. Lookups open only files containing a trace and read only those rows. Files
+//! without an index, e.g. Vortex files or files written before indexes existed, are always read.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Directory of indexes, inside the directory of their data files.
+pub const DIR: &str = "index";
+
+const MAGIC: &[u8; 4] = b"STI2";
+/// Indexes keeping only the first and the last row of a trace, still read.
+const MAGIC_V1: &[u8; 4] = b"STI1";
+const ENTRY_V1: usize = 16 + 4 + 4;
+
+/// Collects trace_ids of rows in the order they are written.
+#[derive(Debug, Default)]
+pub struct Builder {
+    rows: HashMap<[u8; 16], Vec<Range<u32>>>,
+    len: u32,
+}
+
+impl Builder {
+    pub fn push(&mut self, trace_id: [u8; 16]) {
+        let row = self.len;
+        self.len += 1;
+
+        let ranges = self.rows.entry(trace_id).or_default();
+        match ranges.last_mut() {
+            Some(last) if last.end == row => last.end += 1,
+            _ => ranges.push(row..row + 1),
+        }
+    }
+
+    /// Index of every pushed row, the builder starts over at row 0.
+    pub fn take(&mut self) -> TraceIndex {
+        let mut entries: Vec<_> = self.rows.drain().collect();
+        entries.sort_unstable_by_key(|(trace_id, _)| *trace_id);
+        self.len = 0;
+
+        TraceIndex { entries }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct TraceIndex {
+    /// Sorted by trace_id, with the sorted runs of rows of the trace.
+    entries: Vec<([u8; 16], Vec<Range<u32>>)>,
+}
+
+impl TraceIndex {
+    /// Rows holding spans of `trace_id`, sorted and not overlapping. Indexes of the first version
+    /// return a single range, possibly along with rows of other traces.
+    pub fn rows(&self, trace_id: &[u8; 16]) -> Option<impl Iterator<Item = Range<usize>> + '_> {
+        let idx = self
+            .entries
+            .binary_search_by(|(id, _)| id.cmp(trace_id))
+            .ok()?;
+        let (_, ranges) = &self.entries[idx];
+
+        Some(
+            ranges
+                .iter()
+                .map(|range| range.start as usize..range.end as usize),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Every entry is the trace_id, the number of ranges and their starts and ends.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(MAGIC.len() + 4 + self.entries.len() * (16 + 4 + 8));
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+
+        for (trace_id, ranges) in &self.entries {
+            buf.extend_from_slice(trace_id);
+            buf.extend_from_slice(&(ranges.len() as u32).to_le_bytes());
+
+            for range in ranges {
+                buf.extend_from_slice(&range.start.to_le_bytes());
+                buf.extend_from_slice(&range.end.to_le_bytes());
+            }
+        }
+
+        buf
+    }
+
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if let Some(rest) = buf.strip_prefix(MAGIC_V1) {
+            return Self::decode_v1(rest);
+        }
+
+        let rest = buf.strip_prefix(MAGIC)?;
+        let (len, mut rest) = take_u32(rest)?;
+        let mut entries = Vec::with_capacity((len as usize).min(rest.len() / 20));
+
+        for _ in 0..len {
+            let (trace_id, tail) = rest.split_first_chunk::<16>()?;
+            let (count, mut tail) = take_u32(tail)?;
+            let mut ranges = Vec::with_capacity((count as usize).min(tail.len() / 8));
+
+            for _ in 0..count {
+                let (start, next) = take_u32(tail)?;
+                let (end, next) = take_u32(next)?;
+                ranges.push(start..end);
+                tail = next;
+            }
+
+            entries.push((*trace_id, ranges));
+            rest = tail;
+        }
+
+        rest.is_empty().then_some(Self { entries })
+    }
+
+    fn decode_v1(rest: &[u8]) -> Option<Self> {
+        let (len, rest) = take_u32(rest)?;
+
+        if rest.len() != len as usize * ENTRY_V1 {
+            return None;
+        }
+
+        let entries = rest
+            .chunks_exact(ENTRY_V1)
+            .map(|entry| {
+                let (trace_id, rows) = entry.split_first_chunk::<16>().expect("entry.len");
+                let (first, rows) = take_u32(rows).expect("entry.len");
+                let (last, _) = take_u32(rows).expect("entry.len");
+
+                (*trace_id, std::iter::once(first..last + 1).collect())
+            })
+            .collect();
+
+        Some(Self { entries })
+    }
+}
+
+fn take_u32(buf: &[u8]) -> Option<(u32, &[u8])> {
+    let (value, rest) = buf.split_first_chunk::<4>()?;
+    Some((u32::from_le_bytes(*value), rest))
+}
+
+/// Path of the index of a data file.
+pub fn path(file: &Path) -> PathBuf {
+    let dir = file.parent().expect("file.dir");
+    dir.join(DIR).join(file.file_name().expect("file.name"))
+}
+
+pub fn write(file: &Path, index: &TraceIndex) {
+    let path = path(file);
+    std::fs::create_dir_all(path.parent().expect("index.dir")).expect("index.dir.create");
+
+    // Readers never see a partially written index.
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, index.encode()).expect("index.write");
+    std::fs::rename(&tmp, &path).expect("index.rename");
+
+    tracing::info!(file = ?file, traces = index.len(), "index.written");
+}
+
+/// Index of a data file, `None` when it has none or it can't be decoded.
+pub fn load(file: &Path) -> Option<TraceIndex> {
+    let buf = std::fs::read(path(file)).ok()?;
+    let index = TraceIndex::decode(&buf);
+
+    if index.is_none() {
+        tracing::warn!(file = ?file, "index.invalid");
+    }
+
+    index
+}
+
+pub fn remove(file: &Path) {
+    let _ = std::fs::remove_file(path(file));
+}
+
+/// Rows to read of indexed files, see [lookup].
+pub type FileRows = HashMap<Box<Path>, Vec<Range<usize>>>;
+
+/// Files which may hold spans of `trace_ids`, with the rows to read for indexed files.
+///
+/// Ranges of a file are sorted and don't overlap.
+pub fn lookup(files: Vec<Box<Path>>, trace_ids: &[[u8; 16]]) -> (Vec<Box<Path>>, FileRows) {
+    let mut found = Vec::new();
+    let mut rows = HashMap::new();
+
+    for file in files {
+        let Some(index) = load(&file) else {
+            found.push(file);
+            continue;
+        };
+
+        let mut ranges: Vec<_> = trace_ids
+            .iter()
+            .filter_map(|id| index.rows(id))
+            .flatten()
+            .collect();
+
+        if ranges.is_empty() {
+            continue;
+        }
+
+        ranges.sort_unstable_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        rows.insert(file.clone(), merged);
+        found.push(file);
+    }
+
+    (found, rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(n: u8) -> [u8; 16] {
+        [n; 16]
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn index_round_trips_and_finds_rows() {
+        let mut builder = Builder::default();
+        for n in [3, 1, 3, 2, 1, 3] {
+            builder.push(id(n));
+        }
+
+        let index = builder.take();
+        let index = TraceIndex::decode(&index.encode()).expect("index.decode");
+        let rows = |n| index.rows(&id(n)).map(Iterator::collect::<Vec<_>>);
+
+        assert_eq!(index.len(), 3);
+        assert_eq!(rows(1), Some(vec![1..2, 4..5]));
+        assert_eq!(rows(2), Some(vec![3..4]));
+        assert_eq!(rows(3), Some(vec![0..1, 2..3, 5..6]));
+        assert_eq!(rows(4), None);
+
+        assert!(TraceIndex::decode(&index.encode()[..10]).is_none());
+        assert!(builder.take().is_empty());
+
+        // Indexes of the first version keep the first and the last row of a trace.
+        let mut v1 = MAGIC_V1.to_vec();
+        v1.extend_from_slice(&1u32.to_le_bytes());
+        v1.extend_from_slice(&id(1));
+        v1.extend_from_slice(&1u32.to_le_bytes());
+        v1.extend_from_slice(&4u32.to_le_bytes());
+
+        let index = TraceIndex::decode(&v1).expect("index.decode");
+        assert_eq!(index.rows(&id(1)).unwrap().collect::<Vec<_>>(), vec![1..5]);
+    }
+
+    #[test]
+    fn lookup_skips_files_and_merges_ranges() {
+        let dir = crate::testing::temp_dir("index");
+
+        let file = |name: &str| -> Box<Path> { dir.join(name).into_boxed_path() };
+        let indexed = |name: &str, rows: &[u8]| {
+            let mut builder = Builder::default();
+            for n in rows {
+                builder.push(id(*n));
+            }
+            write(&file(name), &builder.take());
+            file(name)
+        };
+
+        // Rows of 1 and 2 overlap, 3 follows right after them, 5 is further away.
+        let a = indexed("a", &[1, 2, 1, 2, 3, 4, 4, 5]);
+        let b = indexed("b", &[6, 6]);
+        let c = file("c");
+
+        let (found, rows) = lookup(
+            vec![a.clone(), b.clone(), c.clone()],
+            &[id(5), id(2), id(1), id(3), id(7)],
+        );
+
+        // Files without an index are always read, indexed files only when they hold a trace.
+        assert_eq!(found, vec![a.clone(), c.clone()]);
+        assert_eq!(rows.get(&a), Some(&vec![0..5, 7..8]));
+        assert_eq!(rows.get(&b), None);
+        assert_eq!(rows.get(&c), None);
+
+        // A corrupt index is treated like a missing one.
+        std::fs::write(path(&b), b"STI1").unwrap();
+        let (found, rows) = lookup(vec![b.clone()], &[id(1)]);
+        assert_eq!(found, vec![b]);
+        assert!(rows.is_empty());
+
+        remove(&a);
+        assert!(load(&a).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,12 +1,27 @@
+pub mod alert;
 pub mod arrow;
+pub mod convert;
+pub mod export;
+pub mod exporter;
+pub mod index;
+pub mod load;
+pub mod metrics;
 pub mod misc;
+pub mod query;
+pub mod scan;
+pub mod sql;
+pub mod tail;
+pub mod tier;
 pub mod vortex;
 pub mod write;
 
-pub use write::{Format, Sink, Stats};
+#[cfg(test)]
+mod testing;
+
+pub use write::{Command, FileFormat, FileInfo, Format, Location, Sink, Stats};
 pub(crate) use write::{SpanBuilder, SpanWriter};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SpanData {
     pub trace_id: [u8; 16],
     pub span_id: [u8; 8],
@@ -18,7 +33,23 @@ pub struct SpanData {
     pub time_start: u64,
     pub time_end: u64,
     pub time_duration: u64,
+    pub trace_state: Option<String>,
+    pub flags: u32,
+    pub dropped_attributes_count: u32,
+    pub dropped_events_count: u32,
+    pub dropped_links_count: u32,
     pub span_attributes: Vec<opentelemetry_proto::tonic::common::v1::KeyValue>,
     // TODO: revisit type
     pub resource_attributes: std::sync::Arc<Vec<opentelemetry_proto::tonic::common::v1::KeyValue>>,
+    pub resource_schema_url: Option<String>,
+    /// Instrumentation scope, shared by every span of a single `ScopeSpans`.
+    pub scope: std::sync::Arc<ScopeData>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ScopeData {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub attributes: Vec<opentelemetry_proto::tonic::common::v1::KeyValue>,
+    pub schema_url: Option<String>,
 }
@@ -0,0 +1,307 @@
+//! Synthetic trace trees used by `spaniel load` and the benches.
+//!
+//! Generation is seeded, the same [Options] always produce the same spans, so that runs against
+//! different formats compare like with like.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use opentelemetry_proto::tonic::common::v1::{AnyValue, KeyValue, any_value};
+
+use crate::write::{Options as WriterOptions, start_writer};
+use crate::{Format, ScopeData, SpanData};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    /// Number of services, each with its own resource attributes.
+    pub services: usize,
+    /// Maximum number of children of a single span.
+    pub fanout: usize,
+    /// Maximum depth of a trace, root spans are at depth 0.
+    pub depth: usize,
+    /// Number of attributes set on every span.
+    pub attributes: usize,
+    /// Number of distinct values of a single attribute.
+    pub cardinality: usize,
+    /// Number of distinct span names of a single service.
+    pub span_names: usize,
+    pub seed: u64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            services: 8,
+            fanout: 4,
+            depth: 4,
+            attributes: 8,
+            cardinality: 100,
+            span_names: 24,
+            seed: 0x05ba_01e1,
+        }
+    }
+}
+
+/// SplitMix64, good enough for synthetic data and stable across platforms.
+#[derive(Clone, Debug)]
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `0..n`, `n` has to be positive.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn string_value(key: String, value: String) -> KeyValue {
+    KeyValue {
+        key,
+        value: Some(AnyValue {
+            value: Some(any_value::Value::StringValue(value)),
+        }),
+    }
+}
+
+#[derive(Debug)]
+pub struct Generator {
+    options: Options,
+    rng: Rng,
+    resources: Vec<Arc<Vec<KeyValue>>>,
+    scope: Arc<ScopeData>,
+}
+
+impl Generator {
+    pub fn new(options: Options) -> Self {
+        assert!(options.services > 0 && options.span_names > 0 && options.cardinality > 0);
+
+        let resources = (0..options.services)
+            .map(|idx| {
+                Arc::new(vec![
+                    string_value("service.name".into(), format!("service-{idx}")),
+                    string_value("service.instance.id".into(), format!("instance-{idx}")),
+                    string_value("host.name".into(), format!("host-{}", idx % 3)),
+                ])
+            })
+            .collect();
+
+        let scope = Arc::new(ScopeData {
+            name: Some("spaniel-load".into()),
+            version: Some(env!("CARGO_PKG_VERSION").into()),
+            ..Default::default()
+        });
+
+        Self {
+            rng: Rng(options.seed),
+            options,
+            resources,
+            scope,
+        }
+    }
+
+    /// Spans of a single trace whose root span starts at `time_start` nanoseconds.
+    pub fn trace(&mut self, time_start: u64) -> Vec<SpanData> {
+        let mut trace_id = [0u8; 16];
+        trace_id[..8].copy_from_slice(&self.rng.next().to_be_bytes());
+        trace_id[8..].copy_from_slice(&self.rng.next().to_be_bytes());
+
+        let service = self.rng.below(self.options.services);
+        let mut spans = Vec::new();
+
+        self.span(&mut spans, trace_id, None, service, 0, time_start);
+
+        spans
+    }
+
+    /// Appends a span with its subtree and returns its end time.
+    fn span(
+        &mut self,
+        spans: &mut Vec<SpanData>,
+        trace_id: [u8; 16],
+        parent_span_id: Option<[u8; 8]>,
+        service: usize,
+        depth: usize,
+        time_start: u64,
+    ) -> u64 {
+        let span_id = self.rng.next().to_be_bytes();
+        let idx = spans.len();
+
+        let name = match self.rng.below(3) {
+            0 => format!("GET /api/{}", self.rng.below(self.options.span_names)),
+            _ => format!(
+                "service-{service}.op-{}",
+                self.rng.below(self.options.span_names)
+            ),
+        };
+
+        let span_attributes = (0..self.options.attributes)
+            .map(|attr| {
+                let value = self.rng.below(self.options.cardinality);
+                string_value(format!("attr.{attr}"), format!("value-{value}"))
+            })
+            .collect();
+
+        let failed = self.rng.below(50) == 0;
+
+        spans.push(SpanData {
+            trace_id,
+            span_id,
+            parent_span_id,
+            name,
+            kind: if depth == 0 { 2 } else { 1 },
+            status_code: failed.then_some(2),
+            status_message: failed.then(|| "synthetic failure".to_owned()),
+            time_start,
+            time_end: 0,
+            time_duration: 0,
+            trace_state: None,
+            flags: 0,
+            dropped_attributes_count: 0,
+            dropped_events_count: 0,
+            dropped_links_count: 0,
+            span_attributes,
+            resource_attributes: self.resources[service].clone(),
+            resource_schema_url: None,
+            scope: self.scope.clone(),
+        });
+
+        // Own work of at least 50us, then children run one after another.
+        let mut time_end = time_start + 50_000 + self.rng.below(5_000_000) as u64;
+
+        if depth < self.options.depth {
+            for _ in 0..self.rng.below(self.options.fanout + 1) {
+                // Some calls cross service boundaries.
+                let child_service = match self.rng.below(4) {
+                    0 => self.rng.below(self.options.services),
+                    _ => service,
+                };
+
+                time_end = self.span(
+                    spans,
+                    trace_id,
+                    Some(span_id),
+                    child_service,
+                    depth + 1,
+                    time_end,
+                );
+            }
+        }
+
+        let span = &mut spans[idx];
+        span.time_end = time_end;
+        span.time_duration = time_end - time_start;
+
+        time_end
+    }
+}
+
+#[derive(Debug)]
+pub struct Ingest {
+    pub spans: usize,
+    /// Time from the first write to the last closed file.
+    pub elapsed: Duration,
+    /// Size of every closed file.
+    pub bytes: u64,
+}
+
+impl Ingest {
+    pub fn spans_per_sec(&self) -> f64 {
+        self.spans as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn bytes_per_span(&self) -> f64 {
+        self.bytes as f64 / self.spans as f64
+    }
+}
+
+/// Writes `spans` into `dir` with [start_writer] and waits until every file is closed.
+///
+/// Spans are passed in chunks of `builder_flush_threshold`, so every chunk is written right away.
+pub async fn ingest(
+    format: &Format,
+    dir: Box<Path>,
+    spans: Vec<SpanData>,
+    options: WriterOptions,
+) -> Ingest {
+    let len = spans.len();
+    let start = Instant::now();
+
+    let (sink, stats, task) = start_writer(format, dir, options);
+
+    let send = async move {
+        let mut spans = spans.into_iter().peekable();
+
+        while spans.peek().is_some() {
+            let chunk: Vec<_> = spans
+                .by_ref()
+                .take(options.builder_flush_threshold)
+                .collect();
+            sink.send(chunk).await;
+        }
+    };
+
+    tokio::join!(Box::into_pin(task), send);
+
+    let elapsed = start.elapsed();
+    let files: Vec<Box<Path>> = { stats.files.read().await.iter().cloned().collect() };
+    let bytes = files
+        .iter()
+        .filter_map(|file| std::fs::metadata(file).ok())
+        .map(|meta| meta.len())
+        .sum();
+
+    Ingest {
+        spans: len,
+        elapsed,
+        bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_same_trees_for_same_seed() {
+        let mut a = Generator::new(Options::default());
+        let mut b = Generator::new(Options::default());
+
+        for idx in 0..16 {
+            let (a, b) = (a.trace(idx * 1_000), b.trace(idx * 1_000));
+
+            assert_eq!(a.len(), b.len());
+            for (a, b) in a.iter().zip(&b) {
+                assert_eq!(a.span_id, b.span_id);
+                assert_eq!(a.name, b.name);
+                assert_eq!(a.time_end, b.time_end);
+            }
+        }
+    }
+
+    #[test]
+    fn children_fit_in_parents() {
+        let mut generator = Generator::new(Options::default());
+        let spans = generator.trace(0);
+
+        assert!(spans[0].parent_span_id.is_none());
+
+        for span in &spans[1..] {
+            let parent = spans
+                .iter()
+                .find(|p| Some(p.span_id) == span.parent_span_id)
+                .expect("parent.present");
+
+            assert_eq!(parent.trace_id, span.trace_id);
+            assert!(parent.time_start <= span.time_start);
+            assert!(span.time_end <= parent.time_end);
+        }
+    }
+}
@@ -0,0 +1,297 @@
+//! In-process metrics rendered in the Prometheus text exposition format.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// Bucket bounds in seconds, from 1ms to 30s.
+pub const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Bucket bounds in bytes, from 64KiB to 1GiB.
+pub const SIZE_BUCKETS: &[f64] = &[
+    65_536.0,
+    262_144.0,
+    1_048_576.0,
+    4_194_304.0,
+    16_777_216.0,
+    67_108_864.0,
+    268_435_456.0,
+    1_073_741_824.0,
+];
+
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn add(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// Non-cumulative counts, the last one is for values above every bound.
+    buckets: Vec<AtomicU64>,
+    /// Bits of the `f64` sum of observed values.
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0f64.to_bits()),
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        let idx = self.bounds.partition_point(|bound| *bound < value);
+        self.buckets[idx].fetch_add(1, Ordering::Relaxed);
+
+        let _ = self
+            .sum
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + value).to_bits())
+            });
+    }
+}
+
+/// Label names and values of a metric.
+type Labels = Vec<(String, String)>;
+
+/// Metrics of the same name told apart by labels.
+#[derive(Debug)]
+pub struct Family<M> {
+    make: fn() -> M,
+    items: Mutex<BTreeMap<Labels, Arc<M>>>,
+}
+
+impl<M> Family<M> {
+    pub fn new(make: fn() -> M) -> Self {
+        Self {
+            make,
+            items: Mutex::default(),
+        }
+    }
+
+    pub fn get(&self, labels: &[(&str, &str)]) -> Arc<M> {
+        let key = labels
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        self.items
+            .lock()
+            .expect("family.lock")
+            .entry(key)
+            .or_insert_with(|| Arc::new((self.make)()))
+            .clone()
+    }
+
+    fn snapshot(&self) -> Vec<(Labels, Arc<M>)> {
+        let items = self.items.lock().expect("family.lock");
+        items.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+}
+
+/// Metrics recorded by writers, shared by every [crate::Sink].
+#[derive(Debug)]
+pub struct WriterMetrics {
+    /// Time spent building and writing a buffered batch.
+    pub flush_seconds: Histogram,
+    /// Size of data files when they are closed.
+    pub file_bytes: Histogram,
+    pub written_bytes: Counter,
+    pub written_spans: Counter,
+}
+
+pub static WRITER: LazyLock<WriterMetrics> = LazyLock::new(|| WriterMetrics {
+    flush_seconds: Histogram::new(LATENCY_BUCKETS),
+    file_bytes: Histogram::new(SIZE_BUCKETS),
+    written_bytes: Counter::default(),
+    written_spans: Counter::default(),
+});
+
+impl WriterMetrics {
+    pub fn render(&self, text: &mut Text) {
+        text.histogram(
+            "spaniel_writer_flush_seconds",
+            "Latency of writing buffered spans.",
+            &[],
+            &self.flush_seconds,
+        );
+        text.histogram(
+            "spaniel_file_size_bytes",
+            "Size of closed data files.",
+            &[],
+            &self.file_bytes,
+        );
+        text.counter(
+            "spaniel_written_bytes_total",
+            "Bytes of closed data files.",
+            &[],
+            self.written_bytes.get(),
+        );
+        text.counter(
+            "spaniel_written_spans_total",
+            "Spans passed to writers.",
+            &[],
+            self.written_spans.get(),
+        );
+    }
+}
+
+/// Prometheus text exposition format.
+#[derive(Debug, Default)]
+pub struct Text(String);
+
+impl Text {
+    fn header(&mut self, name: &str, help: &str, kind: &str) {
+        writeln!(self.0, "# HELP {name} {help}").unwrap();
+        writeln!(self.0, "# TYPE {name} {kind}").unwrap();
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+        self.0.push_str(name);
+
+        if !labels.is_empty() {
+            self.0.push('{');
+            for (idx, (key, value)) in labels.iter().enumerate() {
+                if idx > 0 {
+                    self.0.push(',');
+                }
+                let value = value
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n");
+                write!(self.0, "{key}=\"{value}\"").unwrap();
+            }
+            self.0.push('}');
+        }
+
+        writeln!(self.0, " {value}").unwrap();
+    }
+
+    pub fn counter(&mut self, name: &str, help: &str, labels: &[(&str, &str)], value: u64) {
+        self.header(name, help, "counter");
+        self.sample(name, labels, value);
+    }
+
+    /// Gauge with one sample per value of the `label`.
+    pub fn gauge_by(&mut self, name: &str, help: &str, label: &str, samples: &[(&str, u64)]) {
+        self.header(name, help, "gauge");
+        for (value, sample) in samples {
+            self.sample(name, &[(label, value)], sample);
+        }
+    }
+
+    pub fn counter_family(&mut self, name: &str, help: &str, family: &Family<Counter>) {
+        self.header(name, help, "counter");
+        for (labels, counter) in family.snapshot() {
+            let labels: Vec<_> = labels
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect();
+            self.sample(name, &labels, counter.get());
+        }
+    }
+
+    pub fn histogram(
+        &mut self,
+        name: &str,
+        help: &str,
+        labels: &[(&str, &str)],
+        histogram: &Histogram,
+    ) {
+        self.header(name, help, "histogram");
+        self.histogram_samples(name, labels, histogram);
+    }
+
+    pub fn histogram_family(&mut self, name: &str, help: &str, family: &Family<Histogram>) {
+        self.header(name, help, "histogram");
+        for (labels, histogram) in family.snapshot() {
+            let labels: Vec<_> = labels
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect();
+            self.histogram_samples(name, &labels, &histogram);
+        }
+    }
+
+    fn histogram_samples(&mut self, name: &str, labels: &[(&str, &str)], histogram: &Histogram) {
+        let bucket = format!("{name}_bucket");
+        let mut cumulative = 0;
+
+        for (idx, count) in histogram.buckets.iter().enumerate() {
+            cumulative += count.load(Ordering::Relaxed);
+
+            let le = match histogram.bounds.get(idx) {
+                Some(bound) => bound.to_string(),
+                None => "+Inf".to_owned(),
+            };
+            let mut labels = labels.to_vec();
+            labels.push(("le", &le));
+
+            self.sample(&bucket, &labels, cumulative);
+        }
+
+        let sum = f64::from_bits(histogram.sum.load(Ordering::Relaxed));
+        self.sample(&format!("{name}_sum"), labels, sum);
+        self.sample(&format!("{name}_count"), labels, cumulative);
+    }
+
+    pub fn finish(self) -> String {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_histogram_family() {
+        let family = Family::new(|| Histogram::new(&[0.1, 1.0]));
+        family.get(&[("endpoint", "/v0/query")]).observe(0.05);
+        family.get(&[("endpoint", "/v0/query")]).observe(0.5);
+        family.get(&[("endpoint", "/v0/query")]).observe(5.0);
+
+        let mut text = Text::default();
+        text.histogram_family("latency_seconds", "Query latency.", &family);
+
+        assert_eq!(
+            text.finish(),
+            "# HELP latency_seconds Query latency.
+# TYPE latency_seconds histogram
+latency_seconds_bucket{endpoint=\"/v0/query\",le=\"0.1\"} 1
+latency_seconds_bucket{endpoint=\"/v0/query\",le=\"1\"} 2
+latency_seconds_bucket{endpoint=\"/v0/query\",le=\"+Inf\"} 3
+latency_seconds_sum{endpoint=\"/v0/query\"} 5.55
+latency_seconds_count{endpoint=\"/v0/query\"} 3
+"
+        );
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        let family = Family::new(Counter::default);
+        family.get(&[("tenant", "a\"b")]).add(2);
+
+        let mut text = Text::default();
+        text.counter_family("spans_total", "Spans.", &family);
+
+        assert!(
+            text.finish()
+                .ends_with("spans_total{tenant=\"a\\\"b\"} 2\n")
+        );
+    }
+}
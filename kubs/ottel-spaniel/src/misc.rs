@@ -17,7 +17,8 @@ pub fn get_next_file_id(dir: impl AsRef<Path>, prefix: &str) -> usize {
     let mut max: usize = 0;
     let mut count: usize = 0;
 
-    for name in read_dir(&dir) {
+    // Uploaded files may be gone locally, their ids must not be reused.
+    for name in read_dir(&dir).chain(read_remote_manifest(&dir)) {
         if !name.starts_with(prefix) {
             continue;
         }
@@ -35,6 +36,47 @@ pub fn get_next_file_id(dir: impl AsRef<Path>, prefix: &str) -> usize {
     if count == 0 { 0 } else { max + 1 }
 }
 
+/// Name of the file listing data files uploaded to the object store, see [crate::tier].
+pub const REMOTE_MANIFEST: &str = "remote-files";
+
+/// Names of uploaded files in upload order.
+pub fn read_remote_manifest(dir: impl AsRef<Path>) -> Vec<String> {
+    let Ok(content) = fs::read_to_string(dir.as_ref().join(REMOTE_MANIFEST)) else {
+        return Vec::new();
+    };
+
+    content.lines().map(str::to_owned).collect()
+}
+
+pub fn append_remote_manifest(dir: impl AsRef<Path>, name: &str) {
+    use std::io::Write;
+
+    let mut file = File::options()
+        .append(true)
+        .create(true)
+        .open(dir.as_ref().join(REMOTE_MANIFEST))
+        .expect("manifest.open");
+
+    writeln!(file, "{name}").expect("manifest.write");
+    file.sync_all().expect("manifest.sync");
+}
+
+/// Tenant ids end up in paths, so only short ids of `[A-Za-z0-9_-]` are accepted.
+pub fn is_valid_tenant(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Directory of a tenant under the data directory `root`.
+pub fn tenant_dir(root: impl AsRef<Path>, tenant: &str) -> Box<Path> {
+    assert!(is_valid_tenant(tenant));
+
+    root.as_ref().join("tenants").join(tenant).into_boxed_path()
+}
+
 pub fn read_dir(dir: impl AsRef<Path>) -> impl Iterator<Item = String> {
     let files = fs::read_dir(dir.as_ref()).expect("dir.read.ok");
     let mut files = files
@@ -47,7 +89,7 @@ pub fn read_dir(dir: impl AsRef<Path>) -> impl Iterator<Item = String> {
         })
         .collect::<Vec<_>>();
 
-    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+    files.sort_by_key(|(created, _)| *created);
     files.into_iter().map(|(_, f)| f)
 }
 
@@ -63,11 +105,14 @@ pub fn open_file(path: impl AsRef<Path>) -> BufWriter<File> {
     BufWriter::new(file)
 }
 
-pub fn load_existing_files(dir: impl AsRef<Path>, prefix: &str) -> Vec<Box<Path>> {
+/// Files with any of the `prefixes`, uploaded ones first and the rest in creation order.
+pub fn load_existing_files(dir: impl AsRef<Path>, prefixes: &[&str]) -> Vec<Box<Path>> {
     let mut result = Vec::with_capacity(8);
+    let remote = read_remote_manifest(dir.as_ref());
+    let local = read_dir(dir.as_ref()).filter(|name| !remote.contains(name));
 
-    for name in read_dir(dir.as_ref()) {
-        if !name.starts_with(prefix) {
+    for name in remote.iter().cloned().chain(local) {
+        if !prefixes.iter().any(|prefix| name.starts_with(prefix)) {
             continue;
         }
 
@@ -0,0 +1,113 @@
+//! Small subset of TraceQL used to filter spans stored in both formats.
+//!
+//! ```text
+//! { resource.service.name = "http-server" && duration > 200ms && span.http.response.status_code >= 500 }
+//! ```
+//!
+//! Supported fields are the `name`, `duration`, `status` and `kind` intrinsics, the
+//! `instrumentation.name` and `instrumentation.version` of the instrumentation scope, plus
+//! attributes scoped with `span.`, `resource.`, `instrumentation.` or `.` (span or resource).
+//! Conditions can be combined with `&&`, `||` and parentheses.
+
+mod parse;
+mod plan;
+mod source;
+
+pub use parse::parse;
+pub use plan::{matches_span, to_arrow, to_vortex};
+pub use source::{FileError, Source};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    /// `None` for the empty query `{}`, which matches every span.
+    pub expr: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Cmp {
+        field: Field,
+        op: Op,
+        value: Literal,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    Name,
+    Duration,
+    Status,
+    Kind,
+    ScopeName,
+    ScopeVersion,
+    Attribute { scope: Scope, key: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    Span,
+    Resource,
+    Instrumentation,
+    /// Either span or resource.
+    Any,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Neq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    /// Duration in nanoseconds.
+    Duration(u64),
+    /// `unset`, `ok` or `error`.
+    Status(i32),
+    /// `unspecified`, `internal`, `server`, `client`, `producer` or `consumer`.
+    Kind(i32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    /// Byte offset in the query source, set for syntax errors.
+    pub pos: Option<usize>,
+    pub message: String,
+}
+
+impl Error {
+    fn new(pos: usize, message: impl Into<String>) -> Self {
+        Self {
+            pos: Some(pos),
+            message: message.into(),
+        }
+    }
+
+    fn unsupported(message: impl Into<String>) -> Self {
+        Self {
+            pos: None,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.pos {
+            Some(pos) => write!(f, "{} at {}", self.message, pos),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,495 @@
+use super::{Error, Expr, Field, Literal, Op, Query, Scope};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LBrace,
+    RBrace,
+    LParen,
+    RParen,
+    And,
+    Or,
+    Op(Op),
+    Ident(String),
+    Str(String),
+    Number(String),
+}
+
+struct Lexer<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(src: &'a str) -> Self {
+        Self { src, pos: 0 }
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek_char()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let start = self.pos;
+
+        while let Some(c) = self.peek_char() {
+            if !f(c) {
+                break;
+            }
+            self.bump();
+        }
+
+        &self.src[start..self.pos]
+    }
+
+    fn expect(&mut self, expected: char, at: usize) -> Result<(), Error> {
+        match self.bump() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(Error::new(at, format!("expected '{expected}'"))),
+        }
+    }
+
+    fn tokenize(mut self) -> Result<Vec<(usize, Token)>, Error> {
+        let mut tokens = Vec::new();
+
+        loop {
+            self.take_while(char::is_whitespace);
+
+            let at = self.pos;
+            let Some(c) = self.bump() else {
+                break;
+            };
+
+            let token = match c {
+                '{' => Token::LBrace,
+                '}' => Token::RBrace,
+                '(' => Token::LParen,
+                ')' => Token::RParen,
+                '&' => {
+                    self.expect('&', at)?;
+                    Token::And
+                }
+                '|' => {
+                    self.expect('|', at)?;
+                    Token::Or
+                }
+                '=' => Token::Op(Op::Eq),
+                '!' => {
+                    self.expect('=', at)?;
+                    Token::Op(Op::Neq)
+                }
+                '>' | '<' => {
+                    let eq = self.peek_char() == Some('=');
+                    if eq {
+                        self.bump();
+                    }
+
+                    Token::Op(match (c, eq) {
+                        ('>', false) => Op::Gt,
+                        ('>', true) => Op::Gte,
+                        ('<', false) => Op::Lt,
+                        _ => Op::Lte,
+                    })
+                }
+                '"' => {
+                    let mut value = String::new();
+
+                    loop {
+                        match self.bump() {
+                            Some('"') => break,
+                            Some('\\') => match self.bump() {
+                                Some(escaped) => value.push(escaped),
+                                None => return Err(Error::new(at, "unterminated string")),
+                            },
+                            Some(c) => value.push(c),
+                            None => return Err(Error::new(at, "unterminated string")),
+                        }
+                    }
+
+                    Token::Str(value)
+                }
+                c if c.is_ascii_digit() || c == '-' => {
+                    let rest =
+                        self.take_while(|c| c.is_ascii_alphanumeric() || c == '.' || c == 'µ');
+                    Token::Number(format!("{c}{rest}"))
+                }
+                c if c.is_alphabetic() || c == '.' || c == '_' => {
+                    let rest =
+                        self.take_while(|c| c.is_alphanumeric() || matches!(c, '.' | '_' | '-'));
+                    Token::Ident(format!("{c}{rest}"))
+                }
+                c => return Err(Error::new(at, format!("unexpected character '{c}'"))),
+            };
+
+            tokens.push((at, token));
+        }
+
+        Ok(tokens)
+    }
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(_, t)| t)
+    }
+
+    fn pos(&self) -> usize {
+        self.tokens
+            .get(self.index)
+            .map(|(at, _)| *at)
+            .unwrap_or(self.end)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.index).map(|(_, t)| t.clone());
+        self.index += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), Error> {
+        let at = self.pos();
+
+        match self.next() {
+            Some(t) if t == expected => Ok(()),
+            _ => Err(Error::new(at, format!("expected {expected:?}"))),
+        }
+    }
+
+    fn query(&mut self) -> Result<Query, Error> {
+        self.expect(Token::LBrace)?;
+
+        let expr = if self.peek() == Some(&Token::RBrace) {
+            None
+        } else {
+            Some(self.or()?)
+        };
+
+        self.expect(Token::RBrace)?;
+
+        if self.peek().is_some() {
+            return Err(Error::new(self.pos(), "unexpected input after '}'"));
+        }
+
+        Ok(Query { expr })
+    }
+
+    fn or(&mut self) -> Result<Expr, Error> {
+        let mut lhs = self.and()?;
+
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            lhs = Expr::Or(Box::new(lhs), Box::new(self.and()?));
+        }
+
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Expr, Error> {
+        let mut lhs = self.unary()?;
+
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            lhs = Expr::And(Box::new(lhs), Box::new(self.unary()?));
+        }
+
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        if self.peek() == Some(&Token::LParen) {
+            self.next();
+            let expr = self.or()?;
+            self.expect(Token::RParen)?;
+            return Ok(expr);
+        }
+
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, Error> {
+        let at = self.pos();
+        let field = match self.next() {
+            Some(Token::Ident(ident)) => parse_field(&ident)
+                .ok_or_else(|| Error::new(at, format!("unknown field '{ident}'")))?,
+            _ => return Err(Error::new(at, "expected field")),
+        };
+
+        let at = self.pos();
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            _ => return Err(Error::new(at, "expected comparison operator")),
+        };
+
+        let at = self.pos();
+        let value = match self.next() {
+            Some(Token::Str(s)) => Literal::Str(s),
+            Some(Token::Number(n)) => {
+                parse_number(&n).ok_or_else(|| Error::new(at, format!("invalid number '{n}'")))?
+            }
+            Some(Token::Ident(ident)) => parse_keyword(&ident)
+                .ok_or_else(|| Error::new(at, format!("unknown value '{ident}'")))?,
+            _ => return Err(Error::new(at, "expected value")),
+        };
+
+        Ok(Expr::Cmp { field, op, value })
+    }
+}
+
+fn parse_field(ident: &str) -> Option<Field> {
+    let attribute = |scope, key: &str| {
+        if key.is_empty() {
+            return None;
+        }
+
+        Some(Field::Attribute {
+            scope,
+            key: key.to_owned(),
+        })
+    };
+
+    match ident {
+        "name" => Some(Field::Name),
+        "duration" => Some(Field::Duration),
+        "status" => Some(Field::Status),
+        "kind" => Some(Field::Kind),
+        "instrumentation.name" => Some(Field::ScopeName),
+        "instrumentation.version" => Some(Field::ScopeVersion),
+        _ => {
+            if let Some(key) = ident.strip_prefix("span.") {
+                return attribute(Scope::Span, key);
+            }
+
+            if let Some(key) = ident.strip_prefix("resource.") {
+                return attribute(Scope::Resource, key);
+            }
+
+            if let Some(key) = ident.strip_prefix("instrumentation.") {
+                return attribute(Scope::Instrumentation, key);
+            }
+
+            attribute(Scope::Any, ident.strip_prefix('.')?)
+        }
+    }
+}
+
+fn parse_keyword(ident: &str) -> Option<Literal> {
+    Some(match ident {
+        "true" => Literal::Bool(true),
+        "false" => Literal::Bool(false),
+        "unset" => Literal::Status(0),
+        "ok" => Literal::Status(1),
+        "error" => Literal::Status(2),
+        "unspecified" => Literal::Kind(0),
+        "internal" => Literal::Kind(1),
+        "server" => Literal::Kind(2),
+        "client" => Literal::Kind(3),
+        "producer" => Literal::Kind(4),
+        "consumer" => Literal::Kind(5),
+        _ => return None,
+    })
+}
+
+fn parse_number(value: &str) -> Option<Literal> {
+    let split = value
+        .char_indices()
+        .skip(1)
+        .find(|(_, c)| !c.is_ascii_digit() && *c != '.')
+        .map(|(idx, _)| idx)
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let multiplier: u64 = match unit {
+        "" if number.contains('.') => return number.parse().ok().map(Literal::Float),
+        "" => return number.parse().ok().map(Literal::Int),
+        "ns" => 1,
+        "us" | "µs" => 1_000,
+        "ms" => 1_000_000,
+        "s" => 1_000_000_000,
+        "m" => 60_000_000_000,
+        "h" => 3_600_000_000_000,
+        _ => return None,
+    };
+
+    let number: f64 = number.parse().ok()?;
+
+    if number < 0.0 {
+        return None;
+    }
+
+    Some(Literal::Duration((number * multiplier as f64) as u64))
+}
+
+pub fn parse(src: &str) -> Result<Query, Error> {
+    let tokens = Lexer::new(src).tokenize()?;
+    let mut parser = Parser {
+        tokens,
+        index: 0,
+        end: src.len(),
+    };
+
+    parser.query()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmp(field: Field, op: Op, value: Literal) -> Expr {
+        Expr::Cmp { field, op, value }
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(parse("{}").unwrap(), Query { expr: None });
+        assert_eq!(parse(" { } ").unwrap(), Query { expr: None });
+    }
+
+    #[test]
+    fn intrinsics() {
+        let query = parse(r#"{ name = "GET /" && duration > 200ms && status = error }"#).unwrap();
+
+        assert_eq!(
+            query.expr.unwrap(),
+            Expr::And(
+                Box::new(Expr::And(
+                    Box::new(cmp(Field::Name, Op::Eq, Literal::Str("GET /".into()))),
+                    Box::new(cmp(Field::Duration, Op::Gt, Literal::Duration(200_000_000))),
+                )),
+                Box::new(cmp(Field::Status, Op::Eq, Literal::Status(2))),
+            )
+        );
+    }
+
+    #[test]
+    fn attributes() {
+        let query = parse(
+            r#"{ resource.service.name = "http-server" && span.http.response.status_code >= 500 }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            query.expr.unwrap(),
+            Expr::And(
+                Box::new(cmp(
+                    Field::Attribute {
+                        scope: Scope::Resource,
+                        key: "service.name".into(),
+                    },
+                    Op::Eq,
+                    Literal::Str("http-server".into()),
+                )),
+                Box::new(cmp(
+                    Field::Attribute {
+                        scope: Scope::Span,
+                        key: "http.response.status_code".into(),
+                    },
+                    Op::Gte,
+                    Literal::Int(500),
+                )),
+            )
+        );
+
+        let query = parse("{ .db.system != true }").unwrap();
+
+        assert_eq!(
+            query.expr.unwrap(),
+            cmp(
+                Field::Attribute {
+                    scope: Scope::Any,
+                    key: "db.system".into(),
+                },
+                Op::Neq,
+                Literal::Bool(true),
+            )
+        );
+    }
+
+    #[test]
+    fn precedence() {
+        let query = parse("{ kind = server || kind = client && duration <= 1.5s }").unwrap();
+
+        assert_eq!(
+            query.expr.unwrap(),
+            Expr::Or(
+                Box::new(cmp(Field::Kind, Op::Eq, Literal::Kind(2))),
+                Box::new(Expr::And(
+                    Box::new(cmp(Field::Kind, Op::Eq, Literal::Kind(3))),
+                    Box::new(cmp(
+                        Field::Duration,
+                        Op::Lte,
+                        Literal::Duration(1_500_000_000)
+                    )),
+                )),
+            )
+        );
+
+        let query = parse("{ (kind = server || kind = client) && .x < -1.5 }").unwrap();
+
+        assert_eq!(
+            query.expr.unwrap(),
+            Expr::And(
+                Box::new(Expr::Or(
+                    Box::new(cmp(Field::Kind, Op::Eq, Literal::Kind(2))),
+                    Box::new(cmp(Field::Kind, Op::Eq, Literal::Kind(3))),
+                )),
+                Box::new(cmp(
+                    Field::Attribute {
+                        scope: Scope::Any,
+                        key: "x".into(),
+                    },
+                    Op::Lt,
+                    Literal::Float(-1.5),
+                )),
+            )
+        );
+    }
+
+    #[test]
+    fn instrumentation() {
+        let query = parse(
+            r#"{ instrumentation.name = "tracing-opentelemetry" && instrumentation.otel.lib = 1 }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            query.expr.unwrap(),
+            Expr::And(
+                Box::new(cmp(
+                    Field::ScopeName,
+                    Op::Eq,
+                    Literal::Str("tracing-opentelemetry".into()),
+                )),
+                Box::new(cmp(
+                    Field::Attribute {
+                        scope: Scope::Instrumentation,
+                        key: "otel.lib".into(),
+                    },
+                    Op::Eq,
+                    Literal::Int(1),
+                )),
+            )
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse("name = \"x\"").unwrap_err().pos, Some(0));
+        assert_eq!(parse("{ name = }").unwrap_err().pos, Some(9));
+        assert_eq!(parse("{ foo = 1 }").unwrap_err().pos, Some(2));
+        assert_eq!(parse("{ duration > 5parsecs }").unwrap_err().pos, Some(13));
+        assert_eq!(parse("{ name = \"x }").unwrap_err().pos, Some(9));
+        assert_eq!(parse("{ name = \"x\" } {").unwrap_err().pos, Some(15));
+        assert!(parse("{ name = \"x\" & kind = server }").is_err());
+    }
+}
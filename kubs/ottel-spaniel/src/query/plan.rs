@@ -0,0 +1,507 @@
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use opentelemetry_proto::tonic::common::v1::KeyValue;
+use opentelemetry_proto::tonic::common::v1::any_value::Value;
+use parquet::schema::types::SchemaDescriptor;
+
+use super::{Error, Expr, Field, Literal, Op, Query, Scope};
+use crate::SpanData;
+use crate::arrow::{AttrFilter, Boolean, CustomFilter, Filter, Null, columns};
+
+const STATUS_UNSET: i32 = 0;
+
+/// Compiles the query into filters for [crate::arrow::Read]; top level `&&` conditions become
+/// separate row filters.
+pub fn to_arrow(
+    query: &Query,
+    schema: &SchemaDescriptor,
+) -> Result<Vec<Box<dyn CustomFilter>>, Error> {
+    fn split(
+        expr: &Expr,
+        schema: &SchemaDescriptor,
+        out: &mut Vec<Box<dyn CustomFilter>>,
+    ) -> Result<(), Error> {
+        if let Expr::And(lhs, rhs) = expr {
+            split(lhs, schema, out)?;
+            split(rhs, schema, out)?;
+        } else {
+            out.push(arrow_expr(expr, schema)?);
+        }
+
+        Ok(())
+    }
+
+    let mut filters = Vec::new();
+
+    if let Some(expr) = query.expr.as_ref() {
+        split(expr, schema, &mut filters)?;
+    }
+
+    Ok(filters)
+}
+
+fn arrow_expr(expr: &Expr, schema: &SchemaDescriptor) -> Result<Box<dyn CustomFilter>, Error> {
+    match expr {
+        Expr::And(lhs, rhs) => Ok(Box::new(Boolean::and(vec![
+            Arc::from(arrow_expr(lhs, schema)?),
+            Arc::from(arrow_expr(rhs, schema)?),
+        ]))),
+        Expr::Or(lhs, rhs) => Ok(Box::new(Boolean::or(vec![
+            Arc::from(arrow_expr(lhs, schema)?),
+            Arc::from(arrow_expr(rhs, schema)?),
+        ]))),
+        Expr::Cmp { field, op, value } => arrow_cmp(field, *op, value, schema),
+    }
+}
+
+fn with_op(filter: Filter, op: Op) -> Filter {
+    match op {
+        Op::Eq => filter,
+        Op::Neq => filter.neq(),
+        Op::Gt => filter.gt(),
+        Op::Gte => filter.gte(),
+        Op::Lt => filter.lt(),
+        Op::Lte => filter.lte(),
+    }
+}
+
+fn arrow_cmp(
+    field: &Field,
+    op: Op,
+    value: &Literal,
+    schema: &SchemaDescriptor,
+) -> Result<Box<dyn CustomFilter>, Error> {
+    use columns::*;
+
+    match field {
+        Field::Name => {
+            let Literal::Str(name) = value else {
+                return Err(mismatch(field, value));
+            };
+
+            Ok(Box::new(with_op(
+                Filter::new_str(schema, SPAN_NAME.name(), name),
+                op,
+            )))
+        }
+        Field::Duration => {
+            let duration = duration(field, value)?;
+
+            Ok(Box::new(with_op(
+                Filter::new_u64(schema, TIME_DURATION.name(), duration),
+                op,
+            )))
+        }
+        Field::Status => {
+            let code = code(field, value)?;
+            let filter = with_op(Filter::new_i32(schema, STATUS_CODE.name(), code), op);
+
+            // Spans sent without a status are stored with a null code, which compares as unset.
+            if !matches(op, STATUS_UNSET.cmp(&code)) {
+                return Ok(Box::new(filter));
+            }
+
+            Ok(Box::new(Boolean::or(vec![
+                Arc::new(Null::is_null(schema, STATUS_CODE.name())),
+                Arc::new(filter),
+            ])))
+        }
+        Field::Kind => {
+            let kind = code(field, value)?;
+
+            Ok(Box::new(with_op(
+                Filter::new_i32(schema, SPAN_KIND.name(), kind),
+                op,
+            )))
+        }
+        Field::ScopeName | Field::ScopeVersion => {
+            let Literal::Str(value) = value else {
+                return Err(mismatch(field, value));
+            };
+            let column = match field {
+                Field::ScopeName => SCOPE_NAME.name(),
+                _ => SCOPE_VERSION.name(),
+            };
+
+            Ok(Box::new(with_op(
+                Filter::new_str(schema, column, value),
+                op,
+            )))
+        }
+        Field::Attribute { scope, key } => {
+            let predicate = attribute_predicate(op, value.clone());
+
+            let filter: Box<dyn CustomFilter> = match scope {
+                Scope::Span => Box::new(AttrFilter::span(schema, key, predicate)),
+                Scope::Resource => Box::new(AttrFilter::resource(schema, key, predicate)),
+                Scope::Instrumentation => Box::new(AttrFilter::scope(schema, key, predicate)),
+                Scope::Any => Box::new(Boolean::or(vec![
+                    Arc::new(AttrFilter::span(schema, key, predicate.clone())),
+                    Arc::new(AttrFilter::resource(schema, key, predicate)),
+                ])),
+            };
+
+            Ok(filter)
+        }
+    }
+}
+
+/// Compiles the query into a filter expression for [crate::vortex::read::Read].
+///
+/// Vortex files do not store attributes, so only intrinsics are supported. `has` tells whether
+/// the file has a field, comparisons with fields it lacks match no span.
+pub fn to_vortex(
+    query: &Query,
+    has: &dyn Fn(&str) -> bool,
+) -> Result<Option<vortex::expr::Expression>, Error> {
+    query
+        .expr
+        .as_ref()
+        .map(|expr| vortex_expr(expr, has))
+        .transpose()
+}
+
+fn vortex_expr(expr: &Expr, has: &dyn Fn(&str) -> bool) -> Result<vortex::expr::Expression, Error> {
+    use vortex::expr::*;
+
+    match expr {
+        Expr::And(lhs, rhs) => Ok(and(vortex_expr(lhs, has)?, vortex_expr(rhs, has)?)),
+        Expr::Or(lhs, rhs) => Ok(or(vortex_expr(lhs, has)?, vortex_expr(rhs, has)?)),
+        Expr::Cmp { field, op, value } => {
+            let cmp: fn(Expression, Expression) -> Expression = match op {
+                Op::Eq => eq,
+                Op::Neq => not_eq,
+                Op::Gt => gt,
+                Op::Gte => gt_eq,
+                Op::Lt => lt,
+                Op::Lte => lt_eq,
+            };
+
+            match field {
+                Field::Name => {
+                    let Literal::Str(name) = value else {
+                        return Err(mismatch(field, value));
+                    };
+
+                    Ok(cmp(get_item("name", root()), lit(name.clone())))
+                }
+                Field::Duration => Ok(cmp(
+                    get_item("time_duration", root()),
+                    lit(duration(field, value)?),
+                )),
+                Field::Status => {
+                    let code = code(field, value)?;
+                    let filter = cmp(get_item("status_code", root()), lit(code));
+
+                    if !matches(*op, STATUS_UNSET.cmp(&code)) {
+                        return Ok(filter);
+                    }
+
+                    Ok(or(is_null(get_item("status_code", root())), filter))
+                }
+                Field::Kind => Ok(cmp(get_item("kind", root()), lit(code(field, value)?))),
+                Field::ScopeName | Field::ScopeVersion => {
+                    let Literal::Str(value) = value else {
+                        return Err(mismatch(field, value));
+                    };
+                    let column = match field {
+                        Field::ScopeName => "scope_name",
+                        _ => "scope_version",
+                    };
+
+                    // Files written before scopes were stored.
+                    if !has(column) {
+                        return Ok(lit(false));
+                    }
+
+                    Ok(cmp(get_item(column, root()), lit(value.clone())))
+                }
+                Field::Attribute { .. } => Err(Error::unsupported(
+                    "attribute conditions are not supported for Vortex files",
+                )),
+            }
+        }
+    }
+}
+
+/// Evaluates the query on a single span in memory, with the semantics of [to_arrow].
+pub fn matches_span(query: &Query, span: &SpanData) -> Result<bool, Error> {
+    query
+        .expr
+        .as_ref()
+        .map_or(Ok(true), |expr| span_expr(expr, span))
+}
+
+fn span_expr(expr: &Expr, span: &SpanData) -> Result<bool, Error> {
+    match expr {
+        Expr::And(lhs, rhs) => Ok(span_expr(lhs, span)? && span_expr(rhs, span)?),
+        Expr::Or(lhs, rhs) => Ok(span_expr(lhs, span)? || span_expr(rhs, span)?),
+        Expr::Cmp { field, op, value } => span_cmp(field, *op, value, span),
+    }
+}
+
+fn span_cmp(field: &Field, op: Op, value: &Literal, span: &SpanData) -> Result<bool, Error> {
+    match field {
+        Field::Name => {
+            let Literal::Str(name) = value else {
+                return Err(mismatch(field, value));
+            };
+
+            Ok(matches(op, span.name.as_str().cmp(name)))
+        }
+        Field::Duration => Ok(matches(
+            op,
+            span.time_duration.cmp(&duration(field, value)?),
+        )),
+        Field::Status => {
+            let code = code(field, value)?;
+            Ok(matches(
+                op,
+                span.status_code.unwrap_or(STATUS_UNSET).cmp(&code),
+            ))
+        }
+        Field::Kind => Ok(matches(op, span.kind.cmp(&code(field, value)?))),
+        Field::ScopeName | Field::ScopeVersion => {
+            let Literal::Str(value) = value else {
+                return Err(mismatch(field, value));
+            };
+            let column = match field {
+                Field::ScopeName => span.scope.name.as_deref(),
+                _ => span.scope.version.as_deref(),
+            };
+
+            // Missing values are stored as nulls, which match no comparison.
+            Ok(column.is_some_and(|column| matches(op, column.cmp(value))))
+        }
+        Field::Attribute { scope, key } => {
+            let predicate = attribute_predicate(op, value.clone());
+            let any = |attributes: &[KeyValue]| {
+                attributes.iter().any(|kv| {
+                    kv.key == *key
+                        && kv
+                            .value
+                            .as_ref()
+                            .and_then(|v| v.value.as_ref())
+                            .is_some_and(&*predicate)
+                })
+            };
+
+            Ok(match scope {
+                Scope::Span => any(&span.span_attributes),
+                Scope::Resource => any(&span.resource_attributes),
+                Scope::Instrumentation => any(&span.scope.attributes),
+                Scope::Any => any(&span.span_attributes) || any(&span.resource_attributes),
+            })
+        }
+    }
+}
+
+fn mismatch(field: &Field, value: &Literal) -> Error {
+    Error::unsupported(format!("cannot compare {field:?} with {value:?}"))
+}
+
+fn duration(field: &Field, value: &Literal) -> Result<u64, Error> {
+    match value {
+        Literal::Duration(ns) => Ok(*ns),
+        Literal::Int(ns) if *ns >= 0 => Ok(*ns as u64),
+        _ => Err(mismatch(field, value)),
+    }
+}
+
+/// Accepts either the matching keyword literal or a raw integer code.
+fn code(field: &Field, value: &Literal) -> Result<i32, Error> {
+    match (field, value) {
+        (_, Literal::Int(code)) => i32::try_from(*code).map_err(|_| mismatch(field, value)),
+        (Field::Status, Literal::Status(code)) | (Field::Kind, Literal::Kind(code)) => Ok(*code),
+        _ => Err(mismatch(field, value)),
+    }
+}
+
+fn attribute_predicate(op: Op, literal: Literal) -> Arc<dyn Fn(&Value) -> bool + Send + Sync> {
+    Arc::new(move |value: &Value| {
+        let ordering = match (value, &literal) {
+            (Value::StringValue(a), Literal::Str(b)) => Some(a.as_str().cmp(b.as_str())),
+            (Value::BoolValue(a), Literal::Bool(b)) => Some(a.cmp(b)),
+            (Value::IntValue(a), Literal::Int(b)) => Some(a.cmp(b)),
+            (Value::IntValue(a), Literal::Float(b)) => (*a as f64).partial_cmp(b),
+            (Value::DoubleValue(a), Literal::Float(b)) => a.partial_cmp(b),
+            (Value::DoubleValue(a), Literal::Int(b)) => a.partial_cmp(&(*b as f64)),
+            _ => None,
+        };
+
+        ordering.is_some_and(|ordering| matches(op, ordering))
+    })
+}
+
+fn matches(op: Op, ordering: Ordering) -> bool {
+    match op {
+        Op::Eq => ordering.is_eq(),
+        Op::Neq => ordering.is_ne(),
+        Op::Gt => ordering.is_gt(),
+        Op::Gte => ordering.is_ge(),
+        Op::Lt => ordering.is_lt(),
+        Op::Lte => ordering.is_le(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Format;
+    use crate::load::{Generator, Options as LoadOptions, ingest};
+    use crate::query::{Source, parse};
+    use crate::testing;
+
+    const QUERIES: [&str; 6] = [
+        "{ status = error }",
+        "{ status != error }",
+        "{ status = unset }",
+        "{ status != ok && kind = server }",
+        "{ duration > 2ms || name = \"GET /api/1\" }",
+        "{ instrumentation.name = \"spaniel-load\" }",
+    ];
+
+    /// Spans with every status, unset ones alternate between a null and an explicit code.
+    fn spans() -> Vec<SpanData> {
+        let mut generator = Generator::new(LoadOptions::default());
+        let mut spans: Vec<_> = (0..20)
+            .flat_map(|idx| generator.trace(idx * 10_000_000))
+            .collect();
+
+        for (idx, span) in spans.iter_mut().enumerate() {
+            span.status_code = match idx % 4 {
+                0 => Some(1),
+                1 => Some(0),
+                _ => span.status_code,
+            };
+        }
+
+        spans
+    }
+
+    /// Same conditions as the queries above, evaluated in memory.
+    fn expected(query: &str, spans: &[SpanData]) -> usize {
+        let status = |span: &SpanData| span.status_code.unwrap_or(STATUS_UNSET);
+
+        spans
+            .iter()
+            .filter(|span| match query {
+                "{ status = error }" => status(span) == 2,
+                "{ status != error }" => status(span) != 2,
+                "{ status = unset }" => status(span) == STATUS_UNSET,
+                "{ status != ok && kind = server }" => status(span) != 1 && span.kind == 2,
+                "{ duration > 2ms || name = \"GET /api/1\" }" => {
+                    span.time_duration > 2_000_000 || span.name == "GET /api/1"
+                }
+                _ => span.scope.name.as_deref() == Some("spaniel-load"),
+            })
+            .count()
+    }
+
+    async fn count(format: &Format, files: Vec<Box<std::path::Path>>, query: &str) -> usize {
+        let parsed = parse(query).expect("query.parse");
+        let mut source = Source::open(format, files, &parsed, (0, u64::MAX / 1_000_000), None)
+            .expect("source.open");
+        let mut count = 0;
+
+        while let Some(spans) = source.next_batch().await {
+            count += spans.len();
+        }
+
+        count
+    }
+
+    fn store(format: &Format, name: &str, spans: Vec<SpanData>) -> Vec<Box<std::path::Path>> {
+        let dir = testing::temp_dir(&format!("plan-{name}"));
+
+        testing::runtime().block_on(ingest(
+            format,
+            dir.clone().into_boxed_path(),
+            spans,
+            testing::writer_options(),
+        ));
+
+        crate::misc::load_existing_files(&dir, &[format.file_prefix()])
+    }
+
+    #[test]
+    fn both_formats_match_in_memory_evaluation() {
+        let rt = testing::runtime();
+
+        for (name, format) in [("arrow", Format::Arrow), ("vortex", Format::vortex())] {
+            let files = store(&format, name, spans());
+
+            for query in QUERIES {
+                let found = rt.block_on(count(&format, files.clone(), query));
+                assert_eq!(found, expected(query, &spans()), "{name}: {query}");
+            }
+
+            let dir = files[0].parent().unwrap().to_owned();
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn spans_match_like_stored_spans() {
+        let spans = spans();
+
+        for query in QUERIES {
+            let parsed = parse(query).expect("query.parse");
+            let found = spans
+                .iter()
+                .filter(|span| matches_span(&parsed, span).expect("query.supported"))
+                .count();
+
+            assert_eq!(found, expected(query, &spans), "{query}");
+        }
+
+        let parsed = parse("{ span.missing = 1 || .service.name = \"service-1\" }").unwrap();
+        assert!(
+            spans
+                .iter()
+                .any(|span| matches_span(&parsed, span).unwrap())
+        );
+        assert!(matches_span(&parse("{ name = 1 }").unwrap(), &spans[0]).is_err());
+    }
+
+    #[test]
+    fn resource_conditions_match_every_span() {
+        let spans = spans();
+        let service = |span: &SpanData| {
+            span.resource_attributes.iter().find_map(|kv| {
+                match kv.value.as_ref()?.value.as_ref()? {
+                    Value::StringValue(name) if kv.key == "service.name" => Some(name.clone()),
+                    _ => None,
+                }
+            })
+        };
+        let expected = spans
+            .iter()
+            .filter(|span| service(span).as_deref() == Some("service-1"))
+            .count();
+        assert!(expected > 0);
+
+        let files = store(&Format::Arrow, "resource", spans);
+        let rt = testing::runtime();
+        let query = "{ resource.service.name = \"service-1\" }";
+
+        assert_eq!(
+            rt.block_on(count(&Format::Arrow, files.clone(), query)),
+            expected
+        );
+        assert!(
+            Source::open(
+                &Format::vortex(),
+                vec![],
+                &parse(query).unwrap(),
+                (0, 1),
+                None
+            )
+            .is_err()
+        );
+
+        let dir = files[0].parent().unwrap().to_owned();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
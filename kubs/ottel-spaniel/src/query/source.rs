@@ -0,0 +1,142 @@
+use std::path::Path;
+
+use arrow::array::RecordBatch;
+
+use super::{Error, Query, to_arrow, to_vortex};
+use crate::arrow::stream;
+use crate::{Format, SpanData};
+
+/// Reader of files of a single format, matching a query within a time window.
+pub enum Source {
+    Arrow(crate::arrow::Read),
+    Vortex(crate::vortex::read::Read),
+}
+
+impl Source {
+    pub fn open(
+        format: &Format,
+        files: Vec<Box<Path>>,
+        parsed: &Query,
+        (start_time_ms, end_time_ms): (u64, u64),
+        limit: Option<usize>,
+    ) -> Result<Self, Error> {
+        match format {
+            Format::Arrow => {
+                use crate::arrow::{
+                    CustomFilter, Filter, Read,
+                    columns::{TIME_END, TIME_START},
+                };
+
+                let mut read = Read::try_new(
+                    None::<Vec<&str>>,
+                    |schema| {
+                        let mut base: Vec<Box<dyn CustomFilter>> = vec![
+                            Box::new(
+                                Filter::new_u64(
+                                    schema,
+                                    TIME_START.name(),
+                                    start_time_ms * 1_000_000,
+                                )
+                                .gte(),
+                            ),
+                            Box::new(
+                                Filter::new_u64(schema, TIME_END.name(), end_time_ms * 1_000_000)
+                                    .lte(),
+                            ),
+                        ];
+
+                        base.extend(to_arrow(parsed, schema)?);
+
+                        Ok(base)
+                    },
+                    files,
+                )?;
+
+                if let Some(limit) = limit {
+                    read = read.with_limit(limit);
+                }
+
+                Ok(Self::Arrow(read))
+            }
+            f @ Format::Vortex { .. } => {
+                use crate::vortex::read::Read;
+                use vortex::expr::*;
+
+                let base = and(
+                    gt_eq(
+                        get_item("time_start", root()),
+                        lit(start_time_ms * 1_000_000),
+                    ),
+                    lt_eq(get_item("time_end", root()), lit(end_time_ms * 1_000_000)),
+                );
+
+                // Unsupported conditions are reported before any file is opened.
+                to_vortex(parsed, &|_| true)?;
+                let parsed = parsed.clone();
+
+                let mut read = Read::new(f, files).with_file_filter(move |has| {
+                    match to_vortex(&parsed, has).expect("query.supported") {
+                        Some(expr) => and(base.clone(), expr),
+                        None => base.clone(),
+                    }
+                });
+
+                if let Some(limit) = limit {
+                    read = read.with_limit(limit);
+                }
+
+                Ok(Self::Vortex(read))
+            }
+        }
+    }
+
+    /// Spans of the next batch, unreadable files are logged and skipped.
+    pub async fn next_batch(&mut self) -> Option<Vec<SpanData>> {
+        loop {
+            if let Ok(spans) = self.try_next_batch().await? {
+                return Some(spans);
+            }
+        }
+    }
+
+    /// Spans of the next batch or the error of a file which couldn't be read.
+    pub async fn try_next_batch(&mut self) -> Option<Result<Vec<SpanData>, FileError>> {
+        match self {
+            Self::Arrow(read) => {
+                use crate::arrow::AsSpanData;
+
+                let batch = match read.try_next_batch().await? {
+                    Ok(batch) => batch,
+                    Err(e) => return Some(Err(e.into())),
+                };
+                Some(Ok(batch.get_span_data(read.resources()).collect()))
+            }
+            Self::Vortex(read) => {
+                use crate::vortex::read::AsSpanData;
+
+                let arr = match read.try_next_batch().await? {
+                    Ok(arr) => arr,
+                    Err(e) => return Some(Err(e.into())),
+                };
+                Some(Ok(arr.get_span_data().collect()))
+            }
+        }
+    }
+
+    /// Next batch as [stream::STREAM_SCHEMA], Arrow batches are passed on without decoding spans.
+    pub async fn try_next_stream_batch(&mut self) -> Option<Result<RecordBatch, FileError>> {
+        match self {
+            Self::Arrow(read) => {
+                let batch = match read.try_next_batch().await? {
+                    Ok(batch) => batch,
+                    Err(e) => return Some(Err(e.into())),
+                };
+                Some(Ok(stream::inline_resources(&batch, read.resources())))
+            }
+            Self::Vortex(_) => Some(self.try_next_batch().await?.map(stream::from_span_data)),
+        }
+    }
+}
+
+/// A file of a [Source] which couldn't be read, either an Arrow or a Vortex one.
+pub type FileError = Box<dyn std::error::Error + Send + Sync>;
@@ -0,0 +1,272 @@
+//! Concurrent scans over several files, used by both readers.
+//!
+//! Every file is decoded by its own task and at most `parallelism` of them run at once, each
+//! buffering a couple of batches ahead of the consumer. Dropping a [Scan] stops its tasks.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::num::NonZero;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::Poll;
+
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Batches decoded ahead of the consumer by a single file.
+const BUFFERED: usize = 2;
+
+static PARALLELISM: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets the parallelism of scans which don't set their own, 0 uses every core.
+pub fn set_parallelism(parallelism: usize) {
+    PARALLELISM.store(parallelism, Ordering::Relaxed);
+}
+
+pub fn parallelism() -> usize {
+    match PARALLELISM.load(Ordering::Relaxed) {
+        0 => std::thread::available_parallelism().map_or(1, NonZero::get),
+        n => n,
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    /// Number of files decoded at once.
+    pub parallelism: usize,
+    /// Yields batches in the order of the given files, otherwise in the order they are decoded.
+    /// Files of [crate::Stats] are listed as they were closed or merged, not sorted by time.
+    pub ordered: bool,
+    /// Stops once this many rows were yielded. Limited scans are always ordered, so that they
+    /// return the same rows every time.
+    pub limit: Option<usize>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            parallelism: parallelism(),
+            ordered: false,
+            limit: None,
+        }
+    }
+}
+
+type Produce<T> =
+    Box<dyn Fn(Box<Path>, mpsc::Sender<T>) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
+
+struct Active<T> {
+    rx: mpsc::Receiver<T>,
+    task: JoinHandle<()>,
+}
+
+pub struct Scan<T> {
+    files: VecDeque<Box<Path>>,
+    /// Decodes a file and sends its batches until done or the receiver is gone.
+    produce: Produce<T>,
+    rows: fn(&T) -> usize,
+    options: Options,
+    active: VecDeque<Active<T>>,
+    yielded: usize,
+    /// File polled first by unordered scans, rotated so that every file makes progress.
+    next: usize,
+}
+
+impl<T: Send + 'static> Scan<T> {
+    pub fn new<F>(
+        files: Vec<Box<Path>>,
+        options: Options,
+        rows: fn(&T) -> usize,
+        produce: impl Fn(Box<Path>, mpsc::Sender<T>) -> F + Send + 'static,
+    ) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        Self {
+            files: files.into(),
+            produce: Box::new(move |path, tx| Box::pin(produce(path, tx))),
+            rows,
+            options,
+            active: VecDeque::new(),
+            yielded: 0,
+            next: 0,
+        }
+    }
+
+    /// Starts files until `parallelism` of them are decoded.
+    fn fill(&mut self) {
+        while self.active.len() < self.options.parallelism.max(1)
+            && let Some(path) = self.files.pop_front()
+        {
+            let (tx, rx) = mpsc::channel(BUFFERED);
+            let task = tokio::spawn((self.produce)(path, tx));
+
+            self.active.push_back(Active { rx, task });
+        }
+    }
+
+    async fn recv_any(&mut self) -> (usize, Option<T>) {
+        let active = &mut self.active;
+        let len = active.len();
+        let start = self.next % len;
+        self.next = self.next.wrapping_add(1);
+
+        std::future::poll_fn(|cx| {
+            for offset in 0..len {
+                let idx = (start + offset) % len;
+
+                if let Poll::Ready(item) = active[idx].rx.poll_recv(cx) {
+                    return Poll::Ready((idx, item));
+                }
+            }
+
+            Poll::Pending
+        })
+        .await
+    }
+
+    pub async fn next(&mut self) -> Option<T> {
+        if self
+            .options
+            .limit
+            .is_some_and(|limit| self.yielded >= limit)
+        {
+            self.stop();
+            return None;
+        }
+
+        loop {
+            self.fill();
+
+            if self.active.is_empty() {
+                return None;
+            }
+
+            let (idx, item) = if self.options.ordered || self.options.limit.is_some() {
+                (0, self.active[0].rx.recv().await)
+            } else {
+                self.recv_any().await
+            };
+
+            let Some(item) = item else {
+                let done = self.active.remove(idx).expect("active.exists");
+
+                // Failures of a file are not hidden by the scan.
+                if let Err(e) = done.task.await
+                    && e.is_panic()
+                {
+                    std::panic::resume_unwind(e.into_panic());
+                }
+
+                continue;
+            };
+
+            self.yielded += (self.rows)(&item);
+            return Some(item);
+        }
+    }
+
+    fn stop(&mut self) {
+        self.files.clear();
+
+        for active in self.active.drain(..) {
+            active.task.abort();
+        }
+    }
+}
+
+impl<T> Drop for Scan<T> {
+    fn drop(&mut self) {
+        for active in &self.active {
+            active.task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Indexes of files, every file yields its index in `rows` batches of one row.
+    fn collect(files: usize, rows: usize, options: Options) -> Vec<usize> {
+        let files = (0..files).map(|idx| Path::new(&idx.to_string()).into());
+
+        let mut scan = Scan::new(
+            files.collect(),
+            options,
+            |_| 1,
+            move |path, tx| async move {
+                let idx: usize = path.to_str().unwrap().parse().unwrap();
+
+                for _ in 0..rows {
+                    tokio::task::yield_now().await;
+                    if tx.send(idx).await.is_err() {
+                        return;
+                    }
+                }
+            },
+        );
+
+        let rt = crate::testing::runtime();
+
+        rt.block_on(async {
+            let mut seen = Vec::new();
+            while let Some(idx) = scan.next().await {
+                seen.push(idx);
+            }
+            seen
+        })
+    }
+
+    #[test]
+    fn ordered_scan_keeps_file_order() {
+        let options = Options {
+            parallelism: 4,
+            ordered: true,
+            limit: None,
+        };
+
+        let expected: Vec<_> = (0..8).flat_map(|idx| [idx; 3]).collect();
+        assert_eq!(collect(8, 3, options), expected);
+    }
+
+    #[test]
+    fn unordered_scan_reads_every_file() {
+        let options = Options {
+            parallelism: 3,
+            ordered: false,
+            limit: None,
+        };
+
+        let mut seen = collect(8, 5, options);
+        seen.sort();
+
+        let expected: Vec<_> = (0..8).flat_map(|idx| [idx; 5]).collect();
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn scan_stops_at_limit() {
+        let options = Options {
+            parallelism: 2,
+            ordered: true,
+            limit: Some(4),
+        };
+
+        assert_eq!(collect(8, 3, options), vec![0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn limited_scan_is_ordered() {
+        let options = Options {
+            parallelism: 4,
+            ordered: false,
+            limit: Some(7),
+        };
+
+        for _ in 0..8 {
+            assert_eq!(collect(8, 3, options), vec![0, 0, 0, 1, 1, 1, 2]);
+        }
+    }
+}
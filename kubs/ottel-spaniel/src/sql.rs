@@ -0,0 +1,718 @@
+//! Read-only SQL over stored Arrow files, run by DataFusion.
+//!
+//! Files are registered as the [TABLE] table with the [crate::arrow::SCHEMA] layout and a [FILE]
+//! column numbering the files. Attributes are kept in the name/type/value list columns, typed
+//! values are extracted with UDFs:
+//!
+//! ```sql
+//! SELECT span_name, attr_int(attr_name, attr_type, attr_value, 'http.response.status_code') AS status
+//! FROM spans
+//! WHERE attr_str(attr_name, attr_type, attr_value, 'http.route') = '/api/users'
+//! ```
+//!
+//! `resource_id` indexes the resource table of its own file, the [RESOURCES] table holds those of
+//! every file with their `service_name`:
+//!
+//! ```sql
+//! SELECT service_name, count(*) FROM spans JOIN resources USING (file, resource_id)
+//! GROUP BY service_name
+//! ```
+//!
+//! Uploaded files are read through the object store of the installed [Tier]. Columns added since
+//! a file was written read as nulls, or zeros when not nullable, like with [crate::arrow::Read].
+//! Files of the first releases keeping resources in every row have no resource table to join
+//! and are skipped, as are Vortex files.
+
+use std::any::Any;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::array::*;
+use arrow::datatypes::{DataType, Field, Int8Type, Schema, SchemaRef};
+use datafusion::common::tree_node::{Transformed, TransformedResult, TreeNode};
+use datafusion::common::{DataFusionError, ScalarValue};
+use datafusion::datasource::MemTable;
+use datafusion::datasource::file_format::parquet::ParquetFormat;
+use datafusion::datasource::listing::{
+    ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl,
+};
+use datafusion::execution::object_store::ObjectStoreUrl;
+use datafusion::logical_expr::{
+    ColumnarValue, ScalarFunctionArgs, ScalarUDF, ScalarUDFImpl, Signature, Volatility,
+};
+use datafusion::physical_expr::PhysicalExpr;
+use datafusion::physical_expr::expressions::{self, Column};
+use datafusion::physical_expr_adapter::{
+    DefaultPhysicalExprAdapterFactory, PhysicalExprAdapter, PhysicalExprAdapterFactory,
+};
+use datafusion::prelude::{SQLOptions, SessionContext};
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use object_store::path::Path as ObjectPath;
+use object_store::{
+    CopyOptions, GetOptions, GetResult, ListResult, MultipartUpload, ObjectMeta, ObjectStore,
+    ObjectStoreExt, PutMultipartOptions, PutOptions, PutPayload, PutResult,
+};
+use opentelemetry_proto::tonic::common::v1::any_value::Value;
+
+use crate::FileFormat;
+use crate::arrow::{Attribute, Resources, SCHEMA, columns, load_resource_table};
+use crate::tier::Tier;
+
+pub const TABLE: &str = "spans";
+pub const RESOURCES: &str = "resources";
+/// Number of the file of a row, in both tables.
+pub const FILE: &str = "file";
+
+/// Files of a session are listed below this URL, see [FileStore].
+const FILES_URL: &str = "spaniel-files://files/";
+
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    /// Queries running longer are cancelled.
+    pub timeout: Duration,
+    /// Rows returned at most, the rest of a result is dropped.
+    pub max_rows: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_rows: 10_000,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Invalid, unsupported or failed query.
+    Query(DataFusionError),
+    Timeout(Duration),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Query(e) => write!(f, "{e}"),
+            Self::Timeout(timeout) => write!(f, "query exceeded {timeout:?}"),
+        }
+    }
+}
+
+impl From<DataFusionError> for Error {
+    fn from(e: DataFusionError) -> Self {
+        Self::Query(e)
+    }
+}
+
+/// Session with Arrow files of `files` registered as [TABLE] and their resources as
+/// [RESOURCES].
+pub async fn context(files: Vec<Box<Path>>) -> Result<SessionContext, DataFusionError> {
+    let ctx = SessionContext::new();
+
+    for kind in Kind::ALL {
+        ctx.register_udf(ScalarUDF::new_from_impl(AttrUdf::new(kind)));
+    }
+
+    let local = ctx
+        .runtime_env()
+        .object_store(ObjectStoreUrl::local_filesystem())?;
+    let tier = Tier::installed();
+
+    let mut store = FileStore::default();
+    let mut resources = Vec::new();
+
+    for file in FileFormat::Arrow.only(files) {
+        let location = match tier {
+            _ if file.exists() => (
+                local.clone(),
+                ObjectPath::from_filesystem_path(&file).map_err(object_store::Error::from)?,
+            ),
+            Some(tier) => (tier.store(), tier.key(&file)),
+            None => continue,
+        };
+
+        let file_resources = match load_resource_table(file.clone()).await {
+            Ok(Some(file_resources)) => file_resources,
+            Ok(None) => {
+                tracing::warn!(file = ?file, "sql.file.skipped");
+                continue;
+            }
+            Err(e) => {
+                tracing::error!(file = ?file, error = %e, "file.unreadable");
+                continue;
+            }
+        };
+
+        resources.push(file_resources);
+        store.files.push(location);
+    }
+
+    ctx.register_object_store(ObjectStoreUrl::parse(FILES_URL)?.as_ref(), Arc::new(store));
+
+    #[allow(clippy::borrow_interior_mutable_const)]
+    let schema = SCHEMA.clone();
+    // Stored files have no extension, the number of a file is its partition.
+    let options = ListingOptions::new(Arc::new(ParquetFormat::default()))
+        .with_file_extension("")
+        .with_table_partition_cols(vec![(FILE.to_owned(), DataType::UInt32)]);
+    let config = ListingTableConfig::new(ListingTableUrl::parse(FILES_URL)?)
+        .with_listing_options(options)
+        .with_schema(schema)
+        .with_expr_adapter_factory(Arc::new(Evolve));
+
+    ctx.register_table(TABLE, Arc::new(ListingTable::try_new(config)?))?;
+    ctx.register_table(RESOURCES, Arc::new(resource_table(&resources)?))?;
+
+    Ok(ctx)
+}
+
+/// Fills columns missing in files written by older versions with the values [crate::arrow::Read]
+/// gives them: nulls, or zeros for columns which aren't nullable.
+#[derive(Debug)]
+struct Evolve;
+
+impl PhysicalExprAdapterFactory for Evolve {
+    fn create(
+        &self,
+        logical: SchemaRef,
+        physical: SchemaRef,
+    ) -> Result<Arc<dyn PhysicalExprAdapter>, DataFusionError> {
+        Ok(Arc::new(EvolveAdapter {
+            default: DefaultPhysicalExprAdapterFactory.create(logical.clone(), physical.clone())?,
+            logical,
+            physical,
+        }))
+    }
+}
+
+#[derive(Debug)]
+struct EvolveAdapter {
+    logical: SchemaRef,
+    physical: SchemaRef,
+    /// Rewrites the remaining columns, missing nullable ones become nulls.
+    default: Arc<dyn PhysicalExprAdapter>,
+}
+
+impl PhysicalExprAdapter for EvolveAdapter {
+    fn rewrite(
+        &self,
+        expr: Arc<dyn PhysicalExpr>,
+    ) -> Result<Arc<dyn PhysicalExpr>, DataFusionError> {
+        let expr = expr
+            .transform(|expr| {
+                let Some(column) = expr.as_any().downcast_ref::<Column>() else {
+                    return Ok(Transformed::no(expr));
+                };
+
+                match self.logical.field_with_name(column.name()) {
+                    Ok(field)
+                        if !field.is_nullable()
+                            && self.physical.index_of(column.name()).is_err() =>
+                    {
+                        let value = ScalarValue::new_default(field.data_type())?;
+                        Ok(Transformed::yes(expressions::lit(value)))
+                    }
+                    _ => Ok(Transformed::no(expr)),
+                }
+            })
+            .data()?;
+
+        self.default.rewrite(expr)
+    }
+}
+
+/// Read-only store listing the files of a session as `file=<n>/<name>`, which numbers them in
+/// the [FILE] partition column of a single table.
+#[derive(Debug, Default)]
+struct FileStore {
+    /// Store and location of every file, local or uploaded.
+    files: Vec<(Arc<dyn ObjectStore>, ObjectPath)>,
+}
+
+impl FileStore {
+    fn path(n: usize, location: &ObjectPath) -> ObjectPath {
+        ObjectPath::from(format!(
+            "{FILE}={n}/{}",
+            location.filename().unwrap_or_default()
+        ))
+    }
+
+    fn resolve(
+        &self,
+        path: &ObjectPath,
+    ) -> object_store::Result<&(Arc<dyn ObjectStore>, ObjectPath)> {
+        path.parts()
+            .next()
+            .and_then(|part| {
+                part.as_ref()
+                    .strip_prefix(&format!("{FILE}="))
+                    .map(str::to_owned)
+            })
+            .and_then(|n| n.parse::<usize>().ok())
+            .and_then(|n| self.files.get(n))
+            .ok_or_else(|| object_store::Error::NotFound {
+                path: path.to_string(),
+                source: "not a file of the session".into(),
+            })
+    }
+
+    fn read_only(operation: &str) -> object_store::Error {
+        object_store::Error::NotImplemented {
+            operation: operation.to_owned(),
+            implementer: "FileStore".to_owned(),
+        }
+    }
+}
+
+impl std::fmt::Display for FileStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FileStore({} files)", self.files.len())
+    }
+}
+
+#[async_trait::async_trait]
+impl ObjectStore for FileStore {
+    async fn put_opts(
+        &self,
+        _: &ObjectPath,
+        _: PutPayload,
+        _: PutOptions,
+    ) -> object_store::Result<PutResult> {
+        Err(Self::read_only("put"))
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        _: &ObjectPath,
+        _: PutMultipartOptions,
+    ) -> object_store::Result<Box<dyn MultipartUpload>> {
+        Err(Self::read_only("put_multipart"))
+    }
+
+    async fn get_opts(
+        &self,
+        location: &ObjectPath,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        let (store, path) = self.resolve(location)?;
+        let mut result = store.get_opts(path, options).await?;
+        result.meta.location = location.clone();
+
+        Ok(result)
+    }
+
+    fn delete_stream(
+        &self,
+        locations: BoxStream<'static, object_store::Result<ObjectPath>>,
+    ) -> BoxStream<'static, object_store::Result<ObjectPath>> {
+        locations.map(|_| Err(Self::read_only("delete"))).boxed()
+    }
+
+    fn list(
+        &self,
+        prefix: Option<&ObjectPath>,
+    ) -> BoxStream<'static, object_store::Result<ObjectMeta>> {
+        let files: Vec<_> = self
+            .files
+            .iter()
+            .enumerate()
+            .map(|(n, (store, path))| (Self::path(n, path), store.clone(), path.clone()))
+            .filter(|(location, _, _)| prefix.is_none_or(|prefix| location.prefix_matches(prefix)))
+            .collect();
+
+        futures::stream::iter(files)
+            .then(|(location, store, path)| async move {
+                let mut meta = store.head(&path).await?;
+                meta.location = location;
+                Ok(meta)
+            })
+            .boxed()
+    }
+
+    async fn list_with_delimiter(
+        &self,
+        prefix: Option<&ObjectPath>,
+    ) -> object_store::Result<ListResult> {
+        // Every file is alone in the directory of its number.
+        let Some(prefix) = prefix else {
+            return Ok(ListResult {
+                common_prefixes: (0..self.files.len())
+                    .map(|n| ObjectPath::from(format!("{FILE}={n}")))
+                    .collect(),
+                objects: vec![],
+            });
+        };
+
+        Ok(ListResult {
+            common_prefixes: vec![],
+            objects: self.list(Some(prefix)).try_collect().await?,
+        })
+    }
+
+    async fn copy_opts(
+        &self,
+        _: &ObjectPath,
+        _: &ObjectPath,
+        _: CopyOptions,
+    ) -> object_store::Result<()> {
+        Err(Self::read_only("copy"))
+    }
+}
+
+/// Every resource of the files, numbered as in [FILE].
+fn resource_table(resources: &[Resources]) -> Result<MemTable, DataFusionError> {
+    use columns::{RES_ATTR_NAME, RES_ATTR_TYPE, RES_ATTR_VALUE};
+
+    let mut files = UInt32Builder::new();
+    let mut ids = UInt32Builder::new();
+    let mut services = StringBuilder::new();
+    let mut names =
+        ListBuilder::new(StringViewBuilder::new()).with_field(RES_ATTR_NAME.as_list_field());
+    let mut types = ListBuilder::new(Int8Builder::new()).with_field(RES_ATTR_TYPE.as_list_field());
+    let mut values =
+        ListBuilder::new(BinaryViewBuilder::new()).with_field(RES_ATTR_VALUE.as_list_field());
+
+    for (file, file_resources) in resources.iter().enumerate() {
+        for (id, attrs) in file_resources.iter().enumerate() {
+            files.append_value(file as u32);
+            ids.append_value(id as u32);
+            services.append_option(file_resources.service_name(id as u32));
+
+            Attribute::append(&mut names, &mut types, &mut values, attrs);
+            names.append(true);
+            types.append(true);
+            values.append(true);
+        }
+    }
+
+    let schema = Schema::new(vec![
+        Field::new(FILE, DataType::UInt32, false),
+        Field::new(columns::RESOURCE_ID.name(), DataType::UInt32, false),
+        Field::new("service_name", DataType::Utf8, true),
+        RES_ATTR_NAME.as_field(),
+        RES_ATTR_TYPE.as_field(),
+        RES_ATTR_VALUE.as_field(),
+    ]);
+    let batch = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(files.finish()),
+            Arc::new(ids.finish()),
+            Arc::new(services.finish()),
+            Arc::new(names.finish()),
+            Arc::new(types.finish()),
+            Arc::new(values.finish()),
+        ],
+    )?;
+
+    MemTable::try_new(batch.schema(), vec![vec![batch]])
+}
+
+/// Runs a single read-only statement over `files`.
+pub async fn run(
+    files: Vec<Box<Path>>,
+    sql: &str,
+    options: Options,
+) -> Result<Vec<RecordBatch>, Error> {
+    let read_only = SQLOptions::new()
+        .with_allow_ddl(false)
+        .with_allow_dml(false)
+        .with_allow_statements(false);
+
+    // Loading the resources of remote files counts against the timeout as well.
+    let query = async {
+        let ctx = context(files).await?;
+        let df = ctx.sql_with_options(sql, read_only).await?;
+        df.limit(0, Some(options.max_rows))?.collect().await
+    };
+
+    // Dropping the query cancels its execution.
+    match tokio::time::timeout(options.timeout, query).await {
+        Ok(batches) => Ok(batches?),
+        Err(_) => Err(Error::Timeout(options.timeout)),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Kind {
+    Str,
+    Int,
+    Float,
+    Bool,
+}
+
+impl Kind {
+    const ALL: [Kind; 4] = [Kind::Str, Kind::Int, Kind::Float, Kind::Bool];
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Str => "attr_str",
+            Kind::Int => "attr_int",
+            Kind::Float => "attr_float",
+            Kind::Bool => "attr_bool",
+        }
+    }
+
+    fn data_type(self) -> DataType {
+        match self {
+            Kind::Str => DataType::Utf8,
+            Kind::Int => DataType::Int64,
+            Kind::Float => DataType::Float64,
+            Kind::Bool => DataType::Boolean,
+        }
+    }
+}
+
+/// `attr_<kind>(names, types, values, key)`, value of the attribute `key` when it has the type
+/// of the function, NULL otherwise. Works on span and scope attribute columns alike.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct AttrUdf {
+    kind: Kind,
+    signature: Signature,
+}
+
+impl AttrUdf {
+    fn new(kind: Kind) -> Self {
+        Self {
+            kind,
+            signature: Signature::any(4, Volatility::Immutable),
+        }
+    }
+}
+
+/// Value of the attribute `key` of a row of the name/type/value list columns.
+fn find_attr(
+    names: &ListArray,
+    types: &ListArray,
+    values: &ListArray,
+    row: usize,
+    key: &str,
+) -> Option<Value> {
+    if names.is_null(row) {
+        return None;
+    }
+
+    let names = names.value(row);
+    let names = names.as_string_view();
+    let idx = (0..names.len()).find(|idx| names.value(*idx) == key)?;
+
+    let types = types.value(row);
+    let values = values.value(row);
+
+    Attribute::decode(
+        types.as_primitive::<Int8Type>().value(idx),
+        values.as_binary_view().value(idx),
+    )
+}
+
+impl ScalarUDFImpl for AttrUdf {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        self.kind.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _: &[DataType]) -> Result<DataType, DataFusionError> {
+        Ok(self.kind.data_type())
+    }
+
+    fn invoke_with_args(&self, args: ScalarFunctionArgs) -> Result<ColumnarValue, DataFusionError> {
+        let key = match &args.args[3] {
+            ColumnarValue::Scalar(key) => key.try_as_str().flatten().map(str::to_owned),
+            ColumnarValue::Array(_) => None,
+        };
+        let Some(key) = key else {
+            return Err(DataFusionError::Plan(format!(
+                "{}: key has to be a string literal",
+                self.name()
+            )));
+        };
+
+        let arrays = ColumnarValue::values_to_arrays(&args.args[..3])?;
+        let (names, types, values) = (
+            arrays[0].as_list::<i32>(),
+            arrays[1].as_list::<i32>(),
+            arrays[2].as_list::<i32>(),
+        );
+
+        let found = (0..args.number_rows).map(|row| find_attr(names, types, values, row, &key));
+
+        let array: ArrayRef = match self.kind {
+            Kind::Str => Arc::new(StringArray::from_iter(found.map(|v| match v {
+                Some(Value::StringValue(v)) => Some(v),
+                _ => None,
+            }))),
+            Kind::Int => Arc::new(Int64Array::from_iter(found.map(|v| match v {
+                Some(Value::IntValue(v)) => Some(v),
+                _ => None,
+            }))),
+            Kind::Float => Arc::new(Float64Array::from_iter(found.map(|v| match v {
+                Some(Value::DoubleValue(v)) => Some(v),
+                _ => None,
+            }))),
+            Kind::Bool => Arc::new(BooleanArray::from_iter(found.map(|v| match v {
+                Some(Value::BoolValue(v)) => Some(v),
+                _ => None,
+            }))),
+        };
+
+        Ok(ColumnarValue::Array(array))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load::{Generator, Options as LoadOptions, ingest};
+    use crate::testing;
+    use crate::write::SpanBuilder;
+
+    /// Value of a single-row result, e.g. of `SELECT count(*)`.
+    fn scalar(batches: &[RecordBatch]) -> Option<ScalarValue> {
+        let batch = batches.iter().find(|batch| batch.num_rows() > 0)?;
+        ScalarValue::try_from_array(batch.column(0), 0).ok()
+    }
+
+    #[test]
+    fn queries_stored_spans_with_attributes() {
+        let dir = testing::temp_dir("sql");
+
+        let mut generator = Generator::new(LoadOptions::default());
+        let spans: Vec<_> = (0..20)
+            .flat_map(|idx| generator.trace(idx * 1_000))
+            .collect();
+        let len = spans.len();
+
+        testing::runtime().block_on(async {
+            let format = crate::Format::Arrow;
+            let options = testing::writer_options();
+            ingest(&format, dir.clone().into_boxed_path(), spans, options).await;
+            let files = crate::misc::load_existing_files(&dir, &[format.file_prefix()]);
+
+            let count = run(
+                files.clone(),
+                "SELECT count(*) FROM spans",
+                Options::default(),
+            )
+            .await
+            .expect("sql.count");
+            assert_eq!(scalar(&count), Some(ScalarValue::Int64(Some(len as i64))));
+
+            let sql = "SELECT count(*) FROM spans \
+                WHERE attr_str(attr_name, attr_type, attr_value, 'attr.0') IS NOT NULL";
+            let with_attr = run(files.clone(), sql, Options::default())
+                .await
+                .expect("sql.attr");
+            assert_eq!(
+                scalar(&with_attr),
+                Some(ScalarValue::Int64(Some(len as i64)))
+            );
+
+            let sql = "SELECT count(*) FROM spans JOIN resources USING (file, resource_id) \
+                WHERE service_name LIKE 'service-%'";
+            let with_service = run(files.clone(), sql, Options::default())
+                .await
+                .expect("sql.resources");
+            assert_eq!(
+                scalar(&with_service),
+                Some(ScalarValue::Int64(Some(len as i64)))
+            );
+
+            let denied = run(files, "CREATE TABLE t AS SELECT 1", Options::default()).await;
+            assert!(matches!(denied, Err(Error::Query(_))));
+        });
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// File of `spans` without the columns added since the first release, with the resource in
+    /// every row instead of a resource table when `legacy`.
+    fn old_file(path: &Path, spans: Vec<crate::SpanData>, legacy: bool) {
+        use columns::*;
+
+        let mut builder = crate::arrow::Builder::new(spans.len(), spans.len());
+        builder.append(spans);
+        let batch = builder.build();
+
+        let data = if legacy {
+            crate::arrow::stream::inline_resources(&batch.data, &batch.resources)
+        } else {
+            batch.data
+        };
+
+        let added = [
+            &FLAGS,
+            &DROPPED_ATTRIBUTES,
+            &DROPPED_EVENTS,
+            &DROPPED_LINKS,
+            &SCOPE_NAME,
+        ];
+        let schema = data.schema();
+        let indices: Vec<usize> = (0..schema.fields().len())
+            .filter(|idx| !added.iter().any(|c| c.name() == schema.field(*idx).name()))
+            .collect();
+        let data = data.project(&indices).unwrap();
+
+        let file = std::fs::File::create(path).unwrap();
+        let mut writer = parquet::arrow::ArrowWriter::try_new(file, data.schema(), None).unwrap();
+        writer.write(&data).unwrap();
+
+        if !legacy {
+            writer.append_key_value_metadata(parquet::file::metadata::KeyValue::new(
+                Resources::METADATA_KEY.to_owned(),
+                batch.resources.encode(),
+            ));
+        }
+
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn old_files_are_evolved_and_legacy_ones_skipped() {
+        let dir = testing::temp_dir("sql-old");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut generator = Generator::new(LoadOptions::default());
+        let spans = generator.trace(0);
+        let len = spans.len();
+
+        let old = dir.join(format!("{}old", crate::arrow::Writer::PREF));
+        old_file(&old, spans, false);
+        let legacy = dir.join(format!("{}legacy", crate::arrow::Writer::PREF));
+        old_file(&legacy, generator.trace(1_000), true);
+        let files = vec![old.into_boxed_path(), legacy.into_boxed_path()];
+
+        testing::runtime().block_on(async {
+            let sql = "SELECT count(*) FROM spans";
+            let count = run(files.clone(), sql, Options::default())
+                .await
+                .expect("sql.count");
+            assert_eq!(scalar(&count), Some(ScalarValue::Int64(Some(len as i64))));
+
+            // Missing columns which aren't nullable read as zeros.
+            let sql = "SELECT count(*) FROM spans JOIN resources USING (file, resource_id) \
+                WHERE flags = 0 AND dropped_events_count = 0 AND scope_name IS NULL";
+            let count = run(files.clone(), sql, Options::default())
+                .await
+                .expect("sql.old");
+            assert_eq!(scalar(&count), Some(ScalarValue::Int64(Some(len as i64))));
+
+            let sql = "SELECT sum(flags) FROM spans";
+            let flags = run(files, sql, Options::default())
+                .await
+                .expect("sql.flags");
+            assert_eq!(scalar(&flags), Some(ScalarValue::UInt64(Some(0))));
+        });
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,208 @@
+//! Live tail of ingested spans.
+//!
+//! A [Tap] sits beside [crate::Sink::send], ingestion publishes every accepted request to it and
+//! each [Subscription] receives the spans matching its [Filter]. Subscribers have their own
+//! bounded buffer, spans which don't fit are dropped and counted instead of slowing down ingestion.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use opentelemetry_proto::tonic::common::v1::any_value::Value;
+use tokio::sync::mpsc;
+
+use crate::SpanData;
+use crate::arrow::ext::Span;
+
+/// Span status code of errors, `STATUS_CODE_ERROR`.
+const STATUS_ERROR: i32 = 2;
+
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    /// `service.name` resource attribute.
+    pub service: Option<String>,
+    pub span_name: Option<String>,
+    pub min_duration_nanos: Option<u64>,
+    pub errors_only: bool,
+}
+
+impl Filter {
+    pub fn matches(&self, span: &SpanData) -> bool {
+        if self.errors_only && span.status_code != Some(STATUS_ERROR) {
+            return false;
+        }
+
+        if self
+            .min_duration_nanos
+            .is_some_and(|min| span.time_duration < min)
+        {
+            return false;
+        }
+
+        if self
+            .span_name
+            .as_ref()
+            .is_some_and(|name| *name != span.name)
+        {
+            return false;
+        }
+
+        let Some(service) = self.service.as_ref() else {
+            return true;
+        };
+
+        span.resource_attributes.iter().any(|kv| {
+            kv.key == "service.name"
+                && matches!(
+                    kv.value.as_ref().and_then(|v| v.value.as_ref()),
+                    Some(Value::StringValue(name)) if name == service
+                )
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    id: u64,
+    filter: Filter,
+    tx: mpsc::Sender<Vec<Span>>,
+    /// Spans which didn't fit into the buffer.
+    dropped: AtomicU64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Tap {
+    subscribers: Arc<Mutex<Vec<Arc<Subscriber>>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl Tap {
+    /// Subscribes to spans matching `filter`, buffering up to `buffer` published batches.
+    pub fn subscribe(&self, filter: Filter, buffer: usize) -> Subscription {
+        let (tx, rx) = mpsc::channel(buffer.max(1));
+        let subscriber = Arc::new(Subscriber {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            filter,
+            tx,
+            dropped: AtomicU64::new(0),
+        });
+
+        self.subscribers
+            .lock()
+            .expect("tap.lock")
+            .push(subscriber.clone());
+
+        Subscription {
+            rx,
+            subscriber,
+            tap: self.clone(),
+        }
+    }
+
+    pub fn subscribers(&self) -> usize {
+        self.subscribers.lock().expect("tap.lock").len()
+    }
+
+    /// Passes matching spans to every subscriber without waiting for any of them.
+    pub fn publish(&self, spans: &[SpanData]) {
+        // Filtering happens without the lock, subscribing and unsubscribing never wait for it.
+        let subscribers = self.subscribers.lock().expect("tap.lock").clone();
+
+        for subscriber in subscribers.iter() {
+            let matched: Vec<Span> = spans
+                .iter()
+                .filter(|span| subscriber.filter.matches(span))
+                .map(Span::from)
+                .collect();
+
+            if matched.is_empty() {
+                continue;
+            }
+
+            if let Err(mpsc::error::TrySendError::Full(matched)) = subscriber.tx.try_send(matched) {
+                subscriber
+                    .dropped
+                    .fetch_add(matched.len() as u64, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Spans of a [Tap], unsubscribed once dropped.
+#[derive(Debug)]
+pub struct Subscription {
+    rx: mpsc::Receiver<Vec<Span>>,
+    subscriber: Arc<Subscriber>,
+    tap: Tap,
+}
+
+impl Subscription {
+    pub async fn recv(&mut self) -> Option<Vec<Span>> {
+        self.rx.recv().await
+    }
+
+    /// Spans dropped so far because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.subscriber.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let id = self.subscriber.id;
+
+        self.tap
+            .subscribers
+            .lock()
+            .expect("tap.lock")
+            .retain(|subscriber| subscriber.id != id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load::{Generator, Options};
+
+    #[test]
+    fn slow_subscribers_drop_spans() {
+        let tap = Tap::default();
+        let mut spans = Generator::new(Options::default()).trace(0);
+
+        for span in spans.iter_mut().step_by(2) {
+            span.status_code = Some(STATUS_ERROR);
+        }
+        let failed = spans
+            .iter()
+            .filter(|span| span.status_code == Some(STATUS_ERROR))
+            .count();
+        assert!(failed > 0);
+
+        let mut all = tap.subscribe(Filter::default(), 2);
+        let mut errors = tap.subscribe(
+            Filter {
+                errors_only: true,
+                ..Filter::default()
+            },
+            2,
+        );
+        assert_eq!(tap.subscribers(), 2);
+
+        for _ in 0..3 {
+            tap.publish(&spans);
+        }
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        assert_eq!(rt.block_on(all.recv()).map(|v| v.len()), Some(spans.len()));
+        assert_eq!(all.dropped(), spans.len() as u64);
+
+        // Only errors are buffered, the third batch of them didn't fit.
+        assert_eq!(rt.block_on(errors.recv()).map(|v| v.len()), Some(failed));
+        assert_eq!(errors.dropped(), failed as u64);
+
+        drop(errors);
+        assert_eq!(tap.subscribers(), 1);
+    }
+}
@@ -0,0 +1,34 @@
+//! Fixtures shared by the tests of the library and of the binaries, which include this file.
+
+use std::path::PathBuf;
+
+use crate::write::Options;
+
+/// Writer flushing every 10ms and never suspending, small enough to close a few files per test.
+pub fn writer_options() -> Options {
+    Options {
+        flush_interval_millis: 10,
+        suspend_interval_millis: 1000,
+        suspend_after: u64::MAX,
+        sink_channel_size: 16,
+        request_waitlist_size: 16,
+        spans_per_file: 256,
+        builder_flush_threshold: 128,
+        builder_capacity: 128,
+    }
+}
+
+/// Directory `spaniel-<name>-<pid>` in the temp dir, left over content of earlier runs is removed.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("spaniel-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+pub fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()
+        .unwrap()
+}
@@ -0,0 +1,634 @@
+//! Tiered storage: closed data files are uploaded to an object store and read back on demand.
+//!
+//! Uploaded files are listed in [crate::misc::REMOTE_MANIFEST] of the data directory, so they stay
+//! known to [Stats] after their local copies are deleted. Readers fall back to the installed
+//! [Tier] for files missing locally.
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use bytes::{Buf, Bytes};
+use object_store::buffered::BufWriter;
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, ObjectStoreExt};
+use parquet::errors::ParquetError;
+use parquet::file::reader::{ChunkReader, Length};
+
+use crate::{Location, Stats};
+
+pub struct Options {
+    /// Object store URL, e.g. `s3://bucket/prefix` or `file:///srv/spaniel`.
+    ///
+    /// S3 credentials and endpoint are taken from the `AWS_*` environment variables.
+    pub url: String,
+    /// Directory holding cached parts of remote files.
+    pub cache_dir: &'static str,
+    /// Size of cached blocks, also the size of a single range request.
+    pub block_size: u64,
+    /// Cached blocks and files are evicted, oldest first, once they take more bytes.
+    pub cache_capacity: u64,
+    /// Interval between checks for files to upload or delete.
+    pub sync_interval_millis: u64,
+    /// Local copies are deleted once uploaded and not modified for this long.
+    pub local_retention_secs: u64,
+}
+
+impl Options {
+    /// Options with default cache and retention settings.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            cache_dir: "data-cache",
+            block_size: 1024 * 1024,
+            cache_capacity: 10 * 1024 * 1024 * 1024,
+            sync_interval_millis: 60_000,
+            local_retention_secs: 3_600,
+        }
+    }
+}
+
+static INSTALLED: OnceLock<Tier> = OnceLock::new();
+
+#[derive(Clone, Debug)]
+pub struct Tier {
+    store: Arc<dyn ObjectStore>,
+    prefix: ObjectPath,
+    cache_dir: PathBuf,
+    block_size: u64,
+    cache_capacity: u64,
+}
+
+impl Tier {
+    pub fn new(
+        store: Arc<dyn ObjectStore>,
+        prefix: ObjectPath,
+        cache_dir: impl Into<PathBuf>,
+        block_size: u64,
+        cache_capacity: u64,
+    ) -> Self {
+        assert!(block_size > 0);
+
+        Self {
+            store,
+            prefix,
+            cache_dir: cache_dir.into(),
+            block_size,
+            cache_capacity,
+        }
+    }
+
+    pub fn from_options(options: &Options) -> Result<Self, object_store::Error> {
+        let url = url::Url::parse(&options.url).map_err(|e| object_store::Error::Generic {
+            store: "url",
+            source: Box::new(e),
+        })?;
+        let (store, prefix) = object_store::parse_url_opts(&url, std::env::vars())?;
+
+        Ok(Self::new(
+            Arc::from(store),
+            prefix,
+            options.cache_dir,
+            options.block_size,
+            options.cache_capacity,
+        ))
+    }
+
+    /// Makes remote files available to readers of both formats.
+    pub fn install(self) {
+        INSTALLED.set(self).expect("tier.install.once");
+    }
+
+    pub fn installed() -> Option<&'static Tier> {
+        INSTALLED.get()
+    }
+
+    pub fn store(&self) -> Arc<dyn ObjectStore> {
+        self.store.clone()
+    }
+
+    /// Location of the remote copy of a local path.
+    pub fn key(&self, path: &Path) -> ObjectPath {
+        path.iter().fold(self.prefix.clone(), |key, part| {
+            key.join(part.to_string_lossy().as_ref())
+        })
+    }
+
+    /// Tenants name their files alike, so the cache keeps the whole path like [Tier::key].
+    fn cache_path(&self, path: &Path) -> PathBuf {
+        path.components()
+            .filter_map(|part| match part {
+                Component::Normal(part) => Some(part),
+                _ => None,
+            })
+            .fold(self.cache_dir.clone(), |dir, part| dir.join(part))
+    }
+
+    /// Streams a local file to the object store, large files are sent as multipart uploads.
+    pub async fn upload(&self, path: &Path) -> Result<(), object_store::Error> {
+        use tokio::io::AsyncWriteExt;
+
+        let generic = |e: std::io::Error| object_store::Error::Generic {
+            store: "local",
+            source: Box::new(e),
+        };
+
+        let mut file = tokio::fs::File::open(path).await.map_err(generic)?;
+        let mut writer = BufWriter::new(self.store.clone(), self.key(path));
+
+        let copied = match tokio::io::copy(&mut file, &mut writer).await {
+            Ok(_) => writer.shutdown().await,
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = copied {
+            // Parts of a multipart upload are kept by the store until aborted.
+            let _ = writer.abort().await;
+            return Err(generic(e));
+        }
+
+        Ok(())
+    }
+
+    /// Opens a remote file for range reads through the block cache.
+    pub async fn open(&self, path: &Path) -> Result<RemoteFile, object_store::Error> {
+        let location = self.key(path);
+        let meta = self.store.head(&location).await?;
+        let cache_dir = self.cache_path(path);
+
+        std::fs::create_dir_all(&cache_dir).expect("cache.dir.create");
+
+        Ok(RemoteFile {
+            store: self.store.clone(),
+            location,
+            size: meta.size,
+            block_size: self.block_size,
+            cache_dir,
+            handle: tokio::runtime::Handle::current(),
+        })
+    }
+
+    /// Downloads the whole file into the cache, for readers which need a local path.
+    pub async fn fetch(&self, path: &Path) -> Result<Box<Path>, object_store::Error> {
+        let cached = self.cache_path(path).with_extension("full");
+
+        if cached.exists() {
+            return Ok(cached.into_boxed_path());
+        }
+
+        let data = self.store.get(&self.key(path)).await?.bytes().await?;
+        let tmp = cached.with_extension("tmp");
+
+        std::fs::create_dir_all(cached.parent().expect("cache.dir")).expect("cache.dir.create");
+        std::fs::write(&tmp, &data).expect("cache.write");
+        std::fs::rename(&tmp, &cached).expect("cache.rename");
+
+        // Whole files are large, the cache is trimmed right away.
+        self.evict().await;
+
+        Ok(cached.into_boxed_path())
+    }
+
+    /// Deletes the oldest cached blocks and files until the cache fits its capacity.
+    pub async fn evict(&self) {
+        let dir = self.cache_dir.clone();
+        let capacity = self.cache_capacity;
+
+        tokio::task::spawn_blocking(move || evict(&dir, capacity))
+            .await
+            .expect("cache.evict");
+    }
+
+    /// Uploads closed files and deletes local copies past retention.
+    pub async fn sync(&self, stats: &Stats, retention: Duration) {
+        let files: Vec<Box<Path>> = { stats.files.read().await.clone() };
+
+        for file in files {
+            match stats.location(&file).await {
+                Location::Local => {
+                    if !stats.files.read().await.contains(&file) {
+                        continue;
+                    }
+
+                    if let Err(e) = self.upload(&file).await {
+                        tracing::warn!(file = ?file, error = %e, "tier.upload.failed");
+                        continue;
+                    }
+
+                    // Files merged by a compaction meanwhile are retired, their rows are in the
+                    // merged file which is uploaded on its own.
+                    if !stats.set_uploaded(&file).await {
+                        if let Err(e) = self.store.delete(&self.key(&file)).await {
+                            tracing::warn!(file = ?file, error = %e, "tier.delete.failed");
+                        }
+
+                        tracing::info!(file = ?file, "tier.upload.discarded");
+                        continue;
+                    }
+
+                    tracing::info!(file = ?file, "tier.uploaded");
+                }
+                Location::Both => {
+                    let age = std::fs::metadata(&file)
+                        .and_then(|meta| meta.modified())
+                        .ok()
+                        .and_then(|time| time.elapsed().ok());
+
+                    if age.is_some_and(|age| age >= retention) {
+                        // The copy may be gone already, e.g. deleted by hand.
+                        match std::fs::remove_file(&file) {
+                            Ok(()) => tracing::info!(file = ?file, "tier.local.deleted"),
+                            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                            Err(e) => {
+                                tracing::warn!(file = ?file, error = %e, "tier.local.delete.failed")
+                            }
+                        }
+                    }
+                }
+                Location::Remote => {}
+            }
+        }
+
+        self.evict().await;
+    }
+
+    pub async fn run(self, stats: Stats, options: Options) {
+        let interval = Duration::from_millis(options.sync_interval_millis);
+        let retention = Duration::from_secs(options.local_retention_secs);
+
+        loop {
+            self.sync(&stats, retention).await;
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+/// Files below `dir` with their size and modification time.
+fn cached_files(dir: &Path, out: &mut Vec<(PathBuf, u64, std::time::SystemTime)>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+
+        if meta.is_dir() {
+            cached_files(&entry.path(), out);
+        } else if let Ok(modified) = meta.modified() {
+            out.push((entry.path(), meta.len(), modified));
+        }
+    }
+}
+
+/// Deletes the oldest files below `dir` until the rest takes at most `capacity` bytes.
+fn evict(dir: &Path, capacity: u64) {
+    let mut files = Vec::new();
+    cached_files(dir, &mut files);
+
+    let mut size: u64 = files.iter().map(|(_, len, _)| len).sum();
+    if size <= capacity {
+        return;
+    }
+
+    files.sort_by_key(|(_, _, modified)| *modified);
+
+    for (path, len, _) in files {
+        if size <= capacity {
+            break;
+        }
+
+        // Readers of an evicted block request it again, open files stay readable.
+        if std::fs::remove_file(&path).is_ok() {
+            size -= len;
+        }
+    }
+
+    tracing::info!(dir = ?dir, size, "tier.cache.evicted");
+}
+
+/// Remote file read with range requests, every fetched block is cached on local disk.
+///
+/// Reads block the calling thread until the runtime which opened the file completes the request.
+pub struct RemoteFile {
+    store: Arc<dyn ObjectStore>,
+    location: ObjectPath,
+    size: u64,
+    block_size: u64,
+    cache_dir: PathBuf,
+    handle: tokio::runtime::Handle,
+}
+
+impl RemoteFile {
+    fn block(&self, idx: u64) -> Result<Bytes, object_store::Error> {
+        let path = self.cache_dir.join(idx.to_string());
+
+        if let Ok(data) = std::fs::read(&path) {
+            return Ok(Bytes::from(data));
+        }
+
+        let start = idx * self.block_size;
+        let end = (start + self.block_size).min(self.size);
+        let request = self.store.get_range(&self.location, start..end);
+
+        let data = match tokio::runtime::Handle::try_current() {
+            // Worker threads may not block on the runtime, they hand their tasks over first.
+            Ok(_) => tokio::task::block_in_place(|| self.handle.block_on(request))?,
+            Err(_) => self.handle.block_on(request)?,
+        };
+
+        // Caching is best effort, a failed write only means another request later.
+        let tmp = path.with_extension("tmp");
+        if std::fs::write(&tmp, &data).is_ok() {
+            let _ = std::fs::rename(&tmp, &path);
+        }
+
+        Ok(data)
+    }
+
+    pub fn read_range(&self, start: u64, length: u64) -> Result<Bytes, object_store::Error> {
+        let end = start + length;
+        assert!(end <= self.size);
+
+        if length == 0 {
+            return Ok(Bytes::new());
+        }
+
+        let first = start / self.block_size;
+        let last = (end - 1) / self.block_size;
+
+        if first == last {
+            let offset = (start - first * self.block_size) as usize;
+            return Ok(self.block(first)?.slice(offset..offset + length as usize));
+        }
+
+        let mut buf = Vec::with_capacity(length as usize);
+
+        for idx in first..=last {
+            let block = self.block(idx)?;
+            let block_start = idx * self.block_size;
+            let from = start.max(block_start) - block_start;
+            let to = end.min(block_start + block.len() as u64) - block_start;
+
+            buf.extend_from_slice(&block[from as usize..to as usize]);
+        }
+
+        Ok(Bytes::from(buf))
+    }
+}
+
+impl Length for RemoteFile {
+    fn len(&self) -> u64 {
+        self.size
+    }
+}
+
+impl ChunkReader for RemoteFile {
+    type T = bytes::buf::Reader<Bytes>;
+
+    fn get_read(&self, start: u64) -> parquet::errors::Result<Self::T> {
+        Ok(self
+            .get_bytes(start, (self.size - start) as usize)?
+            .reader())
+    }
+
+    fn get_bytes(&self, start: u64, length: usize) -> parquet::errors::Result<Bytes> {
+        self.read_range(start, length as u64)
+            .map_err(|e| ParquetError::External(Box::new(e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+
+    use super::*;
+    use crate::testing::runtime;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = crate::testing::temp_dir(&format!("tier-{name}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn upload_delete_and_read_back() {
+        let rt = runtime();
+
+        let root = temp_dir("read-back");
+        let dir = root.join("data");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let content: Vec<u8> = (0..=255).collect();
+        let file = dir.join("spaniel-live-arrow-0");
+        std::fs::write(&file, &content).unwrap();
+
+        let store = Arc::new(InMemory::new());
+        let tier = Tier::new(
+            store.clone(),
+            ObjectPath::from("spans"),
+            root.join("cache"),
+            10,
+            u64::MAX,
+        );
+
+        let stats = Stats::new(&dir);
+        let file = file.into_boxed_path();
+
+        rt.block_on(async {
+            assert_eq!(stats.location(&file).await, Location::Local);
+
+            tier.sync(&stats, Duration::ZERO).await;
+            assert_eq!(stats.location(&file).await, Location::Both);
+
+            tier.sync(&stats, Duration::ZERO).await;
+            assert_eq!(stats.location(&file).await, Location::Remote);
+        });
+
+        let key = ObjectPath::from_iter(
+            ["spans"]
+                .into_iter()
+                .chain(file.iter().map(|part| part.to_str().unwrap())),
+        );
+        assert!(rt.block_on(store.head(&key)).is_ok());
+
+        // A restart still knows the file, and does not reuse its id.
+        let restarted = Stats::new(&dir);
+        assert_eq!(*rt.block_on(restarted.files.read()), vec![file.clone()]);
+        assert_eq!(rt.block_on(restarted.location(&file)), Location::Remote);
+        assert_eq!(
+            crate::misc::get_next_file_id(&dir, "spaniel-live-arrow-"),
+            1
+        );
+
+        let remote = rt.block_on(tier.open(&file)).unwrap();
+        assert_eq!(remote.len(), 256);
+
+        let (read, cached) = std::thread::spawn(move || {
+            let read = [
+                remote.get_bytes(0, 4).unwrap(),
+                remote.get_bytes(8, 15).unwrap(),
+                remote.get_bytes(250, 6).unwrap(),
+                remote.get_bytes(0, 256).unwrap(),
+            ];
+            let cached = std::fs::read_dir(&remote.cache_dir).unwrap().count();
+
+            (read, cached)
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(read[0], content[0..4]);
+        assert_eq!(read[1], content[8..23]);
+        assert_eq!(read[2], content[250..256]);
+        assert_eq!(read[3], content);
+        assert_eq!(cached, 26);
+
+        let fetched = rt.block_on(tier.fetch(&file)).unwrap();
+        assert_eq!(std::fs::read(fetched).unwrap(), content);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn merged_and_uploaded_files_exclude_each_other() {
+        let rt = runtime();
+        let dir = temp_dir("merge-race");
+
+        let file = |name: &str| -> Box<Path> {
+            let path = dir.join(format!("spaniel-live-arrow-{name}"));
+            std::fs::write(&path, b"data").unwrap();
+            path.into_boxed_path()
+        };
+        let (a, b, merged) = (file("0"), file("1"), file("2"));
+
+        let stats = Stats::new(&dir);
+
+        rt.block_on(async {
+            // Uploaded first, the merge of `a` is given up.
+            assert!(stats.set_uploaded(&a).await);
+            assert!(!stats.replace_files(&[a.clone(), b.clone()], &merged).await);
+
+            // Merged first, the upload of `b` is discarded.
+            assert!(stats.replace_files(std::slice::from_ref(&b), &merged).await);
+            assert!(!stats.set_uploaded(&b).await);
+        });
+
+        assert_eq!(
+            crate::misc::read_remote_manifest(&dir),
+            vec!["spaniel-live-arrow-0".to_owned()]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reads_block_on_runtime_workers() {
+        let rt = runtime();
+
+        let root = temp_dir("workers");
+        let dir = root.join("data");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let content: Vec<u8> = (0..100).collect();
+        let file = dir.join("spaniel-live-arrow-0");
+        std::fs::write(&file, &content).unwrap();
+        let file = file.into_boxed_path();
+
+        let tier = Tier::new(
+            Arc::new(InMemory::new()),
+            ObjectPath::from("spans"),
+            root.join("cache"),
+            10,
+            u64::MAX,
+        );
+        let stats = Stats::new(&dir);
+
+        let read = rt.block_on(async {
+            tier.sync(&stats, Duration::from_secs(3_600)).await;
+            assert_eq!(stats.location(&file).await, Location::Both);
+
+            let remote = tier.open(&file).await.unwrap();
+            tokio::spawn(async move { remote.get_bytes(5, 20).unwrap() })
+                .await
+                .unwrap()
+        });
+
+        assert_eq!(read, content[5..25]);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn cache_is_trimmed_to_capacity_oldest_first() {
+        let dir = temp_dir("evict");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+
+        for (idx, name) in ["a", "b", "nested/c", "d"].into_iter().enumerate() {
+            let path = dir.join(name);
+            std::fs::write(&path, [0; 10]).unwrap();
+
+            let modified = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(idx as u64);
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        }
+
+        evict(&dir, 25);
+
+        assert!(!dir.join("a").exists());
+        assert!(!dir.join("b").exists());
+        assert!(dir.join("nested/c").exists());
+        assert!(dir.join("d").exists());
+
+        evict(&dir, 100);
+        assert!(dir.join("d").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn tenants_do_not_share_cached_blocks() {
+        let rt = runtime();
+
+        let root = temp_dir("tenants");
+        let tier = Tier::new(
+            Arc::new(InMemory::new()),
+            ObjectPath::from("spans"),
+            root.join("cache"),
+            10,
+            u64::MAX,
+        );
+
+        let files: Vec<_> = [("tenant-a", 1u8), ("tenant-b", 2u8)]
+            .into_iter()
+            .map(|(tenant, byte)| {
+                let dir = root.join("data").join(tenant);
+                std::fs::create_dir_all(&dir).unwrap();
+
+                let file = dir.join("spaniel-live-arrow-0");
+                std::fs::write(&file, [byte; 16]).unwrap();
+                rt.block_on(tier.upload(&file)).unwrap();
+
+                (file, byte)
+            })
+            .collect();
+
+        for (file, byte) in files.iter() {
+            let remote = rt.block_on(tier.open(file)).unwrap();
+            let read = std::thread::spawn(move || remote.get_bytes(0, 16).unwrap())
+                .join()
+                .unwrap();
+            assert_eq!(read, vec![*byte; 16]);
+
+            let fetched = rt.block_on(tier.fetch(file)).unwrap();
+            assert_eq!(std::fs::read(fetched).unwrap(), vec![*byte; 16]);
+        }
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
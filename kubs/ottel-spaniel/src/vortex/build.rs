@@ -20,6 +20,12 @@ pub struct FieldTypes {
     time_start: DType,
     time_end: DType,
     time_duration: DType,
+    trace_state: DType,
+    flags: DType,
+    dropped_count: DType,
+    schema_url: DType,
+    scope_name: DType,
+    scope_version: DType,
 }
 
 fn create_types() -> FieldTypes {
@@ -36,6 +42,13 @@ fn create_types() -> FieldTypes {
     let time_end = DType::Primitive(PType::U64, NonNullable);
     let time_duration = DType::Primitive(PType::U64, NonNullable);
 
+    let trace_state = DType::Utf8(Nullable);
+    let flags = DType::Primitive(PType::U32, NonNullable);
+    let dropped_count = DType::Primitive(PType::U32, NonNullable);
+    let schema_url = DType::Utf8(Nullable);
+    let scope_name = DType::Utf8(Nullable);
+    let scope_version = DType::Utf8(Nullable);
+
     FieldTypes {
         trace_id_element,
         trace_id,
@@ -48,6 +61,12 @@ fn create_types() -> FieldTypes {
         time_start,
         time_end,
         time_duration,
+        trace_state,
+        flags,
+        dropped_count,
+        schema_url,
+        scope_name,
+        scope_version,
     }
 }
 
@@ -65,6 +84,15 @@ fn create_struct_fields() -> StructFields {
         ("time_start", fields.time_start.clone()),
         ("time_end", fields.time_end.clone()),
         ("time_duration", fields.time_duration.clone()),
+        ("trace_state", fields.trace_state.clone()),
+        ("flags", fields.flags.clone()),
+        ("dropped_attributes_count", fields.dropped_count.clone()),
+        ("dropped_events_count", fields.dropped_count.clone()),
+        ("dropped_links_count", fields.dropped_count.clone()),
+        ("resource_schema_url", fields.schema_url.clone()),
+        ("scope_name", fields.scope_name.clone()),
+        ("scope_version", fields.scope_version.clone()),
+        ("scope_schema_url", fields.schema_url.clone()),
     ])
 }
 
@@ -96,6 +124,12 @@ impl Builder {
     }
 
     fn to_scalar(&self, data: SpanData) -> Scalar {
+        let utf8 = |value: Option<&String>, dtype: &DType| {
+            value
+                .map(|v| Scalar::utf8(v.as_str(), Nullable))
+                .unwrap_or(Scalar::null(dtype.clone()))
+        };
+
         let trace_id: Vec<Scalar> = data
             .trace_id
             .iter()
@@ -119,13 +153,25 @@ impl Builder {
                 Scalar::primitive(data.kind, NonNullable),
                 data.status_code
                     .map(|v| Scalar::primitive(v, Nullable))
-                    .unwrap_or_else(Scalar::null_native::<i64>),
+                    .unwrap_or_else(Scalar::null_native::<i32>),
                 data.status_message
                     .map(|v| Scalar::utf8(v.as_str(), Nullable))
                     .unwrap_or(Scalar::null(self.field_types.status_message.clone())),
                 Scalar::primitive(data.time_start, NonNullable),
                 Scalar::primitive(data.time_end, NonNullable),
                 Scalar::primitive(data.time_duration, NonNullable),
+                utf8(data.trace_state.as_ref(), &self.field_types.trace_state),
+                Scalar::primitive(data.flags, NonNullable),
+                Scalar::primitive(data.dropped_attributes_count, NonNullable),
+                Scalar::primitive(data.dropped_events_count, NonNullable),
+                Scalar::primitive(data.dropped_links_count, NonNullable),
+                utf8(
+                    data.resource_schema_url.as_ref(),
+                    &self.field_types.schema_url,
+                ),
+                utf8(data.scope.name.as_ref(), &self.field_types.scope_name),
+                utf8(data.scope.version.as_ref(), &self.field_types.scope_version),
+                utf8(data.scope.schema_url.as_ref(), &self.field_types.schema_url),
             ],
         )
     }
@@ -4,3 +4,5 @@ mod write;
 
 pub(crate) use build::Builder;
 pub(crate) use write::Writer;
+
+pub use read::file_summary;
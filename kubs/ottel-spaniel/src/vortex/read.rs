@@ -1,16 +1,23 @@
 use std::path::Path;
+use std::sync::Arc;
 
+use tokio::sync::mpsc;
 use vortex::array::arrays::Struct;
 use vortex::array::{Array, ArrayRef};
+use vortex::dtype::DType;
 use vortex::error::VortexError;
 use vortex::expr::*;
-use vortex::file::OpenOptionsSessionExt;
+use vortex::file::{OpenOptionsSessionExt, VortexFile};
+use vortex::io::runtime::current::CurrentThreadRuntime;
 use vortex::scalar::Scalar;
+use vortex::session::VortexSession;
 
 use crate::Format;
+use crate::scan::{self, Scan};
 
 pub trait AsSpanData {
     fn get_names(&self) -> impl Iterator<Item = Scalar>;
+    fn get_span_data(&self) -> impl Iterator<Item = crate::SpanData>;
 }
 
 impl AsSpanData for Array<Struct> {
@@ -46,31 +53,249 @@ impl AsSpanData for Array<Struct> {
             index: 0,
         }
     }
+
+    fn get_span_data(&self) -> impl Iterator<Item = crate::SpanData> {
+        (0..self.len()).map(|idx| {
+            let row = self.scalar_at(idx).expect("elem.exists");
+            let row = row.as_struct();
+            let field = |name: &str| row.field(name).expect("field.exists");
+
+            // Files written by older versions lack fields added since, read as null or zero.
+            let utf8 = |name: &str| {
+                let value = row.field(name)?;
+                value.as_utf8().value().map(|v| v.as_str().to_owned())
+            };
+            let count = |name: &str| {
+                row.field(name)
+                    .and_then(|value| value.as_primitive().typed_value::<u32>())
+                    .unwrap_or(0)
+            };
+
+            crate::SpanData {
+                trace_id: trace_id(&field("trace_id")),
+                span_id: field("span_id")
+                    .as_primitive()
+                    .typed_value::<i64>()
+                    .unwrap()
+                    .to_be_bytes(),
+                parent_span_id: field("parent_span_id")
+                    .as_primitive()
+                    .typed_value::<i64>()
+                    .map(i64::to_be_bytes),
+                name: utf8("name").unwrap(),
+                kind: field("kind").as_primitive().typed_value::<i32>().unwrap(),
+                status_code: field("status_code").as_primitive().typed_value::<i32>(),
+                status_message: utf8("status_message"),
+                time_start: field("time_start")
+                    .as_primitive()
+                    .typed_value::<u64>()
+                    .unwrap(),
+                time_end: field("time_end")
+                    .as_primitive()
+                    .typed_value::<u64>()
+                    .unwrap(),
+                time_duration: field("time_duration")
+                    .as_primitive()
+                    .typed_value::<u64>()
+                    .unwrap(),
+                trace_state: utf8("trace_state"),
+                flags: count("flags"),
+                dropped_attributes_count: count("dropped_attributes_count"),
+                dropped_events_count: count("dropped_events_count"),
+                dropped_links_count: count("dropped_links_count"),
+                span_attributes: Vec::new(),
+                resource_attributes: std::sync::Arc::new(Vec::new()),
+                resource_schema_url: utf8("resource_schema_url"),
+                scope: std::sync::Arc::new(crate::ScopeData {
+                    name: utf8("scope_name"),
+                    version: utf8("scope_version"),
+                    attributes: Vec::new(),
+                    schema_url: utf8("scope_schema_url"),
+                }),
+            }
+        })
+    }
+}
+
+fn trace_id(scalar: &Scalar) -> [u8; 16] {
+    let trace_id: Vec<u8> = scalar
+        .as_list()
+        .elements()
+        .expect("trace_id.not_null")
+        .iter()
+        .map(|v| v.as_primitive().typed_value::<u8>().unwrap())
+        .collect();
+
+    trace_id.try_into().expect("trace_id.len")
+}
+
+async fn open_file(session: &VortexSession, path: &Path) -> Result<VortexFile, VortexError> {
+    let mut path: Box<Path> = path.into();
+
+    // Vortex reads by path, so remote files are fetched into the cache as a whole.
+    if let Some(tier) = crate::tier::Tier::installed()
+        && !path.exists()
+    {
+        path = tier.fetch(&path).await?;
+    }
+
+    session.open_options().open_path(&path).await
+}
+
+/// Whether a file has the top level field `name`.
+fn has_field(file: &VortexFile, name: &str) -> bool {
+    match file.dtype() {
+        DType::Struct(fields, _) => fields.find(name).is_some(),
+        _ => false,
+    }
+}
+
+/// Builds the filter of a file, given whether the file has a field.
+pub type FileFilter = Arc<dyn Fn(&dyn Fn(&str) -> bool) -> Expression + Send + Sync>;
+
+/// Row count of a file, time range is not available without a scan. Only the footer is read,
+/// remote files are read with range requests instead of being fetched.
+pub async fn file_summary(
+    session: &VortexSession,
+    path: &Path,
+) -> Result<(u64, Option<u64>, Option<u64>), VortexError> {
+    let file = match crate::tier::Tier::installed() {
+        Some(tier) if !path.exists() => {
+            let key = tier.key(path);
+            let options = session.open_options();
+            options
+                .open_object_store(&tier.store(), key.as_ref())
+                .await?
+        }
+        _ => session.open_options().open_path(path).await?,
+    };
+
+    Ok((file.row_count(), None, None))
+}
+
+/// A file which couldn't be opened or scanned, arrays read from it before were yielded.
+#[derive(Debug)]
+pub struct FileError {
+    pub path: Box<Path>,
+    pub error: VortexError,
+}
+
+impl std::fmt::Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+impl std::error::Error for FileError {}
+
+type Scanned = Result<Array<Struct>, FileError>;
+
+type Reader = Box<dyn Iterator<Item = Result<ArrayRef, VortexError>> + Send + 'static>;
+
+/// Opens a file and starts its scan.
+async fn open_scan(
+    session: &VortexSession,
+    runtime: &CurrentThreadRuntime,
+    path: &Path,
+    filter: Option<FileFilter>,
+    projection: Option<Expression>,
+) -> Result<Reader, VortexError> {
+    let file = open_file(session, path).await?;
+    let mut scan = file.scan()?;
+
+    if let Some(filter) = filter {
+        scan = scan.with_filter(filter(&|name| has_field(&file, name)));
+    }
+
+    if let Some(projection) = projection {
+        scan = scan.with_projection(projection);
+    }
+
+    Ok(Box::new(scan.into_iter(runtime)?))
+}
+
+/// Scans a file on a blocking thread and sends its arrays until done, an error or `tx` is
+/// closed.
+async fn scan_vortex_file(
+    format: Format,
+    path: Box<Path>,
+    filter: Option<FileFilter>,
+    projection: Option<Expression>,
+    tx: mpsc::Sender<Scanned>,
+) {
+    let Format::Vortex { session, runtime } = &format else {
+        unreachable!();
+    };
+
+    let mut reader = match open_scan(session, runtime, &path, filter, projection).await {
+        Ok(reader) => reader,
+        Err(error) => {
+            tracing::error!(file = ?path, error = %error, "file.unreadable");
+            let _ = tx.send(Err(FileError { path, error })).await;
+            return;
+        }
+    };
+
+    loop {
+        let (returned, next) = tokio::task::spawn_blocking(move || {
+            let next = reader.next();
+            (reader, next)
+        })
+        .await
+        .unwrap();
+        reader = returned;
+
+        let Some(next) = next else {
+            break;
+        };
+
+        let arr = match next {
+            Ok(arr) => Ok(arr.downcast::<Struct>()),
+            Err(error) => {
+                tracing::error!(file = ?path, error = %error, "file.unreadable");
+                let _ = tx.send(Err(FileError { path, error })).await;
+                break;
+            }
+        };
+
+        if tx.send(arr).await.is_err() {
+            break;
+        }
+    }
 }
 
-pub struct Read<'a> {
+pub struct Read {
     files: Vec<Box<Path>>,
-    index: usize,
-    format: &'a Format,
-    filter: Option<Expression>,
+    format: Format,
+    filter: Option<FileFilter>,
     projection: Option<Expression>,
-    reader: Option<Box<dyn Iterator<Item = Result<ArrayRef, VortexError>> + Send + 'static>>,
+    options: scan::Options,
+    scan: Option<Scan<Scanned>>,
 }
 
-impl<'a> Read<'a> {
-    pub fn new(format: &'a Format, files: Vec<Box<Path>>) -> Self {
+impl Read {
+    pub fn new(format: &Format, files: Vec<Box<Path>>) -> Self {
         Self {
             files,
-            format,
-            index: 0,
+            format: format.clone(),
             filter: None,
             projection: None,
-            reader: None,
+            options: scan::Options::default(),
+            scan: None,
         }
     }
 
-    pub fn with_filter(mut self, filter: Expression) -> Self {
-        self.filter.replace(filter);
+    pub fn with_filter(self, filter: Expression) -> Self {
+        self.with_file_filter(move |_| filter.clone())
+    }
+
+    /// Filter depending on the fields of a file, e.g. a comparison with a field older files
+    /// lack can't match any of their rows.
+    pub fn with_file_filter(
+        mut self,
+        filter: impl Fn(&dyn Fn(&str) -> bool) -> Expression + Send + Sync + 'static,
+    ) -> Self {
+        self.filter.replace(Arc::new(filter));
         self
     }
 
@@ -79,52 +304,56 @@ impl<'a> Read<'a> {
         self
     }
 
-    async fn init_reader(&mut self) {
-        let Format::Vortex { session, runtime } = self.format else {
-            unreachable!();
-        };
-
-        let file = &self.files[self.index];
-        let file = session
-            .open_options()
-            .open_path(file)
-            .await
-            .expect("vortex.open");
+    /// Number of files scanned at once, defaults to [scan::parallelism].
+    pub fn with_parallelism(mut self, parallelism: usize) -> Self {
+        self.options.parallelism = parallelism;
+        self
+    }
 
-        let mut scan = file.scan().unwrap();
+    /// Yields arrays in the order of files instead of the order they are scanned in.
+    pub fn ordered(mut self) -> Self {
+        self.options.ordered = true;
+        self
+    }
 
-        if let Some(ref filter) = self.filter {
-            scan = scan.with_filter(filter.clone());
-        }
+    /// Stops once `limit` rows were read.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.options.limit = Some(limit);
+        self
+    }
 
-        if let Some(ref projection) = self.projection {
-            scan = scan.with_projection(projection.clone());
-        }
+    fn start(&mut self) -> Scan<Scanned> {
+        let format = self.format.clone();
+        let filter = self.filter.clone();
+        let projection = self.projection.clone();
 
-        let iter = scan.into_iter(runtime).unwrap();
-        let _ = self.reader.replace(Box::new(iter));
+        Scan::new(
+            std::mem::take(&mut self.files),
+            self.options,
+            |scanned| scanned.as_ref().map_or(0, |arr| arr.len()),
+            move |path, tx| {
+                scan_vortex_file(format.clone(), path, filter.clone(), projection.clone(), tx)
+            },
+        )
     }
 
+    /// Next array, unreadable files are logged and skipped.
     pub async fn next_batch(&mut self) -> Option<Array<Struct>> {
-        tracing::info!(idx = self.index, len = self.files.len(), "next_batch");
-
-        while self.index < self.files.len() {
-            if self.reader.is_none() {
-                self.init_reader().await;
-            }
-
-            let next = self.reader.as_mut().expect("reader.exists").next();
-
-            if next.is_none() {
-                self.index += 1;
-                self.reader = None;
-                continue;
+        loop {
+            if let Ok(arr) = self.try_next_batch().await? {
+                return Some(arr);
             }
+        }
+    }
 
-            return Some(next.unwrap().expect("vortex.item.ok").downcast::<Struct>());
+    /// Next array or the error of a file which couldn't be read, reading continues with the
+    /// other files.
+    pub async fn try_next_batch(&mut self) -> Option<Scanned> {
+        if self.scan.is_none() {
+            self.scan = Some(self.start());
         }
 
-        None
+        self.scan.as_mut().expect("scan.exists").next().await
     }
 }
 
@@ -184,3 +413,59 @@ impl<'a> Read<'a> {
 //
 //     set
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load::{Generator, Options, ingest};
+    use crate::testing;
+
+    #[test]
+    fn unreadable_files_are_reported() {
+        let format = Format::vortex();
+        let Format::Vortex { session, .. } = &format else {
+            unreachable!();
+        };
+
+        let spans = Generator::new(Options::default()).trace(0);
+        let len = spans.len();
+        let dir = testing::temp_dir("vortex-read");
+
+        let rt = testing::runtime();
+        rt.block_on(ingest(
+            &format,
+            dir.clone().into_boxed_path(),
+            spans,
+            testing::writer_options(),
+        ));
+        let good = crate::misc::load_existing_files(&dir, &[format.file_prefix()]);
+        assert_eq!(good.len(), 1);
+
+        let corrupt = dir.join("corrupt").into_boxed_path();
+        std::fs::write(&corrupt, b"not a vortex file").unwrap();
+        let missing = dir.join("missing").into_boxed_path();
+
+        rt.block_on(async {
+            let files = vec![corrupt.clone(), good[0].clone(), missing.clone()];
+            let mut read = Read::new(&format, files).ordered();
+            let mut failed = Vec::new();
+            let mut rows = 0;
+
+            while let Some(next) = read.try_next_batch().await {
+                match next {
+                    Ok(arr) => rows += arr.len(),
+                    Err(e) => failed.push(e.path),
+                }
+            }
+            assert_eq!(rows, len);
+            assert_eq!(failed, vec![corrupt.clone(), missing.clone()]);
+
+            let (rows, _, _) = file_summary(session, &good[0]).await.unwrap();
+            assert_eq!(rows, len as u64);
+            assert!(file_summary(session, &corrupt).await.is_err());
+            assert!(file_summary(session, &missing).await.is_err());
+        });
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -6,10 +6,14 @@ use vortex::file::WriteOptionsSessionExt;
 use vortex::io::runtime::current::CurrentThreadRuntime;
 use vortex::session::VortexSession;
 
+use std::path::Path;
+
 use crate::{SpanWriter, Stats};
 
 pub struct Writer<'a> {
     file_id: usize,
+    /// Directory holding the files of this writer.
+    dir: Box<Path>,
     stats: Stats,
     dtype: DType,
     writes: usize,
@@ -20,17 +24,16 @@ pub struct Writer<'a> {
 }
 
 impl<'a> Writer<'a> {
-    const DIR: &'static str = "data-vortex";
-    const PREF: &'static str = "spaniel-live-vortex-";
+    pub const PREF: &'static str = "spaniel-live-vortex-";
 
     fn init_file_id(&mut self) {
-        self.file_id = crate::misc::get_next_file_id(Self::DIR, Self::PREF);
+        self.file_id = crate::misc::get_next_file_id(&self.dir, Self::PREF);
     }
 
     async fn create_new_writer(&mut self) {
         self.close_writer().await;
 
-        let file_path = format!("{}/{}{}", Self::DIR, Self::PREF, self.file_id);
+        let file_path = self.dir.join(format!("{}{}", Self::PREF, self.file_id));
 
         let writer = self
             .session
@@ -47,6 +50,8 @@ impl<'a> Writer<'a> {
         if let Some(writer) = self.writer.take() {
             let result = writer.finish().expect("writer.finish.ok");
             tracing::info!(len = result.footer().row_count(), "writer.finish");
+
+            self.writes = 0;
             self.stats.end_dirty_file().await;
         }
     }
@@ -60,17 +65,21 @@ impl<'a> Writer<'a> {
     pub fn new(
         session: &'a VortexSession,
         rt: &'a CurrentThreadRuntime,
+        dir: Box<Path>,
         spans_per_file: usize,
     ) -> Writer<'a> {
+        std::fs::create_dir_all(&dir).expect("writer.dir.create");
+
         Self {
             file_id: 0,
+            stats: Stats::new(&dir),
+            dir,
             threshold: spans_per_file,
             dtype: super::build::create_struct_dtype(),
             session,
             runtime: rt,
             writes: 0,
             writer: None,
-            stats: Stats::new(Self::DIR, Self::PREF),
         }
     }
 
@@ -120,6 +129,10 @@ impl<'a> SpanWriter for Writer<'a> {
         self.close_writer().await;
     }
 
+    async fn compact(&mut self) {
+        tracing::warn!("compact.unsupported");
+    }
+
     async fn finish(mut self) {
         self.close_writer().await;
     }
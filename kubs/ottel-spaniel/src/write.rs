@@ -1,5 +1,7 @@
+use std::collections::HashSet;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
 use std::time::Duration;
 
 use vortex::io::runtime::current::CurrentThreadRuntime;
@@ -8,6 +10,9 @@ use vortex::session::VortexSession;
 use tokio::sync::RwLock;
 use tokio::sync::{mpsc, oneshot};
 
+/// Default directory of stored files, shared by both formats so switching keeps history readable.
+pub const DATA_DIR: &str = "data";
+
 #[derive(Clone)]
 pub enum Format {
     /// Store the span data using Arrow/Parquet.
@@ -19,6 +24,149 @@ pub enum Format {
     },
 }
 
+impl Format {
+    /// Creates Vortex format with a session bound to a new current thread runtime.
+    pub fn vortex() -> Self {
+        use vortex::VortexSessionDefault;
+        use vortex::io::runtime::BlockingRuntime;
+        use vortex::io::session::RuntimeSessionExt;
+
+        let runtime = CurrentThreadRuntime::new();
+        let session = VortexSession::default().with_handle(runtime.handle());
+
+        Format::Vortex { runtime, session }
+    }
+
+    /// Format of the files created by the writer of this format.
+    pub fn file_format(&self) -> FileFormat {
+        match self {
+            Format::Arrow => FileFormat::Arrow,
+            Format::Vortex { .. } => FileFormat::Vortex,
+        }
+    }
+
+    /// Format able to read files of `file_format`.
+    ///
+    /// Reading Vortex files next to an Arrow writer needs a session, it is created on first use.
+    pub fn reader(&self, file_format: FileFormat) -> &Format {
+        static ARROW: Format = Format::Arrow;
+        static VORTEX: OnceLock<Format> = OnceLock::new();
+
+        if self.file_format() == file_format {
+            return self;
+        }
+
+        match file_format {
+            FileFormat::Arrow => &ARROW,
+            FileFormat::Vortex => VORTEX.get_or_init(Format::vortex),
+        }
+    }
+
+    /// Groups files by their format, the order of files within a group is kept.
+    pub fn split(&self, files: Vec<Box<Path>>) -> Vec<(&Format, Vec<Box<Path>>)> {
+        let mut groups: Vec<(FileFormat, Vec<Box<Path>>)> = Vec::new();
+
+        for file in files {
+            let Some(file_format) = FileFormat::of(&file) else {
+                continue;
+            };
+
+            match groups.iter_mut().find(|(f, _)| *f == file_format) {
+                Some((_, group)) => group.push(file),
+                None => groups.push((file_format, vec![file])),
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|(file_format, files)| (self.reader(file_format), files))
+            .collect()
+    }
+
+    /// Reads row count and time range of a closed file, fails when its footer can't be read.
+    pub async fn file_info(
+        &self,
+        path: Box<Path>,
+        stats: &Stats,
+    ) -> Result<FileInfo, Box<dyn std::error::Error + Send + Sync>> {
+        let location = stats.location(&path).await;
+        let size = std::fs::metadata(&path).ok().map(|meta| meta.len());
+        let file_format = FileFormat::of(&path).expect("file.format");
+
+        let (rows, time_start, time_end) = match self.reader(file_format) {
+            Format::Arrow => crate::arrow::file_summary(path.clone()).await?,
+            Format::Vortex { session, .. } => crate::vortex::file_summary(session, &path).await?,
+        };
+
+        Ok(FileInfo {
+            path,
+            location,
+            size,
+            rows,
+            time_start,
+            time_end,
+        })
+    }
+
+    /// Prefix of the file names created by the writer of this format.
+    pub fn file_prefix(&self) -> &'static str {
+        match self {
+            Format::Arrow => crate::arrow::Writer::PREF,
+            Format::Vortex { .. } => crate::vortex::Writer::PREF,
+        }
+    }
+}
+
+/// Format of a single data file, told by the prefix of its name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FileFormat {
+    Arrow,
+    Vortex,
+}
+
+impl FileFormat {
+    pub const ALL: [FileFormat; 2] = [FileFormat::Arrow, FileFormat::Vortex];
+
+    pub fn prefix(self) -> &'static str {
+        match self {
+            FileFormat::Arrow => crate::arrow::Writer::PREF,
+            FileFormat::Vortex => crate::vortex::Writer::PREF,
+        }
+    }
+
+    pub fn prefixes() -> [&'static str; 2] {
+        Self::ALL.map(Self::prefix)
+    }
+
+    /// Files of this format, in the same order.
+    pub fn only(self, files: Vec<Box<Path>>) -> Vec<Box<Path>> {
+        files
+            .into_iter()
+            .filter(|file| Self::of(file) == Some(self))
+            .collect()
+    }
+
+    pub fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+
+        Self::ALL
+            .into_iter()
+            .find(|file_format| name.starts_with(file_format.prefix()))
+    }
+}
+
+impl std::str::FromStr for FileFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "arrow" => Ok(FileFormat::Arrow),
+            "vortex" => Ok(FileFormat::Vortex),
+            other => Err(format!("unknown format: {other}")),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Options {
     /// Interval at which data buffered by Bulider should be passed down to Writer.
@@ -45,16 +193,111 @@ pub struct Stats {
     dirty_file: Arc<RwLock<Option<Box<Path>>>>,
     /// Files available for reading.
     pub files: Arc<RwLock<Vec<Box<Path>>>>,
+    /// Files uploaded to the object store, local copies may already be deleted.
+    pub remote: Arc<RwLock<HashSet<Box<Path>>>>,
+    /// Set while the writer future accepts messages.
+    running: Arc<AtomicBool>,
+}
+
+/// Summary of a closed file, see [Format::file_info].
+#[derive(Clone, Debug)]
+pub struct FileInfo {
+    pub path: Box<Path>,
+    pub location: Location,
+    /// Size of the local copy.
+    pub size: Option<u64>,
+    pub rows: u64,
+    /// Earliest span start, if the format keeps column statistics.
+    pub time_start: Option<u64>,
+    /// Latest span end, if the format keeps column statistics.
+    pub time_end: Option<u64>,
+}
+
+/// Where the data of a file can be read from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Location {
+    Local,
+    /// Uploaded and still available locally.
+    Both,
+    Remote,
 }
 
 impl Stats {
-    pub fn new(dir: impl AsRef<Path>, prefix: &str) -> Self {
+    /// Loads existing files of every format, so that switching formats keeps old data readable.
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        let prefixes = FileFormat::prefixes();
+        let remote = crate::misc::read_remote_manifest(dir.as_ref())
+            .into_iter()
+            .filter(|name| prefixes.iter().any(|prefix| name.starts_with(prefix)))
+            .map(|name| dir.as_ref().join(name).into_boxed_path())
+            .collect();
+
         Self {
             dirty_file: Arc::new(RwLock::new(None)),
-            files: Arc::new(RwLock::new(crate::misc::load_existing_files(dir, prefix))),
+            files: Arc::new(RwLock::new(crate::misc::load_existing_files(
+                &dir, &prefixes,
+            ))),
+            remote: Arc::new(RwLock::new(remote)),
+            running: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// Existing files are loaded on creation, so this only waits for the writer.
+    pub fn is_ready(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    /// Replaces compacted files with the file they were merged into.
+    /// Lists `new` instead of `old`, unless one of `old` was uploaded meanwhile, see
+    /// [Stats::set_uploaded].
+    pub async fn replace_files(&self, old: &[Box<Path>], new: impl AsRef<Path>) -> bool {
+        let mut files = self.files.write().await;
+        let remote = self.remote.read().await;
+
+        if old.iter().any(|file| remote.contains(file)) {
+            return false;
+        }
+
+        files.retain(|file| !old.contains(file));
+        files.push(new.as_ref().to_path_buf().into());
+
+        true
+    }
+
+    /// Records an uploaded file in the remote manifest, unless it was replaced meanwhile, see
+    /// [Stats::replace_files].
+    pub async fn set_uploaded(&self, path: &Path) -> bool {
+        let files = self.files.write().await;
+
+        if !files.iter().any(|file| **file == *path) {
+            return false;
+        }
+
+        let name = path.file_name().expect("file.name").to_string_lossy();
+        crate::misc::append_remote_manifest(path.parent().expect("file.dir"), &name);
+        self.remote.write().await.insert(path.into());
+
+        true
+    }
+
+    pub async fn location(&self, path: &Path) -> Location {
+        let uploaded = self.remote.read().await.contains(path);
+
+        match (uploaded, path.exists()) {
+            (false, _) => Location::Local,
+            (true, true) => Location::Both,
+            (true, false) => Location::Remote,
+        }
+    }
+
+    /// Number of files being written and of files available for reading.
+    pub async fn file_counts(&self) -> (usize, usize) {
+        let open = self.dirty_file.read().await.is_some() as usize;
+        let closed = self.files.read().await.len();
+
+        (open, closed)
+    }
+
     pub async fn append_files(&self, add: &[impl AsRef<Path>]) {
         let to_add: Vec<Box<Path>> = add
             .iter()
@@ -76,20 +319,42 @@ impl Stats {
         }
     }
 
+    /// Called by writers once the dirty file is closed, its size includes the footer then.
     pub async fn end_dirty_file(&self) {
         let old = self.dirty_file.write().await.take();
 
         if let Some(old) = old {
+            record_closed(old.clone()).await;
             let mut files = self.files.write().await;
             files.push(old);
         }
     }
 }
 
+async fn record_closed(path: Box<Path>) {
+    let Ok(Ok(meta)) = tokio::task::spawn_blocking(move || std::fs::metadata(path)).await else {
+        return;
+    };
+
+    crate::metrics::WRITER.written_bytes.add(meta.len());
+    crate::metrics::WRITER.file_bytes.observe(meta.len() as f64);
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Write(Vec<crate::SpanData>),
+    /// Writes buffered spans without waiting for the flush interval.
+    Flush,
+    /// Writes buffered spans and closes the current file, the next write starts a new one.
+    Rotate,
+    /// Rotates and merges small closed files.
+    Compact,
+}
+
 #[derive(Debug)]
 pub struct Message {
     on_done: oneshot::Sender<()>,
-    pub data: Vec<crate::SpanData>,
+    pub command: Command,
 }
 
 #[derive(Clone, Debug)]
@@ -100,11 +365,16 @@ pub struct Sink {
 impl Sink {
     /// Sends [crate::SpanData] to be written to storage.
     pub async fn send(&self, data: Vec<crate::SpanData>) {
+        self.command(Command::Write(data)).await;
+    }
+
+    /// Sends a command and waits until the writer completes it.
+    pub async fn command(&self, command: Command) {
         let (send, recv) = oneshot::channel::<()>();
 
         self.sender
             .send(Message {
-                data,
+                command,
                 on_done: send,
             })
             .await
@@ -112,6 +382,11 @@ impl Sink {
 
         recv.await.expect("oneshot.recv");
     }
+
+    /// Number of messages waiting for the writer.
+    pub fn queued(&self) -> usize {
+        self.sender.max_capacity() - self.sender.capacity()
+    }
 }
 
 pub trait SpanWriter {
@@ -121,6 +396,8 @@ pub trait SpanWriter {
     fn stats(&self) -> &Stats;
     fn write(&mut self, data: Self::Input) -> impl Future<Output = ()>;
     fn suspend(&mut self) -> impl Future<Output = ()>;
+    /// Merges small closed files, the current file has to be closed.
+    fn compact(&mut self) -> impl Future<Output = ()>;
     fn finish(self) -> impl Future<Output = ()>;
 }
 
@@ -132,6 +409,22 @@ pub trait SpanBuilder {
     fn build(&mut self) -> Self::Output;
 }
 
+async fn flush<T, W, B>(writer: &mut W, builder: &mut B)
+where
+    W: SpanWriter<Input = T>,
+    B: SpanBuilder<Output = T>,
+{
+    let start = std::time::Instant::now();
+    let size = builder.size();
+
+    writer.write(builder.build()).await;
+
+    crate::metrics::WRITER.written_spans.add(size as u64);
+    crate::metrics::WRITER
+        .flush_seconds
+        .observe(start.elapsed().as_secs_f64());
+}
+
 async fn run_writer<T, W, B>(
     mut writer: W,
     mut builder: B,
@@ -151,6 +444,7 @@ async fn run_writer<T, W, B>(
     };
 
     let mut times_since_last_action = 1;
+    writer.stats().running.store(true, Ordering::Relaxed);
 
     'forever: loop {
         let interval = if times_since_last_action >= options.suspend_after {
@@ -168,8 +462,28 @@ async fn run_writer<T, W, B>(
                 waitlist.push(message.on_done);
                 times_since_last_action = 0;
 
-                if builder.append(message.data) {
-                    writer.write(builder.build()).await;
+                let data = match message.command {
+                    Command::Write(data) => data,
+                    command => {
+                        if builder.size() > 0 {
+                            flush(&mut writer, &mut builder).await;
+                        }
+
+                        if let Command::Rotate | Command::Compact = command {
+                            writer.suspend().await;
+                        }
+
+                        if let Command::Compact = command {
+                            writer.compact().await;
+                        }
+
+                        done(&mut waitlist);
+                        continue 'forever;
+                    }
+                };
+
+                if builder.append(data) {
+                    flush(&mut writer, &mut builder).await;
                     done(&mut waitlist);
                 }
             }
@@ -182,7 +496,7 @@ async fn run_writer<T, W, B>(
                     "No new messages",
                 );
                 if builder.size() > 0 {
-                    writer.write(builder.build()).await;
+                    flush(&mut writer, &mut builder).await;
                     done(&mut waitlist);
                 } else {
                     times_since_last_action += 1;
@@ -195,14 +509,17 @@ async fn run_writer<T, W, B>(
         }
     }
 
+    writer.stats().running.store(false, Ordering::Relaxed);
     writer.finish().await;
 
     assert!(rx.is_empty());
     assert!(rx.is_closed());
 }
 
+/// Starts a writer storing files in `dir`, usually [DATA_DIR] or a tenant directory inside of it.
 pub fn start_writer(
     format: &Format,
+    dir: Box<Path>,
     options: Options,
 ) -> (Sink, Stats, Box<dyn Future<Output = ()> + '_>) {
     let (tx, rx) = mpsc::channel(options.sink_channel_size);
@@ -211,7 +528,7 @@ pub fn start_writer(
     if let Format::Arrow = format {
         use crate::arrow::*;
 
-        let writer = Writer::new(options.spans_per_file);
+        let writer = Writer::new(dir, options.spans_per_file);
         let stats = writer.stats().clone();
         let builder = Builder::new(options.builder_flush_threshold, options.builder_capacity);
         let job = run_writer(writer, builder, rx, options);
@@ -225,7 +542,7 @@ pub fn start_writer(
 
     use crate::vortex::*;
 
-    let writer = Writer::new(session, runtime, options.spans_per_file);
+    let writer = Writer::new(session, runtime, dir, options.spans_per_file);
     let stats = writer.stats().clone();
     let builder = Builder::new(options.builder_flush_threshold, options.builder_capacity);
     let job = run_writer(writer, builder, rx, options);
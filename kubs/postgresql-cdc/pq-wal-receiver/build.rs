@@ -1,19 +1,39 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::env;
 
+/// Output of `pg_config <arg>`, used when no postgres checkout was built next to the crate.
+fn pg_config(arg: &str) -> String {
+    let output = std::process::Command::new("pg_config")
+        .arg(arg)
+        .output()
+        .expect("pg_config not found, build ../postgres or install the libpq development files");
+
+    String::from_utf8(output.stdout).unwrap().trim().to_owned()
+}
+
 fn main() {
-    println!("cargo:rustc-link-lib=pgport");
-    println!("cargo:rustc-link-lib=pgcommon");
-    println!("cargo:rustc-link-lib=pq");
-
-    println!("cargo:rustc-link-search=../postgres/src/port");
-    println!("cargo:rustc-link-search=../postgres/src/common");
-    println!("cargo:rustc-link-search=../postgres/src/interfaces/libpq");
-
-    bindgen::Builder::default()
-        .header("./src/libpq-in.h")
-        .clang_arg("-I../postgres/src/interfaces/libpq/")
-        .clang_arg("-I../postgres/src/include/")
+    let mut bindings = bindgen::Builder::default().header("./src/libpq-in.h");
+
+    if Path::new("../postgres/src/interfaces/libpq").exists() {
+        println!("cargo:rustc-link-lib=pgport");
+        println!("cargo:rustc-link-lib=pgcommon");
+        println!("cargo:rustc-link-lib=pq");
+
+        println!("cargo:rustc-link-search=../postgres/src/port");
+        println!("cargo:rustc-link-search=../postgres/src/common");
+        println!("cargo:rustc-link-search=../postgres/src/interfaces/libpq");
+
+        bindings = bindings
+            .clang_arg("-I../postgres/src/interfaces/libpq/")
+            .clang_arg("-I../postgres/src/include/");
+    } else {
+        println!("cargo:rustc-link-lib=pq");
+        println!("cargo:rustc-link-search={}", pg_config("--libdir"));
+
+        bindings = bindings.clang_arg(format!("-I{}", pg_config("--includedir")));
+    }
+
+    bindings
         .newtype_enum("ConnStatusType")
         .newtype_enum("ExecStatusType")
         .generate()
@@ -12,11 +12,15 @@ unsafe fn print_err(prefix: &str, err: *const i8) {
 }
 
 pub struct Connection {
-    info: std::ffi::CString,
     conn: *mut bindings::PGconn,
 }
 
 impl Connection {
+    /// Connects with a libpq connection string, exits the process when that fails.
+    ///
+    /// # Safety
+    ///
+    /// Calls into libpq, the connection must only be used from one thread at a time.
     pub unsafe fn new(connection_string: String) -> Self {
         use bindings::{PQconnectdb, PQstatus, ConnStatusType};
 
@@ -32,14 +36,14 @@ impl Connection {
                 std::process::exit(69);
             }
 
-            Self {
-                info: conn_info,
-                conn,
-            }
+            Self { conn }
         }
     }
 
-    pub unsafe fn exec_unchecked<'a, 't>(&'a self, query: &std::ffi::CStr) -> Tuples<'t> {
+    /// # Safety
+    ///
+    /// The result must not be used after the connection is dropped.
+    pub unsafe fn exec_unchecked<'t>(&self, query: &std::ffi::CStr) -> Tuples<'t> {
         unsafe {
             let result = bindings::PQexec(self.conn, query.as_ptr());
             let status = bindings::PQresultStatus(result);
@@ -48,11 +52,17 @@ impl Connection {
         }
     }
 
+    /// # Safety
+    ///
+    /// Reads the error message libpq keeps for the connection.
     pub unsafe fn get_err_owned(&self) -> String {
         format!("Error: {:?}", unsafe { std::ffi::CStr::from_ptr(bindings::PQerrorMessage(self.conn)) })
     }
     
-    pub unsafe fn exec<'a, 't>(&'a self, query: &std::ffi::CStr) -> Result<Tuples<'t>, String> {
+    /// # Safety
+    ///
+    /// The result must not be used after the connection is dropped.
+    pub unsafe fn exec<'t>(&self, query: &std::ffi::CStr) -> Result<Tuples<'t>, String> {
         use bindings::ExecStatusType;
 
         let result = unsafe {
@@ -69,6 +79,9 @@ impl Connection {
         }
     }
 
+    /// # Safety
+    ///
+    /// The connection has to be in COPY IN or COPY BOTH mode.
     pub unsafe fn put_copy_msg(&self, data: &[u8]) -> Result<std::ffi::c_int, String> {
         unsafe {
             let result = bindings::PQputCopyData(
@@ -82,11 +95,22 @@ impl Connection {
                 return Err(self.get_err_owned());
             }
 
+            // Copy data is buffered by libpq, replies have to reach the server right away.
+            if bindings::PQflush(self.conn) != 0 {
+                return Err(self.get_err_owned());
+            }
+
             Ok(result)
         }
     }
 
-    pub unsafe fn get_copy_msg(&self) -> Result<(std::ffi::c_int, *mut std::ffi::c_char), String> {
+    /// Next message of a COPY, `None` once the server ended it.
+    ///
+    /// # Safety
+    ///
+    /// The connection has to be in COPY OUT or COPY BOTH mode, a returned buffer has to be freed
+    /// with [`Self::free_copy_msg`].
+    pub unsafe fn get_copy_msg(&self) -> Result<Option<(std::ffi::c_int, *mut std::ffi::c_char)>, String> {
         let mut buf: *mut std::ffi::c_char = std::ptr::null_mut();
 
         unsafe {
@@ -101,11 +125,77 @@ impl Connection {
             }
 
             if rawlen == -1 {
-                unimplemented!();
+                self.end_copy()?;
+                return Ok(None);
+            }
+
+            Ok(Some((rawlen, buf)))
+        }
+    }
+
+    /// Reads the results following a COPY ended by the server, answering its CopyDone while the
+    /// copy is still open in this direction, like `libpqrcv_receive` of the walreceiver.
+    unsafe fn end_copy(&self) -> Result<(), String> {
+        use bindings::ExecStatusType;
+
+        loop {
+            let result = unsafe {
+                let result = bindings::PQgetResult(self.conn);
+
+                if result.is_null() {
+                    return Ok(());
+                }
+
+                Tuples::new(bindings::PQresultStatus(result), result)
+            };
+
+            match result.status {
+                ExecStatusType::PGRES_COPY_IN => unsafe {
+                    if bindings::PQputCopyEnd(self.conn, std::ptr::null()) <= 0 || bindings::PQflush(self.conn) != 0 {
+                        return Err(self.get_err_owned());
+                    }
+                },
+                ExecStatusType::PGRES_COMMAND_OK | ExecStatusType::PGRES_TUPLES_OK => {},
+                _ => return Err(unsafe { self.get_err_owned() }),
+            }
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `buf` has to come from [`Self::get_copy_msg`] and must not be used afterwards.
+    pub unsafe fn free_copy_msg(&self, buf: *mut std::ffi::c_char) {
+        unsafe {
+            bindings::PQfreemem(buf as *mut std::ffi::c_void);
+        }
+    }
+
+    /// Waits until the server sends something or `timeout` passes, forever without a timeout, and
+    /// reads what arrived so `get_copy_msg` can return it.
+    pub fn wait_readable(&self, timeout: Option<std::time::Duration>) -> Result<(), String> {
+        let mut fd = libc::pollfd {
+            fd: unsafe { bindings::PQsocket(self.conn) },
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = match timeout {
+            Some(t) => t.as_millis().min(i32::MAX as u128) as i32,
+            None => -1,
+        };
+
+        if unsafe { libc::poll(&mut fd, 1, timeout) } < 0 {
+            let err = std::io::Error::last_os_error();
+
+            if err.kind() != std::io::ErrorKind::Interrupted {
+                return Err(format!("poll: {err}"));
             }
+        }
 
-            return Ok((rawlen, buf));
+        if 0 == unsafe { bindings::PQconsumeInput(self.conn) } {
+            return Err(unsafe { self.get_err_owned() });
         }
+
+        Ok(())
     }
 
     pub fn check_notifies(&self) {
@@ -116,7 +206,7 @@ impl Connection {
 
         let notifies = unsafe { bindings::PQnotifies(self.conn) };
 
-        if notifies == std::ptr::null_mut() {
+        if notifies.is_null() {
             return;
         }
 
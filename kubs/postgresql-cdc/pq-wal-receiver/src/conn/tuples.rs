@@ -10,6 +10,9 @@ pub struct Tuples<'a> {
 }
 
 impl Tuples<'_> {
+    /// # Safety
+    ///
+    /// `pg_result` has to be a result returned by libpq, it is cleared once dropped.
     pub unsafe fn new(status: bindings::ExecStatusType, pg_result: *mut bindings::PGresult) -> Self {
         unsafe {
             Self {
@@ -27,7 +30,7 @@ impl Tuples<'_> {
 
         for i in 0..self.num_attrs {
             let name = unsafe {
-                std::ffi::CStr::from_ptr(bindings::PQfname(self.result, i as i32))
+                std::ffi::CStr::from_ptr(bindings::PQfname(self.result, i))
             };
 
             if attr_name == name {
@@ -40,7 +43,6 @@ impl Tuples<'_> {
 
     pub fn get_attr_names(&self) -> Vec<String> {
         assert!(self.num_attrs > 0);
-        assert!(self.num_attrs <= std::i32::MAX);
 
         let mut attrs = Vec::with_capacity(self.num_attrs as usize /* SAFETY: assertions above */);
         
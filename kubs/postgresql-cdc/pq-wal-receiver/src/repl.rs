@@ -1,14 +1,21 @@
-/// https://www.postgresql.org/docs/current/protocol-replication.html
+//! https://www.postgresql.org/docs/current/protocol-replication.html
 
 use crate::conn::tuples::{Tuples, Tuple};
 
 mod cmd;
 mod wal_level;
 mod msg;
+mod status;
+pub mod pgoutput;
+pub mod value;
+pub mod change;
 
-use cmd::Cmd;
 use wal_level::WalLevel;
-use msg::CopyMessage;
+pub use msg::CopyMessage;
+use pgoutput::Lsn;
+
+/// Version of the pgoutput protocol requested by START_REPLICATION.
+const PROTO_VERSION: u8 = 4;
 
 pub mod system_id {
     use super::Tuple;
@@ -33,6 +40,7 @@ pub mod system_id {
 pub struct Replication {
     connection: crate::conn::Connection,
     slot_name: String,
+    progress: status::Progress,
 }
 
 impl Replication {
@@ -40,16 +48,28 @@ impl Replication {
         Self {
             connection,
             slot_name,
+            progress: Default::default(),
         }
     }
 
+    /// Confirms that everything up to `lsn` is durably processed, reported with the next status update.
+    pub fn ack(&self, lsn: Lsn) {
+        self.progress.ack(lsn);
+    }
+
+    /// Interval of periodic status updates, `None` when they are disabled.
+    pub fn get_status_interval(&self) -> Result<Option<std::time::Duration>, String> {
+        let result = unsafe { self.connection.exec(c"SHOW wal_receiver_status_interval;")? };
+
+        assert!(result.num_attrs == 1);
+        assert!(result.num_tuples == 1);
+
+        let value = result.get_attr_value(0, 0).to_str().map_err(|e| e.to_string())?;
+        parse_interval(value)
+    }
+
     pub fn get_wal_level(&self) -> Result<WalLevel, String> {
-        let result = unsafe {
-            match self.connection.exec(c"SHOW wal_level;") {
-                Ok(r) => r,
-                Err(msg) => return Err(msg),
-            }
-        };
+        let result = unsafe { self.connection.exec(c"SHOW wal_level;")? };
 
         assert!(result.num_attrs == 1);
         assert!(result.num_tuples == 1);
@@ -60,10 +80,7 @@ impl Replication {
     pub fn create_repl_slot<'r, 's: 'r>(&'s self) -> Result<Tuples<'r>, String> {
         let result = unsafe {
             let cmd = format!("CREATE_REPLICATION_SLOT {} TEMPORARY LOGICAL pgoutput;\0", self.slot_name);
-            match self.connection.exec(std::ffi::CStr::from_bytes_with_nul(cmd.as_bytes()).unwrap()) {
-                Ok(r) => r,
-                Err(msg) => return Err(msg),
-            }
+            self.connection.exec(std::ffi::CStr::from_bytes_with_nul(cmd.as_bytes()).unwrap())?
         };
 
         assert!(result.num_attrs == 4);
@@ -75,10 +92,7 @@ impl Replication {
     pub fn get_repl_slots<'r, 's: 'r>(&'s self) -> Result<Tuples<'r>, String> {
         let result = unsafe {
             let cmd = format!("SELECT * FROM pg_replication_slots WHERE slot_name = '{}'\0", self.slot_name);
-            match self.connection.exec(std::ffi::CStr::from_bytes_with_nul(cmd.as_bytes()).unwrap()) {
-                Ok(r) => r,
-                Err(msg) => return Err(msg),
-            }
+            self.connection.exec(std::ffi::CStr::from_bytes_with_nul(cmd.as_bytes()).unwrap())?
         };
 
         assert!(result.num_tuples == 1);
@@ -87,12 +101,7 @@ impl Replication {
     }
 
     pub fn identify_system<'r, 's: 'r>(&'s self) -> Result<Tuples<'r>, String> {
-        let result = unsafe {
-            match self.connection.exec(c"IDENTIFY_SYSTEM") {
-                Ok(r) => r,
-                Err(msg) => return Err(msg),
-            }
-        };
+        let result = unsafe { self.connection.exec(c"IDENTIFY_SYSTEM")? };
 
         assert!(result.num_attrs == 4);
         assert!(result.num_tuples == 1);
@@ -103,33 +112,27 @@ impl Replication {
     pub fn create_publication(&self) -> Result<(), String> {
         let existing_pub = unsafe {
             let cmd = format!("SELECT pubname FROM pg_publication WHERE pubname = '{}_pub';\0", self.slot_name);  
-            match self.connection.exec(std::ffi::CStr::from_bytes_with_nul(cmd.as_bytes()).unwrap()) {
-                Ok(r) => r,
-                Err(msg) => return Err(msg),
-            }
+            self.connection.exec(std::ffi::CStr::from_bytes_with_nul(cmd.as_bytes()).unwrap())?
         };
 
         if existing_pub.num_tuples == 1 {
             return Ok(());
         }
 
-        let result = unsafe {
+        unsafe {
             let cmd = format!("CREATE PUBLICATION {}_pub FOR ALL TABLES;\0", self.slot_name);
-            match self.connection.exec(std::ffi::CStr::from_bytes_with_nul(cmd.as_bytes()).unwrap()) {
-                Ok(r) => r,
-                Err(msg) => return Err(msg),
-            }
-        };
+            self.connection.exec(std::ffi::CStr::from_bytes_with_nul(cmd.as_bytes()).unwrap())?;
+        }
 
         Ok(())
     }
 
-    fn start_replication(&self, restart_lsn: &std::ffi::CStr) -> Result<bool, String> {
-        let lsn = String::from_utf8(restart_lsn.to_bytes().to_owned()).unwrap();
+    fn start_replication(&self, lsn: &str) -> Result<bool, String> {
         let cmd = format!(
-            "START_REPLICATION SLOT {0} LOGICAL {1} (proto_version '4', streaming 'false', publication_names '{0}_pub')\0",
+            "START_REPLICATION SLOT {0} LOGICAL {1} (proto_version '{2}', streaming 'false', publication_names '{0}_pub')\0",
             self.slot_name,
             lsn,
+            PROTO_VERSION,
         );
         let result = unsafe {
             self.connection.exec_unchecked(std::ffi::CStr::from_bytes_with_nul(cmd.as_bytes()).unwrap())
@@ -142,38 +145,23 @@ impl Replication {
         }
     }
 
-    fn send_status(&self, request_reply: u8) -> Result<(), String> {
-        let get_timestamp = || {
-            let start = time::macros::utc_datetime!(2000-01-01 00:00);
-            let now = time::UtcDateTime::now();
-            (now - start).whole_microseconds() as i64
-        };
-        let mut cmd = Cmd::new();
-        cmd
-            .push_byte(b'r')
-            .push_i64(0)
-            .push_i64(0)
-            .push_i64(0)
-            .push_i64(get_timestamp())
-            .push_byte(request_reply)
-            .finish();
-        
-        let result = unsafe {
-            match self.connection.put_copy_msg(cmd.as_bytes()) {
-                Ok(r) => r,
-                Err(msg) => return Err(msg),
-            }
-        };
+    /// Standby Status Update carrying the acknowledged position.
+    fn send_status(&self, reply_requested: bool) -> Result<(), String> {
+        let cmd = self.progress.status_update(time::UtcDateTime::now(), reply_requested);
+
+        let result = unsafe { self.connection.put_copy_msg(cmd.as_bytes())? };
 
-        println!("send_status({request_reply}): {}", result);
         // ../../postgres/src/interfaces/libpq/fe-exec.c:2691
         assert!(result == 1);
 
         Ok(())
     }
 
-    pub fn start(&self) -> Result<(), String> {
+    /// Streams change events to `on_event`, which acknowledges them through [`Self::ack`] once
+    /// they are durably processed.
+    pub fn start(&self, mut on_event: impl FnMut(change::ChangeEvent)) -> Result<(), String> {
         assert!(WalLevel::Logical == self.get_wal_level().unwrap());
+        let interval = self.get_status_interval()?;
 
         self.create_repl_slot().unwrap();
         self.connection.check_notifies();
@@ -184,24 +172,57 @@ impl Replication {
         let restart_lsn = slot.get_attr_value(slot.get_attr_idx(c"restart_lsn"));
         println!("Slot: {:?}", slot);
         println!("Restart LSN: {:?}", restart_lsn);
+        let restart_lsn = restart_lsn.to_str().map_err(|e| e.to_string())?.to_owned();
 
         loop {
-            if self.start_replication(restart_lsn).unwrap() {
+            // A restarted stream continues after what the consumer acknowledged.
+            let start_lsn = match self.progress.flushed() {
+                0 => restart_lsn.clone(),
+                flushed => lsn_text(flushed),
+            };
+
+            if self.start_replication(&start_lsn)? {
                 // ../../postgres/src/backend/replication/walreceiver.c:437
-                self.send_status(1);
-                loop {
-                    unsafe {
-                        let Ok((len, buf)) = self.connection.get_copy_msg() else {
-                            unimplemented!();
-                        };
-                        if len == 0 {
-                            println!(">> No more copy data");
-                            break;
+                self.send_status(true)?;
+                let mut last_status = std::time::Instant::now();
+                let mut decoder = pgoutput::Decoder::new(PROTO_VERSION)?;
+                // Relations are sent again by every START_REPLICATION.
+                let mut changes = change::Changes::default();
+                // Ends once the server ended the stream, e.g. when its walsender is stopped.
+                while let Some((len, buf)) = unsafe { self.connection.get_copy_msg()? } {
+                    if len == 0 {
+                        let timeout = interval.map(|i| i.saturating_sub(last_status.elapsed()));
+                        self.connection.wait_readable(timeout)?;
+                    } else {
+                        let slice = unsafe { std::slice::from_raw_parts(buf as *const u8, len as usize) };
+                        let cm = CopyMessage::from_slice(slice, &mut decoder);
+                        unsafe { self.connection.free_copy_msg(buf) };
+
+                        match cm? {
+                            CopyMessage::Keepalive { end_of_wal, should_reply, .. } => {
+                                if !changes.in_transaction() {
+                                    self.progress.idle(end_of_wal);
+                                }
+
+                                if should_reply == 1 {
+                                    self.send_status(false)?;
+                                    last_status = std::time::Instant::now();
+                                }
+                            },
+                            CopyMessage::XLogData { wal_start, message, .. } => {
+                                self.progress.receive(wal_start);
+
+                                for event in changes.process(wal_start, &message)? {
+                                    self.progress.deliver(event.lsn);
+                                    on_event(event);
+                                }
+                            },
                         }
-                        let cm = CopyMessage::from_slice(std::slice::from_raw_parts(buf, len as usize));
-                        println!(">> {:?}", cm);
-                        println!(">> got copy_data: {} - header {:?}", len, (*buf as u8) as char);
-                        println!(">> {:?}", std::slice::from_raw_parts(buf, len as usize));
+                    }
+
+                    if interval.is_some_and(|i| last_status.elapsed() >= i) {
+                        self.send_status(false)?;
+                        last_status = std::time::Instant::now();
                     }
                 }
             } else {
@@ -210,7 +231,53 @@ impl Replication {
                 std::thread::sleep(std::time::Duration::from_secs(5));
             }
         }
+    }
+}
 
-        Ok(())
+/// Textual `X/X` form of a WAL position.
+fn lsn_text(lsn: Lsn) -> String {
+    format!("{:X}/{:X}", lsn >> 32, lsn & 0xffff_ffff)
+}
+
+/// Value of an integer setting measured in seconds, with or without a unit.
+fn parse_interval(value: &str) -> Result<Option<std::time::Duration>, String> {
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount.parse().map_err(|_| format!("Invalid interval: {value:?}"))?;
+
+    let ms = match unit.trim() {
+        "ms" => amount,
+        "" | "s" => amount * 1000,
+        "min" => amount * 60 * 1000,
+        "h" => amount * 60 * 60 * 1000,
+        "d" => amount * 24 * 60 * 60 * 1000,
+        _ => return Err(format!("Invalid interval: {value:?}")),
+    };
+
+    Ok(match ms {
+        0 => None,
+        ms => Some(std::time::Duration::from_millis(ms)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn status_interval() {
+        assert_eq!(parse_interval("10s"), Ok(Some(Duration::from_secs(10))));
+        assert_eq!(parse_interval("500ms"), Ok(Some(Duration::from_millis(500))));
+        assert_eq!(parse_interval("2min"), Ok(Some(Duration::from_secs(120))));
+        assert_eq!(parse_interval("0"), Ok(None));
+        assert!(parse_interval("-1").is_err());
+        assert!(parse_interval("10 fortnights").is_err());
+    }
+
+    #[test]
+    fn lsn() {
+        assert_eq!(lsn_text(0x1_a2b3c4d5), "1/A2B3C4D5");
+        assert_eq!(lsn_text(0x16_00000000), "16/0");
     }
 }
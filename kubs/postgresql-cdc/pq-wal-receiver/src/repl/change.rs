@@ -0,0 +1,380 @@
+//! Row changes assembled from pgoutput messages.
+//!
+//! Tuples only reference their table by OID, the server sends a Relation message before the
+//! first change of a table in a session and again whenever the table changes.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::pgoutput::{Column, Lsn, Message, OldTuple, Oid, ReplicaIdentity, TupleData, Xid};
+use super::value::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relation {
+    pub oid: Oid,
+    /// Empty for `pg_catalog`.
+    pub schema: String,
+    pub table: String,
+    pub columns: Vec<Column>,
+    pub replica_identity: ReplicaIdentity,
+}
+
+#[derive(Debug, Default)]
+pub struct RelationCache {
+    relations: HashMap<Oid, Arc<Relation>>,
+}
+
+impl RelationCache {
+    pub fn get(&self, oid: Oid) -> Option<&Arc<Relation>> {
+        self.relations.get(&oid)
+    }
+
+    /// Replaces the relation, changes decoded before keep their previous definition.
+    pub fn insert(&mut self, relation: Relation) -> Arc<Relation> {
+        let relation = Arc::new(relation);
+        self.relations.insert(relation.oid, relation.clone());
+        relation
+    }
+
+    pub fn len(&self) -> usize {
+        self.relations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.relations.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Insert,
+    Update,
+    Delete,
+    Truncate,
+}
+
+/// Values of a row by column name, in the order of the columns of its relation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub columns: Vec<(String, Value)>,
+}
+
+impl Row {
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.columns.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    /// Decodes `tuple`, only the replica identity columns when `key_only`.
+    fn decode(relation: &Relation, tuple: &TupleData, key_only: bool) -> Result<Self, String> {
+        if tuple.len() != relation.columns.len() {
+            return Err(format!(
+                "Tuple of {}.{} has {} columns, expected {}",
+                relation.schema, relation.table, tuple.len(), relation.columns.len()
+            ));
+        }
+
+        let columns = relation
+            .columns
+            .iter()
+            .zip(tuple)
+            .filter(|(column, _)| !key_only || column.is_key)
+            .map(|(column, value)| {
+                let value = Value::decode(column.type_oid, value)
+                    .map_err(|e| format!("{}.{}.{}: {e}", relation.schema, relation.table, column.name))?;
+                Ok((column.name.clone(), value))
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Self { columns })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeEvent {
+    /// Start of the WAL record of the change.
+    pub lsn: Lsn,
+    pub xid: Xid,
+    pub relation: Arc<Relation>,
+    pub op: Op,
+    /// Key columns, or every column with `REPLICA IDENTITY FULL`. Not sent for updates which keep
+    /// the key of a table without full identity.
+    pub old: Option<Row>,
+    pub new: Option<Row>,
+}
+
+/// Turns decoded messages into change events, following transactions and relations.
+#[derive(Debug, Default)]
+pub struct Changes {
+    relations: RelationCache,
+    /// Transaction of the current Begin or BeginPrepare.
+    xid: Option<Xid>,
+    /// Transaction of the current stream segment.
+    streamed: Option<Xid>,
+}
+
+impl Changes {
+    pub fn relations(&self) -> &RelationCache {
+        &self.relations
+    }
+
+    /// Whether a transaction was begun and not yet committed or prepared, or a stream segment
+    /// started and not yet stopped.
+    pub fn in_transaction(&self) -> bool {
+        self.xid.is_some() || self.streamed.is_some()
+    }
+
+    /// Events of `message`, which starts at `lsn`. Truncating several tables yields an event for
+    /// each of them.
+    pub fn process(&mut self, lsn: Lsn, message: &Message) -> Result<Vec<ChangeEvent>, String> {
+        let (xid, oid, op, old, new) = match message {
+            Message::Begin { xid, .. } | Message::BeginPrepare { xid, .. } => {
+                self.xid = Some(*xid);
+                return Ok(vec![]);
+            },
+            Message::StreamStart { xid, .. } => {
+                self.streamed = Some(*xid);
+                return Ok(vec![]);
+            },
+            // Streamed transactions end outside of a segment, which a missing StreamStop must not
+            // outlive.
+            Message::StreamStop | Message::StreamCommit { .. } | Message::StreamAbort { .. } => {
+                self.streamed = None;
+                return Ok(vec![]);
+            },
+            Message::Commit { .. } | Message::Prepare { .. } => {
+                self.xid = None;
+                return Ok(vec![]);
+            },
+            Message::CommitPrepared { .. } | Message::RollbackPrepared { .. } => {
+                self.xid = None;
+                self.streamed = None;
+                return Ok(vec![]);
+            },
+            Message::Relation { oid, namespace, name, replica_identity, columns, .. } => {
+                self.relations.insert(Relation {
+                    oid: *oid,
+                    schema: namespace.clone(),
+                    table: name.clone(),
+                    columns: columns.clone(),
+                    replica_identity: *replica_identity,
+                });
+                return Ok(vec![]);
+            },
+            Message::Truncate { xid, oids, .. } => {
+                let xid = self.current_xid(*xid)?;
+
+                return oids
+                    .iter()
+                    .map(|oid| {
+                        Ok(ChangeEvent {
+                            lsn,
+                            xid,
+                            relation: self.relation(*oid)?,
+                            op: Op::Truncate,
+                            old: None,
+                            new: None,
+                        })
+                    })
+                    .collect();
+            },
+            Message::Insert { xid, oid, new } => (xid, oid, Op::Insert, None, Some(new)),
+            Message::Update { xid, oid, old, new } => (xid, oid, Op::Update, old.as_ref(), Some(new)),
+            Message::Delete { xid, oid, old } => (xid, oid, Op::Delete, Some(old), None),
+            _ => return Ok(vec![]),
+        };
+
+        let xid = self.current_xid(*xid)?;
+        let relation = self.relation(*oid)?;
+
+        let old = match old {
+            Some(OldTuple::Key(tuple)) => Some(Row::decode(&relation, tuple, true)?),
+            Some(OldTuple::Full(tuple)) => Some(Row::decode(&relation, tuple, false)?),
+            None => None,
+        };
+        let new = new.map(|tuple| Row::decode(&relation, tuple, false)).transpose()?;
+
+        Ok(vec![ChangeEvent {
+            lsn,
+            xid,
+            relation,
+            op,
+            old,
+            new,
+        }])
+    }
+
+    fn current_xid(&self, xid: Option<Xid>) -> Result<Xid, String> {
+        xid.or(self.streamed).or(self.xid).ok_or("Change outside of a transaction".to_owned())
+    }
+
+    fn relation(&self, oid: Oid) -> Result<Arc<Relation>, String> {
+        self.relations.get(oid).cloned().ok_or(format!("Change of unknown relation {oid}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::pgoutput::{Decoder, TupleColumn};
+    use super::super::value::oid;
+
+    fn column(name: &str, type_oid: Oid, is_key: bool) -> Column {
+        Column { is_key, name: name.to_owned(), type_oid, type_modifier: -1 }
+    }
+
+    fn text(s: &str) -> TupleColumn {
+        TupleColumn::Text(s.as_bytes().to_owned())
+    }
+
+    #[test]
+    fn values() {
+        let decode = |type_oid, s: &str| Value::from_text(type_oid, s).unwrap();
+
+        assert_eq!(decode(oid::BOOL, "t"), Value::Bool(true));
+        assert_eq!(decode(oid::INT8, "-9000000000"), Value::Int8(-9_000_000_000));
+        assert!(matches!(decode(oid::FLOAT8, "NaN"), Value::Float8(v) if v.is_nan()));
+        assert_eq!(decode(oid::NUMERIC, "12.50"), Value::Numeric("12.50".to_owned()));
+        assert_eq!(
+            decode(oid::TIMESTAMP, "2024-05-01 12:34:56.789"),
+            Value::Timestamp(time::macros::datetime!(2024-05-01 12:34:56.789)),
+        );
+        assert_eq!(
+            decode(oid::TIMESTAMPTZ, "2024-05-01 12:34:56+05:30"),
+            Value::TimestampTz(time::macros::datetime!(2024-05-01 12:34:56 +05:30)),
+        );
+        assert_eq!(
+            decode(oid::TIMESTAMPTZ, "2024-05-01 12:34:56.5-02"),
+            Value::TimestampTz(time::macros::datetime!(2024-05-01 12:34:56.5 -02:00)),
+        );
+        assert_eq!(
+            decode(oid::UUID, "a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11"),
+            Value::Uuid([
+                0xa0, 0xee, 0xbc, 0x99, 0x9c, 0x0b, 0x4e, 0xf8, 0xbb, 0x6d, 0x6b, 0xb9, 0xbd, 0x38, 0x0a, 0x11,
+            ]),
+        );
+        assert_eq!(decode(oid::JSONB, r#"{"a": 1}"#), Value::Json(r#"{"a": 1}"#.to_owned()));
+        assert_eq!(decode(oid::BYTEA, "\\x00ff"), Value::Bytea(vec![0, 255]));
+        assert_eq!(decode(oid::BYTEA, "a\\\\\\001"), Value::Bytea(vec![b'a', b'\\', 1]));
+        assert_eq!(
+            decode(1007, "{{1,NULL},{3,4}}"),
+            Value::Array(vec![
+                Value::Array(vec![Value::Int4(1), Value::Null]),
+                Value::Array(vec![Value::Int4(3), Value::Int4(4)]),
+            ]),
+        );
+        assert_eq!(
+            decode(1009, r#"{"a b","NULL",c,"q\"x"}"#),
+            Value::Array(vec![
+                Value::Text("a b".to_owned()),
+                Value::Text("NULL".to_owned()),
+                Value::Text("c".to_owned()),
+                Value::Text("q\"x".to_owned()),
+            ]),
+        );
+        assert_eq!(decode(1007, "[0:1]={1,2}"), Value::Array(vec![Value::Int4(1), Value::Int4(2)]));
+        assert_eq!(decode(1007, "{}"), Value::Array(vec![]));
+        assert_eq!(decode(600, "(1,2)"), Value::Other { type_oid: 600, text: "(1,2)".to_owned() });
+        assert_eq!(
+            decode(oid::TIMESTAMP, "12345-01-01 00:00:00"),
+            Value::Timestamp(time::PrimitiveDateTime::new(
+                time::Date::from_calendar_date(12345, time::Month::January, 1).unwrap(),
+                time::Time::MIDNIGHT,
+            )),
+        );
+        assert_eq!(
+            decode(oid::TIMESTAMPTZ, "0044-03-15 12:00:00+00 BC"),
+            Value::Other { type_oid: oid::TIMESTAMPTZ, text: "0044-03-15 12:00:00+00 BC".to_owned() },
+        );
+        assert_eq!(
+            decode(1115, "{\"2024-05-01 12:34:56\",\"0044-03-15 12:00:00 BC\"}"),
+            Value::Array(vec![
+                Value::Timestamp(time::macros::datetime!(2024-05-01 12:34:56)),
+                Value::Other { type_oid: oid::TIMESTAMP, text: "0044-03-15 12:00:00 BC".to_owned() },
+            ]),
+        );
+
+        assert!(Value::from_text(oid::INT4, "x").is_err());
+        assert!(Value::from_text(1007, "{1,2").is_err());
+    }
+
+    #[test]
+    fn events() {
+        let mut changes = Changes::default();
+        let relation = Message::Relation {
+            xid: None,
+            oid: 16384,
+            namespace: "public".to_owned(),
+            name: "pokemon".to_owned(),
+            replica_identity: ReplicaIdentity::Default,
+            columns: vec![column("id", oid::INT4, true), column("name", oid::TEXT, false)],
+        };
+
+        let insert = Message::Insert { xid: None, oid: 16384, new: vec![text("1"), text("bulbasaur")] };
+        assert!(changes.process(10, &insert).is_err());
+
+        changes.process(8, &Message::Begin { final_lsn: 40, commit_time: 0, xid: 743 }).unwrap();
+        assert!(changes.process(10, &insert).is_err());
+        changes.process(9, &relation).unwrap();
+        assert_eq!(changes.relations().len(), 1);
+
+        let events = changes.process(10, &insert).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].lsn, 10);
+        assert_eq!(events[0].xid, 743);
+        assert_eq!(events[0].op, Op::Insert);
+        assert_eq!(events[0].relation.table, "pokemon");
+        assert_eq!(events[0].old, None);
+        assert_eq!(events[0].new.as_ref().unwrap().get("name"), Some(&Value::Text("bulbasaur".to_owned())));
+
+        let update = Message::Update {
+            xid: None,
+            oid: 16384,
+            old: Some(OldTuple::Key(vec![text("1"), TupleColumn::Null])),
+            new: vec![text("2"), TupleColumn::UnchangedToast],
+        };
+        let events = changes.process(20, &update).unwrap();
+        assert_eq!(events[0].old, Some(Row { columns: vec![("id".to_owned(), Value::Int4(1))] }));
+        assert_eq!(events[0].new.as_ref().unwrap().get("name"), Some(&Value::UnchangedToast));
+
+        let truncate = Message::Truncate { xid: None, cascade: false, restart_identity: false, oids: vec![16384] };
+        let events = changes.process(30, &truncate).unwrap();
+        assert_eq!(events[0].op, Op::Truncate);
+
+        changes.process(40, &Message::Commit { flags: 0, commit_lsn: 40, end_lsn: 48, commit_time: 0 }).unwrap();
+        assert!(changes.process(50, &insert).is_err());
+
+        // Streamed changes carry their own transaction.
+        let mut decoder = Decoder::new(2).unwrap();
+        let streamed = decoder.decode(b"S\x00\x00\x02\xe8\x01").unwrap();
+        changes.process(60, &streamed).unwrap();
+        let insert = decoder.decode(b"I\x00\x00\x02\xe8\x00\x00\x40\x00N\x00\x02t\x00\x00\x00\x013n").unwrap();
+        let events = changes.process(70, &insert).unwrap();
+        assert_eq!(events[0].xid, 744);
+        assert_eq!(events[0].new.as_ref().unwrap().get("name"), Some(&Value::Null));
+
+        // Whichever message ends the streamed transaction ends it for changes too.
+        let ends = [
+            Message::StreamStop,
+            Message::StreamCommit { xid: 744, flags: 0, commit_lsn: 80, end_lsn: 88, commit_time: 0 },
+            Message::StreamAbort { xid: 744, subxid: 744, abort_lsn: None, abort_time: None },
+            Message::RollbackPrepared {
+                flags: 0,
+                prepare_end_lsn: 80,
+                rollback_end_lsn: 88,
+                prepare_time: 0,
+                rollback_time: 0,
+                xid: 744,
+                gid: "tx-1".to_owned(),
+            },
+        ];
+        let unstreamed = Message::Insert { xid: None, oid: 16384, new: vec![text("3"), text("venusaur")] };
+
+        for end in ends {
+            changes.process(60, &Message::StreamStart { xid: 744, first_segment: false }).unwrap();
+            assert!(changes.in_transaction());
+            changes.process(80, &end).unwrap();
+            assert!(!changes.in_transaction());
+            assert!(changes.process(90, &unstreamed).is_err());
+        }
+    }
+}
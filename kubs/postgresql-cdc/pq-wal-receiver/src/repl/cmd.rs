@@ -17,8 +17,8 @@ impl Cmd {
         self
     }
 
-    pub fn finish(&mut self) -> &mut Self {
-        self.buf.push(0);
+    pub fn push_u64(&mut self, v: u64) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_be_bytes());
         self
     }
 
@@ -1,38 +1,130 @@
-fn i64_from_slice(slice: &[u8], at: usize) -> i64 {
-    assert!(at + 8 <= slice.len(), "Be careful: {at} + 8 <= {}?", slice.len());
-    
-    i64::from_be_bytes(unsafe {
-        *std::mem::transmute::<&u8, &[u8; 8]>(&slice[at])
-    })
+//! https://www.postgresql.org/docs/current/protocol-replication.html#PROTOCOL-REPLICATION-XLOGDATA
+
+use super::pgoutput;
+
+/// Big-endian reader over a message, every read fails instead of panicking on short input.
+pub struct Reader<'a> {
+    buf: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, at: 0 }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.at
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.remaining() < len {
+            return Err(format!("Unexpected end of message: {len} bytes at {} of {}", self.at, self.buf.len()));
+        }
+
+        let bytes = &self.buf[self.at..self.at + len];
+        self.at += len;
+        Ok(bytes)
+    }
+
+    pub fn rest(&mut self) -> &'a [u8] {
+        let bytes = &self.buf[self.at..];
+        self.at = self.buf.len();
+        bytes
+    }
+
+    pub fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn i16(&mut self) -> Result<i16, String> {
+        Ok(i16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub fn i64(&mut self) -> Result<i64, String> {
+        Ok(i64::from_be_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_be_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    /// Null-terminated string, without the terminator.
+    pub fn cstr(&mut self) -> Result<String, String> {
+        let rest = &self.buf[self.at..];
+        let Some(len) = rest.iter().position(|b| *b == 0) else {
+            return Err(format!("Unterminated string at {}", self.at));
+        };
+
+        let s = String::from_utf8(rest[..len].to_owned()).map_err(|e| e.to_string())?;
+        self.at += len + 1;
+        Ok(s)
+    }
+
+    /// Fails when the message has bytes left over.
+    pub fn end(&self) -> Result<(), String> {
+        if self.remaining() != 0 {
+            return Err(format!("{} trailing bytes at {}", self.remaining(), self.at));
+        }
+
+        Ok(())
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum CopyMessage {
     Keepalive {
-        end_of_wal: i64,
+        end_of_wal: u64,
         server_time: i64,
         should_reply: u8,
-    }
+    },
+    XLogData {
+        wal_start: u64,
+        end_of_wal: u64,
+        server_time: i64,
+        message: pgoutput::Message,
+    },
 }
 
 impl CopyMessage {
-    pub fn from_slice(s: &[std::ffi::c_char]) -> Self {
-        let slice: &[u8] = unsafe { std::mem::transmute(s) };
+    pub fn from_slice(slice: &[u8], decoder: &mut pgoutput::Decoder) -> Result<Self, String> {
+        let mut r = Reader::new(slice);
 
-        match slice[0] {
+        match r.u8()? {
             b'k' => {
-                assert!(slice.len() == 18);
-                let end_of_wal = i64_from_slice(slice, 1);
-                let server_time = i64_from_slice(slice, 9);
-                let should_reply = slice[17];
+                let end_of_wal = r.u64()?;
+                let server_time = r.i64()?;
+                let should_reply = r.u8()?;
+                r.end()?;
 
-                Self::Keepalive {
+                Ok(Self::Keepalive {
                     end_of_wal,
                     server_time,
                     should_reply,
-                }
+                })
+            },
+            b'w' => {
+                let wal_start = r.u64()?;
+                let end_of_wal = r.u64()?;
+                let server_time = r.i64()?;
+                let message = decoder.decode(r.rest())?;
+
+                Ok(Self::XLogData {
+                    wal_start,
+                    end_of_wal,
+                    server_time,
+                    message,
+                })
             },
-            _ => unimplemented!(),
+            tag => Err(format!("Unknown copy message: {:?}", tag as char)),
         }
     }
 }
@@ -0,0 +1,838 @@
+//! https://www.postgresql.org/docs/current/protocol-logicalrep-message-formats.html
+//!
+//! Payload of XLogData frames sent by the `pgoutput` plugin. Protocol version 2 adds streamed
+//! transactions, 3 two-phase commit and 4 the abort position of parallel streaming.
+
+use super::msg::Reader;
+
+pub type Lsn = u64;
+pub type Xid = u32;
+pub type Oid = u32;
+
+pub const MIN_VERSION: u8 = 1;
+pub const MAX_VERSION: u8 = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TupleColumn {
+    Null,
+    /// TOASTed value not changed by an update, the value itself is not sent.
+    UnchangedToast,
+    Text(Vec<u8>),
+    Binary(Vec<u8>),
+}
+
+pub type TupleData = Vec<TupleColumn>;
+
+/// Old values of an updated or deleted row, depending on the replica identity of its table.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OldTuple {
+    /// Columns of the replica identity key, others are null.
+    Key(TupleData),
+    /// Every column, for `REPLICA IDENTITY FULL`.
+    Full(TupleData),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplicaIdentity {
+    Default,
+    Nothing,
+    Full,
+    Index,
+}
+
+impl ReplicaIdentity {
+    fn from_byte(b: u8) -> Result<Self, String> {
+        match b {
+            b'd' => Ok(Self::Default),
+            b'n' => Ok(Self::Nothing),
+            b'f' => Ok(Self::Full),
+            b'i' => Ok(Self::Index),
+            _ => Err(format!("Unknown replica identity: {:?}", b as char)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    /// Part of the replica identity key.
+    pub is_key: bool,
+    pub name: String,
+    pub type_oid: Oid,
+    pub type_modifier: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Begin {
+        final_lsn: Lsn,
+        commit_time: i64,
+        xid: Xid,
+    },
+    /// Generic message emitted by `pg_logical_emit_message`.
+    Message {
+        xid: Option<Xid>,
+        transactional: bool,
+        lsn: Lsn,
+        prefix: String,
+        content: Vec<u8>,
+    },
+    Commit {
+        flags: u8,
+        commit_lsn: Lsn,
+        end_lsn: Lsn,
+        commit_time: i64,
+    },
+    Origin {
+        commit_lsn: Lsn,
+        name: String,
+    },
+    Relation {
+        xid: Option<Xid>,
+        oid: Oid,
+        /// Empty for `pg_catalog`.
+        namespace: String,
+        name: String,
+        replica_identity: ReplicaIdentity,
+        columns: Vec<Column>,
+    },
+    Type {
+        xid: Option<Xid>,
+        oid: Oid,
+        namespace: String,
+        name: String,
+    },
+    Insert {
+        xid: Option<Xid>,
+        oid: Oid,
+        new: TupleData,
+    },
+    Update {
+        xid: Option<Xid>,
+        oid: Oid,
+        old: Option<OldTuple>,
+        new: TupleData,
+    },
+    Delete {
+        xid: Option<Xid>,
+        oid: Oid,
+        old: OldTuple,
+    },
+    Truncate {
+        xid: Option<Xid>,
+        cascade: bool,
+        restart_identity: bool,
+        oids: Vec<Oid>,
+    },
+    StreamStart {
+        xid: Xid,
+        first_segment: bool,
+    },
+    StreamStop,
+    StreamCommit {
+        xid: Xid,
+        flags: u8,
+        commit_lsn: Lsn,
+        end_lsn: Lsn,
+        commit_time: i64,
+    },
+    StreamAbort {
+        xid: Xid,
+        subxid: Xid,
+        /// Only sent with parallel streaming, since version 4.
+        abort_lsn: Option<Lsn>,
+        abort_time: Option<i64>,
+    },
+    BeginPrepare {
+        prepare_lsn: Lsn,
+        end_lsn: Lsn,
+        prepare_time: i64,
+        xid: Xid,
+        gid: String,
+    },
+    Prepare {
+        flags: u8,
+        prepare_lsn: Lsn,
+        end_lsn: Lsn,
+        prepare_time: i64,
+        xid: Xid,
+        gid: String,
+    },
+    CommitPrepared {
+        flags: u8,
+        commit_lsn: Lsn,
+        end_lsn: Lsn,
+        commit_time: i64,
+        xid: Xid,
+        gid: String,
+    },
+    RollbackPrepared {
+        flags: u8,
+        prepare_end_lsn: Lsn,
+        rollback_end_lsn: Lsn,
+        prepare_time: i64,
+        rollback_time: i64,
+        xid: Xid,
+        gid: String,
+    },
+    StreamPrepare {
+        flags: u8,
+        prepare_lsn: Lsn,
+        end_lsn: Lsn,
+        prepare_time: i64,
+        xid: Xid,
+        gid: String,
+    },
+}
+
+/// Decodes the messages of a single replication stream.
+///
+/// Messages within a streamed transaction carry its xid, so the decoder follows Stream Start and
+/// Stream Stop.
+#[derive(Debug)]
+pub struct Decoder {
+    version: u8,
+    streaming: bool,
+}
+
+impl Decoder {
+    pub fn new(version: u8) -> Result<Self, String> {
+        if !(MIN_VERSION..=MAX_VERSION).contains(&version) {
+            return Err(format!("Unsupported pgoutput protocol version: {version}"));
+        }
+
+        Ok(Self {
+            version,
+            streaming: false,
+        })
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    fn require(&self, version: u8, tag: u8) -> Result<(), String> {
+        if self.version < version {
+            return Err(format!(
+                "Message {:?} needs protocol version {version}, stream uses {}",
+                tag as char, self.version
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Transaction of a message, only sent within a stream segment.
+    fn xid(&self, r: &mut Reader) -> Result<Option<Xid>, String> {
+        if self.streaming {
+            Ok(Some(r.u32()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn decode(&mut self, buf: &[u8]) -> Result<Message, String> {
+        let mut r = Reader::new(buf);
+        let tag = r.u8()?;
+
+        let message = match tag {
+            b'B' => Message::Begin {
+                final_lsn: r.u64()?,
+                commit_time: r.i64()?,
+                xid: r.u32()?,
+            },
+            b'M' => {
+                let xid = self.xid(&mut r)?;
+                let transactional = r.u8()? & 1 == 1;
+                let lsn = r.u64()?;
+                let prefix = r.cstr()?;
+                let len = r.i32()?;
+                let content = r.bytes(len.try_into().map_err(|_| format!("Invalid length: {len}"))?)?;
+
+                Message::Message {
+                    xid,
+                    transactional,
+                    lsn,
+                    prefix,
+                    content: content.to_owned(),
+                }
+            },
+            b'C' => Message::Commit {
+                flags: r.u8()?,
+                commit_lsn: r.u64()?,
+                end_lsn: r.u64()?,
+                commit_time: r.i64()?,
+            },
+            b'O' => Message::Origin {
+                commit_lsn: r.u64()?,
+                name: r.cstr()?,
+            },
+            b'R' => {
+                let xid = self.xid(&mut r)?;
+                let oid = r.u32()?;
+                let namespace = r.cstr()?;
+                let name = r.cstr()?;
+                let replica_identity = ReplicaIdentity::from_byte(r.u8()?)?;
+                let len = r.i16()?;
+                let mut columns = Vec::with_capacity(len.max(0) as usize);
+
+                for _ in 0..len {
+                    columns.push(Column {
+                        is_key: r.u8()? & 1 == 1,
+                        name: r.cstr()?,
+                        type_oid: r.u32()?,
+                        type_modifier: r.i32()?,
+                    });
+                }
+
+                Message::Relation {
+                    xid,
+                    oid,
+                    namespace,
+                    name,
+                    replica_identity,
+                    columns,
+                }
+            },
+            b'Y' => Message::Type {
+                xid: self.xid(&mut r)?,
+                oid: r.u32()?,
+                namespace: r.cstr()?,
+                name: r.cstr()?,
+            },
+            b'I' => {
+                let xid = self.xid(&mut r)?;
+                let oid = r.u32()?;
+                expect_byte(&mut r, b'N')?;
+
+                Message::Insert {
+                    xid,
+                    oid,
+                    new: tuple(&mut r)?,
+                }
+            },
+            b'U' => {
+                let xid = self.xid(&mut r)?;
+                let oid = r.u32()?;
+
+                let old = match r.u8()? {
+                    b'K' => Some(OldTuple::Key(tuple(&mut r)?)),
+                    b'O' => Some(OldTuple::Full(tuple(&mut r)?)),
+                    b'N' => None,
+                    b => return Err(format!("Unexpected update tuple: {:?}", b as char)),
+                };
+
+                if old.is_some() {
+                    expect_byte(&mut r, b'N')?;
+                }
+
+                Message::Update {
+                    xid,
+                    oid,
+                    old,
+                    new: tuple(&mut r)?,
+                }
+            },
+            b'D' => {
+                let xid = self.xid(&mut r)?;
+                let oid = r.u32()?;
+
+                let old = match r.u8()? {
+                    b'K' => OldTuple::Key(tuple(&mut r)?),
+                    b'O' => OldTuple::Full(tuple(&mut r)?),
+                    b => return Err(format!("Unexpected delete tuple: {:?}", b as char)),
+                };
+
+                Message::Delete { xid, oid, old }
+            },
+            b'T' => {
+                let xid = self.xid(&mut r)?;
+                let len = r.i32()?;
+                let options = r.u8()?;
+                let mut oids = Vec::with_capacity(len.max(0) as usize);
+
+                for _ in 0..len {
+                    oids.push(r.u32()?);
+                }
+
+                Message::Truncate {
+                    xid,
+                    cascade: options & 1 == 1,
+                    restart_identity: options & 2 == 2,
+                    oids,
+                }
+            },
+            b'S' => {
+                self.require(2, tag)?;
+                let xid = r.u32()?;
+                let first_segment = r.u8()? == 1;
+                self.streaming = true;
+
+                Message::StreamStart { xid, first_segment }
+            },
+            b'E' => {
+                self.require(2, tag)?;
+                self.streaming = false;
+
+                Message::StreamStop
+            },
+            b'c' => {
+                self.require(2, tag)?;
+                self.streaming = false;
+
+                Message::StreamCommit {
+                    xid: r.u32()?,
+                    flags: r.u8()?,
+                    commit_lsn: r.u64()?,
+                    end_lsn: r.u64()?,
+                    commit_time: r.i64()?,
+                }
+            },
+            b'A' => {
+                self.require(2, tag)?;
+                self.streaming = false;
+                let xid = r.u32()?;
+                let subxid = r.u32()?;
+
+                // Present only when streaming is set to parallel.
+                let (abort_lsn, abort_time) = if self.version >= 4 && r.remaining() > 0 {
+                    (Some(r.u64()?), Some(r.i64()?))
+                } else {
+                    (None, None)
+                };
+
+                Message::StreamAbort {
+                    xid,
+                    subxid,
+                    abort_lsn,
+                    abort_time,
+                }
+            },
+            b'b' => {
+                self.require(3, tag)?;
+
+                Message::BeginPrepare {
+                    prepare_lsn: r.u64()?,
+                    end_lsn: r.u64()?,
+                    prepare_time: r.i64()?,
+                    xid: r.u32()?,
+                    gid: r.cstr()?,
+                }
+            },
+            b'P' => {
+                self.require(3, tag)?;
+
+                Message::Prepare {
+                    flags: r.u8()?,
+                    prepare_lsn: r.u64()?,
+                    end_lsn: r.u64()?,
+                    prepare_time: r.i64()?,
+                    xid: r.u32()?,
+                    gid: r.cstr()?,
+                }
+            },
+            b'K' => {
+                self.require(3, tag)?;
+                self.streaming = false;
+
+                Message::CommitPrepared {
+                    flags: r.u8()?,
+                    commit_lsn: r.u64()?,
+                    end_lsn: r.u64()?,
+                    commit_time: r.i64()?,
+                    xid: r.u32()?,
+                    gid: r.cstr()?,
+                }
+            },
+            b'r' => {
+                self.require(3, tag)?;
+                self.streaming = false;
+
+                Message::RollbackPrepared {
+                    flags: r.u8()?,
+                    prepare_end_lsn: r.u64()?,
+                    rollback_end_lsn: r.u64()?,
+                    prepare_time: r.i64()?,
+                    rollback_time: r.i64()?,
+                    xid: r.u32()?,
+                    gid: r.cstr()?,
+                }
+            },
+            b'p' => {
+                self.require(3, tag)?;
+
+                Message::StreamPrepare {
+                    flags: r.u8()?,
+                    prepare_lsn: r.u64()?,
+                    end_lsn: r.u64()?,
+                    prepare_time: r.i64()?,
+                    xid: r.u32()?,
+                    gid: r.cstr()?,
+                }
+            },
+            _ => return Err(format!("Unknown pgoutput message: {:?}", tag as char)),
+        };
+
+        r.end()?;
+        Ok(message)
+    }
+}
+
+fn expect_byte(r: &mut Reader, expected: u8) -> Result<(), String> {
+    match r.u8()? {
+        b if b == expected => Ok(()),
+        b => Err(format!("Expected {:?}, got {:?}", expected as char, b as char)),
+    }
+}
+
+fn tuple(r: &mut Reader) -> Result<TupleData, String> {
+    let len = r.i16()?;
+    let mut columns = Vec::with_capacity(len.max(0) as usize);
+
+    for _ in 0..len {
+        let column = match r.u8()? {
+            b'n' => TupleColumn::Null,
+            b'u' => TupleColumn::UnchangedToast,
+            kind @ (b't' | b'b') => {
+                let len = r.i32()?;
+                let value = r.bytes(len.try_into().map_err(|_| format!("Invalid length: {len}"))?)?.to_owned();
+
+                if kind == b't' {
+                    TupleColumn::Text(value)
+                } else {
+                    TupleColumn::Binary(value)
+                }
+            },
+            b => return Err(format!("Unknown tuple column: {:?}", b as char)),
+        };
+
+        columns.push(column);
+    }
+
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::msg::CopyMessage;
+
+    // Messages of a session against `CREATE TABLE pokemon (id int PRIMARY KEY, name text)`.
+
+    const BEGIN: &[u8] = b"B\x00\x00\x00\x00\x01\xa2\xb3\xc8\x00\x02\xb8\xb3\xe1\xf0\xa1\xc2\x00\x00\x02\xe7";
+
+    const RELATION: &[u8] = b"R\x00\x00\x40\x00public\x00pokemon\x00d\x00\x02\
+        \x01id\x00\x00\x00\x00\x17\xff\xff\xff\xff\
+        \x00name\x00\x00\x00\x00\x19\xff\xff\xff\xff";
+
+    const INSERT: &[u8] = b"I\x00\x00\x40\x00N\x00\x02t\x00\x00\x00\x011t\x00\x00\x00\x09bulbasaur";
+
+    const UPDATE: &[u8] = b"U\x00\x00\x40\x00N\x00\x02t\x00\x00\x00\x011t\x00\x00\x00\x07ivysaur";
+
+    const UPDATE_KEY: &[u8] = b"U\x00\x00\x40\x00K\x00\x02t\x00\x00\x00\x011n\
+        N\x00\x02t\x00\x00\x00\x012u";
+
+    const DELETE: &[u8] = b"D\x00\x00\x40\x00K\x00\x02t\x00\x00\x00\x012n";
+
+    const COMMIT: &[u8] = b"C\x00\x00\x00\x00\x00\x01\xa2\xb3\xc8\x00\x00\x00\x00\x01\xa2\xb3\xf8\
+        \x00\x02\xb8\xb3\xe1\xf0\xa1\xc2";
+
+    fn decode_all(version: u8, stream: &[&[u8]]) -> Vec<Message> {
+        let mut decoder = Decoder::new(version).unwrap();
+        stream.iter().map(|buf| decoder.decode(buf).unwrap()).collect()
+    }
+
+    fn text(s: &str) -> TupleColumn {
+        TupleColumn::Text(s.as_bytes().to_owned())
+    }
+
+    #[test]
+    fn transaction() {
+        let messages = decode_all(1, &[BEGIN, RELATION, INSERT, UPDATE, UPDATE_KEY, DELETE, COMMIT]);
+
+        assert_eq!(messages[0], Message::Begin {
+            final_lsn: 0x1a2b3c8,
+            commit_time: 0x2b8b3e1f0a1c2,
+            xid: 743,
+        });
+        assert_eq!(messages[1], Message::Relation {
+            xid: None,
+            oid: 16384,
+            namespace: "public".to_owned(),
+            name: "pokemon".to_owned(),
+            replica_identity: ReplicaIdentity::Default,
+            columns: vec![
+                Column { is_key: true, name: "id".to_owned(), type_oid: 23, type_modifier: -1 },
+                Column { is_key: false, name: "name".to_owned(), type_oid: 25, type_modifier: -1 },
+            ],
+        });
+        assert_eq!(messages[2], Message::Insert {
+            xid: None,
+            oid: 16384,
+            new: vec![text("1"), text("bulbasaur")],
+        });
+        assert_eq!(messages[3], Message::Update {
+            xid: None,
+            oid: 16384,
+            old: None,
+            new: vec![text("1"), text("ivysaur")],
+        });
+        assert_eq!(messages[4], Message::Update {
+            xid: None,
+            oid: 16384,
+            old: Some(OldTuple::Key(vec![text("1"), TupleColumn::Null])),
+            new: vec![text("2"), TupleColumn::UnchangedToast],
+        });
+        assert_eq!(messages[5], Message::Delete {
+            xid: None,
+            oid: 16384,
+            old: OldTuple::Key(vec![text("2"), TupleColumn::Null]),
+        });
+        assert_eq!(messages[6], Message::Commit {
+            flags: 0,
+            commit_lsn: 0x1a2b3c8,
+            end_lsn: 0x1a2b3f8,
+            commit_time: 0x2b8b3e1f0a1c2,
+        });
+    }
+
+    #[test]
+    fn catalog_messages() {
+        let messages = decode_all(1, &[
+            b"Y\x00\x00\x40\x10public\x00mood\x00",
+            b"O\x00\x00\x00\x00\x01\xa2\xb3\xc8upstream\x00",
+            b"T\x00\x00\x00\x02\x03\x00\x00\x40\x00\x00\x00\x40\x01",
+            b"M\x00\x00\x00\x00\x00\x01\xa2\xb4\x00audit\x00\x00\x00\x00\x02hi",
+        ]);
+
+        assert_eq!(messages, vec![
+            Message::Type { xid: None, oid: 16400, namespace: "public".to_owned(), name: "mood".to_owned() },
+            Message::Origin { commit_lsn: 0x1a2b3c8, name: "upstream".to_owned() },
+            Message::Truncate { xid: None, cascade: true, restart_identity: true, oids: vec![16384, 16385] },
+            Message::Message {
+                xid: None,
+                transactional: false,
+                lsn: 0x1a2b400,
+                prefix: "audit".to_owned(),
+                content: b"hi".to_vec(),
+            },
+        ]);
+    }
+
+    #[test]
+    fn streamed_transaction() {
+        let messages = decode_all(4, &[
+            b"S\x00\x00\x02\xe8\x01",
+            b"I\x00\x00\x02\xe8\x00\x00\x40\x00N\x00\x01n",
+            b"E",
+            b"I\x00\x00\x40\x00N\x00\x01n",
+            b"A\x00\x00\x02\xe8\x00\x00\x02\xe9",
+            b"A\x00\x00\x02\xe8\x00\x00\x02\xe9\x00\x00\x00\x00\x01\xa2\xb5\x00\x00\x02\xb8\xb3\xe1\xf0\xa1\xc2",
+            b"c\x00\x00\x02\xe8\x00\x00\x00\x00\x00\x01\xa2\xb5\x00\x00\x00\x00\x00\x01\xa2\xb6\x00\
+                \x00\x02\xb8\xb3\xe1\xf0\xa1\xc2",
+        ]);
+
+        assert_eq!(messages[0], Message::StreamStart { xid: 744, first_segment: true });
+        assert_eq!(messages[1], Message::Insert { xid: Some(744), oid: 16384, new: vec![TupleColumn::Null] });
+        assert_eq!(messages[2], Message::StreamStop);
+        assert_eq!(messages[3], Message::Insert { xid: None, oid: 16384, new: vec![TupleColumn::Null] });
+        assert_eq!(messages[4], Message::StreamAbort { xid: 744, subxid: 745, abort_lsn: None, abort_time: None });
+        assert_eq!(messages[5], Message::StreamAbort {
+            xid: 744,
+            subxid: 745,
+            abort_lsn: Some(0x1a2b500),
+            abort_time: Some(0x2b8b3e1f0a1c2),
+        });
+        assert_eq!(messages[6], Message::StreamCommit {
+            xid: 744,
+            flags: 0,
+            commit_lsn: 0x1a2b500,
+            end_lsn: 0x1a2b600,
+            commit_time: 0x2b8b3e1f0a1c2,
+        });
+    }
+
+    #[test]
+    fn messages_carry_xids_within_segments() {
+        let message = |xid: &[u8]| [b"M".as_slice(), xid, b"\x01\x00\x00\x00\x00\x01\xa2\xb4\x00audit\x00\x00\x00\x00\x02hi"].concat();
+        let commit = b"c\x00\x00\x02\xe8\x00\x00\x00\x00\x00\x01\xa2\xb5\x00\x00\x00\x00\x00\x01\xa2\xb6\x00\
+            \x00\x02\xb8\xb3\xe1\xf0\xa1\xc2";
+
+        // The transaction is committed without a StreamStop, later messages are not streamed.
+        let messages = decode_all(2, &[b"S\x00\x00\x02\xe8\x01", &message(b"\x00\x00\x02\xe8"), commit, &message(b"")]);
+
+        assert!(matches!(&messages[1], Message::Message { xid: Some(744), transactional: true, content, .. } if content == b"hi"));
+        assert!(matches!(&messages[3], Message::Message { xid: None, transactional: true, content, .. } if content == b"hi"));
+    }
+
+    #[test]
+    fn two_phase_commit() {
+        let lsns = b"\x00\x00\x00\x00\x01\xa2\xb3\xc8\x00\x00\x00\x00\x01\xa2\xb3\xf8\x00\x02\xb8\xb3\xe1\xf0\xa1\xc2";
+        let gid = b"\x00\x00\x02\xe7tx-1\x00";
+
+        let begin = [b"b".as_slice(), lsns, gid].concat();
+        let prepare = [b"P\x00".as_slice(), lsns, gid].concat();
+        let commit = [b"K\x00".as_slice(), lsns, gid].concat();
+        let rollback = [b"r\x00".as_slice(), lsns, b"\x00\x02\xb8\xb3\xe1\xf0\xa1\xc3", gid].concat();
+
+        let messages = decode_all(3, &[&begin, &prepare, &commit, &rollback]);
+
+        assert_eq!(messages[0], Message::BeginPrepare {
+            prepare_lsn: 0x1a2b3c8,
+            end_lsn: 0x1a2b3f8,
+            prepare_time: 0x2b8b3e1f0a1c2,
+            xid: 743,
+            gid: "tx-1".to_owned(),
+        });
+        assert!(matches!(&messages[1], Message::Prepare { xid: 743, gid, .. } if gid == "tx-1"));
+        assert!(matches!(&messages[2], Message::CommitPrepared { commit_lsn: 0x1a2b3c8, .. }));
+        assert_eq!(messages[3], Message::RollbackPrepared {
+            flags: 0,
+            prepare_end_lsn: 0x1a2b3c8,
+            rollback_end_lsn: 0x1a2b3f8,
+            prepare_time: 0x2b8b3e1f0a1c2,
+            rollback_time: 0x2b8b3e1f0a1c3,
+            xid: 743,
+            gid: "tx-1".to_owned(),
+        });
+
+        // Version 1 streams never contain them.
+        assert!(Decoder::new(1).unwrap().decode(&begin).is_err());
+    }
+
+    #[test]
+    fn invalid_messages() {
+        let mut decoder = Decoder::new(4).unwrap();
+
+        assert!(Decoder::new(5).is_err());
+        assert!(decoder.decode(&INSERT[..INSERT.len() - 1]).is_err());
+        assert!(decoder.decode(&[INSERT, b"x"].concat()).is_err());
+        assert!(decoder.decode(b"Z").is_err());
+        assert!(decoder.decode(b"I\x00\x00\x40\x00N\x00\x01x").is_err());
+    }
+
+    #[test]
+    fn copy_data_frames() {
+        let mut decoder = Decoder::new(1).unwrap();
+
+        let keepalive = b"k\x00\x00\x00\x00\x01\xa2\xb3\xf8\x00\x02\xb8\xb3\xe1\xf0\xa1\xc2\x01";
+        assert_eq!(CopyMessage::from_slice(keepalive, &mut decoder).unwrap(), CopyMessage::Keepalive {
+            end_of_wal: 0x1a2b3f8,
+            server_time: 0x2b8b3e1f0a1c2,
+            should_reply: 1,
+        });
+
+        let header = b"w\x00\x00\x00\x00\x01\xa2\xb3\xc8\x00\x00\x00\x00\x01\xa2\xb3\xf8\x00\x02\xb8\xb3\xe1\xf0\xa1\xc2";
+        let frame = [header.as_slice(), INSERT].concat();
+        assert_eq!(CopyMessage::from_slice(&frame, &mut decoder).unwrap(), CopyMessage::XLogData {
+            wal_start: 0x1a2b3c8,
+            end_of_wal: 0x1a2b3f8,
+            server_time: 0x2b8b3e1f0a1c2,
+            message: decode_all(1, &[INSERT]).remove(0),
+        });
+
+        assert!(CopyMessage::from_slice(&keepalive[..10], &mut decoder).is_err());
+    }
+
+    /// CopyData messages, tag and length included, read from the socket of a PostgreSQL 15
+    /// walsender started with `proto_version '2', streaming 'on', messages 'true'` and
+    /// `logical_decoding_work_mem = 64kB`, after:
+    ///
+    /// ```sql
+    /// CREATE TYPE mood AS ENUM ('happy', 'sad');
+    /// CREATE TABLE pokemon (id int PRIMARY KEY, name text, mood mood);
+    /// CREATE TABLE sightings (pokemon_id int, note text);
+    /// ALTER TABLE sightings REPLICA IDENTITY FULL;
+    ///
+    /// INSERT INTO pokemon VALUES (1, 'bulbasaur', 'happy');
+    /// BEGIN;
+    /// UPDATE pokemon SET name = 'ivysaur' WHERE id = 1;
+    /// UPDATE pokemon SET id = 2 WHERE id = 1;
+    /// INSERT INTO pokemon VALUES (3, NULL, 'sad');
+    /// DELETE FROM pokemon WHERE id = 3;
+    /// COMMIT;
+    /// INSERT INTO sightings VALUES (2, 'route 1');
+    /// UPDATE sightings SET note = 'route 2';
+    /// DELETE FROM sightings;
+    /// SELECT pg_logical_emit_message(false, 'audit', 'hi');
+    /// BEGIN;
+    /// SELECT pg_logical_emit_message(true, 'audit', 'in tx');
+    /// TRUNCATE sightings;
+    /// COMMIT;
+    /// INSERT INTO sightings SELECT i, md5(i::text) || md5((i * 7)::text) FROM generate_series(1, 800) i;
+    /// ```
+    const CAPTURED: &[u8] = include_bytes!("../../fixtures/pgoutput-v2.bin");
+
+    #[test]
+    fn captured_stream() {
+        let mut decoder = Decoder::new(2).unwrap();
+        let mut r = Reader::new(CAPTURED);
+        let mut messages = Vec::new();
+        let mut keepalives = 0;
+
+        while r.remaining() > 0 {
+            assert_eq!(r.u8().unwrap(), b'd');
+            let len = r.i32().unwrap() as usize - 4;
+
+            match CopyMessage::from_slice(r.bytes(len).unwrap(), &mut decoder).unwrap() {
+                CopyMessage::XLogData { message, .. } => messages.push(message),
+                CopyMessage::Keepalive { .. } => keepalives += 1,
+            }
+        }
+
+        assert_eq!(keepalives, 2);
+        assert_eq!(messages.len(), 835);
+
+        assert_eq!(messages[1], Message::Type {
+            xid: None,
+            oid: 16385,
+            namespace: "public".to_owned(),
+            name: "mood".to_owned(),
+        });
+        assert!(matches!(&messages[2], Message::Relation { oid: 16389, columns, .. } if columns[2].type_oid == 16385));
+        assert_eq!(messages[3], Message::Insert {
+            xid: None,
+            oid: 16389,
+            new: vec![text("1"), text("bulbasaur"), text("happy")],
+        });
+        assert_eq!(messages[7], Message::Update {
+            xid: None,
+            oid: 16389,
+            old: Some(OldTuple::Key(vec![text("1"), TupleColumn::Null, TupleColumn::Null])),
+            new: vec![text("2"), text("ivysaur"), text("happy")],
+        });
+        assert_eq!(messages[8], Message::Insert {
+            xid: None,
+            oid: 16389,
+            new: vec![text("3"), TupleColumn::Null, text("sad")],
+        });
+        assert!(matches!(&messages[12], Message::Relation { replica_identity: ReplicaIdentity::Full, .. }));
+        assert_eq!(messages[16], Message::Update {
+            xid: None,
+            oid: 16396,
+            old: Some(OldTuple::Full(vec![text("2"), text("route 1")])),
+            new: vec![text("2"), text("route 2")],
+        });
+        assert!(matches!(&messages[21], Message::Message { transactional: false, content, .. } if content == b"hi"));
+        assert!(matches!(&messages[23], Message::Message { transactional: true, content, .. } if content == b"in tx"));
+        assert!(matches!(&messages[25], Message::Truncate { oids, .. } if oids == &[16396]));
+
+        // The last transaction didn't fit into memory and was streamed in segments.
+        let streamed = &messages[27..];
+        assert_eq!(streamed[0], Message::StreamStart { xid: 735, first_segment: true });
+        assert!(matches!(streamed.last(), Some(Message::StreamCommit { xid: 735, .. })));
+        assert!(streamed.iter().all(|message| match message {
+            Message::Relation { xid, .. } | Message::Insert { xid, .. } => *xid == Some(735),
+            Message::StreamStart { xid, .. } | Message::StreamCommit { xid, .. } => *xid == 735,
+            Message::StreamStop => true,
+            _ => false,
+        }));
+        let inserts = streamed.iter().filter(|m| matches!(m, Message::Insert { .. })).count();
+        let segments = streamed.iter().filter(|m| matches!(m, Message::StreamStart { .. })).count();
+        assert_eq!((inserts, segments), (800, 3));
+
+        let begins = messages.iter().filter(|m| matches!(m, Message::Begin { .. })).count();
+        let commits = messages.iter().filter(|m| matches!(m, Message::Commit { .. })).count();
+        assert_eq!((begins, commits), (6, 6));
+    }
+}
+
@@ -0,0 +1,124 @@
+//! https://www.postgresql.org/docs/current/protocol-replication.html#PROTOCOL-REPLICATION-STANDBY-STATUS-UPDATE
+
+use std::cell::Cell;
+
+use super::cmd::Cmd;
+use super::pgoutput::Lsn;
+
+/// WAL positions of a replication stream, reported back to the server by status updates.
+#[derive(Debug, Default)]
+pub struct Progress {
+    /// Highest WAL position received from the server.
+    received: Cell<Lsn>,
+    /// Position of the last change event handed to the consumer.
+    delivered: Cell<Lsn>,
+    /// Position acknowledged by the consumer, the server may recycle WAL up to it.
+    flushed: Cell<Lsn>,
+}
+
+impl Progress {
+    pub fn receive(&self, wal_start: Lsn) {
+        self.received.set(self.received.get().max(wal_start));
+    }
+
+    pub fn deliver(&self, lsn: Lsn) {
+        self.delivered.set(lsn);
+    }
+
+    pub fn ack(&self, lsn: Lsn) {
+        self.flushed.set(self.flushed.get().max(lsn));
+    }
+
+    /// Keepalive received between transactions. Nothing is pending once the consumer acknowledged
+    /// all it got, so the slot may advance past WAL without changes for this publication.
+    pub fn idle(&self, end_of_wal: Lsn) {
+        if self.flushed.get() >= self.delivered.get() {
+            self.ack(end_of_wal);
+        }
+    }
+
+    pub fn flushed(&self) -> Lsn {
+        self.flushed.get()
+    }
+
+    /// Status update for the current positions, nothing is reported as written before it was
+    /// flushed.
+    pub fn status_update(&self, now: time::UtcDateTime, reply_requested: bool) -> Cmd {
+        let flushed = self.flushed.get();
+        status_update(self.received.get().max(flushed), flushed, now, reply_requested)
+    }
+}
+
+/// Standby Status Update message, changes are applied once they are flushed.
+pub fn status_update(written: Lsn, flushed: Lsn, now: time::UtcDateTime, reply_requested: bool) -> Cmd {
+    let epoch = time::macros::utc_datetime!(2000-01-01 00:00);
+    let mut cmd = Cmd::new();
+
+    cmd
+        .push_byte(b'r')
+        .push_u64(written)
+        .push_u64(flushed)
+        .push_u64(flushed)
+        .push_i64((now - epoch).whole_microseconds() as i64)
+        .push_byte(reply_requested as u8);
+
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoding() {
+        let now = time::macros::utc_datetime!(2000-01-01 00:00:01.5);
+        let cmd = status_update(0x1_a2b3c4d5, 0x1_a2b3c400, now, true);
+        let bytes = cmd.as_bytes();
+
+        assert_eq!(bytes.len(), 34);
+        assert_eq!(bytes[0], b'r');
+        assert_eq!(bytes[1..9], [0, 0, 0, 1, 0xa2, 0xb3, 0xc4, 0xd5]);
+        assert_eq!(bytes[9..17], [0, 0, 0, 1, 0xa2, 0xb3, 0xc4, 0x00]);
+        assert_eq!(bytes[17..25], bytes[9..17]);
+        assert_eq!(bytes[25..33], 1_500_000i64.to_be_bytes());
+        assert_eq!(bytes[33], 1);
+
+        assert_eq!(status_update(0, 0, now, false).as_bytes()[33], 0);
+    }
+
+    #[test]
+    fn progress() {
+        let progress = Progress::default();
+        let now = time::macros::utc_datetime!(2000-01-01 00:00);
+        let reported = |progress: &Progress| {
+            let cmd = progress.status_update(now, false);
+            let bytes = cmd.as_bytes();
+            (
+                u64::from_be_bytes(bytes[1..9].try_into().unwrap()),
+                u64::from_be_bytes(bytes[9..17].try_into().unwrap()),
+            )
+        };
+
+        progress.receive(100);
+        progress.receive(50);
+        progress.deliver(100);
+        assert_eq!(reported(&progress), (100, 0));
+
+        // The consumer still holds a delivered change.
+        progress.idle(200);
+        assert_eq!(progress.flushed(), 0);
+
+        progress.ack(100);
+        progress.ack(40);
+        assert_eq!(reported(&progress), (100, 100));
+
+        // Everything delivered is acknowledged, the slot may move past unpublished WAL.
+        progress.idle(200);
+        assert_eq!(reported(&progress), (200, 200));
+
+        progress.receive(300);
+        progress.deliver(300);
+        progress.idle(400);
+        assert_eq!(reported(&progress), (300, 200));
+    }
+}
@@ -0,0 +1,303 @@
+//! Typed values of tuple columns, decoded from the text output format of their type.
+//!
+//! Dates are expected in the ISO `DateStyle`, the default of walsender sessions. BC dates don't
+//! parse and are kept as [Value::Other].
+
+use super::pgoutput::{Oid, TupleColumn};
+
+pub mod oid {
+    use super::Oid;
+
+    pub const BOOL: Oid = 16;
+    pub const BYTEA: Oid = 17;
+    pub const NAME: Oid = 19;
+    pub const INT8: Oid = 20;
+    pub const INT2: Oid = 21;
+    pub const INT4: Oid = 23;
+    pub const TEXT: Oid = 25;
+    pub const OID: Oid = 26;
+    pub const JSON: Oid = 114;
+    pub const FLOAT4: Oid = 700;
+    pub const FLOAT8: Oid = 701;
+    pub const BPCHAR: Oid = 1042;
+    pub const VARCHAR: Oid = 1043;
+    pub const TIMESTAMP: Oid = 1114;
+    pub const TIMESTAMPTZ: Oid = 1184;
+    pub const NUMERIC: Oid = 1700;
+    pub const UUID: Oid = 2950;
+    pub const JSONB: Oid = 3802;
+
+    /// Element type of an array type.
+    pub fn element(array: Oid) -> Option<Oid> {
+        let element = match array {
+            1000 => BOOL,
+            1001 => BYTEA,
+            1003 => NAME,
+            1016 => INT8,
+            1005 => INT2,
+            1007 => INT4,
+            1009 => TEXT,
+            1028 => OID,
+            199 => JSON,
+            1021 => FLOAT4,
+            1022 => FLOAT8,
+            1014 => BPCHAR,
+            1015 => VARCHAR,
+            1115 => TIMESTAMP,
+            1185 => TIMESTAMPTZ,
+            1231 => NUMERIC,
+            2951 => UUID,
+            3807 => JSONB,
+            _ => return None,
+        };
+
+        Some(element)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    /// TOASTed value not changed by an update, the previous value still holds.
+    UnchangedToast,
+    Bool(bool),
+    Int2(i16),
+    Int4(i32),
+    Int8(i64),
+    Float4(f32),
+    Float8(f64),
+    /// Kept as text, `NaN` and infinities included.
+    Numeric(String),
+    Text(String),
+    /// `infinity` and `-infinity` become the latest and earliest representable timestamps.
+    Timestamp(time::PrimitiveDateTime),
+    TimestampTz(time::OffsetDateTime),
+    Uuid([u8; 16]),
+    /// `json` and `jsonb`, as text.
+    Json(String),
+    Bytea(Vec<u8>),
+    Array(Vec<Value>),
+    /// Value of a type without a decoder, or a timestamp out of range, in its text format.
+    Other { type_oid: Oid, text: String },
+    /// Value sent in the binary format of its type.
+    Binary { type_oid: Oid, bytes: Vec<u8> },
+}
+
+impl Value {
+    pub fn decode(type_oid: Oid, column: &TupleColumn) -> Result<Self, String> {
+        match column {
+            TupleColumn::Null => Ok(Self::Null),
+            TupleColumn::UnchangedToast => Ok(Self::UnchangedToast),
+            TupleColumn::Binary(bytes) => Ok(Self::Binary { type_oid, bytes: bytes.clone() }),
+            TupleColumn::Text(bytes) => {
+                let text = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+                Self::from_text(type_oid, text).map_err(|e| format!("Type {type_oid}: {e}"))
+            },
+        }
+    }
+
+    pub fn from_text(type_oid: Oid, text: &str) -> Result<Self, String> {
+        let other = || Self::Other { type_oid, text: text.to_owned() };
+
+        let value = match type_oid {
+            oid::BOOL => match text {
+                "t" => Self::Bool(true),
+                "f" => Self::Bool(false),
+                _ => return Err(format!("Invalid bool: {text:?}")),
+            },
+            oid::INT2 => Self::Int2(text.parse().map_err(|_| format!("Invalid int2: {text:?}"))?),
+            oid::INT4 => Self::Int4(text.parse().map_err(|_| format!("Invalid int4: {text:?}"))?),
+            oid::INT8 => Self::Int8(text.parse().map_err(|_| format!("Invalid int8: {text:?}"))?),
+            oid::FLOAT4 => Self::Float4(text.parse().map_err(|_| format!("Invalid float4: {text:?}"))?),
+            oid::FLOAT8 => Self::Float8(text.parse().map_err(|_| format!("Invalid float8: {text:?}"))?),
+            oid::NUMERIC => Self::Numeric(text.to_owned()),
+            oid::TEXT | oid::VARCHAR | oid::BPCHAR | oid::NAME => Self::Text(text.to_owned()),
+            oid::TIMESTAMP => timestamp(text).map_or_else(|_| other(), Self::Timestamp),
+            oid::TIMESTAMPTZ => timestamptz(text).map_or_else(|_| other(), Self::TimestampTz),
+            oid::UUID => Self::Uuid(uuid(text)?),
+            oid::JSON | oid::JSONB => Self::Json(text.to_owned()),
+            oid::BYTEA => Self::Bytea(bytea(text)?),
+            _ => match oid::element(type_oid) {
+                Some(element) => Self::Array(array(element, text)?),
+                None => other(),
+            },
+        };
+
+        Ok(value)
+    }
+}
+
+fn timestamp(text: &str) -> Result<time::PrimitiveDateTime, String> {
+    use time::{Date, PrimitiveDateTime, Time};
+
+    let format = time::macros::format_description!(
+        "[year]-[month]-[day] [hour]:[minute]:[second][optional [.[subsecond]]]"
+    );
+
+    match text {
+        "infinity" => Ok(PrimitiveDateTime::new(Date::MAX, Time::MAX)),
+        "-infinity" => Ok(PrimitiveDateTime::new(Date::MIN, Time::MIDNIGHT)),
+        _ => PrimitiveDateTime::parse(&signed_year(text), format)
+            .map_err(|e| format!("Invalid timestamp {text:?}: {e}")),
+    }
+}
+
+fn timestamptz(text: &str) -> Result<time::OffsetDateTime, String> {
+    use time::UtcOffset;
+
+    let format = time::macros::format_description!(
+        "[year]-[month]-[day] [hour]:[minute]:[second][optional [.[subsecond]]]\
+         [offset_hour sign:mandatory][optional [:[offset_minute]]][optional [:[offset_second]]]"
+    );
+
+    match text {
+        "infinity" | "-infinity" => Ok(timestamp(text)?.assume_offset(UtcOffset::UTC)),
+        _ => time::OffsetDateTime::parse(&signed_year(text), format)
+            .map_err(|e| format!("Invalid timestamptz {text:?}: {e}")),
+    }
+}
+
+/// Years past 9999 are printed with more digits, which the `time` crate only parses after a sign.
+fn signed_year(text: &str) -> std::borrow::Cow<'_, str> {
+    match text.find('-') {
+        Some(digits) if digits > 4 => format!("+{text}").into(),
+        _ => text.into(),
+    }
+}
+
+fn hex_digit(b: u8) -> Result<u8, String> {
+    match b {
+        b'0'..=b'9' => Ok(b - b'0'),
+        b'a'..=b'f' => Ok(b - b'a' + 10),
+        b'A'..=b'F' => Ok(b - b'A' + 10),
+        _ => Err(format!("Invalid hex digit: {:?}", b as char)),
+    }
+}
+
+fn uuid(text: &str) -> Result<[u8; 16], String> {
+    let digits: Vec<u8> = text.bytes().filter(|b| *b != b'-').collect();
+
+    if digits.len() != 32 {
+        return Err(format!("Invalid uuid: {text:?}"));
+    }
+
+    let mut uuid = [0; 16];
+    for (i, pair) in digits.chunks(2).enumerate() {
+        uuid[i] = (hex_digit(pair[0])? << 4) | hex_digit(pair[1])?;
+    }
+
+    Ok(uuid)
+}
+
+/// Hex format, or the escape format of `bytea_output = escape`.
+fn bytea(text: &str) -> Result<Vec<u8>, String> {
+    if let Some(hex) = text.strip_prefix("\\x") {
+        if hex.len() % 2 != 0 {
+            return Err(format!("Invalid bytea: {text:?}"));
+        }
+
+        return hex
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| Ok((hex_digit(pair[0])? << 4) | hex_digit(pair[1])?))
+            .collect();
+    }
+
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            out.push(bytes[i]);
+            i += 1;
+        } else if bytes.get(i + 1) == Some(&b'\\') {
+            out.push(b'\\');
+            i += 2;
+        } else {
+            let octal = bytes.get(i + 1..i + 4).ok_or(format!("Invalid bytea: {text:?}"))?;
+            let octal = std::str::from_utf8(octal).map_err(|e| e.to_string())?;
+            out.push(u8::from_str_radix(octal, 8).map_err(|_| format!("Invalid bytea: {text:?}"))?);
+            i += 4;
+        }
+    }
+
+    Ok(out)
+}
+
+/// Array literal such as `{1,NULL,3}`, `{{"a b",c},{d,e}}` or `[0:1]={1,2}`.
+fn array(element: Oid, text: &str) -> Result<Vec<Value>, String> {
+    // Bounds are only printed for arrays not starting at 1.
+    let text = if text.starts_with('[') {
+        text.split_once('=').ok_or(format!("Invalid array: {text:?}"))?.1
+    } else {
+        text
+    };
+
+    let mut chars = text.chars().peekable();
+    let values = array_items(element, &mut chars)?;
+
+    if chars.next().is_some() {
+        return Err(format!("Invalid array: {text:?}"));
+    }
+
+    Ok(values)
+}
+
+fn array_items(element: Oid, chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Vec<Value>, String> {
+    if chars.next() != Some('{') {
+        return Err("Array must start with '{'".to_owned());
+    }
+
+    let mut values = Vec::new();
+
+    if chars.peek() == Some(&'}') {
+        chars.next();
+        return Ok(values);
+    }
+
+    loop {
+        let value = match chars.peek() {
+            Some('{') => Value::Array(array_items(element, chars)?),
+            Some('"') => {
+                chars.next();
+                let mut item = String::new();
+
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => item.push(chars.next().ok_or("Unterminated array item")?),
+                        Some(c) => item.push(c),
+                        None => return Err("Unterminated array item".to_owned()),
+                    }
+                }
+
+                Value::from_text(element, &item)?
+            },
+            _ => {
+                let mut item = String::new();
+
+                while let Some(c) = chars.peek() {
+                    if *c == ',' || *c == '}' {
+                        break;
+                    }
+                    item.push(*c);
+                    chars.next();
+                }
+
+                match item.as_str() {
+                    "NULL" => Value::Null,
+                    _ => Value::from_text(element, &item)?,
+                }
+            },
+        };
+
+        values.push(value);
+
+        match chars.next() {
+            Some(',') => continue,
+            Some('}') => return Ok(values),
+            _ => return Err("Unterminated array".to_owned()),
+        }
+    }
+}